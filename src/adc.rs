@@ -0,0 +1,389 @@
+//! # Analog to Digital Converter (ADC)
+//!
+//! 10-bit SAR ADC with eight external inputs (AIN0 to AIN7) and internal
+//! channels to monitor the supply rails.
+//!
+//! The MAX78000 has no internal temperature sensor on the ADC input mux,
+//! and no temperature calibration values in its trim registers. To monitor
+//! the die temperature, for example during CNN workloads, connect an
+//! external sensor such as a thermistor divider to one of the AIN inputs.
+use core::marker::PhantomData;
+
+use crate::dma::{Channel as DmaChannel, Request, TransferConfig, TransferWidth};
+use embedded_hal_nb::nb;
+
+pub use crate::pac::adc::ctrl::ChSel as AdcChannel;
+
+/// Largest conversion result (10 bits).
+pub const ADC_MAX_CODE: u16 = 0x3ff;
+
+/// Voltage of the internal bandgap reference, in millivolts.
+const INTERNAL_REF_MV: u32 = 1220;
+/// Nominal voltage of the VDDA supply, in millivolts.
+const VDDA_NOMINAL_MV: u32 = 1800;
+/// Largest DMA transfer, in bytes (24-bit count).
+const MAX_DMA_LEN: usize = (1 << 24) - 1;
+
+/// Errors that can occur while streaming samples from the ADC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AdcError {
+    /// The buffer is empty, has an odd length, or is too long for the DMA.
+    InvalidBuffer,
+    /// Both halves of the buffer filled up before one was read, so the
+    /// stream stopped and samples were dropped.
+    Overrun,
+    /// The DMA channel was stopped by a bus error.
+    DmaBusError,
+}
+
+/// Voltage references of the ADC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AdcReference {
+    /// Internal 1.22 V bandgap reference.
+    Internal,
+    /// The VDDA supply, nominally 1.8 V.
+    Vdda,
+}
+
+impl AdcReference {
+    /// Voltage of the reference in millivolts. For [`AdcReference::Vdda`]
+    /// this is the nominal supply voltage.
+    pub const fn millivolts(self) -> u32 {
+        match self {
+            AdcReference::Internal => INTERNAL_REF_MV,
+            AdcReference::Vdda => VDDA_NOMINAL_MV,
+        }
+    }
+}
+
+/// ADC inputs that can be converted by [`Adc::read_channel()`]. Each
+/// marker type maps to one setting of the ADC input mux, so only channels
+/// that exist can be selected.
+pub trait Channel: crate::Sealed {
+    /// The ADC mux setting of the channel.
+    const SELECT: AdcChannel;
+}
+
+/// Marker types for the ADC input channels.
+pub mod channels {
+    use super::{AdcChannel, Channel};
+
+    macro_rules! channel {
+        ($($(#[$meta:meta])* $name:ident,)+) => {
+            $(
+                $(#[$meta])*
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                pub struct $name;
+
+                impl crate::Sealed for $name {}
+                impl Channel for $name {
+                    const SELECT: AdcChannel = AdcChannel::$name;
+                }
+            )+
+        };
+    }
+
+    channel! {
+        /// External input AIN0.
+        Ain0,
+        /// External input AIN1.
+        Ain1,
+        /// External input AIN2.
+        Ain2,
+        /// External input AIN3.
+        Ain3,
+        /// External input AIN4.
+        Ain4,
+        /// External input AIN5.
+        Ain5,
+        /// External input AIN6.
+        Ain6,
+        /// External input AIN7.
+        Ain7,
+        /// Core supply, VCOREA.
+        VcoreA,
+        /// Core supply, VCOREB.
+        VcoreB,
+        /// Radio receiver output.
+        Vrxout,
+        /// Radio transmitter output.
+        Vtxout,
+        /// Analog supply, VDDA.
+        VddA,
+        /// Digital supply, VDDB, divided by four.
+        VddB,
+        /// I/O supply, VDDIO, divided by four.
+        Vddio,
+        /// High voltage I/O supply, VDDIOH, divided by four.
+        Vddioh,
+        /// Regulator input, VREGI, divided by four.
+        VregI,
+    }
+}
+
+/// # Analog to Digital Converter (ADC) Peripheral
+///
+/// Performs single one-shot conversions. The supply monitoring channels
+/// ([`AdcChannel::VddB`], [`AdcChannel::Vddio`], [`AdcChannel::Vddioh`] and
+/// [`AdcChannel::VregI`]) are divided by four before conversion, so their
+/// voltage is four times the value from [`Adc::to_millivolts()`].
+///
+/// Example:
+/// ```
+/// use hal::adc::{Adc, AdcChannel, AdcReference};
+///
+/// let mut adc = Adc::new(p.adc, &mut gcr.reg, AdcReference::Internal);
+/// let code = adc.read(AdcChannel::Ain0);
+/// let millivolts = adc.to_millivolts(code);
+/// ```
+pub struct Adc {
+    adc: crate::pac::Adc,
+    reference: AdcReference,
+}
+
+impl Adc {
+    /// Create a new ADC peripheral instance, power it up and wait for the
+    /// reference to be ready.
+    pub fn new(
+        adc: crate::pac::Adc,
+        reg: &mut crate::gcr::GcrRegisters,
+        reference: AdcReference,
+    ) -> Self {
+        use crate::gcr::ClockForPeripheral;
+        use crate::gcr::ResetForPeripheral;
+
+        unsafe {
+            adc.reset(&mut reg.gcr);
+            adc.enable_clock(&mut reg.gcr);
+        }
+
+        let internal = reference == AdcReference::Internal;
+        adc.intr().write(|w| w.ref_ready_if().clear_bit_by_one());
+        adc.ctrl().write(|w| {
+            w.pwr().set_bit();
+            w.refbuf_pwr().bit(internal);
+            w.ref_sel().bit(!internal);
+            // Right-align the 10-bit result
+            w.data_align().set_bit()
+        });
+        while adc.status().read().afe_pwr_up_active().bit_is_set() {}
+        if internal {
+            while adc.intr().read().ref_ready_if().bit_is_clear() {}
+        }
+        adc.ctrl().modify(|_, w| w.clk_en().set_bit());
+
+        Self { adc, reference }
+    }
+
+    /// The reference the ADC was configured with.
+    #[inline(always)]
+    pub fn reference(&self) -> AdcReference {
+        self.reference
+    }
+
+    /// Convert a channel once and return the raw 10-bit code, from `0` to
+    /// [`ADC_MAX_CODE`]. Blocks until the conversion completes.
+    pub fn read(&mut self, channel: AdcChannel) -> u16 {
+        self.adc.intr().modify(|_, w| w.done_if().clear_bit_by_one());
+        self.adc.ctrl().modify(|_, w| w.ch_sel().variant(channel));
+        self.adc.ctrl().modify(|_, w| w.start().set_bit());
+        while self.adc.intr().read().done_if().bit_is_clear() {}
+        while self.adc.status().read().active().bit_is_set() {}
+        self.adc.intr().modify(|_, w| w.done_if().clear_bit_by_one());
+        self.adc.data().read().adc_data().bits() & ADC_MAX_CODE
+    }
+
+    /// Convert the channel given by a marker type once and return the raw
+    /// 10-bit code. This is the typed equivalent of [`Adc::read()`], so
+    /// generic drivers can be handed the channel they should sample.
+    ///
+    /// Example:
+    /// ```
+    /// use hal::adc::channels::VcoreA;
+    ///
+    /// // Sanity check the core supply against the internal reference
+    /// let vcore = adc.to_millivolts(adc.read_channel(&mut VcoreA));
+    /// assert!((900..=1200).contains(&vcore));
+    /// ```
+    #[inline(always)]
+    pub fn read_channel<C: Channel>(&mut self, _channel: &mut C) -> u16 {
+        self.read(C::SELECT)
+    }
+
+    /// Stream conversions of a channel into a buffer using DMA.
+    ///
+    /// The MAX78000 ADC has no hardware trigger, so each conversion is
+    /// started by [`AdcStream::trigger()`], normally called from the
+    /// interrupt of a [`crate::timer::Timer`] running at the sample rate.
+    /// The DMA moves each result into the buffer without CPU involvement.
+    ///
+    /// The buffer is split into two halves that are filled in turn. When
+    /// one half is full the DMA reloads onto the other one without
+    /// stopping, so no samples are dropped as long as each half is read
+    /// with [`AdcStream::read_buffer()`] before the other half fills up.
+    ///
+    /// The DMA keeps writing to the buffer for as long as the stream runs,
+    /// so it has to be `'static`; even a leaked stream cannot leave the DMA
+    /// writing to freed memory. [`AdcStream::stop()`] hands it back.
+    ///
+    /// Returns [`AdcError::InvalidBuffer`] if the buffer is empty, has an
+    /// odd length, or each half is longer than a single DMA transfer.
+    ///
+    /// Example:
+    /// ```
+    /// use hal::timer::{Timer, TimerMode};
+    ///
+    /// static mut SAMPLES: [u16; 512] = [0; 512];
+    ///
+    /// let mut timer = Timer::tmr0(p.tmr0, &mut gcr.reg, &clks.pclk);
+    /// timer.configure(TimerMode::Continuous, 125).unwrap(); // 8 kHz
+    /// timer.enable_interrupt();
+    /// let mut stream = adc
+    ///     .stream_dma(&mut channels.ch0, AdcChannel::Ain0, unsafe { &mut SAMPLES })
+    ///     .unwrap();
+    /// timer.start();
+    ///
+    /// #[interrupt]
+    /// fn TMR0() {
+    ///     timer.clear_interrupt();
+    ///     stream.trigger();
+    /// }
+    ///
+    /// loop {
+    ///     // 256 samples every 32 ms
+    ///     if let Ok(level) = stream.read_buffer(|samples| peak(samples)) {
+    ///         report(level);
+    ///     }
+    /// }
+    /// ```
+    pub fn stream_dma<'a, const N: usize>(
+        &'a mut self,
+        dma: &'a mut DmaChannel<N>,
+        channel: AdcChannel,
+        buffer: &'static mut [u16],
+    ) -> Result<AdcStream<'a, N>, AdcError> {
+        let half = buffer.len() / 2;
+        if half == 0 || !buffer.len().is_multiple_of(2) || half * 2 > MAX_DMA_LEN {
+            return Err(AdcError::InvalidBuffer);
+        }
+        self.adc.ctrl().modify(|_, w| w.ch_sel().variant(channel));
+        self.adc.intr().modify(|_, w| w.done_if().clear_bit_by_one());
+
+        let data = self.adc.data().as_ptr() as u32;
+        let addr = buffer.as_mut_ptr() as u32;
+        let len = (half * 2) as u32;
+        // Safety: The buffer is 'static and the channel is borrowed by the stream
+        unsafe {
+            dma.start(&TransferConfig {
+                request: Request::Adc,
+                src: data,
+                src_width: TransferWidth::HalfWord,
+                src_increment: false,
+                dst: addr,
+                dst_width: TransferWidth::HalfWord,
+                dst_increment: true,
+                len,
+                burst_size: 2,
+            });
+            dma.set_reload(data, addr + len, len);
+        }
+        Ok(AdcStream {
+            adc: self,
+            dma,
+            buffer: buffer.as_mut_ptr(),
+            half,
+            active: 0,
+            _buffer: PhantomData,
+        })
+    }
+
+    /// Convert a raw code into millivolts at the ADC input, using the
+    /// configured reference.
+    #[inline(always)]
+    pub fn to_millivolts(&self, code: u16) -> u32 {
+        _code_to_mv(code, self.reference)
+    }
+
+    /// Power down the ADC and release the underlying peripheral.
+    pub fn free(self) -> crate::pac::Adc {
+        self.adc.ctrl().write(|w| w);
+        self.adc
+    }
+}
+
+/// Converts a raw code into millivolts for a reference.
+#[doc(hidden)]
+#[inline(always)]
+fn _code_to_mv(code: u16, reference: AdcReference) -> u32 {
+    (code.min(ADC_MAX_CODE) as u32 * reference.millivolts()) / ADC_MAX_CODE as u32
+}
+
+/// Samples being streamed into a buffer by DMA, returned by
+/// [`Adc::stream_dma()`]. The DMA channel is stopped when this is dropped.
+pub struct AdcStream<'a, const N: usize> {
+    adc: &'a mut Adc,
+    dma: &'a mut DmaChannel<N>,
+    buffer: *mut u16,
+    half: usize,
+    active: usize,
+    _buffer: PhantomData<&'static mut [u16]>,
+}
+
+impl<const N: usize> AdcStream<'_, N> {
+    /// Start a single conversion. Call this at the sample rate, usually
+    /// from a timer interrupt.
+    #[inline(always)]
+    pub fn trigger(&mut self) {
+        self.adc.adc.ctrl().modify(|_, w| w.start().set_bit());
+    }
+
+    /// If a half of the buffer has been filled, pass its samples (raw
+    /// 10-bit codes) to `f` and then hand it back to the DMA to be filled
+    /// again after the half currently being filled.
+    ///
+    /// Returns [`nb::Error::WouldBlock`] while neither half is complete,
+    /// [`AdcError::Overrun`] if both halves filled up and the stream
+    /// stopped, or [`AdcError::DmaBusError`] on a DMA failure.
+    pub fn read_buffer<R>(&mut self, f: impl FnOnce(&[u16]) -> R) -> nb::Result<R, AdcError> {
+        if self.dma.bus_error() {
+            return Err(nb::Error::Other(AdcError::DmaBusError));
+        }
+        if !self.dma.take_reload_event() {
+            return if self.dma.is_busy() {
+                Err(nb::Error::WouldBlock)
+            } else {
+                Err(nb::Error::Other(AdcError::Overrun))
+            };
+        }
+        let done = self.active;
+        self.active ^= 1;
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        // Safety: The DMA is filling the other half until it is re-armed below
+        let samples = unsafe {
+            core::slice::from_raw_parts(self.buffer.add(done * self.half), self.half)
+        };
+        let result = f(samples);
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        let data = self.adc.adc.data().as_ptr() as u32;
+        let addr = unsafe { self.buffer.add(done * self.half) } as u32;
+        unsafe { self.dma.set_reload(data, addr, (self.half * 2) as u32) };
+        Ok(result)
+    }
+
+    /// Stop the stream and return the buffer it was filling.
+    pub fn stop(self) -> &'static mut [u16] {
+        let (buffer, len) = (self.buffer, self.half * 2);
+        drop(self);
+        // Safety: The DMA channel was stopped by the drop above, and the
+        // buffer came from a `&'static mut [u16]` of this length
+        unsafe { core::slice::from_raw_parts_mut(buffer, len) }
+    }
+}
+
+impl<const N: usize> Drop for AdcStream<'_, N> {
+    fn drop(&mut self) {
+        self.dma.stop();
+    }
+}
@@ -3,23 +3,196 @@
 //! The AES is a hardware module that accelerates decryption (and encryption)
 //! AntiAES configured
 
+use core::marker::PhantomData;
+
+use crate::dma::{Channel, Request, TransferConfig, TransferWidth};
+use crate::wait::{Spin, WaitStrategy};
 use crate::pac::aes::ctrl::{KeySize, Type};
+use embedded_hal_nb::nb;
+#[cfg(feature = "async")]
+use core::{cell::RefCell, task::{Poll, Waker}};
+#[cfg(feature = "cipher")]
+use cipher::{
+    consts::{U1, U16},
+    inout::InOut,
+    Block, BlockBackend, BlockCipher, BlockClosure, BlockDecrypt, BlockEncrypt, BlockSizeUser,
+    ParBlocksSizeUser,
+};
 
 /// Address of the AES key registers in memory.
 pub const AES_KEY_REGISTER_ADDR: usize = 0x4000_7800;
 
-#[derive(Debug, Clone, Copy)]
+/// Default number of status polls before a block operation returns
+/// [`AesError::Timeout`]. A single block normally completes well within a
+/// few hundred polls.
+pub const AES_DEFAULT_TIMEOUT: u32 = 100_000;
+
+/// Number of attempts the [`cipher`] trait implementations make at a mode
+/// switch or a block before giving up and latching an error, see
+/// [`AesBlockCipher`].
+#[cfg(feature = "cipher")]
+pub const AES_CIPHER_ATTEMPTS: u32 = 4;
+
+/// Longest buffer, in bytes, that a single DMA transfer such as
+/// [`Aes::encrypt_blocks_dma()`] can process: the largest whole number of
+/// blocks that fits the 24-bit DMA count register.
+pub const AES_DMA_MAX_LEN: usize = (1 << 24) - 16;
+
+/// Length in bytes of the authentication tag produced by
+/// [`Aes::encrypt_gcm()`].
+pub const GCM_TAG_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum AesError {
     NotEmpty,
     Misconfigured,
+    /// The key is not 16, 24, or 32 bytes long.
+    InvalidKeyLength,
+    /// A DMA channel was stopped by a bus error.
+    DmaBusError,
+    /// The peripheral was still busy after the maximum number of polls.
+    Timeout,
+    /// The GCM nonce is empty.
+    InvalidNonceLength,
+    /// The GCM authentication tag did not match the message.
+    TagMismatch,
+    /// The input FIFO was full while a block was pushed, or the output FIFO
+    /// still held data after a block was read, so the FIFOs are out of step
+    /// with the block being processed. See [`Aes::reset_fifos()`].
+    FifoOverflow,
+    /// The output FIFO ran empty while a block was read. See
+    /// [`Aes::reset_fifos()`].
+    FifoUnderflow,
+    /// The key does not lie entirely in flash.
+    InvalidKeyAddress,
+    /// The configured key size does not match the key size of the typed
+    /// handle, see [`Aes::into_encryptor()`].
+    KeySizeMismatch,
+    /// The buffer is not a whole number of blocks long, or is longer than
+    /// [`AES_DMA_MAX_LEN`] for a DMA transfer.
+    InvalidDataLength,
 }
 
 pub type AesSubBlock = u8;
 pub type AesBlock = [AesSubBlock; 16];
-pub type AesKey = [u8; 32];
+/// AES-128 key, see [`Aes::set_key()`].
+pub type AesKey128 = [u8; 16];
+/// AES-192 key, see [`Aes::set_key()`].
+pub type AesKey192 = [u8; 24];
+/// AES-256 key, see [`Aes::set_key()`].
+pub type AesKey256 = [u8; 32];
+/// Key of the largest supported size. Keys of any supported size can be
+/// loaded with [`Aes::set_key()`].
+pub type AesKey = AesKey256;
+
+/// Key sizes supported by the AES peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AesKeyVariant {
+    /// 128-bit (16 byte) key.
+    Bits128,
+    /// 192-bit (24 byte) key.
+    Bits192,
+    /// 256-bit (32 byte) key.
+    Bits256,
+}
+
+impl AesKeyVariant {
+    /// Returns the key variant matching a key length in bytes, if any.
+    pub const fn from_key_len(len: usize) -> Option<Self> {
+        match len {
+            16 => Some(Self::Bits128),
+            24 => Some(Self::Bits192),
+            32 => Some(Self::Bits256),
+            _ => None,
+        }
+    }
+
+    /// Length of the key in bytes.
+    pub const fn key_len(&self) -> usize {
+        match self {
+            Self::Bits128 => 16,
+            Self::Bits192 => 24,
+            Self::Bits256 => 32,
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    const fn _key_size(&self) -> KeySize {
+        match self {
+            Self::Bits128 => KeySize::Aes128,
+            Self::Bits192 => KeySize::Aes192,
+            Self::Bits256 => KeySize::Aes256,
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    const fn _from_key_size(key_size: KeySize) -> Self {
+        match key_size {
+            KeySize::Aes128 => Self::Bits128,
+            KeySize::Aes192 => Self::Bits192,
+            KeySize::Aes256 => Self::Bits256,
+        }
+    }
+}
+
+/// Key size of a typed [`AesEncryptor`] or [`AesDecryptor`].
+pub trait AesKeySize: crate::Sealed {
+    /// Key of this size.
+    type Key: AsRef<[u8]>;
+    /// Matching runtime key variant.
+    const VARIANT: AesKeyVariant;
+}
+
+/// AES-128 key size marker.
+pub struct Aes128;
+/// AES-192 key size marker.
+pub struct Aes192;
+/// AES-256 key size marker.
+pub struct Aes256;
+
+impl crate::Sealed for Aes128 {}
+impl AesKeySize for Aes128 {
+    type Key = AesKey128;
+    const VARIANT: AesKeyVariant = AesKeyVariant::Bits128;
+}
+
+impl crate::Sealed for Aes192 {}
+impl AesKeySize for Aes192 {
+    type Key = AesKey192;
+    const VARIANT: AesKeyVariant = AesKeyVariant::Bits192;
+}
+
+impl crate::Sealed for Aes256 {}
+impl AesKeySize for Aes256 {
+    type Key = AesKey256;
+    const VARIANT: AesKeyVariant = AesKeyVariant::Bits256;
+}
+
+/// Snapshot of the AES status register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AesStatus {
+    /// The AES peripheral is performing a calculation.
+    pub busy: bool,
+    /// The input FIFO is empty.
+    pub input_empty: bool,
+    /// The input FIFO is full.
+    pub input_full: bool,
+    /// The output FIFO is empty.
+    pub output_empty: bool,
+    /// The output FIFO is full.
+    pub output_full: bool,
+}
 
 pub struct Aes {
     aes: crate::pac::Aes,
+    /// Error latched by the infallible cipher traits.
+    #[cfg(feature = "cipher")]
+    cipher_error: core::cell::Cell<Option<AesError>>,
 }
 
 impl Aes {
@@ -33,112 +206,961 @@ impl Aes {
             aes.enable_clock(&mut reg.gcr);
         }
 
-        Self { aes }
+        Self {
+            aes,
+            #[cfg(feature = "cipher")]
+            cipher_error: core::cell::Cell::new(None),
+        }
+    }
+
+    /// Disables the AES peripheral, flushes both FIFOs, zeroes the key
+    /// registers, and returns the underlying PAC peripheral.
+    pub fn free(self) -> crate::pac::Aes {
+        self._disable();
+        // Skip the Drop implementation since the peripheral is handed back
+        let this = core::mem::ManuallyDrop::new(self);
+        unsafe { core::ptr::read(&this.aes) }
     }
 
-    /// Decrypts block with AES256
+    /// Decrypts block with the configured key size
+    ///
+    /// The mode must be set to [`Type::DecExt`] or [`Type::DecInt`].
     #[inline(always)]
     pub fn decrypt_block(&self, in_block: AesBlock) -> Result<AesBlock, AesError> {
-        let in_block_32: [u32; 4] = convert_u8_to_u32_array(in_block);
-        let mut out_block_32: [u32; 4] = [0u32; 4];
+        self._check_decrypt()?;
+        self._process_block(in_block)
+    }
 
-        if self._get_mode() != Type::EncExt {
-            return Err(AesError::Misconfigured)
-        }
+    /// Encrypts block with the configured key size
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    #[inline(always)]
+    pub fn encrypt_block(&self, in_block: AesBlock) -> Result<AesBlock, AesError> {
+        self._check_encrypt()?;
+        self._process_block(in_block)
+    }
 
-        if self._get_key_size() != KeySize::Aes256 {
-            return Err(AesError::Misconfigured)
+    /// Decrypts each block of a slice in place (ECB), returning the number
+    /// of blocks processed. An empty slice is a no-op.
+    ///
+    /// The mode must be set to [`Type::DecExt`] or [`Type::DecInt`].
+    pub fn decrypt_blocks(&self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        self._check_decrypt()?;
+        self._process_blocks(data)
+    }
+
+    /// Encrypts each block of a slice in place (ECB), returning the number
+    /// of blocks processed. An empty slice is a no-op.
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    pub fn encrypt_blocks(&self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        self._check_encrypt()?;
+        self._process_blocks(data)
+    }
+
+    /// Encrypts a byte buffer in place (ECB), one block at a time. Returns
+    /// [`AesError::InvalidDataLength`] unless the buffer is a multiple of 16
+    /// bytes long, in which case it is left unchanged.
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    ///
+    /// Example:
+    /// ```
+    /// let mut data = [0u8; 64];
+    /// aes.encrypt_ecb(&mut data).unwrap();
+    /// assert_eq!(aes.encrypt_ecb(&mut data[..15]), Err(AesError::InvalidDataLength));
+    /// ```
+    pub fn encrypt_ecb(&self, data: &mut [u8]) -> Result<(), AesError> {
+        self._check_encrypt()?;
+        self._process_ecb(data)
+    }
+
+    /// Decrypts a byte buffer in place (ECB), one block at a time. See
+    /// [`Aes::encrypt_ecb()`].
+    ///
+    /// The mode must be set to [`Type::DecExt`] or [`Type::DecInt`].
+    pub fn decrypt_ecb(&self, data: &mut [u8]) -> Result<(), AesError> {
+        self._check_decrypt()?;
+        self._process_ecb(data)
+    }
+
+    /// Decrypts each block of a slice in place (ECB) like
+    /// [`Aes::decrypt_blocks()`], running `strategy` after each block and
+    /// while the peripheral is busy.
+    pub fn decrypt_blocks_with(
+        &self,
+        data: &mut [AesBlock],
+        strategy: &mut impl WaitStrategy,
+    ) -> Result<usize, AesError> {
+        self._check_decrypt()?;
+        self._process_blocks_with(data, strategy)
+    }
+
+    /// Encrypts each block of a slice in place (ECB) like
+    /// [`Aes::encrypt_blocks()`], running `strategy` after each block and
+    /// while the peripheral is busy, for example to feed the watchdog
+    /// during a long batch. In windowed mode the watchdog must not be fed
+    /// before its early window has passed, see [`crate::wait`].
+    ///
+    /// Example:
+    /// ```
+    /// let mut feed = || {
+    ///     if wdt.margin().until_early_ok == 0 {
+    ///         wdt.feed();
+    ///     }
+    /// };
+    /// aes.encrypt_blocks_with(&mut blocks, &mut feed).unwrap();
+    /// ```
+    pub fn encrypt_blocks_with(
+        &self,
+        data: &mut [AesBlock],
+        strategy: &mut impl WaitStrategy,
+    ) -> Result<usize, AesError> {
+        self._check_encrypt()?;
+        self._process_blocks_with(data, strategy)
+    }
+
+    /// Encrypts a slice of blocks in place using cipher block chaining (CBC).
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    ///
+    /// Example:
+    /// ```
+    /// let mut data: [AesBlock; 4] = plaintext;
+    /// aes.set_key(&key).unwrap();
+    /// aes.set_mode(Type::EncExt).unwrap();
+    /// aes.encrypt_cbc(iv, &mut data).unwrap();
+    ///
+    /// aes.set_mode(Type::DecExt).unwrap();
+    /// aes.decrypt_cbc(iv, &mut data).unwrap();
+    /// assert_eq!(data, plaintext);
+    /// ```
+    pub fn encrypt_cbc(&self, iv: AesBlock, data: &mut [AesBlock]) -> Result<(), AesError> {
+        self._check_encrypt()?;
+        cbc_encrypt(iv, data, |block| self._process_block(block))
+    }
+
+    /// Decrypts a slice of blocks in place using cipher block chaining (CBC).
+    ///
+    /// The mode must be set to [`Type::DecExt`] or [`Type::DecInt`].
+    pub fn decrypt_cbc(&self, iv: AesBlock, data: &mut [AesBlock]) -> Result<(), AesError> {
+        self._check_decrypt()?;
+        cbc_decrypt(iv, data, |block| self._process_block(block))
+    }
+
+    /// Encrypts `data` in place using Galois/Counter Mode (GCM) and returns
+    /// the authentication tag, which covers both `aad` and the ciphertext.
+    ///
+    /// `aad` is authenticated but not encrypted. The nonce may be of any
+    /// non-zero length, though 12 bytes is recommended, and must never be
+    /// reused with the same key.
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    ///
+    /// Example:
+    /// ```
+    /// // NIST GCM specification, test case 4 (AES-128)
+    /// let key: [u8; 16] = [
+    ///     0xfe, 0xff, 0xe9, 0x92, 0x86, 0x65, 0x73, 0x1c,
+    ///     0x6d, 0x6a, 0x8f, 0x94, 0x67, 0x30, 0x83, 0x08,
+    /// ];
+    /// let nonce: [u8; 12] = [
+    ///     0xca, 0xfe, 0xba, 0xbe, 0xfa, 0xce, 0xdb, 0xad,
+    ///     0xde, 0xca, 0xf8, 0x88,
+    /// ];
+    /// let aad: [u8; 20] = [
+    ///     0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe, 0xef,
+    ///     0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe, 0xef,
+    ///     0xab, 0xad, 0xda, 0xd2,
+    /// ];
+    /// let mut data: [u8; 60] = [
+    ///     0xd9, 0x31, 0x32, 0x25, 0xf8, 0x84, 0x06, 0xe5,
+    ///     0xa5, 0x59, 0x09, 0xc5, 0xaf, 0xf5, 0x26, 0x9a,
+    ///     0x86, 0xa7, 0xa9, 0x53, 0x15, 0x34, 0xf7, 0xda,
+    ///     0x2e, 0x4c, 0x30, 0x3d, 0x8a, 0x31, 0x8a, 0x72,
+    ///     0x1c, 0x3c, 0x0c, 0x95, 0x95, 0x68, 0x09, 0x53,
+    ///     0x2f, 0xcf, 0x0e, 0x24, 0x49, 0xa6, 0xb5, 0x25,
+    ///     0xb1, 0x6a, 0xed, 0xf5, 0xaa, 0x0d, 0xe6, 0x57,
+    ///     0xba, 0x63, 0x7b, 0x39,
+    /// ];
+    /// aes.set_key(&key).unwrap();
+    /// aes.set_mode(Type::EncExt).unwrap();
+    /// let tag = aes.encrypt_gcm(&nonce, &aad, &mut data).unwrap();
+    /// assert_eq!(data[..4], [0x42, 0x83, 0x1e, 0xc2]);
+    /// assert_eq!(tag, [
+    ///     0x5b, 0xc9, 0x4f, 0xbc, 0x32, 0x21, 0xa5, 0xdb,
+    ///     0x94, 0xfa, 0xe9, 0x5a, 0xe7, 0x12, 0x1a, 0x47,
+    /// ]);
+    ///
+    /// aes.decrypt_gcm(&nonce, &aad, &mut data, &tag).unwrap();
+    /// assert_eq!(data[..4], [0xd9, 0x31, 0x32, 0x25]);
+    /// ```
+    pub fn encrypt_gcm(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        data: &mut [u8],
+    ) -> Result<AesBlock, AesError> {
+        let (mut ghash, j0) = self._gcm_init(nonce)?;
+        let mut ctr_block = j0;
+        increment_u32(&mut ctr_block);
+        Ctr::new(self, ctr_block, CtrCounterWidth::Bits32).apply_keystream(data)?;
+        ghash.update(aad);
+        ghash.update(data);
+        let mut tag = ghash.finalize(aad.len(), data.len());
+        xor_block(&mut tag, &self._process_block(j0)?);
+        Ok(tag)
+    }
+
+    /// Verifies and decrypts `data` in place using Galois/Counter Mode
+    /// (GCM). See [`Aes::encrypt_gcm()`] for the parameters.
+    ///
+    /// The tag is checked in constant time before anything is decrypted.
+    /// If it does not match, [`AesError::TagMismatch`] is returned and
+    /// `data` is left holding the unmodified ciphertext.
+    ///
+    /// The mode must be set to [`Type::EncExt`], since GCM uses the
+    /// encryption direction for both encryption and decryption.
+    pub fn decrypt_gcm(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        data: &mut [u8],
+        tag: &AesBlock,
+    ) -> Result<(), AesError> {
+        let (mut ghash, j0) = self._gcm_init(nonce)?;
+        ghash.update(aad);
+        ghash.update(data);
+        let mut expected = ghash.finalize(aad.len(), data.len());
+        xor_block(&mut expected, &self._process_block(j0)?);
+        if !crate::crypto::ct_eq(&expected, tag) {
+            return Err(AesError::TagMismatch)
         }
+        let mut ctr_block = j0;
+        increment_u32(&mut ctr_block);
+        Ctr::new(self, ctr_block, CtrCounterWidth::Bits32).apply_keystream(data)
+    }
 
-        if !self._in_fifo_empty() {
-            return Err(AesError::NotEmpty)
+    /// Derives the hash key and the pre-counter block J0 from the nonce.
+    #[doc(hidden)]
+    fn _gcm_init(&self, nonce: &[u8]) -> Result<(Ghash, AesBlock), AesError> {
+        if nonce.is_empty() {
+            return Err(AesError::InvalidNonceLength)
         }
+        self._check_encrypt()?;
+        let ghash = Ghash::new(self._process_block([0u8; 16])?);
+        let j0 = if nonce.len() == 12 {
+            let mut j0 = [0u8; 16];
+            j0[..12].copy_from_slice(nonce);
+            j0[15] = 1;
+            j0
+        } else {
+            let mut nonce_hash = Ghash::new(ghash.h.to_be_bytes());
+            nonce_hash.update(nonce);
+            nonce_hash.finalize(0, nonce.len())
+        };
+        Ok((ghash, j0))
+    }
 
-        for subblock in in_block_32 {
-            self._set_in_fifo(subblock);
+    /// Computes the AES-CMAC of a message (RFC 4493), which authenticates
+    /// it with the configured key. Messages of any length are supported,
+    /// including empty messages. See [`Cmac`] for messages fed in pieces.
+    ///
+    /// The MAC should be verified with [`crate::crypto::ct_eq()`] to avoid
+    /// leaking it through timing.
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    ///
+    /// Example:
+    /// ```
+    /// // RFC 4493 section 4 test vectors
+    /// let key: [u8; 16] = [
+    ///     0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6,
+    ///     0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c,
+    /// ];
+    /// let msg: [u8; 40] = [
+    ///     0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96,
+    ///     0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a,
+    ///     0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c,
+    ///     0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf, 0x8e, 0x51,
+    ///     0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11,
+    /// ];
+    /// aes.set_key(&key).unwrap();
+    /// aes.set_mode(Type::EncExt).unwrap();
+    /// // Empty message, padded and masked with K2
+    /// assert_eq!(aes.cmac(&[]).unwrap(), [
+    ///     0xbb, 0x1d, 0x69, 0x29, 0xe9, 0x59, 0x37, 0x28,
+    ///     0x7f, 0xa3, 0x7d, 0x12, 0x9b, 0x75, 0x67, 0x46,
+    /// ]);
+    /// // One complete block, masked with K1
+    /// assert_eq!(aes.cmac(&msg[..16]).unwrap(), [
+    ///     0x07, 0x0a, 0x16, 0xb4, 0x6b, 0x4d, 0x41, 0x44,
+    ///     0xf7, 0x9b, 0xdd, 0x9d, 0xd0, 0x4a, 0x28, 0x7c,
+    /// ]);
+    /// assert_eq!(aes.cmac(&msg).unwrap(), [
+    ///     0xdf, 0xa6, 0x67, 0x47, 0xde, 0x9a, 0xe6, 0x30,
+    ///     0x30, 0xca, 0x32, 0x61, 0x14, 0x97, 0xc8, 0x27,
+    /// ]);
+    /// ```
+    pub fn cmac(&self, msg: &[u8]) -> Result<AesBlock, AesError> {
+        let mut cmac = Cmac::new(self)?;
+        cmac.update(msg)?;
+        cmac.finalize()
+    }
+
+    /// Encrypts a slice of blocks in place (ECB) using two DMA channels,
+    /// one to feed the input FIFO and one to drain the output FIFO.
+    ///
+    /// The returned [`AesDmaTransfer`] can be polled for completion, the
+    /// blocks must not be accessed until it has completed. If the blocks
+    /// are not word-aligned in memory, the memory side of the transfer
+    /// falls back to byte accesses.
+    ///
+    /// Returns [`AesError::InvalidDataLength`] if `data` is longer than
+    /// [`AES_DMA_MAX_LEN`] bytes; split larger buffers into several
+    /// transfers. The mode must be set to [`Type::EncExt`].
+    ///
+    /// # Safety
+    /// Only dropping the transfer stops the DMA channels, so it must not be
+    /// leaked (for example with [`core::mem::forget()`]). A leaked transfer
+    /// keeps reading and writing `data` after its borrow has ended.
+    ///
+    /// Example:
+    /// ```
+    /// let mut channels = hal::dma::Dma::new(p.dma, &mut gcr.reg).split();
+    /// let transfer = unsafe {
+    ///     aes.encrypt_blocks_dma(&mut channels.ch0, &mut channels.ch1, &mut blocks)
+    /// }.unwrap();
+    /// // Do other work while the blocks are encrypted
+    /// while !transfer.is_complete() {}
+    /// transfer.wait().unwrap();
+    /// ```
+    pub unsafe fn encrypt_blocks_dma<'a, const TX: usize, const RX: usize>(
+        &'a self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        data: &'a mut [AesBlock],
+    ) -> Result<AesDmaTransfer<'a, TX, RX>, AesError> {
+        self._check_encrypt()?;
+        unsafe { self._start_dma(tx, rx, data.as_flattened_mut()) }
+    }
+
+    /// Decrypts a slice of blocks in place (ECB) using two DMA channels.
+    /// See [`Aes::encrypt_blocks_dma`].
+    ///
+    /// The mode must be set to [`Type::DecExt`] or [`Type::DecInt`].
+    ///
+    /// # Safety
+    /// The transfer must not be leaked, see [`Aes::encrypt_blocks_dma`].
+    pub unsafe fn decrypt_blocks_dma<'a, const TX: usize, const RX: usize>(
+        &'a self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        data: &'a mut [AesBlock],
+    ) -> Result<AesDmaTransfer<'a, TX, RX>, AesError> {
+        self._check_decrypt()?;
+        unsafe { self._start_dma(tx, rx, data.as_flattened_mut()) }
+    }
+
+    /// Encrypts a byte buffer in place (ECB) using two DMA channels, like
+    /// [`Aes::encrypt_blocks_dma`]. Suited to large buffers such as
+    /// firmware images or packet payloads, which are streamed through the
+    /// FIFOs without the CPU.
+    ///
+    /// Returns [`AesError::InvalidDataLength`] unless the buffer is a
+    /// multiple of 16 bytes long and at most [`AES_DMA_MAX_LEN`] bytes. The
+    /// mode must be set to [`Type::EncExt`].
+    ///
+    /// # Safety
+    /// The transfer must not be leaked, see [`Aes::encrypt_blocks_dma`].
+    ///
+    /// Example:
+    /// ```
+    /// static mut IMAGE: [u8; 32 * 1024] = [0; 32 * 1024];
+    ///
+    /// let image = unsafe { &mut *core::ptr::addr_of_mut!(IMAGE) };
+    /// aes.set_mode(Type::EncExt).unwrap();
+    /// let transfer = unsafe { aes.encrypt_dma(&mut channels.ch0, &mut channels.ch1, image) }.unwrap();
+    /// // A plain feed on every poll would land in the early window of a
+    /// // windowed watchdog and reset the chip
+    /// transfer.wait_with(&mut || {
+    ///     if wdt.margin().until_early_ok == 0 {
+    ///         wdt.feed();
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub unsafe fn encrypt_dma<'a, const TX: usize, const RX: usize>(
+        &'a self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        data: &'a mut [u8],
+    ) -> Result<AesDmaTransfer<'a, TX, RX>, AesError> {
+        self._check_encrypt()?;
+        self._check_data_len(data)?;
+        unsafe { self._start_dma(tx, rx, data) }
+    }
+
+    /// Decrypts a byte buffer in place (ECB) using two DMA channels. See
+    /// [`Aes::encrypt_dma`].
+    ///
+    /// The mode must be set to [`Type::DecExt`] or [`Type::DecInt`].
+    ///
+    /// # Safety
+    /// The transfer must not be leaked, see [`Aes::encrypt_blocks_dma`].
+    pub unsafe fn decrypt_dma<'a, const TX: usize, const RX: usize>(
+        &'a self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        data: &'a mut [u8],
+    ) -> Result<AesDmaTransfer<'a, TX, RX>, AesError> {
+        self._check_decrypt()?;
+        self._check_data_len(data)?;
+        unsafe { self._start_dma(tx, rx, data) }
+    }
+
+    /// Enables the AES calculation done interrupt ([`crate::Interrupt::AES`]).
+    ///
+    /// The interrupt is raised once a block started with
+    /// [`Aes::start_block`] has been processed. The handler should call
+    /// [`Aes::take_result`], which also clears the interrupt flag.
+    ///
+    /// Example:
+    /// ```
+    /// aes.enable_interrupt();
+    /// unsafe { cortex_m::peripheral::NVIC::unmask(hal::Interrupt::AES) };
+    /// aes.start_block(plaintext).unwrap();
+    ///
+    /// #[interrupt]
+    /// fn AES() {
+    ///     // Access the Aes instance, e.g. through a critical section Mutex
+    ///     if let Some(block) = aes.take_result() {
+    ///         // ...
+    ///     }
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn enable_interrupt(&self) {
+        self.aes.intfl().write(|w| w.done().set_bit());
+        self.aes.inten().modify(|_, w| w.done().set_bit());
+    }
+
+    /// Disables the AES calculation done interrupt.
+    #[inline(always)]
+    pub fn disable_interrupt(&self) {
+        self.aes.inten().modify(|_, w| w.done().clear_bit());
+    }
+
+    /// Pushes a block into the input FIFO and returns immediately. The
+    /// block is processed in the currently configured mode.
+    ///
+    /// The result can be retrieved with [`Aes::read_block`] or
+    /// [`Aes::take_result`].
+    ///
+    /// Example (interleaved with other polling):
+    /// ```
+    /// aes.start_block(plaintext).unwrap();
+    /// let ciphertext = loop {
+    ///     match aes.read_block() {
+    ///         Ok(block) => break block,
+    ///         Err(nb::Error::WouldBlock) => uart_task.poll(),
+    ///         Err(nb::Error::Other(error)) => return Err(error),
+    ///     }
+    /// };
+    /// // Or block until it is done
+    /// aes.start_block(plaintext).unwrap();
+    /// let ciphertext = nb::block!(aes.read_block()).unwrap();
+    /// ```
+    #[inline(always)]
+    pub fn start_block(&self, in_block: AesBlock) -> Result<(), AesError> {
+        self._check_ready()?;
+        self._push_block(in_block)
+    }
+
+    /// Reads the block started with [`Aes::start_block`] from the output
+    /// FIFO and clears the calculation done interrupt flag. Returns
+    /// [`nb::Error::WouldBlock`] while the block is still being processed.
+    ///
+    /// Returns [`AesError::FifoUnderflow`] if the output FIFO runs empty
+    /// partway through the block, in which case the FIFOs must be reset
+    /// with [`Aes::reset_fifos()`].
+    pub fn read_block(&self) -> nb::Result<AesBlock, AesError> {
+        let status = self.status();
+        if status.busy || status.output_empty {
+            return Err(nb::Error::WouldBlock)
         }
+        let out_block = self._pop_block()?;
+        self.aes.intfl().write(|w| w.done().set_bit());
+        Ok(out_block)
+    }
 
-        self._wait();
+    /// Reads a processed block from the output FIFO, if one is available,
+    /// and clears the calculation done interrupt flag.
+    ///
+    /// Also returns [`None`] if the output FIFO runs empty partway through
+    /// the block, in which case the FIFOs must be reset with
+    /// [`Aes::reset_fifos()`]. See [`Aes::read_block`] to tell both cases
+    /// apart.
+    #[inline(always)]
+    pub fn take_result(&self) -> Option<AesBlock> {
+        self.read_block().ok()
+    }
+
+    /// Sets key for AES128, AES192, or AES256, depending on the key length.
+    /// The key size is stored in the peripheral and used by every later
+    /// block operation until the next key is set, see [`Aes::key_size()`].
+    ///
+    /// Returns [`AesError::InvalidKeyLength`] unless the key is 16, 24 or 32
+    /// bytes long, see [`AesKey128`], [`AesKey192`] and [`AesKey256`]. Only
+    /// the bytes of the key are loaded into the key registers, the
+    /// remaining key registers are zeroed. Returns [`AesError::Timeout`] if
+    /// the FIFOs could not be flushed because the peripheral stayed busy.
+    ///
+    /// Example:
+    /// ```
+    /// // FIPS-197 Appendix C.1 (AES-128)
+    /// let key: [u8; 16] = [
+    ///     0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+    ///     0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+    /// ];
+    /// let plaintext: AesBlock = [
+    ///     0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77,
+    ///     0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+    /// ];
+    /// aes.set_key(&key).unwrap();
+    /// let ciphertext = aes.encrypt_block(plaintext).unwrap();
+    /// assert_eq!(ciphertext, [
+    ///     0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30,
+    ///     0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4, 0xc5, 0x5a,
+    /// ]);
+    /// ```
+    #[inline(always)]
+    pub fn set_key(&self, key: &[u8]) -> Result<(), AesError> {
+        let variant = AesKeyVariant::from_key_len(key.len()).ok_or(AesError::InvalidKeyLength)?;
+        // Safety: The slice is valid for reads of its whole length
+        unsafe { self._load_key(key.as_ptr(), variant) }
+    }
 
-        for bidx in 0..out_block_32.len() {
-            out_block_32[bidx] = self._get_out_fifo();
+    /// Sets the key from `len` bytes at `src`, copying them straight into
+    /// the key registers.
+    ///
+    /// [`Aes::set_key()`] needs the key in a slice, which usually means a
+    /// copy of it on the stack that outlives the call and can later be
+    /// leaked, for example by a stack overflow or a memory disclosure bug.
+    /// Here each word of the key is read with volatile reads and written to
+    /// its key register, so the only copy made is the word in flight in a
+    /// CPU register. The source is not modified, see
+    /// [`Aes::set_key_and_scrub()`] for keys held in RAM.
+    ///
+    /// There is no DMA path into the key registers, since the DMA
+    /// controller is not documented to have access to them.
+    ///
+    /// # Safety
+    /// `src` must be valid for reads of `len` bytes. It does not need to be
+    /// aligned.
+    pub unsafe fn set_key_from_ptr(&self, src: *const u8, len: usize) -> Result<(), AesError> {
+        let variant = AesKeyVariant::from_key_len(len).ok_or(AesError::InvalidKeyLength)?;
+        self._load_key(src, variant)
+    }
+
+    /// Sets the key from `len` bytes of flash starting at `address`,
+    /// without copying it to RAM. See [`Aes::set_key_from_ptr()`].
+    ///
+    /// Returns [`AesError::InvalidKeyAddress`] if the key is not entirely
+    /// within the flash. The flash must not be written or erased while the
+    /// key is loaded.
+    ///
+    /// Example:
+    /// ```
+    /// // Key provisioned in the last flash page
+    /// aes.set_key_from_flash(0x1007_e000, 32).unwrap();
+    /// ```
+    pub fn set_key_from_flash(&self, address: u32, len: usize) -> Result<(), AesError> {
+        let variant = AesKeyVariant::from_key_len(len).ok_or(AesError::InvalidKeyLength)?;
+        let in_flash = address >= crate::flc::FLASH_BASE
+            && address
+                .checked_add(len as u32)
+                .is_some_and(|end| end <= crate::flc::FLASH_END);
+        if !in_flash {
+            return Err(AesError::InvalidKeyAddress);
         }
+        // Safety: The flash is always mapped and readable
+        unsafe { self._load_key(address as *const u8, variant) }
+    }
 
-        let out_block: [u8; 16] = convert_u32_to_u8_array(out_block_32);
-        Ok(out_block)
+    /// Sets the key, then overwrites `key` with zeroes, so that the key
+    /// registers hold the only copy. See [`Aes::set_key_from_ptr()`].
+    ///
+    /// The source is zeroed with volatile writes so they are not optimized
+    /// away, even if loading the key fails. Copies of the key made before
+    /// this call, such as the one it was decrypted or received into, are
+    /// not affected.
+    ///
+    /// Example:
+    /// ```
+    /// let mut key = [0u8; 32];
+    /// uart.read_bytes(&mut key).unwrap();
+    /// aes.set_key_and_scrub(&mut key).unwrap();
+    /// assert_eq!(key, [0u8; 32]);
+    /// ```
+    pub fn set_key_and_scrub(&self, key: &mut [u8]) -> Result<(), AesError> {
+        let result = self.set_key(key);
+        for byte in key.iter_mut() {
+            // Safety: The reference is valid for writes
+            unsafe { core::ptr::write_volatile(byte, 0) };
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        result
     }
 
-    /// Encrypts block with AES256
+    /// Zeroes all of the key registers. The key must be set again before
+    /// any further blocks can be processed.
+    ///
+    /// This is best-effort: the key registers are overwritten with volatile
+    /// writes so they are not optimized away, but copies of the key held
+    /// elsewhere (e.g. on the stack or in flash) are not affected.
     #[inline(always)]
-    pub fn encrypt_block(&self, in_block: AesBlock) -> Result<AesBlock, AesError> {
-        let in_block_32: [u32; 4] = convert_u8_to_u32_array(in_block);
-        let mut out_block_32: [u32; 4] = [0u32; 4];
+    pub fn zeroize_key(&self) {
+        for widx in 0..(AesKeyVariant::Bits256.key_len() / 4) {
+            self._set_key_register(widx, 0);
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Returns the currently configured key size.
+    #[inline(always)]
+    pub fn key_size(&self) -> Option<AesKeyVariant> {
+        self._get_key_size().map(AesKeyVariant::_from_key_size)
+    }
 
-        if self._get_mode() != Type::DecExt {
+    /// Sets mode, keeping the configured key size
+    ///
+    /// Returns [`AesError::Misconfigured`] if no valid key size is
+    /// configured, since none of the modes can be used without a key, and
+    /// [`AesError::Timeout`] if the peripheral stays busy. Switching modes
+    /// flushes both FIFOs.
+    ///
+    /// Example:
+    /// ```
+    /// aes.set_key(&key).unwrap();
+    /// aes.set_mode(Type::EncExt).unwrap();
+    /// let ciphertext = aes.encrypt_block(plaintext).unwrap();
+    /// aes.set_mode(Type::DecExt).unwrap();
+    /// let decrypted = aes.decrypt_block(ciphertext).unwrap();
+    /// assert_eq!(decrypted, plaintext);
+    /// ```
+    #[inline(always)]
+    pub fn set_mode(&self, mode: Type) -> Result<(), AesError> {
+        if self._get_key_size().is_none() {
             return Err(AesError::Misconfigured)
         }
+        self.aes.ctrl().modify(|_, w| w.en().clear_bit());
+        self.wait_timeout(AES_DEFAULT_TIMEOUT)?;
+        self._write_mode(mode);
+        self.wait_timeout(AES_DEFAULT_TIMEOUT)
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _write_mode(&self, mode: Type) {
+        self.aes.ctrl().modify(|_, w| {
+            w.type_().variant(mode);
+            w.input_flush().set_bit();
+            w.output_flush().set_bit();
+            w.dma_rx_en().clear_bit();
+            w.dma_tx_en().clear_bit();
+            w.en().set_bit()
+        });
+    }
+
+    /// Switches to encryption mode and returns an [`AesEncryptor`], which
+    /// can only encrypt with keys of size `K`. The key must already be set.
+    ///
+    /// Returns [`AesError::KeySizeMismatch`] if the configured key is not
+    /// of size `K`. On any error the peripheral is handed back along with
+    /// it, so the key can be fixed and the switch retried.
+    ///
+    /// Example:
+    /// ```
+    /// use hal::aes::Aes128;
+    ///
+    /// aes.set_key(&key_128).unwrap();
+    /// let encryptor = match aes.into_encryptor::<Aes128>() {
+    ///     Ok(encryptor) => encryptor,
+    ///     Err((_aes, err)) => panic!("{:?}", err),
+    /// };
+    /// ```
+    pub fn into_encryptor<K: AesKeySize>(self) -> Result<AesEncryptor<K>, (Aes, AesError)> {
+        if let Err(err) = self._check_key_size::<K>().and_then(|_| self.set_mode(Type::EncExt)) {
+            return Err((self, err))
+        }
+        Ok(AesEncryptor { aes: self, _key_size: PhantomData })
+    }
+
+    /// Switches to decryption mode using the external key and returns an
+    /// [`AesDecryptor`], which can only decrypt with keys of size `K`. The
+    /// key must already be set.
+    ///
+    /// Returns [`AesError::KeySizeMismatch`] if the configured key is not
+    /// of size `K`. On any error the peripheral is handed back along with
+    /// it, see [`Aes::into_encryptor()`].
+    pub fn into_decryptor<K: AesKeySize>(self) -> Result<AesDecryptor<K>, (Aes, AesError)> {
+        if let Err(err) = self._check_key_size::<K>().and_then(|_| self.set_mode(Type::DecExt)) {
+            return Err((self, err))
+        }
+        Ok(AesDecryptor { aes: self, _key_size: PhantomData })
+    }
+
+    /// Reads the AES status register in a single access. Useful for polling
+    /// the peripheral without blocking, e.g. from an async executor.
+    #[inline(always)]
+    pub fn status(&self) -> AesStatus {
+        let status = self.aes.status().read();
+        AesStatus {
+            busy: status.busy().bit_is_set(),
+            input_empty: status.input_em().bit_is_set(),
+            input_full: status.input_full().bit_is_set(),
+            output_empty: status.output_em().bit_is_set(),
+            output_full: status.output_full().bit_is_set(),
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_encrypt(&self) -> Result<(), AesError> {
+        _check_encrypt_mode(self._get_mode())?;
+        self._check_ready()
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_decrypt(&self) -> Result<(), AesError> {
+        _check_decrypt_mode(self._get_mode())?;
+        self._check_ready()
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_data_len(&self, data: &[u8]) -> Result<(), AesError> {
+        if !data.len().is_multiple_of(core::mem::size_of::<AesBlock>()) {
+            return Err(AesError::InvalidDataLength)
+        }
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_key_size<K: AesKeySize>(&self) -> Result<(), AesError> {
+        if self.key_size() != Some(K::VARIANT) {
+            return Err(AesError::KeySizeMismatch)
+        }
+        Ok(())
+    }
 
-        if self._get_key_size() != KeySize::Aes256 {
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_ready(&self) -> Result<(), AesError> {
+        if self._get_key_size().is_none() {
             return Err(AesError::Misconfigured)
         }
 
         if !self._in_fifo_empty() {
             return Err(AesError::NotEmpty)
         }
+        Ok(())
+    }
 
-        for subblock in in_block_32 {
+    /// Pushes a block through the FIFOs, giving up after
+    /// [`AES_DEFAULT_TIMEOUT`] polls. The mode and FIFOs must already have
+    /// been checked.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _process_block(&self, in_block: AesBlock) -> Result<AesBlock, AesError> {
+        self._process_block_with(in_block, &mut Spin)
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _process_block_with(
+        &self,
+        in_block: AesBlock,
+        strategy: &mut impl WaitStrategy,
+    ) -> Result<AesBlock, AesError> {
+        self._push_block(in_block)?;
+        self._wait_timeout_with(AES_DEFAULT_TIMEOUT, strategy)?;
+        self._pop_block()
+    }
+
+    /// Pushes a block into the input FIFO, checking before each word that
+    /// there is room for it.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _push_block(&self, in_block: AesBlock) -> Result<(), AesError> {
+        for subblock in convert_u8_to_u32_array(in_block) {
+            if self.aes.status().read().input_full().bit_is_set() {
+                return Err(AesError::FifoOverflow)
+            }
             self._set_in_fifo(subblock);
         }
+        Ok(())
+    }
 
-        self._wait();
+    /// Pops a block from the output FIFO, checking before each word that it
+    /// is available.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _pop_block(&self) -> Result<AesBlock, AesError> {
+        let mut out_block_32: [u32; 4] = [0u32; 4];
+        for subblock in out_block_32.iter_mut() {
+            if self._out_fifo_empty() {
+                return Err(AesError::FifoUnderflow)
+            }
+            *subblock = self._get_out_fifo();
+        }
+        Ok(convert_u32_to_u8_array(out_block_32))
+    }
 
-        for bidx in 0..out_block_32.len() {
-            out_block_32[bidx] = self._get_out_fifo();
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _process_blocks(&self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        for block in data.iter_mut() {
+            *block = self._process_block(*block)?;
+            // The output FIFO must be fully drained before the next block
+            if !self._out_fifo_empty() {
+                return Err(AesError::FifoOverflow)
+            }
         }
+        Ok(data.len())
+    }
 
-        let out_block: [u8; 16] = convert_u32_to_u8_array(out_block_32);
-        Ok(out_block)
+    #[doc(hidden)]
+    fn _process_ecb(&self, data: &mut [u8]) -> Result<(), AesError> {
+        self._check_data_len(data)?;
+        for chunk in data.chunks_exact_mut(core::mem::size_of::<AesBlock>()) {
+            let mut block = [0u8; 16];
+            block.copy_from_slice(chunk);
+            self._process_blocks(core::slice::from_mut(&mut block))?;
+            chunk.copy_from_slice(&block);
+        }
+        Ok(())
     }
 
-    /// Sets key for AES256
+    #[doc(hidden)]
+    fn _process_blocks_with(
+        &self,
+        data: &mut [AesBlock],
+        strategy: &mut impl WaitStrategy,
+    ) -> Result<usize, AesError> {
+        for block in data.iter_mut() {
+            *block = self._process_block_with(*block, strategy)?;
+            if !self._out_fifo_empty() {
+                return Err(AesError::FifoOverflow)
+            }
+            // Short blocks may finish before the first poll
+            strategy.wait();
+        }
+        Ok(data.len())
+    }
+
+    /// Safety: The returned transfer must be dropped before `data` is
+    /// released, see [`Aes::encrypt_blocks_dma`].
+    #[doc(hidden)]
+    unsafe fn _start_dma<'a, const TX: usize, const RX: usize>(
+        &'a self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        data: &'a mut [u8],
+    ) -> Result<AesDmaTransfer<'a, TX, RX>, AesError> {
+        let len = data.len();
+        if len > AES_DMA_MAX_LEN {
+            return Err(AesError::InvalidDataLength)
+        }
+        if len > 0 {
+            let addr = data.as_mut_ptr() as u32;
+            let fifo = self.aes.fifo().as_ptr() as u32;
+            let mem_width = if addr & 0b11 == 0 { TransferWidth::Word } else { TransferWidth::Byte };
+            // Safety: The caller guarantees the transfer, which stops both
+            // channels when dropped, is not leaked
+            unsafe {
+                rx.start(&TransferConfig {
+                    request: Request::Aesrx,
+                    src: fifo,
+                    src_width: TransferWidth::Word,
+                    src_increment: false,
+                    dst: addr,
+                    dst_width: mem_width,
+                    dst_increment: true,
+                    len: len as u32,
+                    burst_size: 16,
+                });
+                tx.start(&TransferConfig {
+                    request: Request::Aestx,
+                    src: addr,
+                    src_width: mem_width,
+                    src_increment: true,
+                    dst: fifo,
+                    dst_width: TransferWidth::Word,
+                    dst_increment: false,
+                    len: len as u32,
+                    burst_size: 16,
+                });
+            }
+            self.aes.ctrl().modify(|_, w| {
+                w.dma_rx_en().set_bit();
+                w.dma_tx_en().set_bit()
+            });
+        }
+        Ok(AesDmaTransfer { aes: self, tx, rx, _data: PhantomData })
+    }
+
+    #[doc(hidden)]
     #[inline(always)]
-    pub fn set_key(&self, key: &AesKey) {
+    fn _set_key_register(&self, widx: usize, word: u32) {
         unsafe {
-            for i in 0..256 {
-                core::ptr::write_volatile::<u32>((AES_KEY_REGISTER_ADDR + (i * 4)) as *mut u32, 0u32);
+            core::ptr::write_volatile::<u32>((AES_KEY_REGISTER_ADDR + (widx * 4)) as *mut u32, word);
+        }
+    }
+
+    /// Load a key of `variant` from `src` into the key registers, zeroing
+    /// any unused key registers, and select encryption mode.
+    ///
+    /// Safety: `src` must be valid for reads of the key length.
+    #[doc(hidden)]
+    #[inline(always)]
+    unsafe fn _load_key(&self, src: *const u8, variant: AesKeyVariant) -> Result<(), AesError> {
+        for widx in 0..(AesKeyVariant::Bits256.key_len() / 4) {
+            let mut word = 0u32;
+            if widx * 4 < variant.key_len() {
+                for bidx in 0..4 {
+                    let byte = core::ptr::read_volatile(src.add(widx * 4 + bidx));
+                    word |= (byte as u32) << (bidx * 8);
+                }
             }
-            core::ptr::copy_nonoverlapping::<u8>(key.as_ptr(), AES_KEY_REGISTER_ADDR as *mut u8, key.len());    
+            self._set_key_register(widx, word);
         }
 
         self.aes.ctrl().modify(|_, w| w.en().clear_bit());
-        self._flush();
+        self._flush()?;
 
         self.aes.ctrl().modify(|_, w| {
-            w.key_size().aes256();
-            w.type_().variant(Type::EncExt);
-            return w;
+            w.key_size().variant(variant._key_size());
+            w.type_().variant(Type::EncExt)
         });
 
         self.aes.ctrl().modify(|_, w| w.en().set_bit());
+        Ok(())
     }
 
-    /// Sets mode for AES256
+    #[doc(hidden)]
     #[inline(always)]
-    pub fn set_mode(&self, mode: Type) {
+    fn _disable(&self) {
         self.aes.ctrl().modify(|_, w| w.en().clear_bit());
-        self._wait();
         self.aes.ctrl().modify(|_, w| {
-            w.type_().variant(mode);
-            w.key_size().aes256();
             w.input_flush().set_bit();
-            w.output_flush().set_bit();
-            w.dma_rx_en().clear_bit();
-            w.dma_tx_en().clear_bit();
-            w.en().set_bit();
-
-            return w;
+            w.output_flush().set_bit()
         });
-        self._wait();
+        // Best-effort: do not hang while dropping a wedged peripheral
+        let _ = self.wait_timeout(AES_DEFAULT_TIMEOUT);
+        self.zeroize_key();
     }
 
     #[doc(hidden)]
@@ -159,61 +1181,1266 @@ impl Aes {
         while self._is_busy() {}
     }
 
+    /// Blocks until the AES peripheral is no longer busy, without a timeout.
+    #[inline(always)]
+    pub fn wait(&self) {
+        self._wait();
+    }
+
+    /// Blocks until the AES peripheral is no longer busy, polling the
+    /// status at most `max_polls` times before returning
+    /// [`AesError::Timeout`].
+    #[inline(always)]
+    pub fn wait_timeout(&self, max_polls: u32) -> Result<(), AesError> {
+        self._wait_timeout_with(max_polls, &mut Spin)
+    }
+
+    /// Blocks until the AES peripheral is no longer busy, running
+    /// `strategy` between polls, without a timeout.
+    pub fn wait_with(&self, strategy: &mut impl WaitStrategy) {
+        while self._is_busy() {
+            strategy.wait();
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _wait_timeout_with(
+        &self,
+        max_polls: u32,
+        strategy: &mut impl WaitStrategy,
+    ) -> Result<(), AesError> {
+        for _ in 0..max_polls {
+            if !self._is_busy() {
+                return Ok(())
+            }
+            strategy.wait();
+        }
+        Err(AesError::Timeout)
+    }
+
     #[doc(hidden)]
     #[inline(always)]
     fn _is_busy(&self) -> bool {
-        self.aes.status().read().busy().bit_is_set()
+        self.status().busy
     }
 
     #[doc(hidden)]
     #[inline(always)]
     fn _in_fifo_empty(&self) -> bool {
-        self.aes.status().read().input_em().bit_is_set()
+        self.status().input_empty
     }
 
     #[doc(hidden)]
     #[inline(always)]
     fn _out_fifo_empty(&self) -> bool {
-        self.aes.status().read().output_em().bit_is_set()
+        self.status().output_empty
     }
 
     #[doc(hidden)]
     #[inline(always)]
     fn _out_fifo_full(&self) -> bool {
-        self.aes.status().read().output_full().bit_is_set()
+        self.status().output_full
     }
 
     #[doc(hidden)]
     #[inline(always)]
-    fn _get_key_size(&self) -> KeySize {
-        self.aes.ctrl().read().key_size().variant().unwrap()
+    fn _get_key_size(&self) -> Option<KeySize> {
+        self.aes.ctrl().read().key_size().variant()
     }
 
     #[doc(hidden)]
     #[inline(always)]
-    fn _get_mode(&self) -> Type {
-        self.aes.ctrl().read().type_().variant().unwrap()
+    fn _get_mode(&self) -> Option<Type> {
+        self.aes.ctrl().read().type_().variant()
+    }
+
+    /// Flushes both FIFOs and clears the calculation done flag, bringing
+    /// the FIFOs back in step after [`AesError::FifoOverflow`] or
+    /// [`AesError::FifoUnderflow`]. The key and mode are kept, but any
+    /// block in flight is lost, as is the chaining state of modes such as
+    /// CBC, which must be restarted.
+    ///
+    /// Returns [`AesError::Timeout`] if the peripheral stays busy, and
+    /// [`AesError::NotEmpty`] if a FIFO is still not empty afterwards, for
+    /// example because a DMA transfer keeps feeding it.
+    ///
+    /// Example:
+    /// ```
+    /// match aes.encrypt_block(block) {
+    ///     Err(AesError::FifoOverflow | AesError::FifoUnderflow) => {
+    ///         aes.reset_fifos().unwrap();
+    ///         // Retry from the start of the message
+    ///     }
+    ///     result => { /* ... */ }
+    /// }
+    /// ```
+    pub fn reset_fifos(&self) -> Result<(), AesError> {
+        self.aes.ctrl().modify(|_, w| {
+            w.input_flush().set_bit();
+            w.output_flush().set_bit()
+        });
+        self.wait_timeout(AES_DEFAULT_TIMEOUT)?;
+        self.aes.intfl().write(|w| w.done().set_bit());
+        if !self._in_fifo_empty() || !self._out_fifo_empty() {
+            return Err(AesError::NotEmpty)
+        }
+        Ok(())
     }
 
     #[doc(hidden)]
     #[inline(always)]
-    fn _flush(&self) {
+    fn _flush(&self) -> Result<(), AesError> {
         if !self._in_fifo_empty() {
             self.aes.ctrl().modify(|_, w| w.input_flush().set_bit());
         }
         if !self._out_fifo_empty() {
             self.aes.ctrl().modify(|_, w| w.output_flush().set_bit());
         }
-        self._wait();
+        self.wait_timeout(AES_DEFAULT_TIMEOUT)
+    }
+}
+
+/// Waker of the async operation waiting for the current block.
+#[cfg(feature = "async")]
+static AES_WAKER: critical_section::Mutex<RefCell<Option<Waker>>> =
+    critical_section::Mutex::new(RefCell::new(None));
+
+/// # Async Methods
+/// These methods wait for each block on the calculation done interrupt
+/// instead of polling, so an async executor can run other tasks or sleep
+/// in the meantime. The application must call [`on_interrupt()`] from its
+/// `AES` interrupt handler and unmask the interrupt in the NVIC.
+///
+/// Requires the `async` feature.
+///
+/// Example:
+/// ```
+/// #[interrupt]
+/// fn AES() {
+///     hal::aes::on_interrupt();
+/// }
+///
+/// unsafe { cortex_m::peripheral::NVIC::unmask(hal::Interrupt::AES) };
+/// aes.set_mode(Type::EncExt).unwrap();
+/// aes.encrypt(&mut blocks).await.unwrap();
+/// ```
+#[cfg(feature = "async")]
+impl Aes {
+    /// Encrypts each block of a slice in place (ECB), returning the number
+    /// of blocks processed. See [`Aes::encrypt_blocks()`].
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    pub async fn encrypt(&mut self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        self._check_encrypt()?;
+        self._process_blocks_async(data).await
+    }
+
+    /// Decrypts each block of a slice in place (ECB), returning the number
+    /// of blocks processed. See [`Aes::decrypt_blocks()`].
+    ///
+    /// The mode must be set to [`Type::DecExt`] or [`Type::DecInt`].
+    pub async fn decrypt(&mut self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        self._check_decrypt()?;
+        self._process_blocks_async(data).await
+    }
+
+    // Taking `&mut self` keeps a second operation from replacing the waker
+    #[doc(hidden)]
+    async fn _process_blocks_async(&mut self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        for block in data.iter_mut() {
+            // A stale flag would wake the task before the block is done
+            self.aes.intfl().write(|w| w.done().set_bit());
+            self._push_block(*block)?;
+            *block = core::future::poll_fn(|cx| match self.read_block() {
+                Ok(block) => Poll::Ready(Ok(block)),
+                Err(nb::Error::Other(error)) => Poll::Ready(Err(error)),
+                Err(nb::Error::WouldBlock) => {
+                    critical_section::with(|cs| {
+                        AES_WAKER.borrow_ref_mut(cs).replace(cx.waker().clone())
+                    });
+                    self.aes.inten().modify(|_, w| w.done().set_bit());
+                    // The block may have finished before the waker was stored
+                    if self.aes.intfl().read().done().bit_is_set() {
+                        cx.waker().wake_by_ref();
+                    }
+                    Poll::Pending
+                }
+            })
+            .await?;
+            if !self._out_fifo_empty() {
+                return Err(AesError::FifoOverflow)
+            }
+        }
+        Ok(data.len())
+    }
+}
+
+/// Wakes the async AES operation waiting for the current block, such as
+/// [`Aes::encrypt()`]. Must be called from the `AES` interrupt handler.
+///
+/// The calculation done interrupt is disabled until the operation waits
+/// again, and the flag is left set for it to read.
+#[cfg(feature = "async")]
+pub fn on_interrupt() {
+    // Safety: Only the interrupt enable bit is changed, which the driver
+    // sets again before each wait
+    let aes = unsafe { &*crate::pac::Aes::ptr() };
+    aes.inten().modify(|_, w| w.done().clear_bit());
+    if let Some(waker) = critical_section::with(|cs| AES_WAKER.borrow_ref_mut(cs).take()) {
+        waker.wake();
+    }
+}
+
+/// # AES Encryptor
+///
+/// An [`Aes`] peripheral fixed in encryption mode with a key of size `K`,
+/// created with [`Aes::into_encryptor()`]. Since the mode and key size
+/// cannot change while it is held, its methods do not check the mode at
+/// runtime, and keys of the wrong size do not compile.
+///
+/// Example:
+/// ```
+/// use hal::aes::Aes256;
+///
+/// aes.set_key(&key).unwrap();
+/// let encryptor = aes.into_encryptor::<Aes256>().map_err(|(_, err)| err).unwrap();
+/// let ciphertext = encryptor.process_block(plaintext).unwrap();
+/// let decryptor = encryptor.into_decryptor().map_err(|(_, err)| err).unwrap();
+/// assert_eq!(decryptor.process_block(ciphertext).unwrap(), plaintext);
+/// // Rekeying takes a key of the same size only
+/// decryptor.set_key(&[0u8; 32]).unwrap();
+/// let aes = decryptor.into_inner();
+/// ```
+pub struct AesEncryptor<K: AesKeySize> {
+    aes: Aes,
+    _key_size: PhantomData<K>,
+}
+
+impl<K: AesKeySize> AesEncryptor<K> {
+    /// Encrypts a single block.
+    #[inline(always)]
+    pub fn process_block(&self, block: AesBlock) -> Result<AesBlock, AesError> {
+        self.aes._check_ready()?;
+        self.aes._process_block(block)
+    }
+
+    /// Encrypts each block of a slice in place (ECB), returning the number
+    /// of blocks processed.
+    pub fn process_blocks(&self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        self.aes._check_ready()?;
+        self.aes._process_blocks(data)
+    }
+
+    /// Encrypts a byte buffer in place (ECB) using two DMA channels. See
+    /// [`Aes::encrypt_dma`].
+    ///
+    /// # Safety
+    /// The transfer must not be leaked, see [`Aes::encrypt_blocks_dma`].
+    pub unsafe fn process_dma<'a, const TX: usize, const RX: usize>(
+        &'a self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        data: &'a mut [u8],
+    ) -> Result<AesDmaTransfer<'a, TX, RX>, AesError> {
+        self.aes._check_ready()?;
+        self.aes._check_data_len(data)?;
+        unsafe { self.aes._start_dma(tx, rx, data) }
+    }
+
+    /// Creates a [`Ctr`] keystream generator starting from the given
+    /// counter block.
+    pub fn ctr(&self, counter: AesBlock, width: CtrCounterWidth) -> Ctr<'_> {
+        Ctr::new(&self.aes, counter, width)
+    }
+
+    /// Replaces the key with another key of the same size. See
+    /// [`Aes::set_key()`].
+    pub fn set_key(&self, key: &K::Key) -> Result<(), AesError> {
+        self.aes.set_key(key.as_ref())
+    }
+
+    /// Switches to decryption mode. See [`Aes::into_decryptor()`].
+    ///
+    /// If the mode cannot be switched, the encryptor is handed back along
+    /// with the error, with encryption mode selected again.
+    pub fn into_decryptor(self) -> Result<AesDecryptor<K>, (Self, AesError)> {
+        self.aes.into_decryptor().map_err(|(aes, err)| {
+            aes._write_mode(Type::EncExt);
+            (Self { aes, _key_size: PhantomData }, err)
+        })
+    }
+
+    /// Returns the underlying [`Aes`] peripheral for dynamic mode switching.
+    pub fn into_inner(self) -> Aes {
+        self.aes
+    }
+}
+
+/// # AES Decryptor
+///
+/// An [`Aes`] peripheral fixed in decryption mode with a key of size `K`,
+/// created with [`Aes::into_decryptor()`]. See [`AesEncryptor`].
+pub struct AesDecryptor<K: AesKeySize> {
+    aes: Aes,
+    _key_size: PhantomData<K>,
+}
+
+impl<K: AesKeySize> AesDecryptor<K> {
+    /// Decrypts a single block.
+    #[inline(always)]
+    pub fn process_block(&self, block: AesBlock) -> Result<AesBlock, AesError> {
+        self.aes._check_ready()?;
+        self.aes._process_block(block)
+    }
+
+    /// Decrypts each block of a slice in place (ECB), returning the number
+    /// of blocks processed.
+    pub fn process_blocks(&self, data: &mut [AesBlock]) -> Result<usize, AesError> {
+        self.aes._check_ready()?;
+        self.aes._process_blocks(data)
+    }
+
+    /// Decrypts a byte buffer in place (ECB) using two DMA channels. See
+    /// [`Aes::encrypt_dma`].
+    ///
+    /// # Safety
+    /// The transfer must not be leaked, see [`Aes::encrypt_blocks_dma`].
+    pub unsafe fn process_dma<'a, const TX: usize, const RX: usize>(
+        &'a self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        data: &'a mut [u8],
+    ) -> Result<AesDmaTransfer<'a, TX, RX>, AesError> {
+        self.aes._check_ready()?;
+        self.aes._check_data_len(data)?;
+        unsafe { self.aes._start_dma(tx, rx, data) }
+    }
+
+    /// Replaces the key with another key of the same size, staying in
+    /// decryption mode. See [`Aes::set_key()`].
+    pub fn set_key(&self, key: &K::Key) -> Result<(), AesError> {
+        // Loading a key selects encryption mode
+        self.aes.set_key(key.as_ref())?;
+        self.aes.set_mode(Type::DecExt)
+    }
+
+    /// Switches to encryption mode. See [`Aes::into_encryptor()`].
+    ///
+    /// If the mode cannot be switched, the decryptor is handed back along
+    /// with the error, with decryption mode selected again.
+    pub fn into_encryptor(self) -> Result<AesEncryptor<K>, (Self, AesError)> {
+        self.aes.into_encryptor().map_err(|(aes, err)| {
+            aes._write_mode(Type::DecExt);
+            (Self { aes, _key_size: PhantomData }, err)
+        })
+    }
+
+    /// Returns the underlying [`Aes`] peripheral for dynamic mode switching.
+    pub fn into_inner(self) -> Aes {
+        self.aes
+    }
+}
+
+/// # AES DMA Transfer
+///
+/// An in-progress DMA transfer started by [`Aes::encrypt_blocks_dma`],
+/// [`Aes::encrypt_dma`] or one of their decrypting counterparts. The
+/// blocks and DMA channels stay borrowed
+/// until the transfer is dropped. Dropping an incomplete transfer stops both
+/// DMA channels, which is why the transfer must never be leaked.
+pub struct AesDmaTransfer<'a, const TX: usize, const RX: usize> {
+    aes: &'a Aes,
+    tx: &'a mut Channel<TX>,
+    rx: &'a mut Channel<RX>,
+    _data: PhantomData<&'a mut [u8]>,
+}
+
+impl<const TX: usize, const RX: usize> AesDmaTransfer<'_, TX, RX> {
+    /// Returns [`true`] once every block has been written back to memory.
+    #[inline(always)]
+    pub fn is_complete(&self) -> bool {
+        !self.tx.is_busy() && !self.rx.is_busy()
+    }
+
+    /// Blocks until the transfer has completed, then returns the AES
+    /// peripheral to the polled mode of operation.
+    pub fn wait(self) -> Result<(), AesError> {
+        self.wait_with(&mut Spin)
+    }
+
+    /// Blocks until the transfer has completed like
+    /// [`AesDmaTransfer::wait()`], running `strategy` between polls.
+    pub fn wait_with(self, strategy: &mut impl WaitStrategy) -> Result<(), AesError> {
+        while !self.is_complete() {
+            strategy.wait();
+        }
+        if self.tx.bus_error() || self.rx.bus_error() {
+            return Err(AesError::DmaBusError)
+        }
+        Ok(())
+    }
+}
+
+impl<const TX: usize, const RX: usize> Drop for AesDmaTransfer<'_, TX, RX> {
+    fn drop(&mut self) {
+        self.tx.stop();
+        self.rx.stop();
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        self.aes.aes.ctrl().modify(|_, w| {
+            w.dma_rx_en().clear_bit();
+            w.dma_tx_en().clear_bit()
+        });
+    }
+}
+
+/// Number of low bits of the counter block that are incremented in CTR mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CtrCounterWidth {
+    /// The low 32 bits are a big-endian counter, the high 96 bits are the nonce.
+    Bits32,
+    /// The low 64 bits are a big-endian counter, the high 64 bits are the nonce.
+    Bits64,
+}
+
+/// # AES Counter (CTR) Mode
+///
+/// Generates a keystream by encrypting successive counter blocks, which can
+/// be applied to messages of any length. Leftover keystream bytes from a
+/// partial block are buffered and used by the next call.
+///
+/// Example:
+/// ```
+/// aes.set_key(&key).unwrap();
+/// aes.set_mode(Type::EncExt).unwrap();
+/// let mut ctr = Ctr::new(&aes, initial_counter, CtrCounterWidth::Bits32);
+/// let mut message = *b"Hello, world!";
+/// ctr.apply_keystream(&mut message).unwrap();
+/// ```
+pub struct Ctr<'a> {
+    aes: &'a Aes,
+    counter: AesBlock,
+    width: CtrCounterWidth,
+    keystream: AesBlock,
+    keystream_pos: usize,
+}
+
+impl<'a> Ctr<'a> {
+    /// Create a new CTR mode instance starting from the given counter block.
+    ///
+    /// The AES peripheral must be in [`Type::EncExt`] mode, since CTR mode
+    /// uses the encryption direction for both encryption and decryption.
+    pub fn new(aes: &'a Aes, counter: AesBlock, width: CtrCounterWidth) -> Self {
+        Self {
+            aes,
+            counter,
+            width,
+            keystream: [0u8; 16],
+            keystream_pos: 16,
+        }
+    }
+
+    /// Returns the next counter block to be encrypted.
+    pub fn counter(&self) -> AesBlock {
+        self.counter
+    }
+
+    /// XORs the keystream over `data`, continuing from where the previous
+    /// call left off.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), AesError> {
+        for byte in data.iter_mut() {
+            if self.keystream_pos == self.keystream.len() {
+                self.keystream = self.aes.encrypt_block(self.counter)?;
+                self.keystream_pos = 0;
+                self._increment_counter();
+            }
+            *byte ^= self.keystream[self.keystream_pos];
+            self.keystream_pos += 1;
+        }
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _increment_counter(&mut self) {
+        match self.width {
+            CtrCounterWidth::Bits32 => increment_u32(&mut self.counter),
+            CtrCounterWidth::Bits64 => {
+                let mut low = [0u8; 8];
+                low.copy_from_slice(&self.counter[8..16]);
+                let low = u64::from_be_bytes(low).wrapping_add(1);
+                self.counter[8..16].copy_from_slice(&low.to_be_bytes());
+            }
+        }
+    }
+}
+
+/// # AES-CBC
+///
+/// Encrypts and decrypts byte buffers with cipher block chaining (CBC),
+/// switching the mode of the peripheral as needed. Each call to
+/// [`AesCbc::encrypt()`] or [`AesCbc::decrypt()`] starts a new message
+/// from an IV, and [`AesCbc::encrypt_next()`] or [`AesCbc::decrypt_next()`]
+/// continue it from the last ciphertext block, for messages processed in
+/// pieces.
+///
+/// Buffers must be a multiple of 16 bytes long, padding is left to the
+/// protocol. See [`Aes::encrypt_cbc()`] for slices of blocks.
+///
+/// Example:
+/// ```
+/// aes.set_key(&key).unwrap();
+/// let mut cbc = AesCbc::new(&aes);
+/// cbc.encrypt(iv, &mut message).unwrap();
+///
+/// // The same message in two pieces
+/// cbc.decrypt(iv, &mut message[..32]).unwrap();
+/// cbc.decrypt_next(&mut message[32..]).unwrap();
+/// ```
+pub struct AesCbc<'a> {
+    aes: &'a Aes,
+    chain: AesBlock,
+}
+
+impl<'a> AesCbc<'a> {
+    /// Wraps an AES peripheral with a key already set.
+    pub fn new(aes: &'a Aes) -> Self {
+        Self { aes, chain: [0u8; 16] }
+    }
+
+    /// Encrypts `data` in place as a new message starting from `iv`.
+    ///
+    /// Returns [`AesError::InvalidDataLength`] unless `data` is a multiple
+    /// of 16 bytes long, in which case it is left unchanged.
+    pub fn encrypt(&mut self, iv: AesBlock, data: &mut [u8]) -> Result<(), AesError> {
+        self._encrypt(iv, data)
+    }
+
+    /// Encrypts `data` in place as the continuation of the current message.
+    pub fn encrypt_next(&mut self, data: &mut [u8]) -> Result<(), AesError> {
+        self._encrypt(self.chain, data)
+    }
+
+    /// Decrypts `data` in place as a new message starting from `iv`.
+    ///
+    /// Returns [`AesError::InvalidDataLength`] unless `data` is a multiple
+    /// of 16 bytes long, in which case it is left unchanged.
+    pub fn decrypt(&mut self, iv: AesBlock, data: &mut [u8]) -> Result<(), AesError> {
+        self._decrypt(iv, data)
+    }
+
+    /// Decrypts `data` in place as the continuation of the current message.
+    pub fn decrypt_next(&mut self, data: &mut [u8]) -> Result<(), AesError> {
+        self._decrypt(self.chain, data)
+    }
+
+    /// Returns the last ciphertext block, which chains into the next call.
+    #[inline(always)]
+    pub fn next_iv(&self) -> AesBlock {
+        self.chain
+    }
+
+    /// Encrypts with [`Aes::encrypt_cbc()`], only moving the chain on
+    /// success so a rejected buffer leaves the message state untouched.
+    #[doc(hidden)]
+    fn _encrypt(&mut self, iv: AesBlock, data: &mut [u8]) -> Result<(), AesError> {
+        self.aes._check_data_len(data)?;
+        self._ensure_mode(Type::EncExt)?;
+        let (blocks, _) = data.as_chunks_mut();
+        self.aes.encrypt_cbc(iv, blocks)?;
+        self.chain = blocks.last().copied().unwrap_or(iv);
+        Ok(())
+    }
+
+    /// Decrypts with [`Aes::decrypt_cbc()`], see [`AesCbc::_encrypt()`].
+    #[doc(hidden)]
+    fn _decrypt(&mut self, iv: AesBlock, data: &mut [u8]) -> Result<(), AesError> {
+        self.aes._check_data_len(data)?;
+        self._ensure_mode(Type::DecExt)?;
+        let (blocks, _) = data.as_chunks_mut();
+        // The last ciphertext block chains into the next call
+        let chain = blocks.last().copied().unwrap_or(iv);
+        self.aes.decrypt_cbc(iv, blocks)?;
+        self.chain = chain;
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _ensure_mode(&self, mode: Type) -> Result<(), AesError> {
+        if self.aes._get_mode() != Some(mode) {
+            self.aes.set_mode(mode)?;
+        }
+        self.aes._check_ready()
+    }
+}
+
+/// # AES-CMAC
+///
+/// Computes an AES-CMAC (RFC 4493) over data fed in pieces of any length,
+/// for messages that are not contiguous in memory or are read in chunks.
+/// The last block is held back until [`Cmac::finalize()`], since it is
+/// masked differently from the others.
+///
+/// Example:
+/// ```
+/// aes.set_mode(Type::EncExt).unwrap();
+/// let mut cmac = Cmac::new(&aes).unwrap();
+/// cmac.update(&header).unwrap();
+/// cmac.update(&payload).unwrap();
+/// // Same as aes.cmac() over header and payload concatenated
+/// let mac = cmac.finalize().unwrap();
+/// ```
+pub struct Cmac<'a> {
+    aes: &'a Aes,
+    k1: AesBlock,
+    k2: AesBlock,
+    mac: AesBlock,
+    buffer: AesBlock,
+    buffered: usize,
+}
+
+impl<'a> Cmac<'a> {
+    /// Start a new MAC computation, deriving the subkeys from the key.
+    ///
+    /// The mode must be set to [`Type::EncExt`].
+    pub fn new(aes: &'a Aes) -> Result<Self, AesError> {
+        aes._check_encrypt()?;
+        // Subkeys K1 and K2 are derived by doubling E(K, 0)
+        let k1 = gf128_double(aes._process_block([0u8; 16])?);
+        let k2 = gf128_double(k1);
+        Ok(Self {
+            aes,
+            k1,
+            k2,
+            mac: [0u8; 16],
+            buffer: [0u8; 16],
+            buffered: 0,
+        })
+    }
+
+    /// Feed the next part of the message.
+    pub fn update(&mut self, data: &[u8]) -> Result<(), AesError> {
+        let mut rest = data;
+        while !rest.is_empty() {
+            // A full buffer is only processed once more data shows that it
+            // is not the last block
+            if self.buffered == self.buffer.len() {
+                xor_block(&mut self.mac, &self.buffer);
+                self.mac = self.aes._process_block(self.mac)?;
+                self.buffered = 0;
+            }
+            let len = rest.len().min(self.buffer.len() - self.buffered);
+            self.buffer[self.buffered..self.buffered + len].copy_from_slice(&rest[..len]);
+            self.buffered += len;
+            rest = &rest[len..];
+        }
+        Ok(())
+    }
+
+    /// Finish the computation and return the MAC.
+    pub fn finalize(mut self) -> Result<AesBlock, AesError> {
+        // The last block is masked with K1 if it is complete and padded and
+        // masked with K2 otherwise, so an empty message is one padded block
+        let mut last = [0u8; 16];
+        last[..self.buffered].copy_from_slice(&self.buffer[..self.buffered]);
+        if self.buffered == last.len() {
+            xor_block(&mut last, &self.k1);
+        } else {
+            last[self.buffered] = 0x80;
+            xor_block(&mut last, &self.k2);
+        }
+        xor_block(&mut self.mac, &last);
+        self.aes._process_block(self.mac)
+    }
+}
+
+/// GHASH universal hash used by GCM, computed in software. The multiply
+/// does not branch on the key or the data.
+struct Ghash {
+    h: u128,
+    y: u128,
+}
+
+impl Ghash {
+    fn new(h: AesBlock) -> Self {
+        Self {
+            h: u128::from_be_bytes(h),
+            y: 0,
+        }
+    }
+
+    /// Hashes `data`, zero-padded to a whole number of blocks.
+    fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.y = gf128_mul(self.y ^ u128::from_be_bytes(block), self.h);
+        }
+    }
+
+    /// Hashes the bit lengths of the AAD and the ciphertext and returns
+    /// the result.
+    fn finalize(mut self, aad_len: usize, data_len: usize) -> AesBlock {
+        let lengths = ((aad_len as u128 * 8) << 64) | (data_len as u128 * 8);
+        self.y = gf128_mul(self.y ^ lengths, self.h);
+        self.y.to_be_bytes()
+    }
+}
+
+/// Multiplies two elements of GF(2^128) in the bit order used by GCM, where
+/// the most significant bit is the coefficient of x^0.
+fn gf128_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut z = 0u128;
+    let mut v = y;
+    for i in (0..128).rev() {
+        // Masks instead of branches keep the timing independent of the data
+        z ^= v & 0u128.wrapping_sub((x >> i) & 1);
+        v = (v >> 1) ^ (R & 0u128.wrapping_sub(v & 1));
+    }
+    z
+}
+
+/// Multiplies an element of GF(2^128) by x in the bit order used by CMAC,
+/// where the most significant bit is the coefficient of x^127.
+fn gf128_double(block: AesBlock) -> AesBlock {
+    const R: u128 = 0x87;
+    let value = u128::from_be_bytes(block);
+    ((value << 1) ^ (R & 0u128.wrapping_sub(value >> 127))).to_be_bytes()
+}
+
+/// Increments the low 32 bits of a block as a big-endian counter.
+#[inline(always)]
+fn increment_u32(block: &mut AesBlock) {
+    let mut low = [0u8; 4];
+    low.copy_from_slice(&block[12..16]);
+    let low = u32::from_be_bytes(low).wrapping_add(1);
+    block[12..16].copy_from_slice(&low.to_be_bytes());
+}
+
+/// # RustCrypto Block Cipher
+///
+/// Wrapper around [`Aes`] implementing the block cipher traits from the
+/// [`cipher`] crate, so the peripheral can be used with generic mode
+/// implementations from the RustCrypto ecosystem. This can be enabled with
+/// the `cipher` feature flag.
+///
+/// The key must be set before wrapping the peripheral. The mode is switched
+/// automatically between encryption and decryption as needed.
+///
+/// The traits cannot report errors. A mode switch or block that fails, for
+/// example because the FIFOs were left out of step, is retried after
+/// [`Aes::reset_fifos()`], up to [`AES_CIPHER_ATTEMPTS`] times. If every
+/// attempt fails the error is latched instead of panicking: the block and
+/// every block after it are output as zeroes, so no data is passed through
+/// unencrypted, until [`Aes::take_cipher_error()`] clears the error. Check
+/// it after each message.
+///
+/// The mode crates built on `cipher` 0.4 (such as `cbc` 0.1, `ctr` 0.9 and
+/// `cmac` 0.7) accept an already constructed cipher through their
+/// `InnerIvInit` and `InnerInit` constructors. Those that keep copies of
+/// the cipher, such as `cmac`, need [`AesBlockCipherRef`] instead.
+///
+/// Example:
+/// ```
+/// use cipher::{BlockEncrypt, BlockEncryptMut, InnerIvInit};
+///
+/// aes.set_key(&key).unwrap();
+/// let cipher = AesBlockCipher::new(aes);
+/// let mut block = cipher::Block::<AesBlockCipher>::from(plaintext);
+/// cipher.encrypt_block(&mut block);
+///
+/// // CBC from the `cbc` crate, running on the hardware
+/// let mut cbc = cbc::Encryptor::inner_iv_init(cipher, &iv.into());
+/// cbc.encrypt_blocks_mut(&mut blocks);
+/// ```
+#[cfg(feature = "cipher")]
+pub struct AesBlockCipher {
+    aes: Aes,
+}
+
+#[cfg(feature = "cipher")]
+impl AesBlockCipher {
+    /// Wraps an AES peripheral with a key already set.
+    pub fn new(aes: Aes) -> Self {
+        Self { aes }
+    }
+
+    /// Returns the wrapped AES peripheral.
+    pub fn into_inner(self) -> Aes {
+        self.aes
+    }
+
+    /// Borrows the wrapped AES peripheral as an [`AesBlockCipherRef`].
+    pub fn as_block_cipher(&self) -> AesBlockCipherRef<'_> {
+        AesBlockCipherRef { aes: &self.aes }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _aes(&self) -> &Aes {
+        &self.aes
+    }
+}
+
+/// # Borrowed RustCrypto Block Cipher
+///
+/// Like [`AesBlockCipher`], but borrowing the [`Aes`] peripheral, so it can
+/// be copied. Needed by mode implementations that keep several copies of
+/// the cipher, such as `cmac`. This can be enabled with the `cipher`
+/// feature flag.
+///
+/// Example:
+/// ```
+/// use cipher::{InnerIvInit, StreamCipher};
+/// use cmac::{digest::InnerInit, Mac};
+///
+/// aes.set_key(&key).unwrap();
+///
+/// // AES-CMAC from the `cmac` crate
+/// let mut mac = cmac::Cmac::inner_init(aes.as_block_cipher());
+/// mac.update(&message);
+/// let tag = mac.finalize().into_bytes();
+///
+/// // AES-CTR from the `ctr` crate
+/// let mut ctr = ctr::Ctr128BE::inner_iv_init(aes.as_block_cipher(), &nonce.into());
+/// ctr.apply_keystream(&mut payload);
+/// ```
+#[cfg(feature = "cipher")]
+#[derive(Clone, Copy)]
+pub struct AesBlockCipherRef<'a> {
+    aes: &'a Aes,
+}
+
+#[cfg(feature = "cipher")]
+impl AesBlockCipherRef<'_> {
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _aes(&self) -> &Aes {
+        self.aes
+    }
+}
+
+#[cfg(feature = "cipher")]
+impl Aes {
+    /// Borrows the peripheral as an [`AesBlockCipherRef`], implementing the
+    /// [`cipher`] block cipher traits. The key must already be set.
+    pub fn as_block_cipher(&self) -> AesBlockCipherRef<'_> {
+        AesBlockCipherRef { aes: self }
+    }
+
+    /// Returns and clears the error latched by the [`cipher`] traits, see
+    /// [`AesBlockCipher`]. While an error is latched they output zeroes.
+    ///
+    /// Example:
+    /// ```
+    /// let mut ctr = ctr::Ctr128BE::inner_iv_init(aes.as_block_cipher(), &nonce.into());
+    /// ctr.apply_keystream(&mut payload);
+    /// if aes.take_cipher_error().is_some() {
+    ///     // The payload is zeroed, start the message over
+    /// }
+    /// ```
+    pub fn take_cipher_error(&self) -> Option<AesError> {
+        self.cipher_error.take()
+    }
+
+    /// Selects the mode for one call of the cipher traits.
+    ///
+    /// [`Aes`] is not `Sync`, so other users of a shared `&Aes`, such as a
+    /// copied [`AesBlockCipherRef`], run on the same context and can only
+    /// switch the mode between calls. Each call selects its mode here
+    /// before processing any block, so it never runs in another user's
+    /// mode.
+    ///
+    /// Each wait is bounded by [`AES_DEFAULT_TIMEOUT`]. On a timeout the
+    /// FIFOs are reset and the switch is tried again, at most
+    /// [`AES_CIPHER_ATTEMPTS`] times. After that [`AesError::Timeout`] is
+    /// latched, so no block is processed in the wrong mode.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _ensure_mode(&self, encrypt: bool) {
+        let is_encrypt = self._get_mode() == Some(Type::EncExt);
+        let mode = if encrypt && !is_encrypt {
+            Type::EncExt
+        } else if !encrypt && is_encrypt {
+            Type::DecExt
+        } else {
+            return;
+        };
+        for _ in 0..AES_CIPHER_ATTEMPTS {
+            self.aes.ctrl().modify(|_, w| w.en().clear_bit());
+            if self.wait_timeout(AES_DEFAULT_TIMEOUT).is_ok() {
+                self._write_mode(mode);
+                if self.wait_timeout(AES_DEFAULT_TIMEOUT).is_ok() {
+                    return;
+                }
+            }
+            let _ = self.reset_fifos();
+        }
+        self._latch_cipher_error(AesError::Timeout);
+    }
+
+    /// Latches an error of the cipher traits, keeping the first one.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _latch_cipher_error(&self, error: AesError) {
+        if self.cipher_error.get().is_none() {
+            self.cipher_error.set(Some(error));
+        }
+    }
+}
+
+macro_rules! block_cipher {
+    ($cipher:ty) => {
+        #[cfg(feature = "cipher")]
+        impl BlockSizeUser for $cipher {
+            type BlockSize = U16;
+        }
+
+        #[cfg(feature = "cipher")]
+        impl BlockCipher for $cipher {}
+
+        #[cfg(feature = "cipher")]
+        impl BlockEncrypt for $cipher {
+            fn encrypt_with_backend(&self, f: impl BlockClosure<BlockSize = Self::BlockSize>) {
+                self._aes()._ensure_mode(true);
+                f.call(&mut AesBackend { aes: self._aes() });
+            }
+        }
+
+        #[cfg(feature = "cipher")]
+        impl BlockDecrypt for $cipher {
+            fn decrypt_with_backend(&self, f: impl BlockClosure<BlockSize = Self::BlockSize>) {
+                self._aes()._ensure_mode(false);
+                f.call(&mut AesBackend { aes: self._aes() });
+            }
+        }
+    };
+}
+
+block_cipher!(AesBlockCipher);
+block_cipher!(AesBlockCipherRef<'_>);
+
+/// Block backend that processes blocks in the current mode of the peripheral.
+#[cfg(feature = "cipher")]
+struct AesBackend<'a> {
+    aes: &'a Aes,
+}
+
+#[cfg(feature = "cipher")]
+impl BlockSizeUser for AesBackend<'_> {
+    type BlockSize = U16;
+}
+
+#[cfg(feature = "cipher")]
+impl ParBlocksSizeUser for AesBackend<'_> {
+    type ParBlocksSize = U1;
+}
+
+#[cfg(feature = "cipher")]
+impl BlockBackend for AesBackend<'_> {
+    #[inline(always)]
+    fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+        let mut in_block: AesBlock = [0u8; 16];
+        in_block.copy_from_slice(block.get_in());
+        // The trait is infallible, so rather than output a block out of step,
+        // bring the FIFOs back in step and process the block again, a bounded
+        // number of times. Each block is independent, since the modes chain
+        // in software. Once an error is latched, zeroes are output instead.
+        let mut out_block = [0u8; 16];
+        if self.aes.cipher_error.get().is_none() {
+            let mut result = Err(AesError::Timeout);
+            for _ in 0..AES_CIPHER_ATTEMPTS {
+                if !self.aes._in_fifo_empty() || !self.aes._out_fifo_empty() {
+                    let _ = self.aes.reset_fifos();
+                }
+                result = self.aes._push_block(in_block).and_then(|()| {
+                    self.aes.wait_timeout(AES_DEFAULT_TIMEOUT)?;
+                    self.aes._pop_block()
+                });
+                if result.is_ok() {
+                    break;
+                }
+            }
+            match result {
+                Ok(block) => out_block = block,
+                Err(error) => self.aes._latch_cipher_error(error),
+            }
+        }
+        block.get_out().copy_from_slice(&out_block);
+    }
+}
+
+/// Zeroes the key registers and disables the AES peripheral when the driver
+/// goes out of scope. See [`Aes::zeroize_key`] for the limitations.
+impl Drop for Aes {
+    fn drop(&mut self) {
+        self._disable();
     }
 }
 
+/// Encryption requires the mode to be [`Type::EncExt`].
+#[doc(hidden)]
+#[inline(always)]
+fn _check_encrypt_mode(mode: Option<Type>) -> Result<(), AesError> {
+    if mode != Some(Type::EncExt) {
+        return Err(AesError::Misconfigured)
+    }
+    Ok(())
+}
+
+/// Decryption requires the mode to be [`Type::DecExt`] or [`Type::DecInt`].
+/// A reserved mode value is never accepted.
+#[doc(hidden)]
+#[inline(always)]
+fn _check_decrypt_mode(mode: Option<Type>) -> Result<(), AesError> {
+    if !matches!(mode, Some(Type::DecExt | Type::DecInt)) {
+        return Err(AesError::Misconfigured)
+    }
+    Ok(())
+}
+
+/// Encrypts `data` in place with cipher block chaining, using `encrypt`
+/// for the block cipher. Stops at the first block that fails.
+fn cbc_encrypt(
+    iv: AesBlock,
+    data: &mut [AesBlock],
+    mut encrypt: impl FnMut(AesBlock) -> Result<AesBlock, AesError>,
+) -> Result<(), AesError> {
+    let mut prev = iv;
+    for block in data.iter_mut() {
+        xor_block(block, &prev);
+        *block = encrypt(*block)?;
+        prev = *block;
+    }
+    Ok(())
+}
+
+/// Decrypts `data` in place with cipher block chaining, using `decrypt`
+/// for the block cipher. Stops at the first block that fails.
+fn cbc_decrypt(
+    iv: AesBlock,
+    data: &mut [AesBlock],
+    mut decrypt: impl FnMut(AesBlock) -> Result<AesBlock, AesError>,
+) -> Result<(), AesError> {
+    let mut prev = iv;
+    for block in data.iter_mut() {
+        let ciphertext = *block;
+        *block = decrypt(ciphertext)?;
+        xor_block(block, &prev);
+        prev = ciphertext;
+    }
+    Ok(())
+}
+
+/// XORs `other` into `block`.
+#[inline(always)]
+fn xor_block(block: &mut AesBlock, other: &AesBlock) {
+    for (b, o) in block.iter_mut().zip(other) {
+        *b ^= o;
+    }
+}
+
+/// Packs a block into FIFO words. Bytes are packed little-endian, so the
+/// first byte of the block is the least significant byte of the first word.
+///
+/// For example, `[0x00, 0x11, 0x22, 0x33, ...]` becomes `[0x3322_1100, ...]`.
 fn convert_u8_to_u32_array(bytes: [u8; 16]) -> [u32; 4] {
-    use core::mem::transmute;
-    unsafe { transmute(bytes) }
+    let mut words = [0u32; 4];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    words
 }
 
-fn convert_u32_to_u8_array(bytes: [u32; 4]) -> [u8; 16] {
-    use core::mem::transmute;
-    unsafe { transmute(bytes) }
+/// Unpacks FIFO words into a block. This is the inverse of
+/// [`convert_u8_to_u32_array`].
+fn convert_u32_to_u8_array(words: [u32; 4]) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for the peripheral: applies the same mode checks as
+    /// [`Aes`] around an invertible toy cipher.
+    struct MockAes {
+        mode: core::cell::Cell<Option<Type>>,
+        key: AesBlock,
+    }
+
+    impl MockAes {
+        fn new(key: AesBlock) -> Self {
+            Self { mode: core::cell::Cell::new(None), key }
+        }
+
+        fn set_mode(&self, mode: Type) {
+            self.mode.set(Some(mode));
+        }
+
+        fn encrypt_block(&self, mut block: AesBlock) -> Result<AesBlock, AesError> {
+            _check_encrypt_mode(self.mode.get())?;
+            xor_block(&mut block, &self.key);
+            block.rotate_left(3);
+            Ok(block)
+        }
+
+        fn decrypt_block(&self, mut block: AesBlock) -> Result<AesBlock, AesError> {
+            _check_decrypt_mode(self.mode.get())?;
+            block.rotate_right(3);
+            xor_block(&mut block, &self.key);
+            Ok(block)
+        }
+    }
+
+    #[test]
+    fn block_round_trip() {
+        let aes = MockAes::new(core::array::from_fn(|i| 0xa5 ^ i as u8));
+        let plaintext: AesBlock = *b"sixteen byte msg";
+
+        aes.set_mode(Type::EncExt);
+        let ciphertext = aes.encrypt_block(plaintext).unwrap();
+        assert_ne!(ciphertext, plaintext);
+
+        for mode in [Type::DecExt, Type::DecInt] {
+            aes.set_mode(mode);
+            assert_eq!(aes.decrypt_block(ciphertext), Ok(plaintext));
+        }
+    }
+
+    #[test]
+    fn block_mode_mismatch() {
+        let aes = MockAes::new([0; 16]);
+        assert_eq!(aes.encrypt_block([0; 16]), Err(AesError::Misconfigured));
+        assert_eq!(aes.decrypt_block([0; 16]), Err(AesError::Misconfigured));
+
+        aes.set_mode(Type::EncExt);
+        assert_eq!(aes.decrypt_block([0; 16]), Err(AesError::Misconfigured));
+        aes.set_mode(Type::DecExt);
+        assert_eq!(aes.encrypt_block([0; 16]), Err(AesError::Misconfigured));
+    }
+
+    // NIST SP 800-38A F.2.1 and F.2.2 (CBC-AES128), checked against
+    // `openssl enc -aes-128-cbc -nopad`
+    const CBC_IV: AesBlock = 0x0001_0203_0405_0607_0809_0a0b_0c0d_0e0fu128.to_be_bytes();
+    const CBC_PLAINTEXT: [AesBlock; 4] = [
+        0x6bc1_bee2_2e40_9f96_e93d_7e11_7393_172au128.to_be_bytes(),
+        0xae2d_8a57_1e03_ac9c_9eb7_6fac_45af_8e51u128.to_be_bytes(),
+        0x30c8_1c46_a35c_e411_e5fb_c119_1a0a_52efu128.to_be_bytes(),
+        0xf69f_2445_df4f_9b17_ad2b_417b_e66c_3710u128.to_be_bytes(),
+    ];
+    const CBC_CIPHERTEXT: [AesBlock; 4] = [
+        0x7649_abac_8119_b246_cee9_8e9b_12e9_197du128.to_be_bytes(),
+        0x5086_cb9b_5072_19ee_95db_113a_9176_78b2u128.to_be_bytes(),
+        0x73be_d6b8_e3c1_743b_7116_e69e_2222_9516u128.to_be_bytes(),
+        0x3ff1_caa1_681f_ac09_120e_ca30_7586_e1a7u128.to_be_bytes(),
+    ];
+    // The AES-128 input blocks of the vector, each the plaintext XORed
+    // with the previous ciphertext block. AES maps them to the ciphertext.
+    const CBC_INPUT_BLOCKS: [AesBlock; 4] = [
+        0x6bc0_bce1_2a45_9991_e134_741a_7f9e_1925u128.to_be_bytes(),
+        0xd864_21fb_9f1a_1eda_505e_e137_5746_972cu128.to_be_bytes(),
+        0x604e_d7dd_f32e_fdff_7020_d023_8b7c_2a5du128.to_be_bytes(),
+        0x8521_f2fd_3c8e_ef2c_dc3d_a7e5_c44e_a206u128.to_be_bytes(),
+    ];
+
+    /// AES-128 with the vector key, as far as the vector exercises it.
+    fn cbc_vector_encrypt(block: AesBlock) -> Result<AesBlock, AesError> {
+        let index = CBC_INPUT_BLOCKS.iter().position(|input| *input == block);
+        index.map(|i| CBC_CIPHERTEXT[i]).ok_or(AesError::Misconfigured)
+    }
+
+    /// The inverse of [`cbc_vector_encrypt`].
+    fn cbc_vector_decrypt(block: AesBlock) -> Result<AesBlock, AesError> {
+        let index = CBC_CIPHERTEXT.iter().position(|output| *output == block);
+        index.map(|i| CBC_INPUT_BLOCKS[i]).ok_or(AesError::Misconfigured)
+    }
+
+    #[test]
+    fn cbc_encrypt_vector() {
+        let mut data = CBC_PLAINTEXT;
+        cbc_encrypt(CBC_IV, &mut data, cbc_vector_encrypt).unwrap();
+        assert_eq!(data, CBC_CIPHERTEXT);
+    }
+
+    #[test]
+    fn cbc_decrypt_vector() {
+        let mut data = CBC_CIPHERTEXT;
+        cbc_decrypt(CBC_IV, &mut data, cbc_vector_decrypt).unwrap();
+        assert_eq!(data, CBC_PLAINTEXT);
+    }
+
+    #[test]
+    fn cbc_round_trip() {
+        let aes = MockAes::new([0x3c; 16]);
+        let plaintext: [AesBlock; 3] = [[0x42; 16]; 3];
+        let mut data = plaintext;
+
+        aes.set_mode(Type::EncExt);
+        cbc_encrypt(CBC_IV, &mut data, |block| aes.encrypt_block(block)).unwrap();
+        // Equal plaintext blocks must not give equal ciphertext blocks
+        assert_ne!(data[1], data[2]);
+
+        aes.set_mode(Type::DecExt);
+        cbc_decrypt(CBC_IV, &mut data, |block| aes.decrypt_block(block)).unwrap();
+        assert_eq!(data, plaintext);
+    }
+
+    #[test]
+    fn cbc_stops_at_failure() {
+        let mut data = [[0; 16]; 2];
+        let mut calls = 0;
+        let result = cbc_encrypt(CBC_IV, &mut data, |_| {
+            calls += 1;
+            Err(AesError::Timeout)
+        });
+        assert_eq!(result, Err(AesError::Timeout));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn gf128_mul_by_one() {
+        // In the GCM bit order the most significant bit is x^0
+        let one = 1u128 << 127;
+        let x = 0x66e9_4bd4_ef8a_2c3b_884c_fa59_ca34_2b2e;
+        assert_eq!(gf128_mul(x, one), x);
+        assert_eq!(gf128_mul(one, x), x);
+        assert_eq!(gf128_mul(x, 0), 0);
+    }
+
+    #[test]
+    fn gf128_mul_ghash_vector() {
+        // NIST GCM specification, test case 2: GHASH over one ciphertext
+        // block and the length block
+        let h = 0x66e9_4bd4_ef8a_2c3b_884c_fa59_ca34_2b2e;
+        let c = 0x0388_dace_60b6_a392_f328_c2b9_71b2_fe78;
+        let lengths = 128;
+        let y = gf128_mul(gf128_mul(c, h) ^ lengths, h);
+        assert_eq!(y, 0xf38c_bb1a_d692_23dc_c345_7ae5_b6b0_f885);
+        assert_eq!(gf128_mul(c, h), gf128_mul(h, c));
+    }
+
+    #[test]
+    fn gf128_double_cmac_subkeys() {
+        // RFC 4493 section 4, subkeys derived from L = AES-128(K, 0)
+        let l = 0x7df7_6b0c_1ab8_99b3_3e42_f047_b91b_546fu128.to_be_bytes();
+        let k1 = gf128_double(l);
+        assert_eq!(k1, 0xfbee_d618_3571_3366_7c85_e08f_7236_a8deu128.to_be_bytes());
+        assert_eq!(gf128_double(k1), 0xf7dd_ac30_6ae2_66cc_f90b_c11e_e46d_513bu128.to_be_bytes());
+    }
+
+    #[test]
+    fn fifo_words_are_little_endian() {
+        let block: AesBlock = core::array::from_fn(|i| i as u8);
+        let words = convert_u8_to_u32_array(block);
+        assert_eq!(words, [0x0302_0100, 0x0706_0504, 0x0b0a_0908, 0x0f0e_0d0c]);
+        assert_eq!(convert_u32_to_u8_array(words), block);
+    }
 }
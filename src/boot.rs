@@ -0,0 +1,171 @@
+//! # Secure Boot Image Verification
+//!
+//! Checks an image stored in flash before a bootloader jumps to it, by
+//! streaming the image through the AES-CMAC or the CRC peripheral and
+//! comparing the result against a stored value in constant time.
+//!
+//! - [`ImageCheck::Cmac`] authenticates the image with a secret key, so a
+//!   modified image is rejected even if the attacker recomputes its MAC.
+//! - [`ImageCheck::Crc`] only detects accidental corruption, such as an
+//!   interrupted update.
+//!
+//! Images do not need to be a whole number of AES blocks or words long.
+use crate::aes::{Aes, AesBlock, AesError, Cmac};
+use crate::crc::Crc;
+use crate::crypto::ct_eq;
+use crate::flc::{FlashError, Flc, FLASH_END};
+
+/// Number of bytes read from flash at a time.
+const CHUNK_LEN: usize = 64;
+/// Value of erased flash.
+const ERASED_BYTE: u8 = 0xff;
+
+/// Location of an image in flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ImageRegion {
+    /// Address of the first byte of the image.
+    pub address: u32,
+    /// Length of the image in bytes.
+    pub len: u32,
+}
+
+/// How an image is checked, with the expected value stored alongside it.
+pub enum ImageCheck<'a> {
+    /// Authenticate the image with AES-CMAC, using the key already loaded
+    /// into the AES peripheral. The mode must be set to
+    /// [`Type::EncExt`](crate::pac::aes::ctrl::Type::EncExt).
+    Cmac {
+        aes: &'a Aes,
+        /// Expected MAC of the image.
+        mac: AesBlock,
+    },
+    /// Check the image integrity with the CRC algorithm configured in the
+    /// CRC peripheral. The computation is restarted first.
+    Crc {
+        crc: &'a mut Crc,
+        /// Expected CRC of the image.
+        value: u32,
+    },
+}
+
+/// Outcome of [`verify_image()`]. Only [`VerifyResult::Valid`] means the
+/// image may be booted.
+#[must_use]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VerifyResult {
+    /// The image matches the expected value.
+    Valid,
+    /// The image does not match the expected value.
+    Mismatch,
+    /// The image does not match and every byte of the region is erased, so
+    /// no image has been installed.
+    Erased,
+    /// The region is empty.
+    EmptyRegion,
+    /// The region extends outside of the flash.
+    InvalidRegion(FlashError),
+    /// The AES peripheral could not compute the MAC, for example because no
+    /// key is loaded or the mode is not set to encryption.
+    Aes(AesError),
+}
+
+impl VerifyResult {
+    /// Returns [`true`] if the image matches the expected value.
+    #[inline(always)]
+    pub const fn is_valid(&self) -> bool {
+        matches!(self, VerifyResult::Valid)
+    }
+}
+
+/// Verify the image in `region` of flash with `check`.
+///
+/// The whole region is always processed, and the computed value is
+/// compared with [`ct_eq()`], so the time taken does not reveal how close
+/// a forged image came to the expected MAC. The computed value itself is
+/// not returned.
+///
+/// Example:
+/// ```
+/// use hal::boot::{verify_image, ImageCheck, ImageRegion, VerifyResult};
+///
+/// // The MAC is stored in the 16 bytes following the image
+/// let region = ImageRegion { address: 0x1001_0000, len: image_len };
+/// let mut mac = [0u8; 16];
+/// flc.read(region.address + region.len, &mut mac).unwrap();
+///
+/// aes.set_key(&boot_key).unwrap();
+/// aes.set_mode(Type::EncExt).unwrap();
+/// match verify_image(&flc, region, ImageCheck::Cmac { aes: &aes, mac }) {
+///     VerifyResult::Valid => jump_to_image(region.address),
+///     VerifyResult::Erased => wait_for_update(),
+///     _ => panic!("image rejected"),
+/// }
+/// ```
+pub fn verify_image(flc: &Flc, region: ImageRegion, check: ImageCheck<'_>) -> VerifyResult {
+    if region.len == 0 {
+        return VerifyResult::EmptyRegion;
+    }
+    let Some(end) = region.address.checked_add(region.len) else {
+        return VerifyResult::InvalidRegion(FlashError::InvalidAddress(region.address, FLASH_END));
+    };
+    if let Err(error) = flc
+        .check_address(region.address)
+        .and_then(|_| flc.check_address(end - 1))
+    {
+        return VerifyResult::InvalidRegion(error);
+    }
+
+    let mut erased = true;
+    let matches = match check {
+        ImageCheck::Cmac { aes, mac } => {
+            let computed = Cmac::new(aes).and_then(|mut cmac| {
+                _for_each_chunk(flc, region, &mut erased, |chunk| cmac.update(chunk))?;
+                cmac.finalize()
+            });
+            match computed {
+                Ok(computed) => ct_eq(&computed, &mac),
+                Err(error) => return VerifyResult::Aes(error),
+            }
+        }
+        ImageCheck::Crc { crc, value } => {
+            crc.restart();
+            let _ = _for_each_chunk(flc, region, &mut erased, |chunk| {
+                crc.feed(chunk);
+                Ok::<(), core::convert::Infallible>(())
+            });
+            ct_eq(&crc.result().to_le_bytes(), &value.to_le_bytes())
+        }
+    };
+    match (matches, erased) {
+        (true, _) => VerifyResult::Valid,
+        (false, true) => VerifyResult::Erased,
+        (false, false) => VerifyResult::Mismatch,
+    }
+}
+
+/// Read a checked region of flash in chunks, clearing `erased` if any byte
+/// is not erased.
+#[doc(hidden)]
+fn _for_each_chunk<E>(
+    flc: &Flc,
+    region: ImageRegion,
+    erased: &mut bool,
+    mut f: impl FnMut(&[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut buffer = [0u8; CHUNK_LEN];
+    let mut address = region.address;
+    let mut remaining = region.len as usize;
+    while remaining > 0 {
+        let len = remaining.min(CHUNK_LEN);
+        let chunk = &mut buffer[..len];
+        // The region was checked to be within the flash
+        let _ = flc.read(address, chunk);
+        *erased &= chunk.iter().all(|&byte| byte == ERASED_BYTE);
+        f(chunk)?;
+        address += len as u32;
+        remaining -= len;
+    }
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! # Convolutional Neural Network (CNN) Accelerator
+//!
+//! A thin interface for running models generated by the vendor's
+//! synthesis tool (`ai8xize.py`). The generated `cnn.c` describes the
+//! network as raw register writes and memory blocks, which map onto this
+//! module as follows:
+//! - `cnn_enable()` and `cnn_init()`: [`Cnn::new()`]
+//! - `cnn_load_weights()`: [`Cnn::load_weights()`] with the `KERNELS` array
+//!   from `weights.h`
+//! - `cnn_load_bias()`: [`Cnn::load_bias()`] for each bias array
+//! - `cnn_configure()`: [`Cnn::write_register()`] for each register write
+//! - `load_input()`: [`Cnn::load_input()`]
+//! - `cnn_start()`: [`Cnn::start()`]
+//! - `cnn_unload()`: [`Cnn::read_output()`]
+//!
+//! The accelerator is made of four quadrants of 16 processors each, and
+//! quadrant 0 acts as the master that starts the network and reports
+//! completion.
+use crate::gcr::clocks::{Clock, SystemClock};
+use crate::gcr::{ClockForPeripheral, ResetForPeripheral};
+use crate::wait::{Spin, WaitStrategy};
+pub use crate::pac::gcr::pclkdiv::{Cnnclkdiv as CnnClockDivider, Cnnclksel as CnnClockSource};
+
+/// Number of quadrants of the accelerator.
+pub const CNN_QUADRANTS: usize = 4;
+/// Base address of the registers of quadrant 0.
+pub const CNN_BASE_ADDR: u32 = 0x5010_0000;
+/// Distance between the register blocks of consecutive quadrants.
+pub const CNN_QUADRANT_STRIDE: u32 = 0x0040_0000;
+/// End of the address range of the CNN registers and memories.
+const CNN_END_ADDR: u32 = 0x5110_0000;
+/// Always-on control register of the accelerator.
+const CNN_AON_CTRL_ADDR: u32 = 0x5000_1000;
+/// Enable bit of the quadrant control register.
+const CTRL_EN: u32 = 1 << 0;
+/// Done flag of the quadrant control register.
+const CTRL_DONE: u32 = 1 << 12;
+/// Time to wait for the power switches of the quadrants to settle.
+const POWER_UP_DELAY_US: u32 = 10_000;
+
+/// Errors that can occur while loading or running a network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CnnError {
+    /// An address, or a block starting at an address, is outside of the
+    /// CNN registers and memories or is not word-aligned.
+    AddressOutOfRange,
+    /// The kernel data does not follow the `KERNELS` format: a block
+    /// length runs past the end of the data, or the terminating zero
+    /// address is missing.
+    InvalidKernelData,
+    /// The network did not finish within the maximum number of polls.
+    Timeout,
+}
+
+/// # Convolutional Neural Network (CNN) Accelerator
+///
+/// The accelerator has no register block in the PAC, so it is represented
+/// by the [`Gcfr`](crate::pac::Gcfr) peripheral, which controls its power
+/// domains. All other accesses go through checked raw addresses.
+///
+/// The PAC does not expose the CNN interrupt vector either, so completion
+/// is detected by polling [`Cnn::is_done()`].
+///
+/// Example:
+/// ```
+/// // KERNELS, BIAS_0, and INPUT_0 are ported from the generated headers
+/// let mut cnn = Cnn::new(
+///     p.gcfr,
+///     &mut gcr.reg,
+///     &clks.sys_clk,
+///     CnnClockSource::Pclk,
+///     CnnClockDivider::Div1,
+/// );
+/// cnn.load_weights(&KERNELS).unwrap();
+/// cnn.load_bias(0x5010_8000, &BIAS_0).unwrap();
+/// for &(address, value) in CONFIGURATION.iter() {
+///     cnn.write_register(address, value).unwrap();
+/// }
+/// cnn.load_input(0x5040_0000, &INPUT_0).unwrap();
+/// cnn.start(MASTER_CTRL);
+/// cnn.wait();
+/// let mut output = [0u32; 10];
+/// cnn.read_output(0x5040_4000, &mut output).unwrap();
+/// ```
+pub struct Cnn {
+    gcfr: crate::pac::Gcfr,
+}
+
+impl Cnn {
+    /// Power up the accelerator, select its clock, and clear all of its
+    /// registers.
+    ///
+    /// The system clock is used to time the power-up delay.
+    pub fn new(
+        gcfr: crate::pac::Gcfr,
+        reg: &mut crate::gcr::GcrRegisters,
+        sys_clk: &Clock<SystemClock>,
+        source: CnnClockSource,
+        divider: CnnClockDivider,
+    ) -> Self {
+        // Hold all domains in reset, unmask the memories, and switch on
+        // the power before releasing the isolation and the reset
+        unsafe {
+            gcfr.reg3().write(|w| w.bits(0xf));
+            gcfr.reg1().write(|w| w.bits(0xf));
+            gcfr.reg0().write(|w| w.bits(0xf));
+        }
+        let cycles = sys_clk.frequency as u64 * POWER_UP_DELAY_US as u64 / 1_000_000;
+        cortex_m::asm::delay(cycles as u32);
+        unsafe {
+            gcfr.reg2().write(|w| w.bits(0));
+            gcfr.reg3().write(|w| w.bits(0));
+        }
+        reg.gcr.pclkdiv().modify(|_, w| {
+            w.cnnclkdiv().variant(divider);
+            w.cnnclksel().variant(source)
+        });
+        unsafe {
+            gcfr.reset(&mut reg.gcr);
+            gcfr.enable_clock(&mut reg.gcr);
+        }
+        let cnn = Self { gcfr };
+        // Clear the always-on control and the control of each quadrant
+        unsafe { core::ptr::write_volatile(CNN_AON_CTRL_ADDR as *mut u32, 0) };
+        for quadrant in 0..CNN_QUADRANTS {
+            cnn._write(Self::_ctrl_addr(quadrant), 0);
+        }
+        cnn
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    const fn _ctrl_addr(quadrant: usize) -> u32 {
+        CNN_BASE_ADDR + quadrant as u32 * CNN_QUADRANT_STRIDE
+    }
+
+    /// Check that a block of `words` words at `address` lies within the
+    /// CNN address range.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_range(address: u32, words: usize) -> Result<(), CnnError> {
+        let len = (words as u32).checked_mul(4).ok_or(CnnError::AddressOutOfRange)?;
+        let end = address.checked_add(len).ok_or(CnnError::AddressOutOfRange)?;
+        if !address.is_multiple_of(4) || address < CNN_BASE_ADDR || end > CNN_END_ADDR {
+            return Err(CnnError::AddressOutOfRange);
+        }
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _write(&self, address: u32, value: u32) {
+        unsafe { core::ptr::write_volatile(address as *mut u32, value) };
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _read(&self, address: u32) -> u32 {
+        unsafe { core::ptr::read_volatile(address as *const u32) }
+    }
+
+    /// Write a register of the accelerator, as done by the generated
+    /// `cnn_configure()`.
+    pub fn write_register(&mut self, address: u32, value: u32) -> Result<(), CnnError> {
+        Self::_check_range(address, 1)?;
+        self._write(address, value);
+        Ok(())
+    }
+
+    /// Read a register of the accelerator.
+    pub fn read_register(&self, address: u32) -> Result<u32, CnnError> {
+        Self::_check_range(address, 1)?;
+        Ok(self._read(address))
+    }
+
+    /// Load the kernel memories from the `KERNELS` array generated in
+    /// `weights.h`.
+    ///
+    /// The array is a sequence of blocks, each made of a start address, a
+    /// length in words, and that many words of kernel data, and is
+    /// terminated by a zero address. The whole array is validated before
+    /// anything is written.
+    pub fn load_weights(&mut self, kernels: &[u32]) -> Result<(), CnnError> {
+        self._for_each_kernel_block(kernels, |_, _, _| {})?;
+        self._for_each_kernel_block(kernels, |cnn, address, data| {
+            // Writing the low byte with bit 0 set latches the start address
+            // of the kernel memory
+            unsafe { core::ptr::write_volatile((address | 1) as *mut u8, 0x01) };
+            for (i, word) in data.iter().enumerate() {
+                cnn._write(address + i as u32 * 4, *word);
+            }
+        })
+    }
+
+    #[doc(hidden)]
+    fn _for_each_kernel_block(
+        &mut self,
+        kernels: &[u32],
+        mut f: impl FnMut(&mut Self, u32, &[u32]),
+    ) -> Result<(), CnnError> {
+        let mut rest = kernels;
+        loop {
+            let (&address, tail) = rest.split_first().ok_or(CnnError::InvalidKernelData)?;
+            if address == 0 {
+                return Ok(());
+            }
+            let (&len, tail) = tail.split_first().ok_or(CnnError::InvalidKernelData)?;
+            let len = len as usize;
+            if len > tail.len() {
+                return Err(CnnError::InvalidKernelData);
+            }
+            Self::_check_range(address, len)?;
+            let (data, tail) = tail.split_at(len);
+            f(self, address, data);
+            rest = tail;
+        }
+    }
+
+    /// Load a bias memory. The generated bias arrays hold one byte per
+    /// bias, and each byte is stored in its own word of the memory.
+    pub fn load_bias(&mut self, address: u32, bias: &[u8]) -> Result<(), CnnError> {
+        Self::_check_range(address, bias.len())?;
+        for (i, byte) in bias.iter().enumerate() {
+            self._write(address + i as u32 * 4, *byte as u32);
+        }
+        Ok(())
+    }
+
+    /// Load input data into the data memory of the accelerator.
+    pub fn load_input(&mut self, address: u32, data: &[u32]) -> Result<(), CnnError> {
+        Self::_check_range(address, data.len())?;
+        for (i, word) in data.iter().enumerate() {
+            self._write(address + i as u32 * 4, *word);
+        }
+        Ok(())
+    }
+
+    /// Start the network. `ctrl` holds the generated values of the control
+    /// registers of each quadrant, which must include the enable bit of
+    /// every quadrant the network uses.
+    ///
+    /// Quadrants 1 to 3 are enabled first and quadrant 0 last, since it
+    /// starts the others.
+    pub fn start(&mut self, ctrl: [u32; CNN_QUADRANTS]) {
+        for quadrant in (1..CNN_QUADRANTS).chain(0..1) {
+            self._write(Self::_ctrl_addr(quadrant), ctrl[quadrant]);
+        }
+    }
+
+    /// Check if the network has finished.
+    #[inline(always)]
+    pub fn is_done(&self) -> bool {
+        self._read(Self::_ctrl_addr(0)) & CTRL_DONE != 0
+    }
+
+    /// Wait for the network to finish, then clear the done flag and stop
+    /// the master quadrant.
+    pub fn wait(&mut self) {
+        self.wait_with(&mut Spin);
+    }
+
+    /// Wait for the network to finish like [`Cnn::wait()`], running
+    /// `strategy` between polls. Inference can take milliseconds, so this
+    /// is a good place to sleep or feed the watchdog.
+    pub fn wait_with(&mut self, strategy: &mut impl WaitStrategy) {
+        while !self.is_done() {
+            strategy.wait();
+        }
+        self.clear_done();
+    }
+
+    /// Wait for the network to finish, polling at most `max_polls` times
+    /// before returning [`CnnError::Timeout`]. Clears the done flag once
+    /// the network has finished.
+    pub fn wait_timeout(&mut self, max_polls: u32) -> Result<(), CnnError> {
+        for _ in 0..max_polls {
+            if self.is_done() {
+                self.clear_done();
+                return Ok(());
+            }
+        }
+        Err(CnnError::Timeout)
+    }
+
+    /// Clear the done flag and the enable bit of the master quadrant, so
+    /// the network can be started again.
+    #[inline(always)]
+    pub fn clear_done(&mut self) {
+        let ctrl = self._read(Self::_ctrl_addr(0));
+        self._write(Self::_ctrl_addr(0), ctrl & !(CTRL_DONE | CTRL_EN));
+    }
+
+    /// Read the output of the network from the data memory.
+    pub fn read_output(&self, address: u32, output: &mut [u32]) -> Result<(), CnnError> {
+        Self::_check_range(address, output.len())?;
+        for (i, word) in output.iter_mut().enumerate() {
+            *word = self._read(address + i as u32 * 4);
+        }
+        Ok(())
+    }
+
+    /// Stop the clock and power down the accelerator, then release the
+    /// GCFR peripheral. The weights are lost.
+    pub fn free(self, reg: &mut crate::gcr::GcrRegisters) -> crate::pac::Gcfr {
+        unsafe {
+            self.gcfr.disable_clock(&mut reg.gcr);
+            self.gcfr.reg3().write(|w| w.bits(0xf));
+            self.gcfr.reg2().modify(|r, w| w.bits(r.bits() | 0xf));
+            self.gcfr.reg0().write(|w| w.bits(0));
+            self.gcfr.reg1().write(|w| w.bits(0));
+            self.gcfr.reg3().write(|w| w.bits(0));
+        }
+        self.gcfr
+    }
+}
@@ -0,0 +1,187 @@
+//! # Analog Comparators
+//!
+//! Four low power comparators that compare a fixed pair of analog inputs:
+//! comparator `N` compares AIN(2N+1) (positive) against AIN(2N)
+//! (negative). The comparators keep running in the low power modes and can
+//! wake the device, for example when a battery sense line crosses a
+//! threshold set with a resistor divider on the negative input.
+//!
+//! The comparators have no input multiplexer or configurable hysteresis,
+//! so the input pair of each comparator is fixed.
+
+/// Status flag of a comparator control register, cleared by writing one.
+const CTRL_INT_FLAG: u32 = 1 << 15;
+/// Output state of a comparator control register.
+const CTRL_OUT: u32 = 1 << 14;
+/// Interrupt enable of a comparator control register.
+const CTRL_INT_EN: u32 = 1 << 6;
+/// Interrupt polarity of a comparator control register.
+const CTRL_POL: u32 = 1 << 5;
+/// Enable of a comparator control register.
+const CTRL_EN: u32 = 1 << 0;
+
+/// Output transition of a comparator that raises its interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ComparatorEdge {
+    /// The positive input rises above the negative input.
+    Rising,
+    /// The positive input falls below the negative input.
+    Falling,
+}
+
+/// # Analog Comparators
+///
+/// Owns the low power comparator peripheral and splits it into individual
+/// [`Comparator`] handles.
+///
+/// Example:
+/// ```
+/// use hal::pac::interrupt;
+///
+/// let comparators = Comparators::new(p.lpcmp, &mut gcr.reg);
+/// let mut battery = comparators.cmp0;
+/// battery.set_interrupt_edge(ComparatorEdge::Falling);
+/// battery.enable();
+/// battery.enable_interrupt();
+/// power.enable_wakeup(&mut gcr.reg, WakeSource::Comparator);
+/// unsafe { cortex_m::peripheral::NVIC::unmask(Interrupt::LPCMP) };
+/// power.enter_deepsleep(&mut gcr.reg, &mut core.SCB);
+///
+/// #[interrupt]
+/// fn LPCMP() {
+///     // The battery sense line dropped below the threshold
+///     battery.clear_interrupt();
+/// }
+/// ```
+pub struct Comparators {
+    pub cmp0: Comparator<0>,
+    pub cmp1: Comparator<1>,
+    pub cmp2: Comparator<2>,
+    pub cmp3: Comparator<3>,
+}
+
+impl Comparators {
+    /// Create the comparator handles, with all comparators disabled.
+    ///
+    /// Comparator 0 is controlled from the MCR, the others from the LPCMP
+    /// peripheral, which is taken to ensure a single owner.
+    pub fn new(lpcmp: crate::pac::Lpcmp, reg: &mut crate::gcr::GcrRegisters) -> Self {
+        use crate::gcr::ClockForPeripheral;
+        use crate::gcr::ResetForPeripheral;
+
+        unsafe {
+            lpcmp.reset(&mut reg.lpgcr);
+            lpcmp.enable_clock(&mut reg.lpgcr);
+        }
+        let comparators = Self {
+            cmp0: Comparator::new(),
+            cmp1: Comparator::new(),
+            cmp2: Comparator::new(),
+            cmp3: Comparator::new(),
+        };
+        // Comparator 0 is not affected by the LPCMP reset
+        comparators.cmp0._write(CTRL_INT_FLAG);
+        comparators
+    }
+}
+
+/// Zero-sized handle to a single comparator.
+///
+/// - `N` is the comparator number, comparing AIN(2N+1) against AIN(2N).
+pub struct Comparator<const N: usize> {
+    _private: (),
+}
+
+impl<const N: usize> Comparator<N> {
+    const fn new() -> Self {
+        Self { _private: () }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _ctrl(&self) -> *mut u32 {
+        // Safety: Each handle only accesses its own control register
+        if N == 0 {
+            let mcr = unsafe { &*crate::pac::Mcr::ptr() };
+            mcr.cmp_ctrl().as_ptr()
+        } else {
+            let lpcmp = unsafe { &*crate::pac::Lpcmp::ptr() };
+            lpcmp.ctrl(N - 1).as_ptr()
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _read(&self) -> u32 {
+        unsafe { core::ptr::read_volatile(self._ctrl()) }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _write(&self, value: u32) {
+        unsafe { core::ptr::write_volatile(self._ctrl(), value) }
+    }
+
+    /// Read-modify-write the control register without clearing a pending
+    /// interrupt flag.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _modify(&self, set: u32, clear: u32) {
+        let value = self._read() & !(CTRL_INT_FLAG | CTRL_OUT);
+        self._write((value | set) & !clear);
+    }
+
+    /// Enable the comparator.
+    #[inline(always)]
+    pub fn enable(&mut self) {
+        self._modify(CTRL_EN, 0);
+    }
+
+    /// Disable the comparator.
+    #[inline(always)]
+    pub fn disable(&mut self) {
+        self._modify(0, CTRL_EN);
+    }
+
+    /// Get the current output of the comparator, [`true`] if the positive
+    /// input is above the negative input.
+    #[inline(always)]
+    pub fn output(&self) -> bool {
+        self._read() & CTRL_OUT != 0
+    }
+
+    /// Select the output transition that raises the interrupt.
+    #[inline(always)]
+    pub fn set_interrupt_edge(&mut self, edge: ComparatorEdge) {
+        match edge {
+            ComparatorEdge::Rising => self._modify(0, CTRL_POL),
+            ComparatorEdge::Falling => self._modify(CTRL_POL, 0),
+        }
+    }
+
+    /// Enable the interrupt of the comparator. All comparators share the
+    /// [`crate::Interrupt::LPCMP`] interrupt.
+    #[inline(always)]
+    pub fn enable_interrupt(&mut self) {
+        self._modify(CTRL_INT_EN, 0);
+    }
+
+    /// Disable the interrupt of the comparator.
+    #[inline(always)]
+    pub fn disable_interrupt(&mut self) {
+        self._modify(0, CTRL_INT_EN);
+    }
+
+    /// Check if the selected output transition has occurred.
+    #[inline(always)]
+    pub fn is_interrupt_pending(&self) -> bool {
+        self._read() & CTRL_INT_FLAG != 0
+    }
+
+    /// Clear the interrupt flag of the comparator.
+    #[inline(always)]
+    pub fn clear_interrupt(&mut self) {
+        self._write((self._read() & !CTRL_OUT) | CTRL_INT_FLAG);
+    }
+}
@@ -0,0 +1,215 @@
+//! # Cyclic Redundancy Check (CRC)
+//!
+//! The CRC accelerator computes CRCs of up to 32 bits with a programmable
+//! polynomial, processing a 32-bit word per write to its data register.
+
+/// Parameters of a CRC algorithm, in the notation of the CRC catalogue.
+///
+/// The input and output are either both reflected or both not reflected,
+/// which covers the common algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CrcConfig {
+    /// Width of the CRC in bits (1 to 32).
+    pub width: u8,
+    /// Generator polynomial in normal (MSB-first) form, without the
+    /// implicit top bit.
+    pub poly: u32,
+    /// Initial value of the CRC register, not reflected.
+    pub init: u32,
+    /// Process each byte least significant bit first and reflect the
+    /// result.
+    pub reflect: bool,
+    /// Value XORed with the result.
+    pub xor_out: u32,
+}
+
+impl CrcConfig {
+    /// CRC-32 (ISO-HDLC), as used by Ethernet, zlib and PNG.
+    pub const CRC32: Self = Self {
+        width: 32,
+        poly: 0x04C1_1DB7,
+        init: 0xFFFF_FFFF,
+        reflect: true,
+        xor_out: 0xFFFF_FFFF,
+    };
+    /// CRC-16/CCITT-FALSE (CRC-16/IBM-3740), as commonly used for framed
+    /// messages.
+    pub const CRC16_CCITT: Self = Self {
+        width: 16,
+        poly: 0x1021,
+        init: 0xFFFF,
+        reflect: false,
+        xor_out: 0x0000,
+    };
+
+    /// Mask of the bits that are part of the CRC.
+    #[inline(always)]
+    const fn mask(&self) -> u32 {
+        u32::MAX >> (32 - self.width)
+    }
+}
+
+/// Errors that can occur while configuring the CRC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CrcError {
+    /// The width of the CRC is not between 1 and 32 bits.
+    InvalidWidth,
+}
+
+/// # Cyclic Redundancy Check (CRC) Peripheral
+///
+/// Example:
+/// ```
+/// let mut crc = Crc::new(p.crc, &mut gcr.reg, CrcConfig::CRC32).unwrap();
+/// crc.feed(b"123456789");
+/// assert_eq!(crc.result(), 0xCBF4_3926);
+///
+/// crc.configure(CrcConfig::CRC16_CCITT).unwrap();
+/// assert_eq!(crc.checksum(b"123456789"), 0x29B1);
+/// ```
+pub struct Crc {
+    crc: crate::pac::Crc,
+    config: CrcConfig,
+}
+
+impl Crc {
+    /// Create a new CRC peripheral instance, ready to compute a CRC with
+    /// the given parameters.
+    pub fn new(
+        crc: crate::pac::Crc,
+        reg: &mut crate::gcr::GcrRegisters,
+        config: CrcConfig,
+    ) -> Result<Self, CrcError> {
+        use crate::gcr::ClockForPeripheral;
+        use crate::gcr::ResetForPeripheral;
+        unsafe {
+            crc.reset(&mut reg.gcr);
+            crc.enable_clock(&mut reg.gcr);
+        }
+        let mut crc = Self { crc, config };
+        crc.configure(config)?;
+        Ok(crc)
+    }
+
+    /// Change the CRC parameters and restart the computation.
+    pub fn configure(&mut self, config: CrcConfig) -> Result<(), CrcError> {
+        if config.width == 0 || config.width > 32 {
+            return Err(CrcError::InvalidWidth);
+        }
+        self.config = config;
+        self._wait_busy();
+        self.crc.ctrl().write(|w| w.en().clear_bit());
+        let poly = _to_register(config.poly, &config);
+        self.crc.poly().write(|w| unsafe { w.poly().bits(poly) });
+        self.crc.ctrl().write(|w| {
+            w.msb().bit(!config.reflect);
+            w.en().set_bit()
+        });
+        self.restart();
+        Ok(())
+    }
+
+    /// Wait until the CRC has processed the last write.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _wait_busy(&self) {
+        while self.crc.ctrl().read().busy().bit_is_set() {}
+    }
+
+    /// Restart the computation from the initial value.
+    pub fn restart(&mut self) {
+        let config = self.config;
+        let val = _to_register(config.init & config.mask(), &config);
+        self._wait_busy();
+        self.crc.val().write(|w| unsafe { w.value().bits(val) });
+    }
+
+    /// Feed data into the CRC. Whole words are written to the 32-bit data
+    /// register and any remaining bytes to the 8-bit data register.
+    pub fn feed(&mut self, data: &[u8]) {
+        let mut words = data.chunks_exact(4);
+        for word in &mut words {
+            let bytes = [word[0], word[1], word[2], word[3]];
+            // The first byte must be processed first, which is the lowest
+            // byte when shifting right and the highest when shifting left
+            let word = if self.config.reflect {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            };
+            self._wait_busy();
+            self.crc.datain32().write(|w| unsafe { w.data().bits(word) });
+        }
+        for byte in words.remainder() {
+            self._wait_busy();
+            self.crc.datain8().write(|w| unsafe { w.data().bits(*byte) });
+        }
+    }
+
+    /// Get the CRC of all data fed since the last restart. Does not restart
+    /// the computation, so more data can be fed afterwards.
+    pub fn result(&self) -> u32 {
+        self._wait_busy();
+        let val = self.crc.val().read().value().bits();
+        let config = &self.config;
+        let crc = if config.reflect {
+            val
+        } else {
+            val >> (32 - config.width)
+        };
+        (crc ^ config.xor_out) & config.mask()
+    }
+
+    /// Compute the CRC of a complete message.
+    pub fn checksum(&mut self, data: &[u8]) -> u32 {
+        self.restart();
+        self.feed(data);
+        self.result()
+    }
+
+    /// Release the CRC peripheral.
+    pub fn free(self) -> crate::pac::Crc {
+        self.crc.ctrl().write(|w| w.en().clear_bit());
+        self.crc
+    }
+}
+
+/// Converts a polynomial or register value from catalogue form into the
+/// layout of the CRC registers. In reflected mode the register shifts
+/// right, so the value is reflected into the low bits. Otherwise it shifts
+/// left, so the value is aligned to the top bits.
+#[doc(hidden)]
+#[inline(always)]
+fn _to_register(value: u32, config: &CrcConfig) -> u32 {
+    if config.reflect {
+        value.reverse_bits() >> (32 - config.width)
+    } else {
+        value << (32 - config.width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflected_poly_is_reversed_into_the_low_bits() {
+        let config = CrcConfig::CRC32;
+        assert_eq!(_to_register(config.poly, &config), 0xEDB8_8320);
+        // CRC-8/MAXIM-DOW, poly 0x31
+        let config = CrcConfig { width: 8, poly: 0x31, init: 0, reflect: true, xor_out: 0 };
+        assert_eq!(_to_register(config.poly, &config), 0x8C);
+    }
+
+    #[test]
+    fn normal_poly_is_aligned_to_the_top_bits() {
+        let config = CrcConfig::CRC16_CCITT;
+        assert_eq!(_to_register(config.poly, &config), 0x1021_0000);
+        assert_eq!(_to_register(config.init, &config), 0xFFFF_0000);
+        // CRC-8/SMBUS, poly 0x07
+        let config = CrcConfig { width: 8, poly: 0x07, init: 0, reflect: false, xor_out: 0 };
+        assert_eq!(_to_register(config.poly, &config), 0x0700_0000);
+    }
+}
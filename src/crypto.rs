@@ -0,0 +1,69 @@
+//! # Cryptographic Utilities
+//!
+//! Helpers for handling secret data that are shared by the cryptographic
+//! peripherals and usable by applications, such as for comparing
+//! authentication tags or tokens without leaking them through timing.
+
+/// Compares two byte slices in constant time.
+///
+/// Every byte of `a` is examined regardless of where the first difference
+/// is, and the result is computed without branching on the data, so the
+/// running time only depends on the length of `a`. Slices of different
+/// lengths are never equal, but are still compared byte by byte so that the
+/// timing does not reveal how much of a prefix matched. The lengths
+/// themselves are not treated as secret.
+///
+/// Example:
+/// ```
+/// use hal::crypto::ct_eq;
+///
+/// assert!(ct_eq(b"token", b"token"));
+/// assert!(!ct_eq(b"token", b"tokem"));
+/// // A matching prefix of a different length is not equal
+/// assert!(!ct_eq(b"token", b"tok"));
+/// assert!(!ct_eq(b"tok", b"token"));
+/// assert!(ct_eq(b"", b""));
+/// ```
+#[inline(never)]
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    // Any difference in length is folded into the accumulator instead of
+    // returning early
+    let mut diff = (a.len() != b.len()) as u8;
+    for (i, x) in a.iter().enumerate() {
+        // Bytes past the end of `b` are compared against zero, the length
+        // check above already makes the result false
+        let y = b.get(i).copied().unwrap_or(0);
+        diff |= x ^ y;
+    }
+    // Keep the compiler from turning the accumulation into an early exit
+    core::hint::black_box(diff) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_slices() {
+        assert!(ct_eq(b"token", b"token"));
+        assert!(ct_eq(b"", b""));
+    }
+
+    #[test]
+    fn differing_bytes() {
+        assert!(!ct_eq(b"token", b"tokem"));
+        assert!(!ct_eq(b"token", b"Token"));
+    }
+
+    #[test]
+    fn differing_lengths() {
+        // A matching prefix is not equal in either direction
+        assert!(!ct_eq(b"token", b"tok"));
+        assert!(!ct_eq(b"tok", b"token"));
+        assert!(!ct_eq(b"", b"token"));
+        // Bytes past the end are compared against zero, which must not
+        // make a zero-padded prefix equal
+        assert!(!ct_eq(b"tok\0\0", b"tok"));
+        assert!(!ct_eq(b"tok", b"tok\0\0"));
+    }
+}
@@ -0,0 +1,303 @@
+//! # Direct Memory Access (DMA)
+//!
+//! The DMA controller moves data between memory and peripherals without CPU
+//! involvement. The controller is split into independent [`Channel`]s which
+//! can be handed to the peripherals that support DMA.
+pub use crate::pac::dma::ch::ctrl::Request;
+
+/// Width of each bus transaction on one side of a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransferWidth {
+    /// 8-bit transactions.
+    Byte,
+    /// 16-bit transactions.
+    HalfWord,
+    /// 32-bit transactions.
+    Word,
+}
+
+/// Description of a single DMA transfer.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TransferConfig {
+    /// Request line that paces the transfer.
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub request: Request,
+    /// Source address.
+    pub src: u32,
+    /// Width of each read from the source.
+    pub src_width: TransferWidth,
+    /// Increment the source address after each read.
+    pub src_increment: bool,
+    /// Destination address.
+    pub dst: u32,
+    /// Width of each write to the destination.
+    pub dst_width: TransferWidth,
+    /// Increment the destination address after each write.
+    pub dst_increment: bool,
+    /// Number of bytes to transfer (at most 2^24 - 1).
+    pub len: u32,
+    /// Number of bytes moved in a single burst (1 to 32).
+    pub burst_size: u8,
+}
+
+/// # Direct Memory Access (DMA) Peripheral
+///
+/// Drivers that start a transfer on borrowed buffers, such as
+/// [`Aes::encrypt_blocks_dma()`](crate::aes::Aes::encrypt_blocks_dma), are
+/// `unsafe`: only dropping the transfer stops the channels, so it must not
+/// be leaked while it borrows the buffers.
+///
+/// Example:
+/// ```
+/// let dma = hal::dma::Dma::new(p.dma, &mut gcr.reg);
+/// let mut channels = dma.split();
+/// // Channels can then be lent to peripherals that support DMA
+/// let transfer = unsafe {
+///     aes.encrypt_blocks_dma(&mut channels.ch0, &mut channels.ch1, &mut blocks)
+/// }.unwrap();
+/// transfer.wait().unwrap();
+/// ```
+pub struct Dma {
+    _dma: crate::pac::Dma,
+}
+
+/// Collection of the independent channels of the DMA controller.
+pub struct Channels {
+    pub ch0: Channel<0>,
+    pub ch1: Channel<1>,
+    pub ch2: Channel<2>,
+    pub ch3: Channel<3>,
+}
+
+impl Dma {
+    /// Create a new DMA peripheral instance.
+    pub fn new(dma: crate::pac::Dma, reg: &mut crate::gcr::GcrRegisters) -> Self {
+        use crate::gcr::ClockForPeripheral;
+        use crate::gcr::ResetForPeripheral;
+
+        unsafe {
+            dma.reset(&mut reg.gcr);
+            dma.enable_clock(&mut reg.gcr);
+        }
+
+        Self { _dma: dma }
+    }
+
+    /// Splits the DMA peripheral into independent channels.
+    pub fn split(self) -> Channels {
+        Channels {
+            ch0: Channel::new(),
+            ch1: Channel::new(),
+            ch2: Channel::new(),
+            ch3: Channel::new(),
+        }
+    }
+}
+
+/// Zero-sized handle to a single DMA channel.
+///
+/// - `N` is the channel number.
+///
+/// A channel can be used directly for memory-to-peripheral or
+/// peripheral-to-memory transfers by describing them with a
+/// [`TransferConfig`], and polling or waiting for the channel interrupt
+/// until the transfer completes.
+///
+/// Example:
+/// ```
+/// // Send a buffer through UART0 without CPU involvement
+/// let msg = b"hello";
+/// let mut ch = channels.ch0;
+/// unsafe {
+///     ch.start(&TransferConfig {
+///         request: Request::Uart0tx,
+///         src: msg.as_ptr() as u32,
+///         src_width: TransferWidth::Byte,
+///         src_increment: true,
+///         dst: uart_fifo_address,
+///         dst_width: TransferWidth::Byte,
+///         dst_increment: false,
+///         len: msg.len() as u32,
+///         burst_size: 1,
+///     });
+/// }
+/// while !ch.is_complete() {}
+/// ch.clear_interrupt();
+/// ```
+pub struct Channel<const N: usize> {
+    _private: (),
+}
+
+impl<const N: usize> Channel<N> {
+    const fn new() -> Self {
+        Self { _private: () }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _regs(&self) -> &'static crate::pac::dma::Ch {
+        // Safety: Each channel handle only accesses its own channel registers
+        let dma = unsafe { &*crate::pac::Dma::ptr() };
+        dma.ch(N)
+    }
+
+    /// Configures and starts a transfer on this channel.
+    ///
+    /// # Safety
+    /// The source and destination must remain valid for the entire
+    /// transfer, and nothing else may access the destination until
+    /// [`Channel::is_busy`] returns [`false`].
+    pub unsafe fn start(&mut self, config: &TransferConfig) {
+        let ch = self._regs();
+        self.stop();
+        // Clear any stale flags
+        ch.status().write(|w| {
+            w.ctz_if().clear_bit_by_one();
+            w.rld_if().clear_bit_by_one();
+            w.bus_err().clear_bit_by_one();
+            w.to_if().clear_bit_by_one()
+        });
+        ch.src().write(|w| w.addr().bits(config.src));
+        ch.dst().write(|w| w.addr().bits(config.dst));
+        ch.cnt().write(|w| w.cnt().bits(config.len));
+        ch.ctrl().write(|w| {
+            w.request().variant(config.request);
+            match config.src_width {
+                TransferWidth::Byte => w.srcwd().byte(),
+                TransferWidth::HalfWord => w.srcwd().half_word(),
+                TransferWidth::Word => w.srcwd().word(),
+            };
+            w.srcinc().bit(config.src_increment);
+            match config.dst_width {
+                TransferWidth::Byte => w.dstwd().byte(),
+                TransferWidth::HalfWord => w.dstwd().half_word(),
+                TransferWidth::Word => w.dstwd().word(),
+            };
+            w.dstinc().bit(config.dst_increment);
+            w.burst_size().bits(config.burst_size.clamp(1, 32) - 1)
+        });
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        ch.ctrl().modify(|_, w| w.en().set_bit());
+    }
+
+    /// Arms the reload registers of this channel, so that once the current
+    /// transfer counts down to zero the channel continues with a new
+    /// source, destination, and length without stopping. The reload is
+    /// used once; call this again after [`Channel::take_reload_event`]
+    /// to chain another transfer.
+    ///
+    /// # Safety
+    /// Same as [`Channel::start`], for the reloaded source and
+    /// destination.
+    pub unsafe fn set_reload(&mut self, src: u32, dst: u32, len: u32) {
+        let ch = self._regs();
+        ch.srcrld().write(|w| w.addr().bits(src));
+        ch.dstrld().write(|w| w.addr().bits(dst));
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        // Writing the count reload register last arms the reload
+        ch.cntrld().write(|w| {
+            w.cnt().bits(len);
+            w.en().set_bit()
+        });
+    }
+
+    /// Returns [`true`] and clears the flag if the channel reloaded since
+    /// the last call.
+    #[inline(always)]
+    pub fn take_reload_event(&mut self) -> bool {
+        let ch = self._regs();
+        let reloaded = ch.status().read().rld_if().bit_is_set();
+        if reloaded {
+            ch.status().write(|w| w.rld_if().clear_bit_by_one());
+        }
+        reloaded
+    }
+
+    /// Returns [`true`] if the transfer count reached zero since the flags
+    /// were last cleared, meaning the transfer completed (or reloaded).
+    #[inline(always)]
+    pub fn is_complete(&self) -> bool {
+        self._regs().status().read().ctz_if().bit_is_set()
+    }
+
+    /// The NVIC interrupt of this channel.
+    pub const fn interrupt(&self) -> crate::Interrupt {
+        match N {
+            0 => crate::Interrupt::DMA0,
+            1 => crate::Interrupt::DMA1,
+            2 => crate::Interrupt::DMA2,
+            _ => crate::Interrupt::DMA3,
+        }
+    }
+
+    /// Enables the channel interrupt when the transfer count reaches zero.
+    ///
+    /// Example:
+    /// ```
+    /// channels.ch2.enable_interrupt();
+    /// unsafe { cortex_m::peripheral::NVIC::unmask(channels.ch2.interrupt()) };
+    /// ```
+    pub fn enable_interrupt(&mut self) {
+        self._regs().ctrl().modify(|_, w| w.ctz_ie().set_bit());
+        // Safety: Each channel handle only modifies its own enable bit, and
+        // the read-modify-write is done in a critical section
+        cortex_m::interrupt::free(|_| {
+            let dma = unsafe { &*crate::pac::Dma::ptr() };
+            dma.inten()
+                .modify(|r, w| unsafe { w.bits(r.bits() | (1 << N)) });
+        });
+    }
+
+    /// Disables the channel interrupt.
+    pub fn disable_interrupt(&mut self) {
+        self._regs().ctrl().modify(|_, w| w.ctz_ie().clear_bit());
+        // Safety: See `enable_interrupt`
+        cortex_m::interrupt::free(|_| {
+            let dma = unsafe { &*crate::pac::Dma::ptr() };
+            dma.inten()
+                .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << N)) });
+        });
+    }
+
+    /// Returns [`true`] if the channel interrupt is pending.
+    #[inline(always)]
+    pub fn is_interrupt_pending(&self) -> bool {
+        // Safety: Read-only access to the shared interrupt flags
+        let dma = unsafe { &*crate::pac::Dma::ptr() };
+        dma.intfl().read().bits() & (1 << N) != 0
+    }
+
+    /// Clears all status flags of this channel, which also clears its
+    /// pending interrupt.
+    #[inline(always)]
+    pub fn clear_interrupt(&mut self) {
+        self._regs().status().write(|w| {
+            w.ctz_if().clear_bit_by_one();
+            w.rld_if().clear_bit_by_one();
+            w.bus_err().clear_bit_by_one();
+            w.to_if().clear_bit_by_one()
+        });
+    }
+
+    /// Stops any transfer in progress on this channel.
+    #[inline(always)]
+    pub fn stop(&mut self) {
+        let ch = self._regs();
+        ch.ctrl().modify(|_, w| w.en().clear_bit());
+        while self.is_busy() {}
+    }
+
+    /// Returns [`true`] if the channel is still transferring data.
+    #[inline(always)]
+    pub fn is_busy(&self) -> bool {
+        self._regs().status().read().status().is_en()
+    }
+
+    /// Returns [`true`] if the channel was stopped by a bus error.
+    #[inline(always)]
+    pub fn bus_error(&self) -> bool {
+        self._regs().status().read().bus_err().bit_is_set()
+    }
+}
@@ -0,0 +1,83 @@
+//! # Errors
+//!
+//! Each peripheral module reports failures with its own error type, which
+//! can be matched on for granular handling. For application code that uses
+//! several peripherals, every module error converts into the crate-level
+//! [`Error`], so `?` works across peripherals with a single error type.
+//!
+//! Example:
+//! ```
+//! fn log_and_sleep(flc: &Flc, rtc: &Rtc, address: u32) -> Result<(), hal::Error> {
+//!     flc.write_32(address, rtc.seconds())?;
+//!     rtc.set_alarm(rtc.seconds().wrapping_add(60))?;
+//!     Ok(())
+//! }
+//!
+//! match log_and_sleep(&flc, &rtc, address) {
+//!     Err(hal::Error::Flash(FlashError::Protected)) => { /* ... */ }
+//!     result => result.unwrap(),
+//! }
+//! ```
+use crate::{adc, aes, cnn, crc, flc, i2c, i2s, kv, power, pulse_train, rtc, simo, spi, timer, trng, uart, wdt};
+
+macro_rules! error {
+    ($($(#[$meta:meta])* $variant:ident($error:ty),)+) => {
+        /// Error of any peripheral of the crate, wrapping the error of the
+        /// module it came from.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        #[non_exhaustive]
+        pub enum Error {
+            $(
+                $(#[$meta])*
+                $variant($error),
+            )+
+        }
+
+        $(
+            impl From<$error> for Error {
+                #[inline(always)]
+                fn from(error: $error) -> Self {
+                    Error::$variant(error)
+                }
+            }
+        )+
+    };
+}
+
+error! {
+    /// Error of the [`adc`] module.
+    Adc(adc::AdcError),
+    /// Error of the [`aes`] module.
+    Aes(aes::AesError),
+    /// Error of the [`cnn`] module.
+    Cnn(cnn::CnnError),
+    /// Error of the [`crc`] module.
+    Crc(crc::CrcError),
+    /// Error of the [`flc`] module.
+    Flash(flc::FlashError),
+    /// Error of the [`i2c`] module.
+    I2c(i2c::I2cError),
+    /// Error of the [`i2s`] module.
+    I2s(i2s::I2sError),
+    /// Error of the [`kv`] module.
+    Kv(kv::KvError),
+    /// Error of the [`power`] module.
+    Power(power::PowerError),
+    /// Error of the [`pulse_train`] module.
+    PulseTrain(pulse_train::PulseTrainError),
+    /// Error of the [`rtc`] module.
+    Rtc(rtc::RtcError),
+    /// Error of the [`simo`] module.
+    Simo(simo::SimoError),
+    /// Error of the [`spi`] module.
+    Spi(spi::SpiError),
+    /// Error of the [`timer`] module.
+    Timer(timer::TimerError),
+    /// Error of the [`trng`] module.
+    Trng(trng::TrngError),
+    /// Error of the [`uart`] module.
+    Uart(uart::UartError),
+    /// Error of the [`wdt`] module.
+    Wdt(wdt::WdtError),
+}
@@ -1,4 +1,30 @@
 //! # Flash Controller (FLC)
+//!
+//! The flash cannot be read while a write or erase is in progress, and that
+//! includes instruction fetches. The functions that start and wait for
+//! these operations must therefore execute from RAM, not from flash.
+//!
+//! By default, these functions are placed in the `.data.ramfunc` section.
+//! The `cortex-m-rt` linker script collects `.data.*` sections into RAM and
+//! copies them there from flash at startup, so no linker script changes are
+//! needed. Use [`ram_func!`](crate::ram_func) to do the same for your own
+//! code that runs while the flash is busy.
+//!
+//! Alternatively, enable the `flashprog-linkage` feature to place them in a
+//! `.flashprog` section instead and locate it with a custom linker script,
+//! for example:
+//!
+//! ```text
+//! .flashprog : ALIGN(4) {
+//!     *(.flashprog*)
+//! } > RAM AT > FLASH
+//! ```
+//!
+//! Only the functions themselves are relocated. Build this crate with
+//! optimizations (`opt-level` of at least 1, even in the dev profile) so the
+//! register accesses they make are inlined into them. Interrupt handlers
+//! that run from flash must also not fire while an operation is in
+//! progress.
 use crate::gcr::clocks::{Clock, SystemClock};
 
 /// Base address of the flash memory.
@@ -11,9 +37,47 @@ pub const FLASH_END: u32 = FLASH_BASE + FLASH_SIZE;
 pub const FLASH_PAGE_COUNT: u32 = 64;
 /// Size of a flash page.
 pub const FLASH_PAGE_SIZE: u32 = 0x2000;
+/// Size of the smallest write to flash memory.
+pub const FLASH_WRITE_SIZE: u32 = 16;
+
+/// Places a function in RAM by putting it in the `.data.ramfunc` section,
+/// which is copied from flash to RAM at startup. The function is never
+/// inlined, so it always executes from RAM.
+///
+/// Functions called from a RAM function are not relocated with it, so any
+/// code that runs while the flash is busy must be inlined or itself be a
+/// RAM function.
+///
+/// Example:
+/// ```
+/// use hal::flc::{Flc, FlashError};
+///
+/// hal::ram_func! {
+///     fn save_key(flc: &Flc, key: &[u8; 16]) -> Result<(), FlashError> {
+///         unsafe { flc.erase_page(0x1006_0000)?; }
+///         flc.write(0x1006_0000, key)
+///     }
+/// }
+///
+/// let key = [0x42u8; 16];
+/// save_key(&flc, &key).unwrap();
+/// let mut read_back = [0u8; 16];
+/// flc.read(0x1006_0000, &mut read_back).unwrap();
+/// assert_eq!(read_back, key);
+/// ```
+#[macro_export]
+macro_rules! ram_func {
+    ($(#[$attr:meta])* $vis:vis fn $name:ident $($rest:tt)*) => {
+        $(#[$attr])*
+        #[link_section = ".data.ramfunc"]
+        #[inline(never)]
+        $vis fn $name $($rest)*
+    };
+}
 
 /// Flash controller errors.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum FlashError {
     /// The target address to write or erase is invalid.
     InvalidAddress(u32, u32),
@@ -22,6 +86,11 @@ pub enum FlashError {
     /// Writing over the old data with new data would cause 0 -> 1 bit transitions.
     /// The target address must be erased before writing new data.
     NeedsErase,
+    /// The address is not aligned to the size of the access, or the access
+    /// crosses a page boundary.
+    Unaligned,
+    /// The target page has been protected from write or erase operations.
+    Protected,
 }
 
 /// # Flash Controller (FLC) Peripheral
@@ -35,6 +104,13 @@ pub enum FlashError {
 /// - Page erase and mass erase
 /// - Read and write protection
 ///
+/// Pages can be protected from writes and erases with
+/// [`Flc::lock_region()`] and from reads with [`Flc::lock_region_read()`].
+/// Any page can be locked, but locks cannot be undone by software: they
+/// stay in effect until the next external or power-on reset, and must be
+/// applied again after every boot. Writing or erasing a locked page fails
+/// with [`FlashError::Protected`].
+///
 /// Example:
 /// ```
 /// let flc = Flc::new(p.flc, sys_clk);
@@ -115,6 +191,34 @@ impl Flc {
         Ok(page_num)
     }
 
+    /// Check if the page containing an address is protected from write or
+    /// erase operations.
+    #[inline]
+    pub fn is_write_locked(&self, address: u32) -> Result<bool, FlashError> {
+        let page_num = self.get_page_number(address)?;
+        // The write enable bit is cleared when the page is locked
+        let welr = if page_num < 32 {
+            self.flc.welr0().read().bits() >> page_num
+        } else {
+            self.flc.welr1().read().bits() >> (page_num - 32)
+        };
+        Ok(welr & 1 == 0)
+    }
+
+    /// Check that no page in the address range `start..=end` is protected
+    /// from write or erase operations.
+    #[inline]
+    fn check_unlocked(&self, start: u32, end: u32) -> Result<(), FlashError> {
+        let mut page = start & !(FLASH_PAGE_SIZE - 1);
+        while page <= end {
+            if self.is_write_locked(page)? {
+                return Err(FlashError::Protected);
+            }
+            page += FLASH_PAGE_SIZE;
+        }
+        Ok(())
+    }
+
     /// Set the target address for a write or erase operation.
     #[inline]
     fn set_address(&self, address: u32) -> Result<(), FlashError> {
@@ -144,6 +248,7 @@ impl Flc {
 
     /// Commit a write operation.
     #[cfg_attr(feature = "flashprog-linkage", link_section = ".flashprog")]
+    #[cfg_attr(not(feature = "flashprog-linkage"), link_section = ".data.ramfunc")]
     #[inline]
     fn commit_write(&self) {
         self.flc.ctrl().modify(|_, w| w.wr().start());
@@ -153,6 +258,7 @@ impl Flc {
 
     /// Commit a page erase operation.
     #[cfg_attr(feature = "flashprog-linkage", link_section = ".flashprog")]
+    #[cfg_attr(not(feature = "flashprog-linkage"), link_section = ".data.ramfunc")]
     #[inline]
     fn commit_erase(&self) {
         self.flc.ctrl().modify(|_, w| w.pge().start());
@@ -164,13 +270,15 @@ impl Flc {
     /// be used by all other write functions.
     #[doc(hidden)]
     #[cfg_attr(feature = "flashprog-linkage", link_section = ".flashprog")]
+    #[cfg_attr(not(feature = "flashprog-linkage"), link_section = ".data.ramfunc")]
     #[inline(never)]
     fn _write_128(&self, address: u32, data: &[u32; 4]) -> Result<(), FlashError> {
         // Target address must be 128-bit aligned
         if address & 0b1111 != 0 {
-            return Err(FlashError::InvalidAddress(address, address & 0b1111));
+            return Err(FlashError::Unaligned);
         }
         self.check_address(address)?;
+        self.check_unlocked(address, address)?;
         // Ensure that the flash controller is configured
         self.config();
         // Verify that only 1 -> 0 transitions are being made by reading the existing data at the target address
@@ -204,8 +312,15 @@ impl Flc {
     /// Erases a page in flash memory.
     #[doc(hidden)]
     #[cfg_attr(feature = "flashprog-linkage", link_section = ".flashprog")]
+    #[cfg_attr(not(feature = "flashprog-linkage"), link_section = ".data.ramfunc")]
     #[inline(never)]
     fn _erase_page(&self, address: u32) -> Result<(), FlashError> {
+        // Target address must be the start of a page
+        if address & (FLASH_PAGE_SIZE - 1) != 0 {
+            return Err(FlashError::Unaligned);
+        }
+        self.check_address(address)?;
+        self.check_unlocked(address, address)?;
         while self.is_busy() {}
         self.set_address(address)?;
         self.unlock_flash();
@@ -222,32 +337,65 @@ impl Flc {
         Ok(())
     }
 
-    /// Writes an slice of u32 to u32 alligned address
+    /// Writes a slice of [`u32`] to flash memory, starting at a 32-bit
+    /// aligned address. The slice must not cross a page boundary.
     pub fn write_u32_slice(&self, address: u32, data: &[u32]) -> Result<(), FlashError> {
         // Target address must be sizeof aligned
         if address & 0b11 != 0 {
-            return Err(FlashError::InvalidAddress(address, address & 0b11));
+            return Err(FlashError::Unaligned);
+        }
+        if data.is_empty() {
+            return Ok(());
         }
 
         let start_addr: u32 = address;
         self.check_address(start_addr)?;
 
-        let end_addr: u32 = address + (data.len() as u32) - 1;
+        let end_addr: u32 = address + (size_of_val(data) as u32) - 1;
         self.check_address(end_addr)?;
+        if self.get_page_number(start_addr)? != self.get_page_number(end_addr)? {
+            return Err(FlashError::Unaligned);
+        }
+        self.check_unlocked(start_addr, end_addr)?;
 
         for (d_idx, data) in data.iter().enumerate() {
             let offset: u32 = (size_of::<u32>() * d_idx) as u32;
-            let _ = match self.write_32(start_addr + offset, *data) {
-                Ok(()) => {},
-                Err(e) => {
-                    return Err(e);
-                } 
-            };
+            self.write_32(start_addr + offset, *data)?;
         }
 
         Ok(())
     }
 
+    /// Writes bytes to flash memory. The target address and the length of
+    /// the data must both be multiples of [`FLASH_WRITE_SIZE`], but the data
+    /// may span several pages.
+    ///
+    /// Example:
+    /// ```
+    /// let record = [0xA5u8; 32];
+    /// flc.write(0x1006_0000, &record).unwrap();
+    /// ```
+    pub fn write(&self, address: u32, data: &[u8]) -> Result<(), FlashError> {
+        if !address.is_multiple_of(FLASH_WRITE_SIZE) || !(data.len() as u32).is_multiple_of(FLASH_WRITE_SIZE) {
+            return Err(FlashError::Unaligned);
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.check_address(address)?;
+        self.check_address(address + data.len() as u32 - 1)?;
+        // Fail before writing anything if part of the range is locked
+        self.check_unlocked(address, address + data.len() as u32 - 1)?;
+        for (i, chunk) in data.chunks_exact(FLASH_WRITE_SIZE as usize).enumerate() {
+            let mut words = [0u32; 4];
+            for (word, bytes) in words.iter_mut().zip(chunk.chunks_exact(4)) {
+                *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            self._write_128(address + (i as u32) * FLASH_WRITE_SIZE, &words)?;
+        }
+        Ok(())
+    }
+
     /// Writes four [`u32`] to flash memory. Uses little-endian byte order.
     /// The lowest [`u32`] in the array is written to the lowest address in flash.
     /// The target address must be 128-bit aligned.
@@ -260,7 +408,7 @@ impl Flc {
     /// // 10060000: 0102 0304 0506 0708 090A 0B0C 0D0E 0F10
     /// ```
     pub fn write_128(&self, address: u32, data: &[u32; 4]) -> Result<(), FlashError> {
-        self._write_128(address, &data)
+        self._write_128(address, data)
     }
 
     /// Write a [`u32`] to flash memory. Uses little-endian byte order.
@@ -281,7 +429,7 @@ impl Flc {
     pub fn write_32(&self, address: u32, data: u32) -> Result<(), FlashError> {
         // Target address must be 32-bit aligned
         if address & 0b11 != 0 {
-            return Err(FlashError::InvalidAddress(address, address & 0b11));
+            return Err(FlashError::Unaligned);
         }
         self.check_address(address)?;
         let addr_128 = address & !0b1111;
@@ -306,8 +454,8 @@ impl Flc {
 
     pub fn read_t<T>(&self, address: u32) -> Result<T, FlashError> {
         // Target address must be sizeof aligned
-        if address % (align_of::<T>() as u32) != 0 {
-            return Err(FlashError::InvalidAddress(address, address % (align_of::<T>() as u32)));
+        if !address.is_multiple_of(align_of::<T>() as u32) {
+            return Err(FlashError::Unaligned);
         }
         self.check_address(address)?;
         let addr_ptr = address as *const T;
@@ -317,13 +465,33 @@ impl Flc {
         }
     }
 
+    /// Reads bytes from flash memory into a buffer, starting at any address.
+    ///
+    /// Example:
+    /// ```
+    /// let mut key = [0u8; 32];
+    /// flc.read(0x1006_0000, &mut key).unwrap();
+    /// ```
+    pub fn read(&self, address: u32, buffer: &mut [u8]) -> Result<(), FlashError> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        self.check_address(address)?;
+        self.check_address(address + buffer.len() as u32 - 1)?;
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            // Safety: We have checked the address range already
+            *byte = unsafe { core::ptr::read_volatile((address + i as u32) as *const u8) };
+        }
+        Ok(())
+    }
+
     /// Reads four [`u32`] from flash memory. Uses little-endian byte order.
     /// The lowest [`u32`] in the array is read from the lowest address in flash.
     /// The target address must be 128-bit aligned.
     pub fn read_128(&self, address: u32) -> Result<[u32; 4], FlashError> {
         // Target address must be 128-bit aligned
         if address & 0b1111 != 0 {
-            return Err(FlashError::InvalidAddress(address, address & 0b1111));
+            return Err(FlashError::Unaligned);
         }
         self.check_address(address)?;
         let addr_128_ptr = address as *const u32;
@@ -343,7 +511,7 @@ impl Flc {
     pub fn read_32(&self, address: u32) -> Result<u32, FlashError> {
         // Target address must be 32-bit aligned
         if address & 0b11 != 0 {
-            return Err(FlashError::InvalidAddress(address, address & 0b11));
+            return Err(FlashError::Unaligned);
         }
         self.check_address(address)?;
         let addr_32_ptr = address as *const u32;
@@ -351,7 +519,8 @@ impl Flc {
         unsafe { Ok(core::ptr::read_volatile(addr_32_ptr)) }
     }
 
-    /// Erases a page in flash memory.
+    /// Erases a page in flash memory. The address must be the start of the
+    /// page.
     ///
     /// # Safety
     /// Care must be taken to not erase the page containing the executing code.
@@ -359,6 +528,28 @@ impl Flc {
         self._erase_page(address)
     }
 
+    /// Erases every page in the address range `from..to`. Both addresses
+    /// must be page-aligned; `to` may be [`FLASH_END`].
+    ///
+    /// # Safety
+    /// Care must be taken to not erase the pages containing the executing code.
+    pub unsafe fn erase(&self, from: u32, to: u32) -> Result<(), FlashError> {
+        if !from.is_multiple_of(FLASH_PAGE_SIZE) || !to.is_multiple_of(FLASH_PAGE_SIZE) {
+            return Err(FlashError::Unaligned);
+        }
+        if from >= to {
+            return Ok(());
+        }
+        self.check_address(from)?;
+        self.check_address(to - 1)?;
+        // Fail before erasing anything if part of the range is locked
+        self.check_unlocked(from, to - 1)?;
+        for page in (from..to).step_by(FLASH_PAGE_SIZE as usize) {
+            self._erase_page(page)?;
+        }
+        Ok(())
+    }
+
     /// Protects a page in flash memory from write or erase operations.
     /// Effective until the next external or power-on reset.
     pub fn disable_page_write(&self, address: u32) -> Result<(), FlashError> {
@@ -398,4 +589,164 @@ impl Flc {
         }
         Ok(())
     }
+
+    /// Protects the pages in the address range `start..start + len` from
+    /// write or erase operations. Both `start` and `len` must be multiples
+    /// of [`FLASH_PAGE_SIZE`]. Effective until the next external or
+    /// power-on reset.
+    ///
+    /// Example:
+    /// ```
+    /// // Lock the two pages holding the device secrets
+    /// flc.lock_region(0x1007_8000, 2 * FLASH_PAGE_SIZE).unwrap();
+    /// assert_eq!(flc.write_32(0x1007_8000, 0), Err(FlashError::Protected));
+    /// ```
+    pub fn lock_region(&self, start: u32, len: u32) -> Result<(), FlashError> {
+        for page in self.region_pages(start, len)? {
+            self.disable_page_write(page)?;
+        }
+        Ok(())
+    }
+
+    /// Protects the pages in the address range `start..start + len` from
+    /// read operations. Both `start` and `len` must be multiples of
+    /// [`FLASH_PAGE_SIZE`]. Effective until the next external or power-on
+    /// reset.
+    ///
+    /// Care must be taken to not lock the pages containing the executing
+    /// code, since instruction fetches are blocked as well.
+    pub fn lock_region_read(&self, start: u32, len: u32) -> Result<(), FlashError> {
+        for page in self.region_pages(start, len)? {
+            self.disable_page_read(page)?;
+        }
+        Ok(())
+    }
+
+    /// Validate a page-aligned region and iterate over its page addresses.
+    #[inline]
+    fn region_pages(&self, start: u32, len: u32) -> Result<impl Iterator<Item = u32>, FlashError> {
+        if !start.is_multiple_of(FLASH_PAGE_SIZE) || !len.is_multiple_of(FLASH_PAGE_SIZE) {
+            return Err(FlashError::Unaligned);
+        }
+        if len != 0 {
+            self.check_address(start)?;
+            self.check_address(start.saturating_add(len - 1))?;
+        }
+        Ok((start..start + len).step_by(FLASH_PAGE_SIZE as usize))
+    }
+}
+
+/// # Flash Region
+///
+/// A page-aligned region of the flash implementing the [`embedded_storage`]
+/// NOR flash traits, so that storage crates such as `sequential-storage`
+/// can manage it. Offsets are relative to the start of the region. This
+/// can be enabled with the `embedded-storage` feature flag.
+///
+/// Reads can start at any byte, writes are made in units of
+/// [`FLASH_WRITE_SIZE`] and erases in whole pages. Writing over bytes that
+/// are not erased fails with [`FlashError::NeedsErase`].
+///
+/// Example:
+/// ```
+/// use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+/// use hal::flc::FlashRegion;
+///
+/// // The last two pages, which hold no code
+/// let mut region = unsafe { FlashRegion::new(&flc, 0x1007_c000, 0x4000) }.unwrap();
+/// region.erase(0, 0x2000).unwrap();
+/// region.write(0, &[0x42; 16]).unwrap();
+/// let mut byte = [0u8; 1];
+/// region.read(15, &mut byte).unwrap();
+/// assert_eq!(byte, [0x42]);
+/// ```
+#[cfg(feature = "embedded-storage")]
+pub struct FlashRegion<'a> {
+    flc: &'a Flc,
+    start: u32,
+    len: u32,
+}
+
+#[cfg(feature = "embedded-storage")]
+impl<'a> FlashRegion<'a> {
+    /// Creates a region of `len` bytes starting at `start`. Both must be
+    /// multiples of [`FLASH_PAGE_SIZE`], and the region must lie within the
+    /// flash.
+    ///
+    /// # Safety
+    /// The trait methods erase and write the region from safe code, so it
+    /// must not hold the executing code or any other data in use.
+    pub unsafe fn new(flc: &'a Flc, start: u32, len: u32) -> Result<Self, FlashError> {
+        let _ = flc.region_pages(start, len)?;
+        Ok(Self { flc, start, len })
+    }
+
+    /// Returns the address of `offset`, checking that `len` bytes from it
+    /// are within the region.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _address(&self, offset: u32, len: usize) -> Result<u32, FlashError> {
+        let end = u32::try_from(len).ok().and_then(|len| offset.checked_add(len));
+        match end {
+            Some(end) if end <= self.len => Ok(self.start + offset),
+            _ => Err(FlashError::InvalidAddress(
+                self.start.wrapping_add(offset),
+                self.start + self.len,
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-storage")]
+impl embedded_storage::nor_flash::NorFlashError for FlashError {
+    fn kind(&self) -> embedded_storage::nor_flash::NorFlashErrorKind {
+        use embedded_storage::nor_flash::NorFlashErrorKind;
+        match self {
+            FlashError::InvalidAddress(..) => NorFlashErrorKind::OutOfBounds,
+            FlashError::Unaligned => NorFlashErrorKind::NotAligned,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(feature = "embedded-storage")]
+impl embedded_storage::nor_flash::ErrorType for FlashRegion<'_> {
+    type Error = FlashError;
+}
+
+#[cfg(feature = "embedded-storage")]
+impl embedded_storage::nor_flash::ReadNorFlash for FlashRegion<'_> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let address = self._address(offset, bytes.len())?;
+        self.flc.read(address, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        self.len as usize
+    }
+}
+
+#[cfg(feature = "embedded-storage")]
+impl embedded_storage::nor_flash::NorFlash for FlashRegion<'_> {
+    const WRITE_SIZE: usize = FLASH_WRITE_SIZE as usize;
+    const ERASE_SIZE: usize = FLASH_PAGE_SIZE as usize;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to {
+            return Err(FlashError::InvalidAddress(
+                self.start.wrapping_add(from),
+                self.start.wrapping_add(to),
+            ));
+        }
+        let address = self._address(from, (to - from) as usize)?;
+        // Safety: The region was handed over to the trait methods in new()
+        unsafe { self.flc.erase(address, address + (to - from)) }
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let address = self._address(offset, bytes.len())?;
+        self.flc.write(address, bytes)
+    }
 }
@@ -12,12 +12,13 @@ pub enum OscillatorSourceEnum {
     Ipo,
     /// Internal Secondary Oscillator (60 MHz)
     Iso,
-    // Inro,
+    /// Internal Nano-Ring Oscillator (8 kHz nominal)
+    Inro,
     /// Internal Baud Rate Oscillator (7.3728 MHz)
     Ibro,
     /// External RTC Oscillator (32.768 kHz)
     ///
-    /// Requires initialization of the RTC peripheral. Currently unsupported.
+    /// Requires initialization of the RTC peripheral.
     Ertco,
 }
 
@@ -29,13 +30,14 @@ pub trait OscillatorSource: crate::Sealed {
 
 pub struct InternalPrimaryOscillator;
 pub struct InternalSecondaryOscillator;
-// pub struct InternalNanoRingOscillator;
+pub struct InternalNanoRingOscillator;
 pub struct InternalBaudRateOscillator;
 pub struct ExternalRtcOscillator;
 // pub struct ExternalClockOscillator;
 
 impl crate::Sealed for InternalPrimaryOscillator {}
 impl crate::Sealed for InternalSecondaryOscillator {}
+impl crate::Sealed for InternalNanoRingOscillator {}
 impl crate::Sealed for InternalBaudRateOscillator {}
 impl crate::Sealed for ExternalRtcOscillator {}
 
@@ -47,6 +49,10 @@ impl OscillatorSource for InternalSecondaryOscillator {
     const SOURCE: OscillatorSourceEnum = OscillatorSourceEnum::Iso;
     const BASE_FREQUENCY: u32 = 60_000_000; // 60 MHz
 }
+impl OscillatorSource for InternalNanoRingOscillator {
+    const SOURCE: OscillatorSourceEnum = OscillatorSourceEnum::Inro;
+    const BASE_FREQUENCY: u32 = 8_000; // 8 kHz, uncalibrated
+}
 impl OscillatorSource for InternalBaudRateOscillator {
     const SOURCE: OscillatorSourceEnum = OscillatorSourceEnum::Ibro;
     const BASE_FREQUENCY: u32 = 7_372_800; // 7.3728 MHz
@@ -81,6 +87,7 @@ impl ClockOption for PeripheralClock {}
 
 impl ClockOption for InternalPrimaryOscillator {}
 impl ClockOption for InternalSecondaryOscillator {}
+impl ClockOption for InternalNanoRingOscillator {}
 impl ClockOption for InternalBaudRateOscillator {}
 impl ClockOption for ExternalRtcOscillator {}
 
@@ -181,6 +188,7 @@ where
 pub struct OscillatorGuards {
     pub ipo: OscillatorGuard<InternalPrimaryOscillator>,
     pub iso: OscillatorGuard<InternalSecondaryOscillator>,
+    pub inro: OscillatorGuard<InternalNanoRingOscillator>,
     pub ibro: OscillatorGuard<InternalBaudRateOscillator>,
     pub ertco: OscillatorGuard<ExternalRtcOscillator>,
 }
@@ -190,6 +198,7 @@ impl OscillatorGuards {
         Self {
             ipo: OscillatorGuard::new(),
             iso: OscillatorGuard::new(),
+            inro: OscillatorGuard::new(),
             ibro: OscillatorGuard::new(),
             ertco: OscillatorGuard::new(),
         }
@@ -256,17 +265,28 @@ impl Oscillator<InternalSecondaryOscillator, Enabled> {
     }
 }
 
-// pub type Inro = Oscillator<InternalNanoRingOscillator, Disabled>;
-// impl Inro {
-//     pub fn enable(self, reg: &mut super::GcrRegisters) -> Oscillator<InternalNanoRingOscillator, Enabled> {
-//         // INRO is always enabled
-//         while reg.gcr.clkctrl().read().inro_rdy().bit_is_clear() {}
-//         Oscillator {
-//             _source: PhantomData,
-//             _state: PhantomData,
-//         }
-//     }
-// }
+pub type Inro = Oscillator<InternalNanoRingOscillator, Disabled>;
+impl Inro {
+    pub fn enable(
+        self,
+        reg: &mut super::GcrRegisters,
+    ) -> Oscillator<InternalNanoRingOscillator, Enabled> {
+        // INRO is always enabled
+        while reg.gcr.clkctrl().read().inro_rdy().bit_is_clear() {}
+        Oscillator {
+            _source: PhantomData,
+            _state: PhantomData,
+        }
+    }
+}
+impl Oscillator<InternalNanoRingOscillator, Enabled> {
+    pub const fn into_clock(self) -> Clock<InternalNanoRingOscillator> {
+        Clock::<InternalNanoRingOscillator> {
+            _src: PhantomData,
+            frequency: InternalNanoRingOscillator::BASE_FREQUENCY,
+        }
+    }
+}
 
 pub type Ibro = Oscillator<InternalBaudRateOscillator, Disabled>;
 impl Ibro {
@@ -293,17 +313,28 @@ impl Oscillator<InternalBaudRateOscillator, Enabled> {
 
 pub type Ertco = Oscillator<ExternalRtcOscillator, Disabled>;
 impl Oscillator<ExternalRtcOscillator, Disabled> {
+    /// Enable the ERTCO as a clock source. The oscillator itself is
+    /// started by the RTC peripheral, so an initialized [`Rtc`](crate::rtc::Rtc)
+    /// is required.
     pub fn enable(
         self,
         reg: &mut super::GcrRegisters,
+        _rtc: &crate::rtc::Rtc,
     ) -> Oscillator<ExternalRtcOscillator, Enabled> {
         reg.gcr.clkctrl().modify(|_, w| w.ertco_en().set_bit());
         while reg.gcr.clkctrl().read().ertco_rdy().bit_is_clear() {}
-        todo!("ERTCO requires initialization of the RTC peripheral");
-        // Oscillator {
-        //     _source: PhantomData,
-        //     _state: PhantomData,
-        // }
+        Oscillator {
+            _source: PhantomData,
+            _state: PhantomData,
+        }
+    }
+}
+impl Oscillator<ExternalRtcOscillator, Enabled> {
+    pub const fn into_clock(self) -> Clock<ExternalRtcOscillator> {
+        Clock::<ExternalRtcOscillator> {
+            _src: PhantomData,
+            frequency: ExternalRtcOscillator::BASE_FREQUENCY,
+        }
     }
 }
 
@@ -313,6 +344,16 @@ pub struct SystemClockConfig<S: OscillatorSource, D: SystemClockDivider> {
     _divider: PhantomData<D>,
 }
 
+impl<S, D> Default for SystemClockConfig<S, D>
+where
+    S: OscillatorSource,
+    D: SystemClockDivider,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Initialized system clock configuration and resulting [`Clock`]s and frequencies.
 pub struct SystemClockResults {
     pub sys_clk: Clock<SystemClock>,
@@ -345,12 +386,14 @@ where
             OscillatorSourceEnum::Iso => {
                 reg.gcr.clkctrl().modify(|_, w| w.sysclk_sel().iso());
             }
+            OscillatorSourceEnum::Inro => {
+                reg.gcr.clkctrl().modify(|_, w| w.sysclk_sel().inro());
+            }
             OscillatorSourceEnum::Ibro => {
                 reg.gcr.clkctrl().modify(|_, w| w.sysclk_sel().ibro());
             }
             OscillatorSourceEnum::Ertco => {
-                // reg.gcr.clkctrl().modify(|_, w| w.sysclk_sel().ertco());
-                todo!("ERTCO requires initialization of the RTC peripheral");
+                reg.gcr.clkctrl().modify(|_, w| w.sysclk_sel().ertco());
             }
         }
         while reg.gcr.clkctrl().read().sysclk_rdy().bit_is_clear() {}
@@ -360,6 +403,40 @@ where
         }
     }
 
+    /// Switch the system clock (SYS_CLK) to a new source and divider in one
+    /// step. The oscillator must be enabled beforehand (enforced by the type
+    /// system).
+    ///
+    /// The divider is raised to the larger of the old and new dividers
+    /// before switching the source, and only then set to its final value,
+    /// so SYS_CLK never runs faster than both the old and the new
+    /// frequency while switching. This matters when the core voltage has
+    /// been scaled down for the lower of the two.
+    ///
+    /// Example:
+    /// ```
+    /// let ipo = Ipo::new(gcr.osc_guards.ipo).enable(&mut gcr.reg);
+    /// let clks = gcr.sys_clk
+    ///     .switch_to::<_, Div2>(&mut gcr.reg, &ipo)
+    ///     .freeze();
+    /// // SYS_CLK is the IPO divided by 2
+    /// assert_eq!(clks.sys_clk.frequency, 50_000_000);
+    /// assert_eq!(clks.pclk.frequency, 25_000_000);
+    /// ```
+    pub fn switch_to<NewS: OscillatorSource, NewD: SystemClockDivider>(
+        self,
+        reg: &mut super::GcrRegisters,
+        oscillator: &Oscillator<NewS, Enabled>,
+    ) -> SystemClockConfig<NewS, NewD> {
+        // The current divider may be unknown, so read it from the hardware
+        let current = 1 << reg.gcr.clkctrl().read().sysclk_div().bits();
+        if NewD::DIVISOR > current {
+            self.set_divider::<NewD>(reg).set_source(reg, oscillator)
+        } else {
+            self.set_source(reg, oscillator).set_divider::<NewD>(reg)
+        }
+    }
+
     /// Set the divider of the system clock (SYS_CLK).
     /// The divider must be a valid value (enforced by the type system).
     pub fn set_divider<NewD: SystemClockDivider>(
@@ -4,10 +4,40 @@
 //!
 //! Initialization of the [`Gcr`] peripheral is required to constrain the
 //! GCR from the PAC and safely use them within the HAL.
+//!
+//! [`Gcr::new()`] takes ownership of the GCR and LPGCR register blocks and
+//! returns the [`GcrRegisters`] token together with the oscillators and
+//! the system clock configuration. Every HAL constructor borrows the token
+//! mutably to reset its peripheral and enable its clock, so no unsafe code
+//! is needed on the application side.
+//!
+//! Example:
+//! ```
+//! let p = hal::pac::Peripherals::take().unwrap();
+//! let mut gcr = hal::gcr::Gcr::new(p.gcr, p.lpgcr);
+//! let aes = hal::aes::Aes::new(p.aes, &mut gcr.reg);
+//!
+//! // Or keep the parts separately
+//! let (mut reg, osc_guards, sys_clk) = gcr.split();
+//! let trng = hal::trng::Trng::new(p.trng, &mut reg).unwrap();
+//! ```
+//!
+//! [`Peripherals::take()`](crate::Peripherals::take) does this as part of
+//! taking the peripherals.
 
 pub mod clocks;
 
-/// Wrapper struct to constrain the GCR.
+/// The system clock configuration as it is after reset.
+pub type ResetSystemClockConfig =
+    clocks::SystemClockConfig<clocks::InternalSecondaryOscillator, clocks::DivUnknown>;
+
+/// Token for the clock gating and reset registers, obtained from
+/// [`Gcr::new()`].
+///
+/// Owning the token means owning the GCR and LPGCR register blocks, so only
+/// one can exist. HAL constructors borrow it mutably, which serializes
+/// their read-modify-write accesses to the shared clock and reset
+/// registers without a critical section.
 pub struct GcrRegisters {
     pub gcr: crate::pac::Gcr,
     pub lpgcr: crate::pac::Lpgcr,
@@ -15,12 +45,18 @@ pub struct GcrRegisters {
 
 /// Global Control Registers (GCR) Peripheral
 pub struct Gcr {
+    /// Token passed to HAL constructors.
     pub reg: GcrRegisters,
+    /// Oscillators that can be enabled and selected as the system clock.
     pub osc_guards: clocks::OscillatorGuards,
-    pub sys_clk: clocks::SystemClockConfig<clocks::InternalSecondaryOscillator, clocks::DivUnknown>,
+    /// System clock configuration, to be frozen once the clock source and
+    /// divider are selected.
+    pub sys_clk: ResetSystemClockConfig,
 }
 
 impl Gcr {
+    /// Constrain the GCR and LPGCR. This is safe since both PAC register
+    /// blocks are moved into the returned [`GcrRegisters`].
     pub fn new(gcr: crate::pac::Gcr, lpgcr: crate::pac::Lpgcr) -> Self {
         Gcr {
             reg: GcrRegisters { gcr, lpgcr },
@@ -28,23 +64,112 @@ impl Gcr {
             sys_clk: clocks::SystemClockConfig::new(),
         }
     }
+
+    /// Split into the register token, the oscillators and the system clock
+    /// configuration, so each can be moved separately.
+    pub fn split(self) -> (GcrRegisters, clocks::OscillatorGuards, ResetSystemClockConfig) {
+        (self.reg, self.osc_guards, self.sys_clk)
+    }
 }
 
 #[doc(hidden)]
-pub trait GcrRegisterType {}
-impl GcrRegisterType for crate::pac::Gcr {}
-impl GcrRegisterType for crate::pac::Lpgcr {}
+pub trait GcrRegisterType {
+    /// Select this register block out of the constrained GCR registers.
+    fn select(reg: &mut GcrRegisters) -> &mut Self;
+}
+impl GcrRegisterType for crate::pac::Gcr {
+    fn select(reg: &mut GcrRegisters) -> &mut Self {
+        &mut reg.gcr
+    }
+}
+impl GcrRegisterType for crate::pac::Lpgcr {
+    fn select(reg: &mut GcrRegisters) -> &mut Self {
+        &mut reg.lpgcr
+    }
+}
+
+/// Safe clock gating and resets for peripherals that are not in use.
+///
+/// The PAC peripheral acts as the token: HAL drivers take ownership of
+/// their PAC peripheral, so a peripheral that can still be borrowed here is
+/// not driven by the HAL, and gating or resetting it cannot break a
+/// driver. Peripherals are reset with their clocks enabled by the HAL
+/// constructors, so a gated peripheral can still be handed to a driver
+/// later.
+///
+/// Any peripheral can be gated at runtime while it is idle. Leave the
+/// clock enabled for:
+/// - GPIO ports with pins in use, since the input and interrupt logic is
+///   clocked.
+/// - The DMA controller while any channel has a transfer in progress.
+/// - Peripherals accessed directly through the PAC, since their registers
+///   cannot be accessed while gated.
+///
+/// Peripherals that share a clock gate or reset with others are only
+/// implemented for the peripheral that owns the shared control, so a
+/// borrowed token can never reach hardware a driver still owns. The pulse
+/// trains are gated and reset through [`crate::pac::Ptg`], never through
+/// an individual `PtN`.
+///
+/// Example:
+/// ```
+/// // Save power by gating the peripherals this application never uses
+/// gcr.reg.disable_clock(&p.i2s);
+/// gcr.reg.disable_clock(&p.owm);
+/// gcr.reg.disable_clock(&p.uart3);
+/// ```
+impl GcrRegisters {
+    /// Enables the clock of a peripheral.
+    pub fn enable_clock<P: ClockForPeripheral>(&mut self, peripheral: &P) {
+        // Safety: The peripheral is not owned by a HAL driver
+        unsafe { peripheral.enable_clock(P::ValidatedGcrRegisterType::select(self)) }
+    }
+
+    /// Disables the clock of a peripheral.
+    pub fn disable_clock<P: ClockForPeripheral>(&mut self, peripheral: &P) {
+        // Safety: The peripheral is not owned by a HAL driver
+        unsafe { peripheral.disable_clock(P::ValidatedGcrRegisterType::select(self)) }
+    }
+
+    /// Resets a peripheral to its power-on state.
+    pub fn reset<P: ResetForPeripheral>(&mut self, peripheral: &P) {
+        // Safety: The peripheral is not owned by a HAL driver
+        unsafe { peripheral.reset(P::ValidatedGcrRegisterType::select(self)) }
+    }
+}
 
 /// Extension trait for enabling and disabling peripheral clocks.
+///
+/// The methods are unsafe because gating the clock of a peripheral that a
+/// HAL driver owns breaks that driver. HAL constructors call them on the
+/// peripheral they take ownership of; applications should use the safe
+/// [`GcrRegisters::enable_clock()`] and [`GcrRegisters::disable_clock()`]
+/// instead.
 pub trait ClockForPeripheral {
     type ValidatedGcrRegisterType: GcrRegisterType;
+    /// Enables the peripheral clock.
+    ///
+    /// # Safety
+    /// See the implementation for each peripheral.
     unsafe fn enable_clock(&self, gcr: &mut Self::ValidatedGcrRegisterType);
+    /// Disables the peripheral clock.
+    ///
+    /// # Safety
+    /// See the implementation for each peripheral.
     unsafe fn disable_clock(&self, gcr: &mut Self::ValidatedGcrRegisterType);
 }
 
-// Extension trait for peripheral resets.
+/// Extension trait for peripheral resets.
+///
+/// The method is unsafe because resetting a peripheral that a HAL driver
+/// owns silently discards its configuration. Applications should use the
+/// safe [`GcrRegisters::reset()`] instead.
 pub trait ResetForPeripheral {
     type ValidatedGcrRegisterType: GcrRegisterType;
+    /// Resets the peripheral.
+    ///
+    /// # Safety
+    /// See the implementation for each peripheral.
     unsafe fn reset(&self, resets: &mut Self::ValidatedGcrRegisterType);
 }
 
@@ -100,7 +225,7 @@ macro_rules! generate_reset {
 
 generate_clock!(Adc, Gcr, pclkdis0, adc);
 generate_clock!(Aes, Gcr, pclkdis1, aes);
-// CNN?
+generate_clock!(Gcfr, Gcr, pclkdis0, cnn); // Note: The CNN accelerator has no register block in the PAC, so its power control registers stand in for it
 // CPU1 (RISC-V core)?
 generate_clock!(Crc, Gcr, pclkdis1, crc);
 generate_clock!(Dma, Gcr, pclkdis0, dma);
@@ -113,7 +238,7 @@ generate_clock!(I2c2, Gcr, pclkdis1, i2c2);
 generate_clock!(I2s, Gcr, pclkdis1, i2s);
 generate_clock!(Lpcmp, Lpgcr, pclkdis, lpcomp);
 generate_clock!(Owm, Gcr, pclkdis1, owm);
-generate_clock!(Pt0, Gcr, pclkdis0, pt);
+generate_clock!(Ptg, Gcr, pclkdis0, pt); // Note: The clock is shared by all pulse trains, so the individual PtN have no impl
 generate_clock!(Sema, Gcr, pclkdis1, smphr);
 generate_clock!(Spi0, Gcr, pclkdis1, spi0);
 generate_clock!(Spi1, Gcr, pclkdis0, spi1);
@@ -134,7 +259,7 @@ generate_clock!(Wdt1, Lpgcr, pclkdis, wdt1);
 // TODO: add system, peripheral, and soft resets
 generate_reset!(Adc, Gcr, rst0, adc);
 generate_reset!(Aes, Gcr, rst1, aes);
-// CNN?
+generate_reset!(Gcfr, Gcr, rst0, cnn); // Note: Resets the CNN accelerator, see the clock note above
 // CPU1 (RISC-V core)?
 generate_reset!(Crc, Gcr, rst1, crc);
 generate_reset!(Dma, Gcr, rst0, dma);
@@ -148,7 +273,7 @@ generate_reset!(I2c2, Gcr, rst1, i2c2);
 generate_reset!(I2s, Gcr, rst1, i2s);
 generate_reset!(Lpcmp, Lpgcr, rst, lpcomp);
 generate_reset!(Owm, Gcr, rst1, owm);
-generate_reset!(Pt0, Gcr, rst1, pt);
+generate_reset!(Ptg, Gcr, rst1, pt); // Note: Resets all pulse trains, so the individual PtN have no impl
 generate_reset!(Rtc, Gcr, rst0, rtc);
 generate_reset!(Sema, Gcr, rst1, smphr);
 generate_reset!(Simo, Gcr, rst1, simo);
@@ -8,19 +8,31 @@ pub trait PinMode: crate::Sealed {}
 
 pub struct Input;
 pub struct InputOutput;
+pub struct OpenDrain;
 pub struct Af1;
 pub struct Af2;
 
 impl crate::Sealed for Input {}
 impl crate::Sealed for InputOutput {}
+impl crate::Sealed for OpenDrain {}
 impl crate::Sealed for Af1 {}
 impl crate::Sealed for Af2 {}
 
 impl PinMode for Input {}
 impl PinMode for InputOutput {}
+impl PinMode for OpenDrain {}
 impl PinMode for Af1 {}
 impl PinMode for Af2 {}
 
+/// Edges that can trigger a GPIO interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
 /// Marker trait for GPIO pin power supply.
 pub trait PowerSupply: crate::Sealed {}
 
@@ -34,7 +46,14 @@ impl PowerSupply for Vddio {}
 impl PowerSupply for Vddioh {}
 
 /// Marker trait for GPIO pin input pad modes.
-pub trait PadMode: crate::Sealed {}
+pub trait PadMode: crate::Sealed {
+    #[doc(hidden)]
+    const PADCTRL0: bool;
+    #[doc(hidden)]
+    const PADCTRL1: bool;
+    #[doc(hidden)]
+    const STRONG: bool;
+}
 
 pub struct HighImpedance;
 pub struct PullUpWeak;
@@ -48,14 +67,41 @@ impl crate::Sealed for PullUpStrong {}
 impl crate::Sealed for PullDownWeak {}
 impl crate::Sealed for PullDownStrong {}
 
-impl PadMode for HighImpedance {}
-impl PadMode for PullUpWeak {}
-impl PadMode for PullUpStrong {}
-impl PadMode for PullDownWeak {}
-impl PadMode for PullDownStrong {}
+impl PadMode for HighImpedance {
+    const PADCTRL0: bool = false;
+    const PADCTRL1: bool = false;
+    const STRONG: bool = false;
+}
+impl PadMode for PullUpWeak {
+    const PADCTRL0: bool = true;
+    const PADCTRL1: bool = false;
+    const STRONG: bool = false;
+}
+impl PadMode for PullUpStrong {
+    const PADCTRL0: bool = true;
+    const PADCTRL1: bool = false;
+    const STRONG: bool = true;
+}
+impl PadMode for PullDownWeak {
+    const PADCTRL0: bool = false;
+    const PADCTRL1: bool = true;
+    const STRONG: bool = false;
+}
+impl PadMode for PullDownStrong {
+    const PADCTRL0: bool = false;
+    const PADCTRL1: bool = true;
+    const STRONG: bool = true;
+}
 
 /// Marker trait for GPIO pin output drive strengths.
-pub trait DriveStrength: crate::Sealed {}
+///
+/// Every pin supports all four strengths, so any combination is valid.
+pub trait DriveStrength: crate::Sealed {
+    #[doc(hidden)]
+    const DS0: bool;
+    #[doc(hidden)]
+    const DS1: bool;
+}
 
 pub struct Strength0;
 pub struct Strength1;
@@ -67,18 +113,47 @@ impl crate::Sealed for Strength1 {}
 impl crate::Sealed for Strength2 {}
 impl crate::Sealed for Strength3 {}
 
-impl DriveStrength for Strength0 {}
-impl DriveStrength for Strength1 {}
-impl DriveStrength for Strength2 {}
-impl DriveStrength for Strength3 {}
+impl DriveStrength for Strength0 {
+    const DS0: bool = false;
+    const DS1: bool = false;
+}
+impl DriveStrength for Strength1 {
+    const DS0: bool = true;
+    const DS1: bool = false;
+}
+impl DriveStrength for Strength2 {
+    const DS0: bool = false;
+    const DS1: bool = true;
+}
+impl DriveStrength for Strength3 {
+    const DS0: bool = true;
+    const DS1: bool = true;
+}
 
 /// Zero-sized abstraction type for a GPIO pin.
 ///
 /// Traits from [`embedded_hal::digital`] are also implemented for each pin.
 ///
+/// Example (blinky, with P2.0 wired to P2.1 in loopback):
+/// ```
+/// use embedded_hal::digital::{InputPin, StatefulOutputPin};
+///
+/// let pins2 = hal::gpio::Gpio2::new(p.gpio2, &mut gcr.reg).split();
+/// let mut led = pins2.p2_0.into_input_output();
+/// let mut loopback = pins2.p2_1;
+/// loop {
+///     led.toggle().unwrap();
+///     assert_eq!(loopback.is_high().unwrap(), led.is_set_high().unwrap());
+///     delay.delay_ms(500);
+/// }
+/// ```
+///
 /// - `P` is the GPIO port number (e.g. `0` for `Gpio0`, `1` for `Gpio1`, etc.)
 /// - `N` is the GPIO pin number.
 /// - `MODE` is one of the pin modes (e.g. `Input`, `InputOutput`, `Af1`, `Af2`).
+/// - `SUPPLY` is the pin power supply (e.g. `Vddio`, `Vddioh`).
+/// - `PAD` is the input pad mode (e.g. `HighImpedance`, `PullUpWeak`).
+/// - `DRIVE` is the output drive strength (e.g. `Strength0`).
 pub struct Pin<
     const P: u8,
     const N: u8,
@@ -94,7 +169,15 @@ pub struct Pin<
 }
 
 /// Default methods that should work across all pin modes.
-impl<const P: u8, const N: u8, MODE: PinMode> Pin<P, N, MODE> {
+impl<
+        const P: u8,
+        const N: u8,
+        MODE: PinMode,
+        SUPPLY: PowerSupply,
+        PAD: PadMode,
+        DRIVE: DriveStrength,
+    > Pin<P, N, MODE, SUPPLY, PAD, DRIVE>
+{
     const fn new() -> Self {
         Self {
             _mode: PhantomData,
@@ -206,40 +289,192 @@ impl<const P: u8, const N: u8, MODE: PinMode> Pin<P, N, MODE> {
 }
 
 /// Methods for input pins.
-impl<const P: u8, const N: u8> Pin<P, N, Input> {
-    /// Configures the pin as an input/output pin.
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    Pin<P, N, Input, SUPPLY, PAD, DRIVE>
+{
+    /// Configures the pin as an input/output pin. Outputs are push-pull,
+    /// the MAX7800x GPIO does not have an open-drain output mode.
     #[inline(always)]
-    pub fn into_input_output(self) -> Pin<P, N, InputOutput> {
+    pub fn into_input_output(self) -> Pin<P, N, InputOutput, SUPPLY, PAD, DRIVE> {
         // Enable the output for the pin
-        let mut pin = Pin::<P, N, InputOutput>::new();
+        let mut pin = Pin::<P, N, InputOutput, SUPPLY, PAD, DRIVE>::new();
         pin._output_enable();
         pin
     }
 
+    /// Configures the pin as an emulated open-drain output, released
+    /// (high) initially. Setting the pin low drives it low, and setting it
+    /// high disables the output so an external or internal pull-up raises
+    /// the line, as needed for I2C or other wired-AND buses.
+    #[inline(always)]
+    pub fn into_open_drain(self) -> Pin<P, N, OpenDrain, SUPPLY, PAD, DRIVE> {
+        let mut pin = Pin::<P, N, OpenDrain, SUPPLY, PAD, DRIVE>::new();
+        pin._set_low();
+        pin
+    }
+
     /// Configures the pin as an alternate function 1 pin.
     #[inline(always)]
-    pub fn into_af1(self) -> Pin<P, N, Af1> {
-        let mut pin = Pin::<P, N, Af1>::new();
+    pub fn into_af1(self) -> Pin<P, N, Af1, SUPPLY, PAD, DRIVE> {
+        let mut pin = Pin::<P, N, Af1, SUPPLY, PAD, DRIVE>::new();
         pin._into_af1();
         pin
     }
 
     /// Configures the pin as an alternate function 2 pin.
     #[inline(always)]
-    pub fn into_af2(self) -> Pin<P, N, Af2> {
-        let mut pin = Pin::<P, N, Af2>::new();
+    pub fn into_af2(self) -> Pin<P, N, Af2, SUPPLY, PAD, DRIVE> {
+        let mut pin = Pin::<P, N, Af2, SUPPLY, PAD, DRIVE>::new();
         pin._into_af2();
         pin
     }
+
+    /// Configures the input pad without any pull resistor (floating).
+    #[inline(always)]
+    pub fn into_high_impedance(self) -> Pin<P, N, Input, SUPPLY, HighImpedance, DRIVE> {
+        self._into_pad_mode()
+    }
+
+    /// Configures the input pad with the weak (1 MOhm) pull-up resistor.
+    #[inline(always)]
+    pub fn into_pull_up_weak(self) -> Pin<P, N, Input, SUPPLY, PullUpWeak, DRIVE> {
+        self._into_pad_mode()
+    }
+
+    /// Configures the input pad with the strong (25 kOhm) pull-up resistor.
+    #[inline(always)]
+    pub fn into_pull_up_strong(self) -> Pin<P, N, Input, SUPPLY, PullUpStrong, DRIVE> {
+        self._into_pad_mode()
+    }
+
+    /// Configures the input pad with the weak (1 MOhm) pull-down resistor.
+    #[inline(always)]
+    pub fn into_pull_down_weak(self) -> Pin<P, N, Input, SUPPLY, PullDownWeak, DRIVE> {
+        self._into_pad_mode()
+    }
+
+    /// Configures the input pad with the strong (25 kOhm) pull-down resistor.
+    #[inline(always)]
+    pub fn into_pull_down_strong(self) -> Pin<P, N, Input, SUPPLY, PullDownStrong, DRIVE> {
+        self._into_pad_mode()
+    }
+
+    /// Configures the pin to trigger an interrupt on an edge.
+    ///
+    /// Example:
+    /// ```
+    /// let mut button = pins2.p2_3.into_pull_up_strong();
+    /// button.trigger_on_edge(Edge::Falling);
+    /// button.enable_interrupt();
+    /// unsafe { NVIC::unmask(Interrupt::GPIO2) };
+    /// ```
+    #[inline(always)]
+    pub fn trigger_on_edge(&mut self, edge: Edge) {
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        let mask = 1 << N;
+        gpio.intmode().modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        match edge {
+            Edge::Rising => {
+                gpio.dualedge().modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+                gpio.intpol().modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+            }
+            Edge::Falling => {
+                gpio.dualedge().modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+                gpio.intpol().modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+            }
+            Edge::Both => {
+                gpio.dualedge().modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+            }
+        }
+    }
+
+    /// Configures the pin to trigger an interrupt while at a level.
+    #[inline(always)]
+    pub fn trigger_on_level(&mut self, high: bool) {
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        let mask = 1 << N;
+        gpio.intmode().modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        gpio.dualedge().modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        gpio.intpol().modify(|r, w| unsafe {
+            w.bits(if high { r.bits() | mask } else { r.bits() & !mask })
+        });
+    }
+
+    /// Enables the interrupt for the pin.
+    ///
+    /// All pins of a port share a single NVIC line (e.g. `GPIO2`), so the
+    /// handler must check each pin it is interested in:
+    /// ```
+    /// #[interrupt]
+    /// fn GPIO2() {
+    ///     if button.is_interrupt_pending() {
+    ///         button.clear_interrupt_pending_bit();
+    ///         // Handle the button press
+    ///     }
+    ///     if sensor.is_interrupt_pending() {
+    ///         sensor.clear_interrupt_pending_bit();
+    ///         // Handle the sensor
+    ///     }
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn enable_interrupt(&mut self) {
+        // Safety: Concurrent write access to the GPIO interrupt enable atomic set register is safe
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.inten_set().write(|w| unsafe { w.bits(1 << N) });
+    }
+
+    /// Disables the interrupt for the pin.
+    #[inline(always)]
+    pub fn disable_interrupt(&mut self) {
+        // Safety: Concurrent write access to the GPIO interrupt enable atomic clear register is safe
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.inten_clr().write(|w| unsafe { w.bits(1 << N) });
+    }
+
+    /// Returns [`true`] if the interrupt flag of the pin is set.
+    #[inline(always)]
+    pub fn is_interrupt_pending(&self) -> bool {
+        // Safety: Concurrent read access to the GPIO interrupt flag register is safe
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.intfl().read().bits() & (1 << N) != 0
+    }
+
+    /// Clears the interrupt flag of the pin.
+    #[inline(always)]
+    pub fn clear_interrupt_pending_bit(&mut self) {
+        // Safety: Concurrent write access to the GPIO interrupt flag atomic clear register is safe
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.intfl_clr().write(|w| unsafe { w.bits(1 << N) });
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _into_pad_mode<NEW: PadMode>(self) -> Pin<P, N, Input, SUPPLY, NEW, DRIVE> {
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        let mask = 1 << N;
+        gpio.padctrl0().modify(|r, w| unsafe {
+            w.bits(if NEW::PADCTRL0 { r.bits() | mask } else { r.bits() & !mask })
+        });
+        gpio.padctrl1().modify(|r, w| unsafe {
+            w.bits(if NEW::PADCTRL1 { r.bits() | mask } else { r.bits() & !mask })
+        });
+        gpio.ps().modify(|r, w| unsafe {
+            w.bits(if NEW::STRONG { r.bits() | mask } else { r.bits() & !mask })
+        });
+        Pin::new()
+    }
 }
 
 /// Methods for input/output pins.
-impl<const P: u8, const N: u8> Pin<P, N, InputOutput> {
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    Pin<P, N, InputOutput, SUPPLY, PAD, DRIVE>
+{
     /// Configures the pin as an input pin (disables output).
     #[inline(always)]
-    pub fn into_input(self) -> Pin<P, N, Input> {
+    pub fn into_input(self) -> Pin<P, N, Input, SUPPLY, PAD, DRIVE> {
         // Disable the output for the pin
-        let mut pin = Pin::<P, N, Input>::new();
+        let mut pin = Pin::<P, N, Input, SUPPLY, PAD, DRIVE>::new();
         pin._output_disable();
         pin
     }
@@ -256,6 +491,16 @@ impl<const P: u8, const N: u8> Pin<P, N, InputOutput> {
         self._set_low();
     }
 
+    /// Toggles the pin output.
+    #[inline(always)]
+    pub fn toggle(&mut self) {
+        if self._is_set_high() {
+            self._set_low();
+        } else {
+            self._set_high();
+        }
+    }
+
     /// Returns [`true`] if the pin is set to high, [`false`] if the pin is set to low.
     #[inline(always)]
     pub fn is_set_high(&self) -> bool {
@@ -268,6 +513,36 @@ impl<const P: u8, const N: u8> Pin<P, N, InputOutput> {
         self._is_set_low()
     }
 
+    /// Sets the output drive strength of the pin, from `Strength0`
+    /// (weakest) to `Strength3` (strongest).
+    ///
+    /// Example:
+    /// ```
+    /// // Sharpen the edges of a heavily loaded clock line
+    /// let sck = pins0.p0_7.into_input_output().set_drive_strength::<Strength3>();
+    /// ```
+    #[inline(always)]
+    pub fn set_drive_strength<NEW: DriveStrength>(self) -> Pin<P, N, InputOutput, SUPPLY, PAD, NEW> {
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        let mask = 1 << N;
+        gpio.ds0().modify(|r, w| unsafe {
+            w.bits(if NEW::DS0 { r.bits() | mask } else { r.bits() & !mask })
+        });
+        gpio.ds1().modify(|r, w| unsafe {
+            w.bits(if NEW::DS1 { r.bits() | mask } else { r.bits() & !mask })
+        });
+        Pin::new()
+    }
+
+    /// Selects the fast (default) or slow output slew rate.
+    #[inline(always)]
+    pub fn set_slew_rate_fast(&mut self, fast: bool) {
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.srsel().modify(|r, w| unsafe {
+            w.bits(if fast { r.bits() & !(1 << N) } else { r.bits() | (1 << N) })
+        });
+    }
+
     /// Sets the pin power supply to VDDIO.
     #[inline(always)]
     pub fn set_power_vddio(&mut self) {
@@ -285,13 +560,54 @@ impl<const P: u8, const N: u8> Pin<P, N, InputOutput> {
     }
 }
 
+/// Methods for emulated open-drain pins.
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    Pin<P, N, OpenDrain, SUPPLY, PAD, DRIVE>
+{
+    /// Configures the pin as an input pin (releases the line).
+    #[inline(always)]
+    pub fn into_input(self) -> Pin<P, N, Input, SUPPLY, PAD, DRIVE> {
+        let mut pin = Pin::<P, N, Input, SUPPLY, PAD, DRIVE>::new();
+        pin._output_disable();
+        pin
+    }
+
+    /// Releases the line, letting the pull-up raise it.
+    #[inline(always)]
+    pub fn set_high(&mut self) {
+        self._output_disable();
+    }
+
+    /// Drives the line low.
+    #[inline(always)]
+    pub fn set_low(&mut self) {
+        self._output_enable();
+    }
+}
+
 /// embedded-hal ErrorType trait
-impl<const P: u8, const N: u8, MODE: PinMode> ErrorType for Pin<P, N, MODE> {
+impl<
+        const P: u8,
+        const N: u8,
+        MODE: PinMode,
+        SUPPLY: PowerSupply,
+        PAD: PadMode,
+        DRIVE: DriveStrength,
+    > ErrorType for Pin<P, N, MODE, SUPPLY, PAD, DRIVE>
+{
     type Error = core::convert::Infallible;
 }
 
 /// embedded-hal InputPin trait
-impl<const P: u8, const N: u8, MODE: PinMode> InputPin for Pin<P, N, MODE> {
+impl<
+        const P: u8,
+        const N: u8,
+        MODE: PinMode,
+        SUPPLY: PowerSupply,
+        PAD: PadMode,
+        DRIVE: DriveStrength,
+    > InputPin for Pin<P, N, MODE, SUPPLY, PAD, DRIVE>
+{
     #[inline(always)]
     fn is_high(&mut self) -> Result<bool, Self::Error> {
         Ok(self._is_high())
@@ -304,7 +620,9 @@ impl<const P: u8, const N: u8, MODE: PinMode> InputPin for Pin<P, N, MODE> {
 }
 
 /// embedded-hal OutputPin trait
-impl<const P: u8, const N: u8> OutputPin for Pin<P, N, InputOutput> {
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    OutputPin for Pin<P, N, InputOutput, SUPPLY, PAD, DRIVE>
+{
     #[inline(always)]
     fn set_high(&mut self) -> Result<(), Self::Error> {
         self._set_high();
@@ -318,8 +636,27 @@ impl<const P: u8, const N: u8> OutputPin for Pin<P, N, InputOutput> {
     }
 }
 
+/// embedded-hal OutputPin trait
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    OutputPin for Pin<P, N, OpenDrain, SUPPLY, PAD, DRIVE>
+{
+    #[inline(always)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self._output_disable();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self._output_enable();
+        Ok(())
+    }
+}
+
 /// embedded-hal StatefulOutputPin trait
-impl<const P: u8, const N: u8> StatefulOutputPin for Pin<P, N, InputOutput> {
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    StatefulOutputPin for Pin<P, N, InputOutput, SUPPLY, PAD, DRIVE>
+{
     #[inline(always)]
     fn is_set_high(&mut self) -> Result<bool, Self::Error> {
         Ok(self._is_set_high())
@@ -329,6 +666,301 @@ impl<const P: u8, const N: u8> StatefulOutputPin for Pin<P, N, InputOutput> {
     fn is_set_low(&mut self) -> Result<bool, Self::Error> {
         Ok(self._is_set_low())
     }
+
+    #[inline(always)]
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        Pin::toggle(self);
+        Ok(())
+    }
+}
+
+/// # GPIO Port
+///
+/// Drives several output pins of port `P` with a single register store,
+/// so all of them change on the same clock cycle. Returned by the `split()`
+/// method of each GPIO peripheral alongside the individual pins.
+///
+/// Pins outside of the mask are never modified, and the output register
+/// only affects pins whose output is enabled.
+///
+/// Example:
+/// ```
+/// let pins2 = hal::gpio::Gpio2::new(p.gpio2, &mut gcr.reg).split();
+/// let mut port2 = pins2.port;
+/// let _red = pins2.p2_0.into_input_output();
+/// let _green = pins2.p2_1.into_input_output();
+/// // Turn red off and green on at the same time
+/// port2.write(0b11, 0b10);
+/// ```
+pub struct Port<const P: u8> {
+    _private: (),
+}
+
+impl<const P: u8> Port<P> {
+    const fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Set the outputs of the pins in `mask` to the matching bits of
+    /// `value` in one store to the output register.
+    #[inline(always)]
+    pub fn write(&mut self, mask: u32, value: u32) {
+        _write_port::<P>(mask, value);
+    }
+
+    /// Set the outputs of the pins in `mask` high.
+    #[inline(always)]
+    pub fn set_high(&mut self, mask: u32) {
+        // Safety: Concurrent write access to the GPIO output atomic set register is safe
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.out_set().write(|w| unsafe { w.bits(mask) });
+    }
+
+    /// Set the outputs of the pins in `mask` low.
+    #[inline(always)]
+    pub fn set_low(&mut self, mask: u32) {
+        // Safety: Concurrent write access to the GPIO output atomic clear register is safe
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.out_clr().write(|w| unsafe { w.bits(mask) });
+    }
+
+    /// Returns the input levels of all pins of the port.
+    #[inline(always)]
+    pub fn read(&self) -> u32 {
+        // Safety: Concurrent read access to the GPIO input register is safe
+        let gpio = unsafe { &*gpiox_ptr::<P>() };
+        gpio.in_().read().gpio_in().bits()
+    }
+}
+
+/// Write the pins of port `P` in `mask` with a single store, inside a
+/// critical section so other pins of the port are not lost.
+#[doc(hidden)]
+#[inline(always)]
+fn _write_port<const P: u8>(mask: u32, value: u32) {
+    let gpio = unsafe { &*gpiox_ptr::<P>() };
+    cortex_m::interrupt::free(|_| {
+        gpio.out()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !mask) | (value & mask)) });
+    });
+}
+
+mod port_sealed {
+    pub trait Sealed {}
+}
+
+/// Output pins of port `P` that can be part of a [`ParallelBus`].
+pub trait PortOutput<const P: u8>: port_sealed::Sealed {
+    #[doc(hidden)]
+    /// Pin number within the port.
+    const PIN: u8;
+}
+
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    port_sealed::Sealed for Pin<P, N, InputOutput, SUPPLY, PAD, DRIVE>
+{
+}
+
+impl<const P: u8, const N: u8, SUPPLY: PowerSupply, PAD: PadMode, DRIVE: DriveStrength>
+    PortOutput<P> for Pin<P, N, InputOutput, SUPPLY, PAD, DRIVE>
+{
+    const PIN: u8 = N;
+}
+
+/// Tuples of output pins of port `P`, from bit 0 to the most significant
+/// bit of a [`ParallelBus`].
+pub trait PinGroup<const P: u8>: port_sealed::Sealed {
+    #[doc(hidden)]
+    /// Pin numbers of each bit.
+    const PINS: &'static [u8];
+}
+
+macro_rules! pin_group {
+    ($($pin:ident),+) => {
+        impl<$($pin),+> port_sealed::Sealed for ($($pin,)+) {}
+        impl<const P: u8, $($pin: PortOutput<P>),+> PinGroup<P> for ($($pin,)+) {
+            const PINS: &'static [u8] = &[$($pin::PIN),+];
+        }
+    };
+}
+
+pin_group!(B0);
+pin_group!(B0, B1);
+pin_group!(B0, B1, B2);
+pin_group!(B0, B1, B2, B3);
+pin_group!(B0, B1, B2, B3, B4);
+pin_group!(B0, B1, B2, B3, B4, B5);
+pin_group!(B0, B1, B2, B3, B4, B5, B6);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11, B12);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11, B12, B13);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11, B12, B13, B14);
+pin_group!(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11, B12, B13, B14, B15);
+
+/// # Parallel Bus
+///
+/// A group of up to 16 output pins of the same port, written as one value
+/// with a single store to the output register. Bit 0 of each value drives
+/// the first pin of the tuple. When the pins are consecutive and in order
+/// the value is shifted into place, otherwise each bit is moved to its
+/// pin.
+///
+/// Example (8-bit parallel LCD data bus on P0.16 to P0.23):
+/// ```
+/// use hal::gpio::ParallelBus;
+///
+/// let mut data = ParallelBus::new((
+///     pins.p0_16.into_input_output(),
+///     pins.p0_17.into_input_output(),
+///     pins.p0_18.into_input_output(),
+///     pins.p0_19.into_input_output(),
+///     pins.p0_20.into_input_output(),
+///     pins.p0_21.into_input_output(),
+///     pins.p0_22.into_input_output(),
+///     pins.p0_23.into_input_output(),
+/// ));
+/// data.write(0xa5);
+/// // Latch the byte with the write strobe
+/// wr.set_low();
+/// wr.set_high();
+/// ```
+pub struct ParallelBus<const P: u8, PINS> {
+    pins: PINS,
+    mask: u32,
+    shift: Option<u8>,
+}
+
+impl<const P: u8, PINS: PinGroup<P>> ParallelBus<P, PINS> {
+    /// Group the pins in `pins` into a bus, with the first pin as bit 0.
+    pub fn new(pins: PINS) -> Self {
+        let first = PINS::PINS[0];
+        let consecutive = PINS::PINS
+            .iter()
+            .enumerate()
+            .all(|(i, &pin)| pin as usize == first as usize + i);
+        let mask = PINS::PINS.iter().fold(0, |mask, &pin| mask | (1 << pin));
+        Self {
+            pins,
+            mask,
+            shift: consecutive.then_some(first),
+        }
+    }
+
+    /// Returns the number of pins in the bus.
+    #[inline(always)]
+    pub fn width(&self) -> usize {
+        PINS::PINS.len()
+    }
+
+    /// Returns the mask of the pins of the bus within the port.
+    #[inline(always)]
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Drive the pins with the low bits of `value`, all at once. Bits above
+    /// the width of the bus are ignored.
+    #[inline]
+    pub fn write(&mut self, value: u16) {
+        let bits = match self.shift {
+            Some(shift) => (value as u32) << shift,
+            None => PINS::PINS
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (i, &pin)| bits | (((value as u32 >> i) & 1) << pin)),
+        };
+        _write_port::<P>(self.mask, bits);
+    }
+
+    /// Release the pins.
+    pub fn free(self) -> PINS {
+        self.pins
+    }
+}
+
+/// # Input Debouncer
+///
+/// Debounces up to 32 inputs sampled together, such as the pins of a
+/// [`Port`]. Each bit of the stable state only changes once the last `N`
+/// samples of that bit agree, so a switch bouncing for less than `N`
+/// sampling periods is filtered out.
+///
+/// The debouncer does no timing of its own: call [`Debouncer::update()`]
+/// at a fixed rate, for example from a timer interrupt. With a 5 ms tick
+/// and `N = 4`, a press is reported 15 to 20 ms after the contacts settle.
+///
+/// Example:
+/// ```
+/// static DEBOUNCER: Mutex<RefCell<Debouncer<4>>> =
+///     Mutex::new(RefCell::new(Debouncer::new(u32::MAX)));
+///
+/// #[interrupt]
+/// fn TMR0() {
+///     timer.clear_interrupt();
+///     interrupt::free(|cs| {
+///         let mut debouncer = DEBOUNCER.borrow(cs).borrow_mut();
+///         // Buttons on P2.3 and P2.4 with pull-ups, pressed when low
+///         let state = debouncer.update(port2.read());
+///         let pressed = debouncer.changed() & !state & 0b1_1000;
+///         if pressed != 0 {
+///             handle_buttons(pressed);
+///         }
+///     });
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Debouncer<const N: usize> {
+    samples: [u32; N],
+    next: usize,
+    state: u32,
+    changed: u32,
+}
+
+impl<const N: usize> Debouncer<N> {
+    const NONZERO_SAMPLES: () = assert!(N > 0, "a debouncer needs at least one sample");
+
+    /// Create a debouncer with all inputs stable at `initial`.
+    pub const fn new(initial: u32) -> Self {
+        let () = Self::NONZERO_SAMPLES;
+        Self {
+            samples: [initial; N],
+            next: 0,
+            state: initial,
+            changed: 0,
+        }
+    }
+
+    /// Add a raw sample of the inputs and return the debounced state.
+    pub fn update(&mut self, raw: u32) -> u32 {
+        self.samples[self.next] = raw;
+        self.next = (self.next + 1) % N;
+        let all_high = self.samples.iter().fold(u32::MAX, |bits, &sample| bits & sample);
+        let any_high = self.samples.iter().fold(0, |bits, &sample| bits | sample);
+        // Bits that were high in every sample are set, bits that were low in
+        // every sample are cleared, and all others keep their state
+        let state = (self.state & any_high) | all_high;
+        self.changed = state ^ self.state;
+        self.state = state;
+        state
+    }
+
+    /// The debounced state of the inputs.
+    #[inline(always)]
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    /// The bits of the debounced state that changed in the last update,
+    /// for detecting presses and releases.
+    #[inline(always)]
+    pub fn changed(&self) -> u32 {
+        self.changed
+    }
 }
 
 /// Macro that generates a GPIO module with an interface for splitting GPIO pins.
@@ -344,6 +976,8 @@ macro_rules! gpio {
             pub mod $MODULE_HAL {
                 /// Collection of GPIO pins from a single GPIO port.
                 pub struct Parts {
+                    /// Port handle for writing several pins at once.
+                    pub port: super::Port<$PORT_NUM>,
                     $(
                         pub [<p $PORT_NUM _ $PIN_NUM>]: [<P $PORT_NUM _ $PIN_NUM>],
                     )+
@@ -374,6 +1008,8 @@ macro_rules! gpio {
                 /// let led_red = pins2.p2_0.into_input_output();
                 /// let led_green = pins2.p2_1.into_input_output();
                 /// let led_blue = pins2.p2_2.into_input_output();
+                /// // Set up a button input with a pull-up resistor
+                /// let button = pins2.p2_3.into_pull_up_strong();
                 ///
                 /// // Acquired pins can then be passed to other peripherals in
                 /// // the HAL or embedded-hal driver crates.
@@ -395,6 +1031,7 @@ macro_rules! gpio {
                     /// Splits the GPIO peripheral into independent pins.
                     pub fn split(self) -> Parts {
                         Parts {
+                            port: super::Port::new(),
                             $(
                                 [<p $PORT_NUM _ $PIN_NUM>]: [<P $PORT_NUM _ $PIN_NUM>]::new(),
                             )+
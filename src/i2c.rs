@@ -0,0 +1,1049 @@
+//! # Inter-Integrated Circuit (I2C)
+use core::convert::Infallible;
+use core::ops::Deref;
+
+use crate::gcr::{
+    clocks::{Clock, PeripheralClock},
+    ClockForPeripheral, ResetForPeripheral,
+};
+use crate::gpio::{Af1, Pin};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::i2c::{NoAcknowledgeSource, Operation, SevenBitAddress};
+use embedded_hal_nb::nb;
+use paste::paste;
+
+/// Largest number of bytes received after a single address (8-bit counter,
+/// `0` selects 256).
+const MAX_READ_CHUNK: usize = 256;
+/// Largest value of the SCL high and low period fields (9 bits).
+const MAX_SCL_PERIOD: u32 = 0x1ff;
+/// First byte of a 10-bit address, before the two high address bits and
+/// the direction bit are added.
+const TEN_BIT_HEADER: u8 = 0b1111_0000;
+/// Number of SCL pulses that frees any target stuck in the middle of a byte.
+const RECOVERY_PULSES: usize = 9;
+
+/// Errors that can occur while configuring or using an I2C peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum I2cError {
+    /// The bus frequency cannot be generated from the peripheral clock.
+    UnachievableFrequency,
+    /// The target did not acknowledge its address or a data byte.
+    Nack(NoAcknowledgeSource),
+    /// No target acknowledged the second byte of a 10-bit address, after
+    /// the first byte was acknowledged.
+    TenBitAddressNack,
+    /// The address does not fit in its addressing mode.
+    InvalidAddress,
+    /// Another master took over the bus.
+    ArbitrationLoss,
+    /// A START or STOP condition appeared at an unexpected time, or the
+    /// bus could not be recovered.
+    Bus,
+    /// SCL was held low for longer than the configured timeout.
+    Timeout,
+    /// The timeout is longer than the hardware counter allows.
+    InvalidTimeout,
+}
+
+impl embedded_hal::i2c::Error for I2cError {
+    fn kind(&self) -> embedded_hal::i2c::ErrorKind {
+        match self {
+            I2cError::Nack(source) => embedded_hal::i2c::ErrorKind::NoAcknowledge(*source),
+            I2cError::ArbitrationLoss => embedded_hal::i2c::ErrorKind::ArbitrationLoss,
+            I2cError::Bus => embedded_hal::i2c::ErrorKind::Bus,
+            I2cError::Timeout => embedded_hal::i2c::ErrorKind::Other,
+            I2cError::TenBitAddressNack => {
+                embedded_hal::i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)
+            }
+            I2cError::UnachievableFrequency
+            | I2cError::InvalidAddress
+            | I2cError::InvalidTimeout => {
+                embedded_hal::i2c::ErrorKind::Other
+            }
+        }
+    }
+}
+
+/// Bus frequency of an I2C master.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusSpeed {
+    /// Standard mode, 100 kHz.
+    Standard,
+    /// Fast mode, 400 kHz.
+    Fast,
+}
+
+impl BusSpeed {
+    /// Returns the bus frequency in Hz.
+    pub const fn frequency(&self) -> u32 {
+        match self {
+            BusSpeed::Standard => 100_000,
+            BusSpeed::Fast => 400_000,
+        }
+    }
+}
+
+/// Address of an I2C target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Address {
+    /// 7-bit address (`0x00` to `0x7f`).
+    SevenBit(u8),
+    /// 10-bit address (`0x000` to `0x3ff`), sent as the two-byte
+    /// `11110xx` sequence.
+    TenBit(u16),
+}
+
+impl From<u8> for Address {
+    fn from(address: u8) -> Self {
+        Address::SevenBit(address)
+    }
+}
+
+/// # Inter-Integrated Circuit (I2C) Master
+///
+/// Implements [`embedded_hal::i2c::I2c`] with 7-bit addresses, and
+/// [`I2c::execute()`] also accepts 10-bit addresses. Each
+/// transaction starts with a START condition, uses a repeated START
+/// whenever the direction changes, and ends with a STOP condition.
+///
+/// Example:
+/// ```
+/// use embedded_hal::i2c::I2c;
+///
+/// let pins = hal::gpio::Gpio0::new(p.gpio0, &mut gcr.reg).split();
+/// let mut i2c = hal::i2c::I2c::i2c1(
+///     p.i2c1,                 // I2C peripheral from the PAC
+///     &mut gcr.reg,           // GCR instance
+///     &clks.pclk,             // Peripheral clock
+///     pins.p0_16.into_af1(),  // SCL pin
+///     pins.p0_17.into_af1(),  // SDA pin
+///     hal::i2c::BusSpeed::Fast,
+/// )
+/// .unwrap();
+///
+/// // Read the WHO_AM_I register of a sensor
+/// let mut id = [0u8];
+/// i2c.write_read(0x68, &[0x75], &mut id).unwrap();
+/// ```
+pub struct I2c<I2C, SCL, SDA> {
+    i2c: I2C,
+    _scl_pin: SCL,
+    _sda_pin: SDA,
+    clk_freq: u32,
+    byte_cycles: u32,
+}
+
+/// Event reported by [`I2cSlave::poll()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlaveEvent {
+    /// The master addressed this device to write to it. Received bytes can
+    /// be read with [`I2cSlave::read()`].
+    WriteRequest,
+    /// The master sent a general call (address `0x00`) to write to every
+    /// device on the bus.
+    GeneralCall,
+    /// The master addressed this device to read from it. SCL is stretched
+    /// until bytes are queued with [`I2cSlave::write()`].
+    ReadRequest,
+    /// The master ended the transaction. Bytes received before the STOP
+    /// stay in the RX FIFO until they are read.
+    Stop,
+}
+
+/// # I2C Slave
+///
+/// Responds to an I2C master at a fixed address, using a polled interface.
+/// SCL is stretched while the RX FIFO is full or the TX FIFO is empty, so
+/// the application does not have to answer each byte in time.
+///
+/// Example:
+/// ```
+/// use hal::i2c::{Address, I2cSlave, SlaveEvent};
+///
+/// let mut slave = I2cSlave::i2c1(
+///     p.i2c1,
+///     &mut gcr.reg,
+///     pins.p0_16.into_af1(),
+///     pins.p0_17.into_af1(),
+///     Address::SevenBit(0x42),
+///     false,                  // Ignore general calls
+/// )
+/// .unwrap();
+///
+/// let mut command = [0u8; 8];
+/// let mut len = 0;
+/// loop {
+///     len += slave.read(&mut command[len..]);
+///     match slave.poll() {
+///         Ok(SlaveEvent::ReadRequest) => {
+///             slave.write(&response);
+///         }
+///         Ok(SlaveEvent::Stop) => {
+///             len += slave.read(&mut command[len..]);
+///             handle(&command[..len]);
+///             len = 0;
+///         }
+///         _ => {}
+///     }
+/// }
+/// ```
+pub struct I2cSlave<I2C, SCL, SDA> {
+    i2c: I2C,
+    _scl_pin: SCL,
+    _sda_pin: SDA,
+}
+
+/// Pins that can be used as the clock line of an I2C peripheral.
+pub trait SclPin<I2C>: crate::Sealed {}
+/// Pins that can be used as the data line of an I2C peripheral.
+pub trait SdaPin<I2C>: crate::Sealed {}
+
+// All I2C peripherals are derived from the same register block
+type I2cRegisterBlock = crate::pac::i2c0::RegisterBlock;
+
+macro_rules! i2c {
+    (
+        $i2c:ident,
+        scl: $scl_pin:ty,
+        sda: $sda_pin:ty,
+    ) => {
+        paste! {
+            use crate::pac::$i2c;
+
+            impl crate::Sealed for $scl_pin {}
+            impl SclPin<$i2c> for $scl_pin {}
+
+            impl crate::Sealed for $sda_pin {}
+            impl SdaPin<$i2c> for $sda_pin {}
+
+            impl I2c<$i2c, $scl_pin, $sda_pin> {
+                #[doc = "Construct and configure a new "]
+                #[doc = stringify!([<$i2c:upper>])]
+                #[doc = " master."]
+                ///
+                /// Returns [`I2cError::UnachievableFrequency`] if the bus
+                /// speed cannot be generated from the clock.
+                pub fn [<$i2c:lower>](
+                    i2c: $i2c,
+                    reg: &mut crate::gcr::GcrRegisters,
+                    clock: &Clock<PeripheralClock>,
+                    scl_pin: $scl_pin,
+                    sda_pin: $sda_pin,
+                    speed: BusSpeed,
+                ) -> Result<Self, I2cError> {
+                    let period = _scl_period(clock.frequency, speed.frequency())?;
+                    unsafe {
+                        i2c.reset(&mut reg.gcr);
+                        i2c.enable_clock(&mut reg.gcr);
+                    }
+                    let i2c = I2c {
+                        i2c,
+                        _scl_pin: scl_pin,
+                        _sda_pin: sda_pin,
+                        clk_freq: clock.frequency,
+                        // Nine SCL periods, in system clock cycles (twice PCLK)
+                        byte_cycles: 9 * 2 * (period as u32 + 1) * 2,
+                    };
+                    i2c._configure(period);
+                    Ok(i2c)
+                }
+            }
+
+            impl I2cSlave<$i2c, $scl_pin, $sda_pin> {
+                #[doc = "Construct and configure a new "]
+                #[doc = stringify!([<$i2c:upper>])]
+                #[doc = " slave."]
+                ///
+                /// If `general_call` is set, the slave also accepts writes
+                /// to the general call address. Returns
+                /// [`I2cError::InvalidAddress`] if the address does not fit
+                /// in its addressing mode.
+                pub fn [<$i2c:lower>](
+                    i2c: $i2c,
+                    reg: &mut crate::gcr::GcrRegisters,
+                    scl_pin: $scl_pin,
+                    sda_pin: $sda_pin,
+                    address: Address,
+                    general_call: bool,
+                ) -> Result<Self, I2cError> {
+                    let (address, ten_bit) = match address {
+                        Address::SevenBit(address) if address <= 0x7f => (address as u16, false),
+                        Address::TenBit(address) if address <= 0x3ff => (address, true),
+                        _ => return Err(I2cError::InvalidAddress),
+                    };
+                    unsafe {
+                        i2c.reset(&mut reg.gcr);
+                        i2c.enable_clock(&mut reg.gcr);
+                    }
+                    let slave = I2cSlave {
+                        i2c,
+                        _scl_pin: scl_pin,
+                        _sda_pin: sda_pin,
+                    };
+                    slave._configure(address, ten_bit, general_call);
+                    Ok(slave)
+                }
+            }
+        }
+    };
+}
+
+i2c! {I2c0,
+    scl: Pin<0, 10, Af1>,
+    sda: Pin<0, 11, Af1>,
+}
+
+i2c! {I2c1,
+    scl: Pin<0, 16, Af1>,
+    sda: Pin<0, 17, Af1>,
+}
+
+impl<I2C, SCL, SDA> I2c<I2C, SCL, SDA>
+where
+    I2C: Deref<Target = I2cRegisterBlock>,
+{
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _configure(&self, period: u16) {
+        self.i2c.ctrl().write(|w| {
+            w.mst_mode().master_mode();
+            w.en().en()
+        });
+        self.i2c.clklo().write(|w| unsafe { w.lo().bits(period) });
+        self.i2c.clkhi().write(|w| unsafe { w.hi().bits(period) });
+        // Disable the SCL timeout
+        self.i2c.timeout().write(|w| unsafe { w.scl_to_val().bits(0) });
+        self._flush();
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _flush(&self) {
+        self.i2c.txctrl0().modify(|_, w| w.flush().flush());
+        self.i2c.rxctrl0().modify(|_, w| w.flush().flush());
+        // Safety: The interrupt flags are write-1-to-clear
+        self.i2c.intfl0().write(|w| unsafe { w.bits(u32::MAX) });
+        self.i2c.intfl1().write(|w| unsafe { w.bits(u32::MAX) });
+    }
+
+    /// Returns the first error flagged by the peripheral, if any.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_errors(&self) -> Result<(), I2cError> {
+        let flags = self.i2c.intfl0().read();
+        if flags.addr_nack_err().bit_is_set() {
+            Err(I2cError::Nack(NoAcknowledgeSource::Address))
+        } else if flags.data_err().bit_is_set() {
+            Err(I2cError::Nack(NoAcknowledgeSource::Data))
+        } else if flags.arb_err().bit_is_set() {
+            Err(I2cError::ArbitrationLoss)
+        } else if flags.to_err().bit_is_set() {
+            Err(I2cError::Timeout)
+        } else if flags.start_err().bit_is_set() || flags.stop_err().bit_is_set() {
+            Err(I2cError::Bus)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Releases the bus after an error.
+    #[doc(hidden)]
+    fn _abort(&self) {
+        self.i2c.mstctrl().modify(|_, w| w.stop().set_bit());
+        while self.i2c.status().read().mst_busy().bit_is_set() {}
+        self._flush();
+    }
+
+    /// Queues an address byte after a START, or after a repeated START if
+    /// the bus is already held.
+    #[doc(hidden)]
+    fn _start(&self, address_byte: u8, restart: bool) -> Result<(), I2cError> {
+        if restart {
+            self.i2c.mstctrl().modify(|_, w| w.restart().set_bit());
+            self._write_fifo(address_byte)
+        } else {
+            self._write_fifo(address_byte)?;
+            self.i2c.mstctrl().modify(|_, w| w.start().set_bit());
+            Ok(())
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _write_fifo(&self, byte: u8) -> Result<(), I2cError> {
+        while self.i2c.status().read().tx_full().bit_is_set() {
+            self._check_errors()?;
+        }
+        self.i2c.fifo().write(|w| unsafe { w.data().bits(byte) });
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _read_fifo(&self) -> Result<u8, I2cError> {
+        while self.i2c.status().read().rx_em().bit_is_set() {
+            self._check_errors()?;
+        }
+        Ok(self.i2c.fifo().read().data().bits())
+    }
+
+    /// Waits until the second byte of a 10-bit address has been
+    /// acknowledged. The byte has been shifted out one byte time after it
+    /// leaves the TX FIFO, and the master stretches SCL while the FIFO is
+    /// empty, so a NACK cannot be confused with one for a data byte.
+    #[doc(hidden)]
+    fn _wait_ten_bit_address(&self) -> Result<(), I2cError> {
+        let check = || match self._check_errors() {
+            Err(I2cError::Nack(NoAcknowledgeSource::Data)) => Err(I2cError::TenBitAddressNack),
+            result => result,
+        };
+        while self.i2c.status().read().tx_em().bit_is_clear() {
+            check()?;
+        }
+        cortex_m::asm::delay(self.byte_cycles);
+        check()
+    }
+
+    /// Addresses the target for writing.
+    #[doc(hidden)]
+    fn _address_write(&self, address: Address, restart: bool) -> Result<(), I2cError> {
+        match address {
+            Address::SevenBit(address) => self._start(address << 1, restart),
+            Address::TenBit(address) => {
+                self._start(TEN_BIT_HEADER | ((address >> 7) as u8 & 0b110), restart)?;
+                self._write_fifo(address as u8)?;
+                self._wait_ten_bit_address()
+            }
+        }
+    }
+
+    /// Addresses the target for reading. A 10-bit read must follow a
+    /// 10-bit write phase to the same target, so if the bus is not held
+    /// yet, the full address is written first and only the first byte is
+    /// repeated after the repeated START.
+    #[doc(hidden)]
+    fn _address_read(&self, address: Address, restart: bool) -> Result<(), I2cError> {
+        match address {
+            Address::SevenBit(address) => self._start((address << 1) | 1, restart),
+            Address::TenBit(address) => {
+                if !restart {
+                    self._address_write(Address::TenBit(address), false)?;
+                }
+                self._start(TEN_BIT_HEADER | ((address >> 7) as u8 & 0b110) | 1, true)
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    fn _stop(&self) -> Result<(), I2cError> {
+        self.i2c.mstctrl().modify(|_, w| w.stop().set_bit());
+        while self.i2c.intfl0().read().stop().bit_is_clear() {
+            self._check_errors()?;
+        }
+        self._check_errors()?;
+        self._flush();
+        Ok(())
+    }
+
+    /// Runs every operation of a transaction and issues the final STOP.
+    #[doc(hidden)]
+    fn _transaction(
+        &mut self,
+        address: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), I2cError> {
+        self._flush();
+        let mut started = false;
+        let mut i = 0;
+        while i < operations.len() {
+            if let Operation::Write(_) = operations[i] {
+                // Adjacent writes share a single address phase
+                self._address_write(address, started)?;
+                started = true;
+                while let Some(Operation::Write(bytes)) = operations.get(i) {
+                    for &byte in bytes.iter() {
+                        self._write_fifo(byte)?;
+                    }
+                    i += 1;
+                }
+                continue;
+            }
+            // Adjacent reads also share an address phase, which is repeated
+            // every 256 bytes because of the width of the receive counter
+            let end = i + operations[i..]
+                .iter()
+                .take_while(|op| matches!(op, Operation::Read(_)))
+                .count();
+            let mut remaining: usize = operations[i..end]
+                .iter()
+                .map(|op| match op {
+                    Operation::Read(buf) => buf.len(),
+                    Operation::Write(_) => 0,
+                })
+                .sum();
+            let mut chunk_remaining = 0;
+            for op in operations[i..end].iter_mut() {
+                let Operation::Read(buf) = op else { continue };
+                for byte in buf.iter_mut() {
+                    if chunk_remaining == 0 {
+                        chunk_remaining = remaining.min(MAX_READ_CHUNK);
+                        self.i2c
+                            .rxctrl1()
+                            .write(|w| unsafe { w.cnt().bits(chunk_remaining as u8) });
+                        self._address_read(address, started)?;
+                        started = true;
+                    }
+                    *byte = self._read_fifo()?;
+                    chunk_remaining -= 1;
+                    remaining -= 1;
+                }
+            }
+            i = end;
+        }
+        if !started {
+            return Ok(());
+        }
+        self._stop()
+    }
+
+    /// Set how long a target may stretch SCL before the transaction is
+    /// aborted with [`I2cError::Timeout`]. `None` waits forever.
+    ///
+    /// Returns [`I2cError::InvalidTimeout`] if the timeout is longer than
+    /// 65535 peripheral clock cycles.
+    ///
+    /// Default: `None`
+    pub fn set_timeout(&mut self, timeout: Option<core::time::Duration>) -> Result<(), I2cError> {
+        let cycles = match timeout {
+            Some(timeout) => (timeout.as_nanos() * self.clk_freq as u128).div_ceil(1_000_000_000),
+            None => 0,
+        };
+        if cycles > u16::MAX as u128 {
+            return Err(I2cError::InvalidTimeout);
+        }
+        self.i2c
+            .timeout()
+            .write(|w| unsafe { w.scl_to_val().bits(cycles as u16) });
+        Ok(())
+    }
+
+    /// Frees a bus where a target holds SDA low, for example after a reset
+    /// in the middle of a read.
+    ///
+    /// The peripheral is switched to software control of its outputs, which
+    /// drives the SCL and SDA pins directly instead of reconfiguring them
+    /// as GPIO. SCL is pulsed up to 9 times until SDA is released, then a
+    /// STOP condition is generated and the peripheral is re-enabled with
+    /// its FIFOs and flags cleared.
+    ///
+    /// Returns [`I2cError::Bus`] if SDA or SCL is still held low.
+    ///
+    /// Example:
+    /// ```
+    /// if let Err(I2cError::Timeout) = i2c.write(0x68, &[0x75]) {
+    ///     i2c.recover_bus().unwrap();
+    /// }
+    /// ```
+    pub fn recover_bus(&mut self) -> Result<(), I2cError> {
+        // A quarter of an SCL period, in system clock cycles
+        let delay = self.byte_cycles / (RECOVERY_PULSES as u32 * 2);
+        self.i2c.ctrl().modify(|_, w| {
+            w.scl_out().release_scl();
+            w.sda_out().release_sda();
+            w.bb_mode().outputs_enable()
+        });
+        for _ in 0..RECOVERY_PULSES {
+            if self.i2c.ctrl().read().sda().bit_is_set() {
+                break;
+            }
+            self.i2c.ctrl().modify(|_, w| w.scl_out().drive_scl_low());
+            cortex_m::asm::delay(delay * 2);
+            self.i2c.ctrl().modify(|_, w| w.scl_out().release_scl());
+            cortex_m::asm::delay(delay * 2);
+        }
+        // STOP: SDA rises while SCL is high
+        self.i2c.ctrl().modify(|_, w| w.scl_out().drive_scl_low());
+        cortex_m::asm::delay(delay);
+        self.i2c.ctrl().modify(|_, w| w.sda_out().drive_sda_low());
+        cortex_m::asm::delay(delay);
+        self.i2c.ctrl().modify(|_, w| w.scl_out().release_scl());
+        cortex_m::asm::delay(delay);
+        self.i2c.ctrl().modify(|_, w| w.sda_out().release_sda());
+        cortex_m::asm::delay(delay);
+        let ctrl = self.i2c.ctrl().read();
+        let released = ctrl.scl().bit_is_set() && ctrl.sda().bit_is_set();
+        // Re-initialize the peripheral
+        self.i2c.ctrl().modify(|_, w| {
+            w.bb_mode().outputs_disable();
+            w.en().dis()
+        });
+        self.i2c.ctrl().modify(|_, w| w.en().en());
+        self._flush();
+        if !released {
+            return Err(I2cError::Bus);
+        }
+        Ok(())
+    }
+
+    /// Disables the peripheral and releases it and its pins, for example to
+    /// re-initialize it from scratch.
+    pub fn free(self) -> (I2C, SCL, SDA) {
+        self.i2c.ctrl().write(|w| w.en().dis());
+        (self.i2c, self._scl_pin, self._sda_pin)
+    }
+
+    /// Runs a transaction with a 7-bit or 10-bit address, as
+    /// [`embedded_hal::i2c::I2c::transaction`] does.
+    ///
+    /// Returns [`I2cError::InvalidAddress`] if the address does not fit in
+    /// its addressing mode, and [`I2cError::TenBitAddressNack`] if only the
+    /// first byte of a 10-bit address was acknowledged.
+    ///
+    /// Example:
+    /// ```
+    /// use embedded_hal::i2c::Operation;
+    /// use hal::i2c::{Address, I2cError};
+    ///
+    /// // Target 0x2a5 acknowledges its address and returns one byte
+    /// let mut value = [0u8];
+    /// i2c.execute(Address::TenBit(0x2a5), &mut [
+    ///     Operation::Write(&[0x10]),
+    ///     Operation::Read(&mut value),
+    /// ]).unwrap();
+    /// // Nothing answers at 0x2b5, which shares the first address byte
+    /// assert_eq!(
+    ///     i2c.execute(Address::TenBit(0x2b5), &mut [Operation::Write(&[0x10])]),
+    ///     Err(I2cError::TenBitAddressNack),
+    /// );
+    /// ```
+    pub fn execute(
+        &mut self,
+        address: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), I2cError> {
+        let valid = match address {
+            Address::SevenBit(address) => address <= 0x7f,
+            Address::TenBit(address) => address <= 0x3ff,
+        };
+        if !valid {
+            return Err(I2cError::InvalidAddress);
+        }
+        let result = self._transaction(address, operations);
+        if result.is_err() {
+            self._abort();
+        }
+        result
+    }
+}
+
+impl<I2C, SCL, SDA> I2cSlave<I2C, SCL, SDA>
+where
+    I2C: Deref<Target = I2cRegisterBlock>,
+{
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _configure(&self, address: u16, ten_bit: bool, general_call: bool) {
+        self.i2c.slave_multi(0).write(|w| unsafe {
+            w.addr().bits(address);
+            w.ext_addr_en().bit(ten_bit)
+        });
+        self.i2c.ctrl().write(|w| {
+            w.mst_mode().slave_mode();
+            if general_call {
+                w.gc_addr_en().en();
+            }
+            w.en().en()
+        });
+        self.i2c.txctrl0().modify(|_, w| w.flush().flush());
+        self.i2c.rxctrl0().modify(|_, w| w.flush().flush());
+        // Safety: The interrupt flags are write-1-to-clear
+        self.i2c.intfl0().write(|w| unsafe { w.bits(u32::MAX) });
+        self.i2c.intfl1().write(|w| unsafe { w.bits(u32::MAX) });
+    }
+
+    /// Returns the next bus event. Address matches are reported before a
+    /// STOP that is pending at the same time, since both then belong to
+    /// the same transaction.
+    ///
+    /// Returns [`I2cError::Timeout`] if the SCL timeout expired while
+    /// stretching the clock.
+    pub fn poll(&mut self) -> nb::Result<SlaveEvent, I2cError> {
+        let flags = self.i2c.intfl0().read();
+        if flags.to_err().bit_is_set() {
+            self.i2c.intfl0().write(|w| w.to_err().pending());
+            return Err(nb::Error::Other(I2cError::Timeout));
+        }
+        if flags.gc_addr_match().bit_is_set() {
+            self.i2c.intfl0().write(|w| {
+                w.gc_addr_match().pending();
+                w.wr_addr_match().set_bit();
+                w.addr_match().pending()
+            });
+            return Ok(SlaveEvent::GeneralCall);
+        }
+        if flags.rd_addr_match().bit_is_set() {
+            // Unlock the TX FIFO so the response can be queued
+            self.i2c.intfl0().write(|w| {
+                w.rd_addr_match().set_bit();
+                w.addr_match().pending();
+                w.tx_lockout().set_bit()
+            });
+            return Ok(SlaveEvent::ReadRequest);
+        }
+        if flags.wr_addr_match().bit_is_set() {
+            self.i2c.intfl0().write(|w| {
+                w.wr_addr_match().set_bit();
+                w.addr_match().pending()
+            });
+            return Ok(SlaveEvent::WriteRequest);
+        }
+        if flags.stop().bit_is_set() {
+            self.i2c.intfl0().write(|w| {
+                w.stop().pending();
+                w.done().pending()
+            });
+            return Ok(SlaveEvent::Stop);
+        }
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Moves received bytes from the RX FIFO into `buf`, returning how
+    /// many were read. Never blocks.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        while count < buf.len() && self.i2c.status().read().rx_em().bit_is_clear() {
+            buf[count] = self.i2c.fifo().read().data().bits();
+            count += 1;
+        }
+        count
+    }
+
+    /// Queues bytes to send to the master, returning how many fit in the
+    /// TX FIFO. Never blocks. Unsent bytes are flushed when the master
+    /// NACKs the last byte it wants.
+    pub fn write(&mut self, bytes: &[u8]) -> usize {
+        let mut count = 0;
+        while count < bytes.len() && self.i2c.status().read().tx_full().bit_is_clear() {
+            self.i2c.fifo().write(|w| unsafe { w.data().bits(bytes[count]) });
+            count += 1;
+        }
+        count
+    }
+}
+
+impl<I2C, SCL, SDA> embedded_hal::i2c::ErrorType for I2c<I2C, SCL, SDA> {
+    type Error = I2cError;
+}
+
+impl<I2C, SCL, SDA> embedded_hal::i2c::I2c<SevenBitAddress> for I2c<I2C, SCL, SDA>
+where
+    I2C: Deref<Target = I2cRegisterBlock>,
+{
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.execute(Address::SevenBit(address), operations)
+    }
+}
+
+/// Highest SCL frequency of the software I2C master, in Hz (fast mode
+/// plus).
+const MAX_SOFTWARE_FREQUENCY: u32 = 1_000_000;
+/// Default time a target may stretch SCL, in microseconds (the SMBus
+/// clock low timeout).
+const DEFAULT_STRETCH_TIMEOUT_US: u32 = 25_000;
+
+/// # Software (Bit-Banged) I2C Master
+///
+/// Drives SCL and SDA on any two GPIO pins, configured with
+/// [`Pin::into_open_drain()`](crate::gpio::Pin::into_open_drain), for
+/// when both hardware controllers are in use or the pins do not map to
+/// one. Implements [`embedded_hal::i2c::I2c`] with 7-bit addresses,
+/// including repeated STARTs, arbitration loss detection, and clock
+/// stretching: after releasing SCL, the master waits for SCL to actually
+/// read high before continuing, and fails with [`I2cError::Timeout`] if a
+/// target holds it low for longer than the stretch timeout.
+///
+/// Both lines need pull-ups, either external resistors or the internal
+/// pull-ups (e.g. [`Pin::into_pull_up_strong()`](crate::gpio::Pin::into_pull_up_strong)
+/// before switching to open-drain).
+///
+/// ## Timing accuracy
+///
+/// Each half of the SCL period is timed with the delay provider, so the
+/// bus always runs at or below the requested frequency:
+/// - The pin accesses, the bookkeeping, and the setup of each delay add a
+///   fixed overhead to every half period, which matters most above
+///   100 kHz.
+/// - Delay providers round their delays up to their own tick, which may
+///   be as coarse as a microsecond (a 500 kHz ceiling).
+/// - Interrupts taken during a transaction stretch the current SCL phase.
+///   This is harmless for I2C, which is clocked by SCL, but makes the bus
+///   frequency irregular.
+///
+/// Use a hardware [`I2c`] whenever the exact bus timing matters.
+///
+/// Example:
+/// ```
+/// use embedded_hal::i2c::I2c;
+///
+/// let sda = pins.p0_2.into_pull_up_strong().into_open_drain();
+/// let scl = pins.p0_3.into_pull_up_strong().into_open_drain();
+/// let timer = hal::timer::Timer::tmr1(p.tmr1, &mut gcr.reg, &clks.pclk);
+/// let delay = hal::timer::Delay::new(timer);
+/// let mut i2c = hal::i2c::SoftwareI2c::new(sda, scl, delay, 100_000).unwrap();
+///
+/// let mut id = [0u8];
+/// i2c.write_read(0x68, &[0x75], &mut id).unwrap();
+/// ```
+pub struct SoftwareI2c<SDA, SCL, D> {
+    sda: SDA,
+    scl: SCL,
+    delay: D,
+    half_period_ns: u32,
+    stretch_timeout_us: u32,
+}
+
+impl<SDA, SCL, D> SoftwareI2c<SDA, SCL, D>
+where
+    SDA: InputPin<Error = Infallible> + OutputPin,
+    SCL: InputPin<Error = Infallible> + OutputPin,
+    D: DelayNs,
+{
+    /// Create a software I2C master running SCL at up to `frequency` Hz
+    /// (at most 1 MHz), with both lines released.
+    pub fn new(sda: SDA, scl: SCL, delay: D, frequency: u32) -> Result<Self, I2cError> {
+        if frequency == 0 || frequency > MAX_SOFTWARE_FREQUENCY {
+            return Err(I2cError::UnachievableFrequency);
+        }
+        let mut i2c = Self {
+            sda,
+            scl,
+            delay,
+            // Round up so the bus never runs faster than requested
+            half_period_ns: 500_000_000u32.div_ceil(frequency),
+            stretch_timeout_us: DEFAULT_STRETCH_TIMEOUT_US,
+        };
+        i2c._release_lines();
+        Ok(i2c)
+    }
+
+    /// Set how long a target may hold SCL low, in microseconds, before a
+    /// transaction fails with [`I2cError::Timeout`].
+    ///
+    /// Default: `25_000` (25 ms)
+    pub fn set_stretch_timeout(&mut self, timeout_us: u32) {
+        self.stretch_timeout_us = timeout_us;
+    }
+
+    /// Release the pins and the delay provider.
+    pub fn free(self) -> (SDA, SCL, D) {
+        (self.sda, self.scl, self.delay)
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _half_period(&mut self) {
+        self.delay.delay_ns(self.half_period_ns);
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _sda_is_high(&mut self) -> bool {
+        let Ok(high) = self.sda.is_high();
+        high
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _release_lines(&mut self) {
+        let _ = self.scl.set_high();
+        let _ = self.sda.set_high();
+    }
+
+    /// Release SCL and wait for it to go high, as long as a target is
+    /// stretching the clock.
+    #[doc(hidden)]
+    fn _release_scl(&mut self) -> Result<(), I2cError> {
+        let _ = self.scl.set_high();
+        let mut waited_us = 0;
+        while let Ok(false) = self.scl.is_high() {
+            if waited_us >= self.stretch_timeout_us {
+                return Err(I2cError::Timeout);
+            }
+            self.delay.delay_us(1);
+            waited_us += 1;
+        }
+        Ok(())
+    }
+
+    /// Send a START condition, or a repeated START if SCL is low.
+    #[doc(hidden)]
+    fn _start(&mut self) -> Result<(), I2cError> {
+        let _ = self.sda.set_high();
+        self._half_period();
+        self._release_scl()?;
+        // A low SDA means the bus is held by another master or a stuck
+        // target
+        if !self._sda_is_high() {
+            return Err(I2cError::Bus);
+        }
+        self._half_period();
+        let _ = self.sda.set_low();
+        self._half_period();
+        let _ = self.scl.set_low();
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    fn _stop(&mut self) -> Result<(), I2cError> {
+        let _ = self.sda.set_low();
+        self._half_period();
+        self._release_scl()?;
+        self._half_period();
+        let _ = self.sda.set_high();
+        self._half_period();
+        if !self._sda_is_high() {
+            return Err(I2cError::ArbitrationLoss);
+        }
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    fn _write_bit(&mut self, bit: bool) -> Result<(), I2cError> {
+        let _ = if bit { self.sda.set_high() } else { self.sda.set_low() };
+        self._half_period();
+        self._release_scl()?;
+        // Another master pulling SDA low while we release it won the bus
+        if bit && !self._sda_is_high() {
+            return Err(I2cError::ArbitrationLoss);
+        }
+        self._half_period();
+        let _ = self.scl.set_low();
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    fn _read_bit(&mut self) -> Result<bool, I2cError> {
+        let _ = self.sda.set_high();
+        self._half_period();
+        self._release_scl()?;
+        let bit = self._sda_is_high();
+        self._half_period();
+        let _ = self.scl.set_low();
+        Ok(bit)
+    }
+
+    /// Send a byte, returning [`true`] if it was acknowledged.
+    #[doc(hidden)]
+    fn _write_byte(&mut self, byte: u8) -> Result<bool, I2cError> {
+        for i in (0..8).rev() {
+            self._write_bit(byte & (1 << i) != 0)?;
+        }
+        Ok(!self._read_bit()?)
+    }
+
+    #[doc(hidden)]
+    fn _read_byte(&mut self, ack: bool) -> Result<u8, I2cError> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = (byte << 1) | self._read_bit()? as u8;
+        }
+        self._write_bit(!ack)?;
+        Ok(byte)
+    }
+
+    #[doc(hidden)]
+    fn _transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), I2cError> {
+        if address > 0x7f {
+            return Err(I2cError::InvalidAddress);
+        }
+        let mut previous_read = None;
+        let count = operations.len();
+        for i in 0..count {
+            let is_read = matches!(operations[i], Operation::Read(_));
+            // Adjacent operations of the same kind share one address byte
+            if previous_read != Some(is_read) {
+                self._start()?;
+                if !self._write_byte((address << 1) | is_read as u8)? {
+                    return Err(I2cError::Nack(NoAcknowledgeSource::Address));
+                }
+                previous_read = Some(is_read);
+            }
+            // The last byte before a repeated START or STOP is NACKed
+            let last_of_kind = !matches!(
+                operations.get(i + 1),
+                Some(Operation::Read(_)) if is_read
+            );
+            match &mut operations[i] {
+                Operation::Read(buffer) => {
+                    let len = buffer.len();
+                    for (j, byte) in buffer.iter_mut().enumerate() {
+                        *byte = self._read_byte(!(last_of_kind && j + 1 == len))?;
+                    }
+                }
+                Operation::Write(bytes) => {
+                    for byte in bytes.iter() {
+                        if !self._write_byte(*byte)? {
+                            return Err(I2cError::Nack(NoAcknowledgeSource::Data));
+                        }
+                    }
+                }
+            }
+        }
+        if previous_read.is_some() {
+            self._stop()?;
+        }
+        Ok(())
+    }
+}
+
+impl<SDA, SCL, D> embedded_hal::i2c::ErrorType for SoftwareI2c<SDA, SCL, D> {
+    type Error = I2cError;
+}
+
+impl<SDA, SCL, D> embedded_hal::i2c::I2c<SevenBitAddress> for SoftwareI2c<SDA, SCL, D>
+where
+    SDA: InputPin<Error = Infallible> + OutputPin,
+    SCL: InputPin<Error = Infallible> + OutputPin,
+    D: DelayNs,
+{
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let result = self._transaction(address, operations);
+        match result {
+            // The bus belongs to another master, leave it alone
+            Err(I2cError::ArbitrationLoss) => self._release_lines(),
+            Err(_) => {
+                let _ = self._stop();
+            }
+            Ok(()) => (),
+        }
+        result
+    }
+}
+
+/// Calculates the value of the SCL high and low period fields for a bus
+/// frequency. Each half of the SCL period lasts `period + 1` peripheral
+/// clock cycles.
+#[doc(hidden)]
+#[inline(always)]
+fn _scl_period(clk_src_freq: u32, frequency: u32) -> Result<u16, I2cError> {
+    // Round up so the bus never runs faster than requested
+    let half_cycles = clk_src_freq.div_ceil(2 * frequency);
+    if !(2..=MAX_SCL_PERIOD + 1).contains(&half_cycles) {
+        return Err(I2cError::UnachievableFrequency);
+    }
+    Ok((half_cycles - 1) as u16)
+}
@@ -0,0 +1,509 @@
+//! # Inter-IC Sound (I2S)
+//!
+//! The I2S peripheral streams audio samples to and from codecs and digital
+//! microphones, for example to feed keyword spotting models on the CNN
+//! accelerator. Samples are exchanged as signed 16-bit values, either by
+//! polling the FIFOs or by DMA.
+use core::marker::PhantomData;
+
+use crate::dma::{Channel, Request, TransferConfig, TransferWidth};
+use crate::gcr::{
+    clocks::{Clock, PeripheralClock},
+    ClockForPeripheral, ResetForPeripheral,
+};
+use crate::gpio::{Af1, Pin};
+use crate::wait::{Spin, WaitStrategy};
+
+/// Depth of the TX and RX FIFOs, in 32-bit words.
+const FIFO_DEPTH: u8 = 32;
+/// TX FIFO level at or below which the TX DMA channel is requested.
+const DMA_TX_THRESHOLD: u8 = FIFO_DEPTH / 2;
+/// RX FIFO level at or above which the RX DMA channel is requested.
+const DMA_RX_THRESHOLD: u8 = 1;
+/// Width of the samples exchanged with the FIFOs, in bits.
+const SAMPLE_BITS: u8 = 16;
+/// Largest value of the 16-bit clock divisor.
+const MAX_CLKDIV: u32 = u16::MAX as u32;
+/// Largest number of bytes in a single DMA transaction (24-bit counter).
+const MAX_DMA_BYTES: usize = (1 << 24) - 1;
+
+/// Serial clock pin of the I2S peripheral.
+pub type SckPin = Pin<1, 2, Af1>;
+/// Word (left/right) select pin of the I2S peripheral.
+pub type WsPin = Pin<1, 3, Af1>;
+/// Serial data input pin of the I2S peripheral.
+pub type SdiPin = Pin<1, 4, Af1>;
+/// Serial data output pin of the I2S peripheral.
+pub type SdoPin = Pin<1, 5, Af1>;
+
+/// Errors that can occur while configuring or using the I2S peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum I2sError {
+    /// The requested sample rate is outside of the range that can be
+    /// generated from the peripheral clock.
+    UnachievableSampleRate,
+    /// The word length is not between 16 and 32 bits.
+    InvalidWordLength,
+    /// The sample buffer does not hold a whole number of 32-bit FIFO words
+    /// (an even number of samples), or is too long for a DMA transfer.
+    InvalidBufferLength,
+    /// The RX FIFO overflowed and samples were lost.
+    Overrun,
+    /// A DMA channel was stopped by a bus error.
+    DmaBusError,
+}
+
+/// Which side generates the serial clock and word select signals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum I2sMode {
+    /// SCK and WS are generated from the peripheral clock.
+    Master,
+    /// SCK and WS are driven by an external device.
+    Slave,
+}
+
+/// Audio channels carried by the data lines.
+///
+/// In [`I2sChannels::Stereo`] the samples are interleaved in the FIFOs and
+/// in all sample buffers, starting with the left channel: `buf[2 * i]` is
+/// the left sample and `buf[2 * i + 1]` the right sample of frame `i`. See
+/// [`deinterleave()`] to split such a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum I2sChannels {
+    /// Both channels, interleaved left first.
+    Stereo,
+    /// Only the left channel, for example a single microphone with its
+    /// select pin tied low.
+    MonoLeft,
+    /// Only the right channel.
+    MonoRight,
+}
+
+/// Configuration of the I2S peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct I2sConfig {
+    /// Sample rate in frames per second, for example 16 kHz for keyword
+    /// spotting. Only used in [`I2sMode::Master`].
+    pub sample_rate: u32,
+    /// Number of SCK cycles per channel slot (16 to 32). The 16-bit samples
+    /// are aligned to the most significant bit of each slot.
+    pub word_bits: u8,
+    /// Whether the serial clock is generated or received.
+    pub mode: I2sMode,
+    /// Audio channels carried by the data lines.
+    pub channels: I2sChannels,
+}
+
+impl Default for I2sConfig {
+    /// 16 kHz stereo master with 16-bit slots.
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            word_bits: 16,
+            mode: I2sMode::Master,
+            channels: I2sChannels::Stereo,
+        }
+    }
+}
+
+/// Compute the clock divisor that generates `sample_rate` from a
+/// peripheral clock of `clk_freq` Hz with `word_bits` SCK cycles per
+/// channel slot, returning `(clkdiv, actual_sample_rate)`.
+///
+/// Every frame has a left and a right slot, so SCK runs at
+/// `sample_rate * 2 * word_bits`, and SCK is the peripheral clock divided
+/// by `2 * (clkdiv + 1)`. The divisor is rounded to the nearest value, so
+/// the actual sample rate can differ slightly from the requested one.
+///
+/// Example:
+/// ```
+/// // 16 kHz with 16-bit slots from a 50 MHz peripheral clock: SCK is
+/// // 512 kHz, giving a divisor of 48 and an actual rate of about 15.94 kHz
+/// assert_eq!(clock_divider(50_000_000, 16_000, 16), Some((48, 15_943)));
+/// ```
+pub const fn clock_divider(clk_freq: u32, sample_rate: u32, word_bits: u8) -> Option<(u16, u32)> {
+    if sample_rate == 0 || word_bits == 0 {
+        return None;
+    }
+    let sck_x2 = sample_rate as u64 * 4 * word_bits as u64;
+    let periods = (clk_freq as u64 + sck_x2 / 2) / sck_x2;
+    if periods == 0 || periods > MAX_CLKDIV as u64 + 1 {
+        return None;
+    }
+    let actual = clk_freq as u64 / (periods * 4 * word_bits as u64);
+    Some(((periods - 1) as u16, actual as u32))
+}
+
+/// Split an interleaved stereo buffer into separate left and right channel
+/// buffers. Stops at the end of the shortest buffer.
+pub fn deinterleave(samples: &[i16], left: &mut [i16], right: &mut [i16]) {
+    for ((frame, l), r) in samples.chunks_exact(2).zip(left).zip(right) {
+        *l = frame[0];
+        *r = frame[1];
+    }
+}
+
+/// # Inter-IC Sound (I2S) Peripheral
+///
+/// Example:
+/// ```
+/// let mut i2s = I2s::new(
+///     p.i2s,
+///     &mut gcr.reg,
+///     &clks.pclk,
+///     pins.p1_2.into_af1(),
+///     pins.p1_3.into_af1(),
+///     pins.p1_4.into_af1(),
+///     pins.p1_5.into_af1(),
+///     I2sConfig {
+///         sample_rate: 16_000,
+///         channels: I2sChannels::MonoLeft,
+///         ..Default::default()
+///     },
+/// )
+/// .unwrap();
+/// let mut audio = [0i16; 16_000];
+/// i2s.read_samples(&mut audio).unwrap();
+/// ```
+pub struct I2s {
+    i2s: crate::pac::I2s,
+    _sck_pin: SckPin,
+    _ws_pin: WsPin,
+    _sdi_pin: SdiPin,
+    _sdo_pin: SdoPin,
+    sample_rate: u32,
+}
+
+impl I2s {
+    /// Construct and configure the I2S peripheral. The transmitter and
+    /// receiver are started once samples are read or written.
+    ///
+    /// Returns [`I2sError::UnachievableSampleRate`] if the requested sample
+    /// rate cannot be generated from the clock in [`I2sMode::Master`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        i2s: crate::pac::I2s,
+        reg: &mut crate::gcr::GcrRegisters,
+        clock: &Clock<PeripheralClock>,
+        sck_pin: SckPin,
+        ws_pin: WsPin,
+        sdi_pin: SdiPin,
+        sdo_pin: SdoPin,
+        config: I2sConfig,
+    ) -> Result<Self, I2sError> {
+        if config.word_bits < SAMPLE_BITS || config.word_bits > 32 {
+            return Err(I2sError::InvalidWordLength);
+        }
+        let (clkdiv, sample_rate) = match config.mode {
+            I2sMode::Master => clock_divider(clock.frequency, config.sample_rate, config.word_bits)
+                .ok_or(I2sError::UnachievableSampleRate)?,
+            I2sMode::Slave => (0, config.sample_rate),
+        };
+        unsafe {
+            i2s.reset(&mut reg.gcr);
+            i2s.enable_clock(&mut reg.gcr);
+        }
+        let i2s = Self {
+            i2s,
+            _sck_pin: sck_pin,
+            _ws_pin: ws_pin,
+            _sdi_pin: sdi_pin,
+            _sdo_pin: sdo_pin,
+            sample_rate,
+        };
+        i2s._configure(&config, clkdiv);
+        Ok(i2s)
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _configure(&self, config: &I2sConfig, clkdiv: u16) {
+        let stereo = match config.channels {
+            I2sChannels::Stereo => 0,
+            I2sChannels::MonoLeft => 2,
+            I2sChannels::MonoRight => 3,
+        };
+        self.i2s.ctrl0ch0().write(|w| unsafe {
+            // The stereo field has no writer in the PAC
+            w.bits(stereo << 12);
+            match config.mode {
+                // Internal SCK and WS, or both from the external device
+                I2sMode::Master => w.ch_mode().bits(0),
+                I2sMode::Slave => w.ch_mode().bits(2),
+            };
+            // Two halfword samples per FIFO word, the first in the low half
+            w.wsize().bits(1);
+            w.rx_thd_val().bits(FIFO_DEPTH / 2)
+        });
+        self.i2s.ctrl1ch0().write(|w| unsafe {
+            w.bits_word().bits(config.word_bits - 1);
+            w.smp_size().bits(SAMPLE_BITS - 1);
+            // MSB justified within the slot
+            w.adjust().clear_bit();
+            w.clkdiv().bits(clkdiv);
+            w.en().bit(config.mode == I2sMode::Master)
+        });
+        self._flush();
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _flush(&self) {
+        self.i2s.ctrl0ch0().modify(|_, w| w.flush().set_bit());
+        while self.i2s.ctrl0ch0().read().flush().bit_is_set() {}
+        self.i2s.intfl().write(|w| unsafe { w.bits(u32::MAX) });
+    }
+
+    /// Get the actual sample rate, which can differ slightly from the
+    /// requested one. See [`clock_divider()`].
+    #[inline(always)]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Start receiving samples into the RX FIFO.
+    #[inline(always)]
+    pub fn start_rx(&mut self) {
+        self.i2s.ctrl0ch0().modify(|_, w| w.rx_en().set_bit());
+    }
+
+    /// Stop receiving samples and discard the RX FIFO.
+    #[inline(always)]
+    pub fn stop_rx(&mut self) {
+        self.i2s.ctrl0ch0().modify(|_, w| w.rx_en().clear_bit());
+        self._flush();
+    }
+
+    /// Start transmitting samples from the TX FIFO.
+    #[inline(always)]
+    pub fn start_tx(&mut self) {
+        self.i2s.ctrl0ch0().modify(|_, w| w.tx_en().set_bit());
+    }
+
+    /// Stop transmitting samples.
+    #[inline(always)]
+    pub fn stop_tx(&mut self) {
+        self.i2s.ctrl0ch0().modify(|_, w| w.tx_en().clear_bit());
+    }
+
+    /// Fill `samples` from the RX FIFO, blocking until every sample has
+    /// been received. Starts the receiver if it is not running.
+    ///
+    /// `samples` must hold an even number of samples. Returns
+    /// [`I2sError::Overrun`] if samples were lost because the FIFO was not
+    /// read fast enough, in which case the buffer is partly filled.
+    pub fn read_samples(&mut self, samples: &mut [i16]) -> Result<(), I2sError> {
+        if !samples.len().is_multiple_of(2) {
+            return Err(I2sError::InvalidBufferLength);
+        }
+        self.start_rx();
+        for pair in samples.chunks_exact_mut(2) {
+            while self.i2s.dmach0().read().rx_lvl().bits() == 0 {
+                if self.i2s.intfl().read().rx_ov_ch0().bit_is_set() {
+                    self.i2s.intfl().write(|w| w.rx_ov_ch0().set_bit());
+                    return Err(I2sError::Overrun);
+                }
+            }
+            let word = self.i2s.fifoch0().read().data().bits();
+            pair[0] = word as i16;
+            pair[1] = (word >> 16) as i16;
+        }
+        Ok(())
+    }
+
+    /// Write `samples` to the TX FIFO, blocking until every sample has been
+    /// queued. Starts the transmitter if it is not running.
+    ///
+    /// `samples` must hold an even number of samples.
+    pub fn write_samples(&mut self, samples: &[i16]) -> Result<(), I2sError> {
+        if !samples.len().is_multiple_of(2) {
+            return Err(I2sError::InvalidBufferLength);
+        }
+        for pair in samples.chunks_exact(2) {
+            while self.i2s.dmach0().read().tx_lvl().bits() >= FIFO_DEPTH {}
+            let word = (pair[0] as u16 as u32) | ((pair[1] as u16 as u32) << 16);
+            self.i2s.fifoch0().write(|w| unsafe { w.data().bits(word) });
+            self.start_tx();
+        }
+        Ok(())
+    }
+
+    /// Start filling `samples` from the RX FIFO using a DMA channel. Starts
+    /// the receiver if it is not running.
+    ///
+    /// The returned [`I2sDmaTransfer`] can be polled for completion, the
+    /// samples must not be accessed until it has completed.
+    ///
+    /// # Safety
+    /// The channel is only stopped when the transfer is dropped, so it must
+    /// not be leaked (for example with [`core::mem::forget()`]). Otherwise
+    /// the DMA keeps writing into `samples` after the borrow has ended.
+    ///
+    /// Example:
+    /// ```
+    /// let transfer = unsafe { i2s.read_samples_dma(&mut channels.ch0, &mut audio) }.unwrap();
+    /// // Run inference on the previous window while this one is recorded
+    /// transfer.wait().unwrap();
+    /// ```
+    pub unsafe fn read_samples_dma<'a, const N: usize>(
+        &'a mut self,
+        channel: &'a mut Channel<N>,
+        samples: &'a mut [i16],
+    ) -> Result<I2sDmaTransfer<'a, N>, I2sError> {
+        let len = Self::_dma_len(samples)?;
+        let fifo = self.i2s.fifoch0().as_ptr() as u32;
+        // Safety: The caller guarantees the transfer is dropped, stopping
+        // the channel, before the samples are released
+        unsafe {
+            channel.start(&TransferConfig {
+                request: Request::I2srx,
+                src: fifo,
+                src_width: TransferWidth::Word,
+                src_increment: false,
+                dst: samples.as_mut_ptr() as u32,
+                dst_width: TransferWidth::HalfWord,
+                dst_increment: true,
+                len,
+                burst_size: 4 * DMA_RX_THRESHOLD,
+            });
+        }
+        self.i2s.dmach0().modify(|_, w| unsafe {
+            w.dma_rx_thd_val().bits(DMA_RX_THRESHOLD);
+            w.dma_rx_en().set_bit()
+        });
+        self.start_rx();
+        Ok(I2sDmaTransfer { i2s: self, channel, _samples: PhantomData })
+    }
+
+    /// Start writing `samples` to the TX FIFO using a DMA channel. Starts
+    /// the transmitter if it is not running.
+    ///
+    /// The returned [`I2sDmaTransfer`] can be polled for completion, the
+    /// samples must not be modified until it has completed.
+    ///
+    /// # Safety
+    /// The transfer must not be leaked, or the DMA keeps reading `samples`
+    /// after the borrow has ended. See [`I2s::read_samples_dma`].
+    pub unsafe fn write_samples_dma<'a, const N: usize>(
+        &'a mut self,
+        channel: &'a mut Channel<N>,
+        samples: &'a [i16],
+    ) -> Result<I2sDmaTransfer<'a, N>, I2sError> {
+        let len = Self::_dma_len(samples)?;
+        let fifo = self.i2s.fifoch0().as_ptr() as u32;
+        // Safety: The caller guarantees the transfer is dropped, stopping
+        // the channel, before the samples are released
+        unsafe {
+            channel.start(&TransferConfig {
+                request: Request::I2stx,
+                src: samples.as_ptr() as u32,
+                src_width: TransferWidth::HalfWord,
+                src_increment: true,
+                dst: fifo,
+                dst_width: TransferWidth::Word,
+                dst_increment: false,
+                len,
+                burst_size: 4,
+            });
+        }
+        self.i2s.dmach0().modify(|_, w| unsafe {
+            w.dma_tx_thd_val().bits(DMA_TX_THRESHOLD);
+            w.dma_tx_en().set_bit()
+        });
+        self.start_tx();
+        Ok(I2sDmaTransfer { i2s: self, channel, _samples: PhantomData })
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _dma_len(samples: &[i16]) -> Result<u32, I2sError> {
+        let len = core::mem::size_of_val(samples);
+        if !len.is_multiple_of(4) || len > MAX_DMA_BYTES {
+            return Err(I2sError::InvalidBufferLength);
+        }
+        Ok(len as u32)
+    }
+
+    /// Enable the interrupt raised when the RX FIFO is half full.
+    #[inline(always)]
+    pub fn enable_rx_interrupt(&mut self) {
+        self.i2s.inten().modify(|_, w| w.rx_thd_ch0().set_bit());
+    }
+
+    /// Disable the RX FIFO half full interrupt.
+    #[inline(always)]
+    pub fn disable_rx_interrupt(&mut self) {
+        self.i2s.inten().modify(|_, w| w.rx_thd_ch0().clear_bit());
+    }
+
+    /// Check if the RX FIFO overflowed and clear the flag.
+    #[inline(always)]
+    pub fn take_overrun(&mut self) -> bool {
+        let overrun = self.i2s.intfl().read().rx_ov_ch0().bit_is_set();
+        self.i2s.intfl().write(|w| w.rx_ov_ch0().set_bit());
+        overrun
+    }
+
+    /// Stop the transmitter and receiver and release the I2S peripheral
+    /// and its pins.
+    pub fn free(self) -> (crate::pac::I2s, SckPin, WsPin, SdiPin, SdoPin) {
+        self.i2s.ctrl0ch0().modify(|_, w| {
+            w.tx_en().clear_bit();
+            w.rx_en().clear_bit()
+        });
+        self.i2s.ctrl1ch0().modify(|_, w| w.en().clear_bit());
+        (self.i2s, self._sck_pin, self._ws_pin, self._sdi_pin, self._sdo_pin)
+    }
+}
+
+/// # I2S DMA Transfer
+///
+/// An in-progress DMA transfer started by [`I2s::read_samples_dma`] or
+/// [`I2s::write_samples_dma`]. The samples and DMA channel stay borrowed
+/// until the transfer is dropped. Dropping an incomplete transfer stops the
+/// DMA channel; leaking it leaves the channel running.
+pub struct I2sDmaTransfer<'a, const N: usize> {
+    i2s: &'a mut I2s,
+    channel: &'a mut Channel<N>,
+    _samples: PhantomData<&'a mut [i16]>,
+}
+
+impl<const N: usize> I2sDmaTransfer<'_, N> {
+    /// Returns [`true`] once every sample has been transferred.
+    #[inline(always)]
+    pub fn is_complete(&self) -> bool {
+        !self.channel.is_busy()
+    }
+
+    /// Blocks until the transfer has completed.
+    pub fn wait(self) -> Result<(), I2sError> {
+        self.wait_with(&mut Spin)
+    }
+
+    /// Blocks until the transfer has completed, running `strategy` between
+    /// polls.
+    pub fn wait_with(self, strategy: &mut impl WaitStrategy) -> Result<(), I2sError> {
+        while !self.is_complete() {
+            strategy.wait();
+        }
+        if self.channel.bus_error() {
+            return Err(I2sError::DmaBusError);
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> Drop for I2sDmaTransfer<'_, N> {
+    fn drop(&mut self) {
+        self.channel.stop();
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        self.i2s.i2s.dmach0().modify(|_, w| {
+            w.dma_rx_en().clear_bit();
+            w.dma_tx_en().clear_bit()
+        });
+    }
+}
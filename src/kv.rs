@@ -0,0 +1,480 @@
+//! # Key-Value Store
+//!
+//! A small append-only key-value store over two pages of flash, for state
+//! that must survive resets, such as boot counters or attestation data.
+//!
+//! [`KvStore::put()`] never overwrites a value in place. It appends a new
+//! record to the active page, which spreads the wear over the whole page,
+//! and each record carries a CRC. A record torn by a reset or power loss
+//! fails its CRC and is ignored, so [`KvStore::get()`] returns the previous
+//! value instead of a partially written one.
+//!
+//! When the active page is full, the latest value of each key is copied to
+//! the other page, which then becomes the active page. The new page is only
+//! marked valid once the copy is complete, and the old page is only erased
+//! after that, so an interrupted compaction loses nothing either.
+//!
+//! ## Layout
+//!
+//! Everything is aligned to the 16-byte flash write size, and each line of
+//! 16 bytes is written only once between erases.
+//!
+//! ```text
+//! Page header:   magic | sequence | !sequence | 0xffffffff
+//! Record header: key, length | CRC of key, length and value | header check | 0xffffffff
+//! Record value:  the value, padded with 0xff to a multiple of 16 bytes
+//! ```
+//!
+//! A record takes 16 bytes plus its value rounded up to 16 bytes. Removing
+//! a key appends a header-only record with a length of `0xffff`.
+use crate::flc::{FlashError, Flc, FLASH_PAGE_SIZE, FLASH_WRITE_SIZE};
+
+/// Identifies a valid page header, "KVS1" in little-endian byte order.
+const PAGE_MAGIC: u32 = 0x3153_564b;
+/// Value of an erased word of flash.
+const ERASED_WORD: u32 = u32::MAX;
+/// Value of an erased byte of flash.
+const ERASED_BYTE: u8 = 0xff;
+/// Length of a removed key's record.
+const TOMBSTONE: u16 = u16::MAX;
+/// Size of a header, and the unit records are aligned to.
+const LINE: u32 = FLASH_WRITE_SIZE;
+
+/// Reserved key, which cannot be stored since it matches erased flash.
+pub const INVALID_KEY: u16 = u16::MAX;
+/// Longest value that can be stored, filling a whole page.
+pub const MAX_VALUE_LEN: usize = (FLASH_PAGE_SIZE - 2 * LINE) as usize;
+
+/// Errors that can occur while accessing the key-value store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KvError {
+    /// The pages are not two different, page-aligned pages of flash.
+    InvalidPages,
+    /// The key is [`INVALID_KEY`].
+    InvalidKey,
+    /// The value is longer than [`MAX_VALUE_LEN`].
+    ValueTooLong,
+    /// The key is not in the store.
+    NotFound,
+    /// The buffer is too small for the value, which is the given number of
+    /// bytes long.
+    BufferTooSmall(usize),
+    /// The latest values of all keys leave no room for the new record, even
+    /// after compaction.
+    Full,
+    /// The flash could not be written or erased.
+    Flash(FlashError),
+}
+
+impl From<FlashError> for KvError {
+    #[inline(always)]
+    fn from(error: FlashError) -> Self {
+        KvError::Flash(error)
+    }
+}
+
+/// # Key-Value Store
+///
+/// Stores values of up to [`MAX_VALUE_LEN`] bytes under 16-bit keys in two
+/// pages of flash. See the [module](self) documentation for how values
+/// survive power loss.
+///
+/// Writing blocks for the duration of the flash operations, and a
+/// [`KvStore::put()`] that triggers a compaction also erases both pages.
+///
+/// Example:
+/// ```
+/// const BOOT_COUNT: u16 = 1;
+///
+/// // Safety: The last two pages of flash are reserved for the store
+/// let mut kv = unsafe { KvStore::new(&flc, 0x1007_c000, 0x1007_e000).unwrap() };
+///
+/// let mut count = [0u8; 4];
+/// let boots = match kv.get(BOOT_COUNT, &mut count) {
+///     Ok(_) => u32::from_le_bytes(count) + 1,
+///     Err(KvError::NotFound) => 1,
+///     Err(error) => panic!("{:?}", error),
+/// };
+/// kv.put(BOOT_COUNT, &boots.to_le_bytes()).unwrap();
+/// ```
+pub struct KvStore<'a> {
+    flc: &'a Flc,
+    pages: [u32; 2],
+    active: usize,
+    sequence: u32,
+    write_offset: u32,
+}
+
+impl<'a> KvStore<'a> {
+    /// Open the store in the pages starting at `first_page` and
+    /// `second_page`, recovering from an interrupted compaction.
+    ///
+    /// If neither page holds a store yet, the first page is erased and
+    /// formatted as an empty store.
+    ///
+    /// # Safety
+    /// Both pages must be reserved for the store: they must not contain
+    /// code or other data, since they are erased when needed.
+    pub unsafe fn new(flc: &'a Flc, first_page: u32, second_page: u32) -> Result<Self, KvError> {
+        let pages = [first_page, second_page];
+        for page in pages {
+            if !page.is_multiple_of(FLASH_PAGE_SIZE) || flc.check_address(page).is_err() {
+                return Err(KvError::InvalidPages);
+            }
+        }
+        if first_page == second_page {
+            return Err(KvError::InvalidPages);
+        }
+
+        let mut store = Self {
+            flc,
+            pages,
+            active: 0,
+            sequence: 0,
+            write_offset: LINE,
+        };
+        match (store._page_sequence(0), store._page_sequence(1)) {
+            (Some(first), Some(second)) => {
+                // A compaction completed but the old page was not erased
+                let (active, sequence) = if (second.wrapping_sub(first) as i32) > 0 {
+                    (1, second)
+                } else {
+                    (0, first)
+                };
+                store.active = active;
+                store.sequence = sequence;
+                flc.erase_page(pages[1 - active])?;
+            }
+            (Some(sequence), None) => store.sequence = sequence,
+            (None, Some(sequence)) => {
+                store.active = 1;
+                store.sequence = sequence;
+            }
+            (None, None) => {
+                flc.erase_page(pages[0])?;
+                store.sequence = 1;
+                store._write_page_header(pages[0], store.sequence)?;
+            }
+        }
+
+        let mut records = store._records(store._active_page());
+        for _ in records.by_ref() {}
+        store.write_offset = records.offset;
+        Ok(store)
+    }
+
+    /// Read the value of `key` into `buffer`, returning its length.
+    ///
+    /// Returns [`KvError::NotFound`] if the key was never stored or was
+    /// removed, and [`KvError::BufferTooSmall`] with the length of the
+    /// value if it does not fit in `buffer`.
+    pub fn get(&self, key: u16, buffer: &mut [u8]) -> Result<usize, KvError> {
+        let record = self
+            ._latest(key)
+            .filter(|record| !record.is_tombstone())
+            .ok_or(KvError::NotFound)?;
+        let len = record.value_len() as usize;
+        if buffer.len() < len {
+            return Err(KvError::BufferTooSmall(len));
+        }
+        self.flc.read(record.value_address(), &mut buffer[..len])?;
+        Ok(len)
+    }
+
+    /// Store `value` under `key`, replacing any previous value. Nothing is
+    /// written if the stored value is already equal.
+    ///
+    /// If the active page is full it is compacted first. Returns
+    /// [`KvError::Full`] if there still is not enough room.
+    pub fn put(&mut self, key: u16, value: &[u8]) -> Result<(), KvError> {
+        if key == INVALID_KEY {
+            return Err(KvError::InvalidKey);
+        }
+        if value.len() > MAX_VALUE_LEN {
+            return Err(KvError::ValueTooLong);
+        }
+        if let Some(record) = self._latest(key) {
+            if !record.is_tombstone() && self._value_eq(&record, value) {
+                return Ok(());
+            }
+        }
+        self._append(key, value.len() as u16, value)
+    }
+
+    /// Remove `key` from the store. Nothing is written if it is not stored.
+    pub fn remove(&mut self, key: u16) -> Result<(), KvError> {
+        if key == INVALID_KEY {
+            return Err(KvError::InvalidKey);
+        }
+        match self._latest(key) {
+            Some(record) if !record.is_tombstone() => self._append(key, TOMBSTONE, &[]),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of bytes left in the active page. A record takes 16 bytes
+    /// plus its value rounded up to 16 bytes.
+    #[inline(always)]
+    pub fn free_space(&self) -> usize {
+        (FLASH_PAGE_SIZE - self.write_offset) as usize
+    }
+
+    /// Copy the latest value of each key to the other page and make it the
+    /// active page, reclaiming the space of replaced and removed values.
+    ///
+    /// This happens automatically when a page is full, but can be done
+    /// ahead of time, for example at boot, so that a later write does not
+    /// have to wait for both page erases.
+    pub fn compact(&mut self) -> Result<(), KvError> {
+        let source = self._active_page();
+        let target = self.pages[1 - self.active];
+        // Safety: The pages are reserved for the store
+        unsafe { self.flc.erase_page(target)? };
+
+        let mut offset = LINE;
+        for record in self._records(source) {
+            if record.is_tombstone() || !self._is_intact(&record) || !self._is_latest(source, &record) {
+                continue;
+            }
+            for line in (0..record.span()).step_by(LINE as usize) {
+                let data = self.flc.read_128(record.address + line)?;
+                self.flc.write_128(target + offset + line, &data)?;
+            }
+            offset += record.span();
+        }
+
+        // The new page only becomes valid once every record is copied
+        let sequence = self.sequence.wrapping_add(1);
+        self._write_page_header(target, sequence)?;
+        self.active = 1 - self.active;
+        self.sequence = sequence;
+        self.write_offset = offset;
+        // Safety: The pages are reserved for the store
+        unsafe { self.flc.erase_page(source)? };
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _active_page(&self) -> u32 {
+        self.pages[self.active]
+    }
+
+    /// Sequence number of a page, if it has a valid header.
+    #[doc(hidden)]
+    fn _page_sequence(&self, index: usize) -> Option<u32> {
+        let header = self.flc.read_128(self.pages[index]).ok()?;
+        (header[0] == PAGE_MAGIC && header[2] == !header[1]).then_some(header[1])
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _write_page_header(&self, page: u32, sequence: u32) -> Result<(), FlashError> {
+        self.flc
+            .write_128(page, &[PAGE_MAGIC, sequence, !sequence, ERASED_WORD])
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _records(&self, page: u32) -> Records<'a> {
+        Records {
+            flc: self.flc,
+            page,
+            offset: LINE,
+        }
+    }
+
+    /// The last intact record of `key` in the active page.
+    #[doc(hidden)]
+    fn _latest(&self, key: u16) -> Option<Record> {
+        self._records(self._active_page())
+            .filter(|record| record.key == key && self._is_intact(record))
+            .last()
+    }
+
+    /// Check if no later intact record of the same key follows `record`.
+    #[doc(hidden)]
+    fn _is_latest(&self, page: u32, record: &Record) -> bool {
+        !self
+            ._records(page)
+            .skip_while(|other| other.address <= record.address)
+            .any(|other| other.key == record.key && self._is_intact(&other))
+    }
+
+    /// Check the CRC of a record against its value in flash.
+    #[doc(hidden)]
+    fn _is_intact(&self, record: &Record) -> bool {
+        let mut crc = _crc32_update(!0, &_key_len_word(record.key, record.len).to_le_bytes());
+        self._for_each_value_chunk(record, |chunk, _| {
+            crc = _crc32_update(crc, chunk);
+            true
+        });
+        !crc == record.crc
+    }
+
+    /// Check if the value of a record equals `value`.
+    #[doc(hidden)]
+    fn _value_eq(&self, record: &Record, value: &[u8]) -> bool {
+        record.value_len() as usize == value.len()
+            && self._for_each_value_chunk(record, |chunk, offset| {
+                chunk == &value[offset..offset + chunk.len()]
+            })
+    }
+
+    /// Read the value of a record in chunks, stopping early if `f` returns
+    /// [`false`]. Returns [`false`] if it stopped early.
+    #[doc(hidden)]
+    fn _for_each_value_chunk(
+        &self,
+        record: &Record,
+        mut f: impl FnMut(&[u8], usize) -> bool,
+    ) -> bool {
+        let mut buffer = [0u8; LINE as usize];
+        let len = record.value_len() as usize;
+        let mut offset = 0;
+        while offset < len {
+            let chunk = &mut buffer[..(len - offset).min(LINE as usize)];
+            // Records are within the pages checked by the constructor
+            let _ = self.flc.read(record.value_address() + offset as u32, chunk);
+            if !f(chunk, offset) {
+                return false;
+            }
+            offset += chunk.len();
+        }
+        true
+    }
+
+    /// Append a record to the active page, compacting it first if needed.
+    #[doc(hidden)]
+    fn _append(&mut self, key: u16, len: u16, value: &[u8]) -> Result<(), KvError> {
+        let span = LINE + (value.len() as u32).next_multiple_of(LINE);
+        if self.write_offset + span > FLASH_PAGE_SIZE {
+            self.compact()?;
+            if self.write_offset + span > FLASH_PAGE_SIZE {
+                return Err(KvError::Full);
+            }
+        }
+        let address = self._active_page() + self.write_offset;
+        // Even if a write fails part of the record may be written, so its
+        // lines cannot be used again
+        self.write_offset += span;
+
+        let word = _key_len_word(key, len);
+        let crc = !_crc32_update(_crc32_update(!0, &word.to_le_bytes()), value);
+        // The header is written first, so that a torn value fails its CRC
+        // rather than being overwritten by the next record
+        self.flc
+            .write_128(address, &[word, crc, _header_check(word, crc), ERASED_WORD])?;
+        for (i, chunk) in value.chunks(LINE as usize).enumerate() {
+            let mut line = [ERASED_BYTE; LINE as usize];
+            line[..chunk.len()].copy_from_slice(chunk);
+            self.flc.write(address + (i as u32 + 1) * LINE, &line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Header of a record in flash.
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    address: u32,
+    key: u16,
+    len: u16,
+    crc: u32,
+}
+
+impl Record {
+    #[inline(always)]
+    fn is_tombstone(&self) -> bool {
+        self.len == TOMBSTONE
+    }
+
+    #[inline(always)]
+    fn value_len(&self) -> u32 {
+        if self.is_tombstone() {
+            0
+        } else {
+            self.len as u32
+        }
+    }
+
+    #[inline(always)]
+    fn value_address(&self) -> u32 {
+        self.address + LINE
+    }
+
+    /// Number of bytes taken by the header and value.
+    #[inline(always)]
+    fn span(&self) -> u32 {
+        LINE + self.value_len().next_multiple_of(LINE)
+    }
+}
+
+/// Iterator over the records of a page with a valid header. Once done,
+/// `offset` is where the next record can be written.
+struct Records<'a> {
+    flc: &'a Flc,
+    page: u32,
+    offset: u32,
+}
+
+impl Iterator for Records<'_> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        if self.offset + LINE > FLASH_PAGE_SIZE {
+            return None;
+        }
+        let address = self.page + self.offset;
+        let header = self.flc.read_128(address).ok()?;
+        if header == [ERASED_WORD; 4] {
+            return None;
+        }
+        let record = Record {
+            address,
+            key: header[0] as u16,
+            len: (header[0] >> 16) as u16,
+            crc: header[1],
+        };
+        if header[2] != _header_check(header[0], header[1])
+            || self.offset + record.span() > FLASH_PAGE_SIZE
+        {
+            // A torn header, nothing after it can be trusted
+            self.offset = FLASH_PAGE_SIZE;
+            return None;
+        }
+        self.offset += record.span();
+        Some(record)
+    }
+}
+
+#[doc(hidden)]
+#[inline(always)]
+const fn _key_len_word(key: u16, len: u16) -> u32 {
+    key as u32 | (len as u32) << 16
+}
+
+/// Check value of the first two words of a record header.
+#[doc(hidden)]
+fn _header_check(word: u32, crc: u32) -> u32 {
+    let check = _crc32_update(!0, &word.to_le_bytes());
+    !_crc32_update(check, &crc.to_le_bytes())
+}
+
+/// Update a CRC-32 (IEEE 802.3) with `data`. The CRC starts at `!0` and
+/// is inverted once all data is processed.
+#[doc(hidden)]
+const fn _crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    let mut i = 0;
+    while i < data.len() {
+        crc ^= data[i] as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
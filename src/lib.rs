@@ -6,18 +6,41 @@ pub use cortex_m_rt::entry;
 /// Re-export of the Peripheral Access Crate (PAC) for the MAX78000.
 pub use max78000_pac as pac;
 pub use pac::Interrupt;
+pub use error::Error;
+pub use peripherals::Peripherals;
 
 mod private {
     pub trait Sealed {}
 }
 use private::Sealed;
 
+pub mod adc;
 pub mod aes;
+pub mod boot;
+pub mod cnn;
+pub mod comparator;
+pub mod crc;
+pub mod crypto;
+pub mod dma;
+pub mod error;
 pub mod flc;
 pub mod gcr;
 pub mod gpio;
+pub mod i2c;
+pub mod i2s;
 pub mod icc;
+pub mod kv;
+pub mod monotonic;
+pub mod peripherals;
+pub mod power;
+pub mod pulse_train;
+pub mod rtc;
+#[cfg(feature = "sdcard")]
+pub mod sdcard;
 pub mod simo;
+pub mod spi;
+pub mod timer;
 pub mod trng;
 pub mod uart;
+pub mod wait;
 pub mod wdt;
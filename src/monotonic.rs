@@ -0,0 +1,110 @@
+//! # Monotonic Clock
+//!
+//! A free-running uptime counter built on the Cortex-M SysTick timer, for
+//! cheap timestamps that never block.
+use core::cell::Cell;
+
+use cortex_m::interrupt::{self, Mutex};
+use cortex_m::peripheral::{syst::SystClkSource, SCB, SYST};
+
+use crate::gcr::clocks::{Clock, SystemClock};
+
+/// SysTick reload value, using the full 24-bit counter to keep the
+/// interrupt rate low.
+const RELOAD: u32 = 0x00ff_ffff;
+const MICROS_PER_SEC: u64 = 1_000_000;
+
+/// Number of SysTick wraps handled by [`Monotonic::on_interrupt()`].
+static WRAPS: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+
+/// # SysTick Monotonic Clock
+///
+/// Counts system clock cycles since it was created. The 24-bit SysTick
+/// counter wraps every 2^24 cycles (167.8 ms at 100 MHz), and each wrap is
+/// accumulated into a 64-bit count by [`Monotonic::on_interrupt()`], which
+/// must be called from the `SysTick` exception handler.
+///
+/// [`Monotonic::now()`] has a resolution of one microsecond and the 64-bit
+/// cycle count only overflows after 2^64 cycles (over 5,800 years at
+/// 100 MHz). The timestamps assume the system clock frequency does not
+/// change after creation.
+///
+/// Example:
+/// ```
+/// use cortex_m_rt::exception;
+/// use hal::monotonic::Monotonic;
+///
+/// let core = cortex_m::Peripherals::take().unwrap();
+/// let mono = Monotonic::new(core.SYST, &clks.sys_clk);
+///
+/// let received_at = mono.now();
+/// // ...
+/// let elapsed_us = mono.now() - received_at;
+///
+/// #[exception]
+/// fn SysTick() {
+///     Monotonic::on_interrupt();
+/// }
+/// ```
+pub struct Monotonic {
+    syst: SYST,
+    clk_freq: u32,
+}
+
+impl Monotonic {
+    /// Start the monotonic clock from zero, counting cycles of the system
+    /// clock. Enables the SysTick exception.
+    pub fn new(mut syst: SYST, clock: &Clock<SystemClock>) -> Self {
+        syst.disable_counter();
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(RELOAD);
+        syst.clear_current();
+        interrupt::free(|cs| WRAPS.borrow(cs).set(0));
+        syst.enable_interrupt();
+        syst.enable_counter();
+        Self {
+            syst,
+            clk_freq: clock.frequency,
+        }
+    }
+
+    /// Account for a wrap of the SysTick counter. Call this from the
+    /// `SysTick` exception handler.
+    #[inline(always)]
+    pub fn on_interrupt() {
+        interrupt::free(|cs| {
+            let wraps = WRAPS.borrow(cs);
+            wraps.set(wraps.get() + 1);
+        });
+    }
+
+    /// Number of system clock cycles since the clock was created.
+    pub fn cycles(&self) -> u64 {
+        interrupt::free(|cs| {
+            let mut wraps = WRAPS.borrow(cs).get();
+            let mut current = SYST::get_current();
+            // A wrap the exception handler has not seen yet, either because
+            // it happened just now or because this runs at a higher priority
+            if SCB::is_pendst_pending() {
+                current = SYST::get_current();
+                wraps += 1;
+            }
+            wraps * (RELOAD as u64 + 1) + (RELOAD - current) as u64
+        })
+    }
+
+    /// Microseconds since the clock was created. Never blocks.
+    pub fn now(&self) -> u64 {
+        let cycles = self.cycles();
+        let clk_freq = self.clk_freq as u64;
+        // Split the division to avoid overflowing the multiplication
+        (cycles / clk_freq) * MICROS_PER_SEC + (cycles % clk_freq) * MICROS_PER_SEC / clk_freq
+    }
+
+    /// Stop the clock and release the SysTick peripheral.
+    pub fn free(mut self) -> SYST {
+        self.syst.disable_interrupt();
+        self.syst.disable_counter();
+        self.syst
+    }
+}
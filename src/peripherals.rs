@@ -0,0 +1,177 @@
+//! # HAL Peripherals
+//!
+//! [`Peripherals::take()`] is the entry point of an application. It takes
+//! the PAC peripherals once, constrains the GCR and returns the drivers
+//! that need no configuration already constructed, so the GPIO ports and
+//! DMA channels are ready to be split up between the other drivers.
+//!
+//! Peripherals that need pins, clocks or a configuration, or whose
+//! constructor changes the state of the device, are passed through as PAC
+//! peripherals to be constructed when the application is ready:
+//! - The watchdogs start counting as soon as they are constructed.
+//! - The SIMO constructor changes the output voltage of VREGO_C.
+//! - The power sequencer constructor clears the wakeup flags, which the
+//!   application may want to read first.
+//! - The flash controller needs the frozen system clock.
+//!
+//! Example:
+//! ```
+//! let p = hal::Peripherals::take().unwrap();
+//! let mut gcr = p.gcr;
+//!
+//! let ipo = Ipo::new(gcr.osc_guards.ipo).enable(&mut gcr.reg);
+//! let clks = gcr.sys_clk
+//!     .switch_to::<_, Div1>(&mut gcr.reg, &ipo)
+//!     .freeze();
+//!
+//! let led = p.gpio2.p2_0.into_input_output();
+//! let wdt = hal::wdt::Wdt0::new(p.wdt0, &mut gcr.reg).unwrap();
+//! let rx = p.dma.ch0;
+//! ```
+use crate::gcr::Gcr;
+use crate::pac;
+
+/// All peripherals of the MAX78000, with the HAL drivers that need no
+/// configuration already constructed. See the [module](self) documentation.
+pub struct Peripherals {
+    // HAL drivers
+    /// The constrained GCR, which owns the registers every other driver
+    /// needs to enable its clock.
+    pub gcr: Gcr,
+    pub gpio0: crate::gpio::gpio0::Parts,
+    pub gpio1: crate::gpio::gpio1::Parts,
+    pub gpio2: crate::gpio::gpio2::Parts,
+    pub aes: crate::aes::Aes,
+    pub dma: crate::dma::Channels,
+    pub icc: crate::icc::Icc,
+
+    // PAC peripherals
+    pub adc: pac::Adc,
+    pub aeskeys: pac::Aeskeys,
+    pub cameraif: pac::Cameraif,
+    pub crc: pac::Crc,
+    pub dvs: pac::Dvs,
+    pub fcr: pac::Fcr,
+    pub flc: pac::Flc,
+    pub gcfr: pac::Gcfr,
+    pub i2c0: pac::I2c0,
+    pub i2c1: pac::I2c1,
+    pub i2c2: pac::I2c2,
+    pub i2s: pac::I2s,
+    pub lpcmp: pac::Lpcmp,
+    pub mcr: pac::Mcr,
+    pub owm: pac::Owm,
+    pub pt0: pac::Pt0,
+    pub pt1: pac::Pt1,
+    pub pt2: pac::Pt2,
+    pub pt3: pac::Pt3,
+    pub ptg: pac::Ptg,
+    pub pwrseq: pac::Pwrseq,
+    pub rtc: pac::Rtc,
+    pub sema: pac::Sema,
+    pub simo: pac::Simo,
+    pub sir: pac::Sir,
+    pub spi0: pac::Spi0,
+    pub spi1: pac::Spi1,
+    pub tmr0: pac::Tmr0,
+    pub tmr1: pac::Tmr1,
+    pub tmr2: pac::Tmr2,
+    pub tmr3: pac::Tmr3,
+    pub tmr4: pac::Tmr4,
+    pub tmr5: pac::Tmr5,
+    pub trimsir: pac::Trimsir,
+    pub trng: pac::Trng,
+    pub uart0: pac::Uart0,
+    pub uart1: pac::Uart1,
+    pub uart2: pac::Uart2,
+    pub uart3: pac::Uart3,
+    pub wdt0: pac::Wdt0,
+    pub wdt1: pac::Wdt1,
+    pub wut: pac::Wut,
+}
+
+impl Peripherals {
+    /// Take the peripherals and construct the HAL drivers. Returns [`None`]
+    /// if the peripherals were already taken, either by this function or by
+    /// [`pac::Peripherals::take()`].
+    ///
+    /// The check is done in a critical section, so it is safe to call from
+    /// interrupt handlers.
+    pub fn take() -> Option<Self> {
+        pac::Peripherals::take().map(Self::from_pac)
+    }
+
+    /// Construct the HAL drivers without checking if the peripherals were
+    /// already taken.
+    ///
+    /// # Safety
+    /// Each peripheral must only be owned once. Any other owner must not be
+    /// used afterwards, since the drivers constructed here reset and
+    /// configure their peripherals.
+    pub unsafe fn steal() -> Self {
+        Self::from_pac(pac::Peripherals::steal())
+    }
+
+    /// Construct the HAL drivers from already taken PAC peripherals.
+    pub fn from_pac(p: pac::Peripherals) -> Self {
+        let mut gcr = Gcr::new(p.gcr, p.lpgcr);
+        let gpio0 = crate::gpio::Gpio0::new(p.gpio0, &mut gcr.reg).split();
+        let gpio1 = crate::gpio::Gpio1::new(p.gpio1, &mut gcr.reg).split();
+        let gpio2 = crate::gpio::Gpio2::new(p.gpio2, &mut gcr.reg).split();
+        let aes = crate::aes::Aes::new(p.aes, &mut gcr.reg);
+        let dma = crate::dma::Dma::new(p.dma, &mut gcr.reg).split();
+        let icc = crate::icc::Icc::new(p.icc0);
+
+        Self {
+            gcr,
+            gpio0,
+            gpio1,
+            gpio2,
+            aes,
+            dma,
+            icc,
+            adc: p.adc,
+            aeskeys: p.aeskeys,
+            cameraif: p.cameraif,
+            crc: p.crc,
+            dvs: p.dvs,
+            fcr: p.fcr,
+            flc: p.flc,
+            gcfr: p.gcfr,
+            i2c0: p.i2c0,
+            i2c1: p.i2c1,
+            i2c2: p.i2c2,
+            i2s: p.i2s,
+            lpcmp: p.lpcmp,
+            mcr: p.mcr,
+            owm: p.owm,
+            pt0: p.pt0,
+            pt1: p.pt1,
+            pt2: p.pt2,
+            pt3: p.pt3,
+            ptg: p.ptg,
+            pwrseq: p.pwrseq,
+            rtc: p.rtc,
+            sema: p.sema,
+            simo: p.simo,
+            sir: p.sir,
+            spi0: p.spi0,
+            spi1: p.spi1,
+            tmr0: p.tmr0,
+            tmr1: p.tmr1,
+            tmr2: p.tmr2,
+            tmr3: p.tmr3,
+            tmr4: p.tmr4,
+            tmr5: p.tmr5,
+            trimsir: p.trimsir,
+            trng: p.trng,
+            uart0: p.uart0,
+            uart1: p.uart1,
+            uart2: p.uart2,
+            uart3: p.uart3,
+            wdt0: p.wdt0,
+            wdt1: p.wdt1,
+            wut: p.wut,
+        }
+    }
+}
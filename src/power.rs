@@ -0,0 +1,409 @@
+//! # Power Management
+//!
+//! Entry into the low power modes of the MAX7800x:
+//! - SLEEP: the CPU clock is stopped while peripherals keep running. Any
+//!   enabled interrupt wakes the CPU. See [`enter_sleep()`].
+//! - DEEPSLEEP: the system clock and its oscillators are stopped, and only
+//!   the enabled [`WakeSource`]s can wake the CPU. RAM and registers are
+//!   retained. See [`Power::enter_deepsleep()`].
+//! - BACKUP: the core is powered off and the device restarts from reset
+//!   on wakeup. See [`Power::enter_backup()`].
+//!
+//! ## RAM Retention
+//!
+//! All RAM is retained in DEEPSLEEP. In BACKUP, only the banks selected
+//! with [`Power::set_ram_retention()`] keep their contents, which saves
+//! their leakage for banks that are not needed. Using retained RAM needs
+//! some care with the linker layout:
+//! - The device restarts from reset, and the runtime zeroes `.bss` and
+//!   initializes `.data`. Retained state must live in a section the runtime
+//!   does not touch, such as `.uninit`, placed in a retained bank by the
+//!   linker script.
+//! - The stack is in use from the very start of the reset handler, before
+//!   retained state can be read. By default it grows down from the end of
+//!   RAM, which is in [`RamBank::Sysram3`], so retaining that bank only
+//!   works if the stack is moved to another bank in `memory.x`. Retaining
+//!   a bank that holds the stack is rejected with
+//!   [`PowerError::StackInRetainedBank`].
+//! - Retained RAM holds garbage after a power-on reset, so store a magic
+//!   value or checksum with the state and check it before use.
+//!
+//! Example `memory.x` that reserves SYSRAM3 for state kept in BACKUP:
+//! ```text
+//! MEMORY {
+//!     FLASH : ORIGIN = 0x10000000, LENGTH = 512K
+//!     RAM : ORIGIN = 0x20000000, LENGTH = 112K
+//!     RETAINED : ORIGIN = 0x2001c000, LENGTH = 16K
+//! }
+//! SECTIONS {
+//!     .retained (NOLOAD) : { *(.retained .retained.*) } > RETAINED
+//! } INSERT AFTER .uninit;
+//! ```
+//!
+//! Shrinking `RAM` also moves the default `_stack_start` to the end of
+//! SYSRAM2.
+use cortex_m::peripheral::SCB;
+
+/// Errors that can occur while configuring the power sequencer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerError {
+    /// The bank holds part of the stack, which is reused from the start of
+    /// the reset handler after BACKUP, so its contents would not survive.
+    StackInRetainedBank(RamBank),
+}
+
+/// Banks of system RAM that can be retained in BACKUP mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RamBank {
+    /// 32 KiB at `0x2000_0000`.
+    Sysram0,
+    /// 32 KiB at `0x2000_8000`.
+    Sysram1,
+    /// 48 KiB at `0x2001_0000`.
+    Sysram2,
+    /// 16 KiB at `0x2001_c000`, at the end of RAM.
+    Sysram3,
+}
+
+impl RamBank {
+    /// All banks, in address order.
+    pub const ALL: [RamBank; 4] = [
+        RamBank::Sysram0,
+        RamBank::Sysram1,
+        RamBank::Sysram2,
+        RamBank::Sysram3,
+    ];
+
+    /// Address range of the bank.
+    pub const fn range(self) -> core::ops::Range<u32> {
+        match self {
+            RamBank::Sysram0 => 0x2000_0000..0x2000_8000,
+            RamBank::Sysram1 => 0x2000_8000..0x2001_0000,
+            RamBank::Sysram2 => 0x2001_0000..0x2001_c000,
+            RamBank::Sysram3 => 0x2001_c000..0x2002_0000,
+        }
+    }
+
+    /// The bank containing `address`, if it is in system RAM.
+    pub fn containing(address: u32) -> Option<RamBank> {
+        RamBank::ALL
+            .into_iter()
+            .find(|bank| bank.range().contains(&address))
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    const fn _mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Set of [`RamBank`]s retained in BACKUP mode.
+///
+/// Example:
+/// ```
+/// let retention = RamRetention::NONE.with(RamBank::Sysram3);
+/// assert!(retention.contains(RamBank::Sysram3));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RamRetention(u8);
+
+impl RamRetention {
+    /// No bank is retained, the lowest BACKUP current.
+    pub const NONE: Self = Self(0);
+    /// Every bank is retained.
+    pub const ALL: Self = Self(0xf);
+
+    /// Add a bank to the set.
+    #[inline(always)]
+    pub const fn with(self, bank: RamBank) -> Self {
+        Self(self.0 | bank._mask())
+    }
+
+    /// Remove a bank from the set.
+    #[inline(always)]
+    pub const fn without(self, bank: RamBank) -> Self {
+        Self(self.0 & !bank._mask())
+    }
+
+    /// Check if a bank is in the set.
+    #[inline(always)]
+    pub const fn contains(self, bank: RamBank) -> bool {
+        self.0 & bank._mask() != 0
+    }
+}
+
+/// Sources that can wake the device from DEEPSLEEP or BACKUP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeSource {
+    /// Pins of GPIO port 0 in the given mask. Each pin must also be
+    /// configured for interrupts in the GPIO peripheral.
+    Gpio0(u32),
+    /// Pins of GPIO port 1 in the given mask.
+    Gpio1(u32),
+    /// Pins of GPIO port 2 in the given mask.
+    Gpio2(u32),
+    /// Pins of GPIO port 3 in the given mask.
+    Gpio3(u32),
+    /// Either alarm of the [`Rtc`](crate::rtc::Rtc).
+    RtcAlarm,
+    /// The wakeup timer.
+    WakeupTimer,
+    /// The analog comparators.
+    Comparator,
+    /// Activity on a UART. Only a UART clocked from an oscillator that
+    /// keeps running in DEEPSLEEP (UART3 on the IBRO or ERTCO) can receive
+    /// the wakeup character.
+    Uart(WakeUart),
+}
+
+/// The UARTs that can wake the device, see [`WakeSource::Uart`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeUart {
+    Uart0,
+    Uart1,
+    Uart2,
+    Uart3,
+}
+
+/// # Power Sequencer (PWRSEQ) Peripheral
+///
+/// Configures the wakeup sources and enters the DEEPSLEEP and BACKUP low
+/// power modes.
+///
+/// Example:
+/// ```
+/// let mut power = Power::new(p.pwrseq);
+/// power.enable_wakeup(&mut gcr.reg, WakeSource::RtcAlarm);
+/// rtc.set_alarm(rtc.seconds() + 60).unwrap();
+/// rtc.enable_alarm_interrupt(RtcAlarm::TimeOfDay);
+/// // Sleep until the alarm, then continue with the same clocks
+/// power.enter_deepsleep(&mut gcr.reg, &mut core.SCB);
+/// ```
+pub struct Power {
+    pwrseq: crate::pac::Pwrseq,
+}
+
+impl Power {
+    /// Create a new power sequencer instance with all wakeup flags cleared.
+    pub fn new(pwrseq: crate::pac::Pwrseq) -> Self {
+        let power = Self { pwrseq };
+        power.clear_wake_status();
+        power
+    }
+
+    /// Enable a source to wake the device from DEEPSLEEP or BACKUP.
+    pub fn enable_wakeup(&mut self, reg: &mut crate::gcr::GcrRegisters, source: WakeSource) {
+        self._set_wakeup(reg, source, true);
+    }
+
+    /// Disable a wakeup source. For GPIO ports only the pins in the mask
+    /// are disabled.
+    pub fn disable_wakeup(&mut self, reg: &mut crate::gcr::GcrRegisters, source: WakeSource) {
+        self._set_wakeup(reg, source, false);
+    }
+
+    #[doc(hidden)]
+    fn _set_wakeup(&mut self, reg: &mut crate::gcr::GcrRegisters, source: WakeSource, enable: bool) {
+        let update = |bits: u32, mask: u32| if enable { bits | mask } else { bits & !mask };
+        let pm = reg.gcr.pm();
+        match source {
+            WakeSource::Gpio0(mask) => {
+                self.pwrseq
+                    .lpwken0()
+                    .modify(|r, w| unsafe { w.bits(update(r.bits(), mask)) });
+            }
+            WakeSource::Gpio1(mask) => {
+                self.pwrseq
+                    .lpwken1()
+                    .modify(|r, w| unsafe { w.bits(update(r.bits(), mask)) });
+            }
+            WakeSource::Gpio2(mask) => {
+                self.pwrseq
+                    .lpwken2()
+                    .modify(|r, w| unsafe { w.bits(update(r.bits(), mask)) });
+            }
+            WakeSource::Gpio3(mask) => {
+                self.pwrseq
+                    .lpwken3()
+                    .modify(|r, w| unsafe { w.bits(update(r.bits(), mask)) });
+            }
+            WakeSource::RtcAlarm => {
+                pm.modify(|_, w| w.rtc_we().bit(enable));
+            }
+            WakeSource::WakeupTimer => {
+                pm.modify(|_, w| w.wut_we().bit(enable));
+            }
+            WakeSource::Comparator => {
+                pm.modify(|_, w| w.aincomp_we().bit(enable));
+                self.pwrseq.lppwen().modify(|_, w| w.aincomp0().bit(enable));
+            }
+            WakeSource::Uart(uart) => {
+                self.pwrseq.lppwen().modify(|_, w| match uart {
+                    WakeUart::Uart0 => w.uart0().bit(enable),
+                    WakeUart::Uart1 => w.uart1().bit(enable),
+                    WakeUart::Uart2 => w.uart2().bit(enable),
+                    WakeUart::Uart3 => w.uart3().bit(enable),
+                });
+            }
+        }
+        // The GPIO wakeup enable covers all ports
+        let any_gpio = self.pwrseq.lpwken0().read().bits() != 0
+            || self.pwrseq.lpwken1().read().bits() != 0
+            || self.pwrseq.lpwken2().read().bits() != 0
+            || self.pwrseq.lpwken3().read().bits() != 0;
+        pm.modify(|_, w| w.gpio_we().bit(any_gpio));
+    }
+
+    /// Select the RAM banks that keep their contents in BACKUP mode. See
+    /// the [module](self) documentation for the linker constraints.
+    ///
+    /// Returns [`PowerError::StackInRetainedBank`] if a bank overlaps the
+    /// stack, from the current stack pointer up to the initial stack
+    /// pointer in the vector table. The retention is not changed then.
+    ///
+    /// Example:
+    /// ```
+    /// #[link_section = ".retained.state"]
+    /// static mut STATE: MaybeUninit<BatteryState> = MaybeUninit::uninit();
+    ///
+    /// // Keep only the 16 KiB bank holding STATE, the stack is in SYSRAM2
+    /// power.set_ram_retention(RamRetention::NONE.with(RamBank::Sysram3)).unwrap();
+    /// power.enter_backup(&mut gcr.reg);
+    /// ```
+    pub fn set_ram_retention(&mut self, retention: RamRetention) -> Result<(), PowerError> {
+        let (stack_low, stack_high) = _stack_range();
+        for bank in RamBank::ALL {
+            let range = bank.range();
+            if retention.contains(bank) && range.start < stack_high && stack_low < range.end {
+                return Err(PowerError::StackInRetainedBank(bank));
+            }
+        }
+        self.pwrseq.lpcn().modify(|_, w| {
+            w.ramret0().bit(retention.contains(RamBank::Sysram0));
+            w.ramret1().bit(retention.contains(RamBank::Sysram1));
+            w.ramret2().bit(retention.contains(RamBank::Sysram2));
+            w.ramret3().bit(retention.contains(RamBank::Sysram3))
+        });
+        Ok(())
+    }
+
+    /// The RAM banks currently retained in BACKUP mode.
+    pub fn ram_retention(&self) -> RamRetention {
+        let lpcn = self.pwrseq.lpcn().read();
+        RamBank::ALL
+            .into_iter()
+            .zip([
+                lpcn.ramret0().bit_is_set(),
+                lpcn.ramret1().bit_is_set(),
+                lpcn.ramret2().bit_is_set(),
+                lpcn.ramret3().bit_is_set(),
+            ])
+            .fold(RamRetention::NONE, |retention, (bank, retained)| {
+                if retained {
+                    retention.with(bank)
+                } else {
+                    retention
+                }
+            })
+    }
+
+    /// Clear the wakeup status flags of all sources. Must be done before
+    /// entering a low power mode, since a pending flag prevents entry.
+    pub fn clear_wake_status(&self) {
+        unsafe {
+            self.pwrseq.lpwkst0().write(|w| w.bits(u32::MAX));
+            self.pwrseq.lpwkst1().write(|w| w.bits(u32::MAX));
+            self.pwrseq.lpwkst2().write(|w| w.bits(u32::MAX));
+            self.pwrseq.lpwkst3().write(|w| w.bits(u32::MAX));
+            self.pwrseq.lppwst().write(|w| w.bits(u32::MAX));
+        }
+    }
+
+    /// Enter DEEPSLEEP until an enabled [`WakeSource`] fires.
+    ///
+    /// The IPO and ISO are powered down while asleep, as is the IBRO unless
+    /// a UART wakeup is enabled. On wakeup, this waits
+    /// for the oscillators that were enabled before entry to become ready
+    /// again and restores the system clock source and divider, so the
+    /// frozen [`Clock`](crate::gcr::clocks::Clock)s stay valid.
+    pub fn enter_deepsleep(&mut self, reg: &mut crate::gcr::GcrRegisters, scb: &mut SCB) {
+        let clkctrl = reg.gcr.clkctrl().read().bits();
+        // Keep the IBRO running if a UART may need it to receive the
+        // wakeup character
+        let lppwen = self.pwrseq.lppwen().read();
+        let uart_wakeup = lppwen.uart0().bit_is_set()
+            || lppwen.uart1().bit_is_set()
+            || lppwen.uart2().bit_is_set()
+            || lppwen.uart3().bit_is_set();
+        self.clear_wake_status();
+        reg.gcr.pm().modify(|_, w| {
+            w.mode().active();
+            w.ipo_pd().set_bit();
+            w.iso_pd().set_bit();
+            w.ibro_pd().bit(!uart_wakeup)
+        });
+        scb.set_sleepdeep();
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfi();
+        scb.clear_sleepdeep();
+        // Wait for the oscillators that were running before entry
+        let ready = |r: &crate::pac::gcr::clkctrl::R| {
+            (!r.ipo_en().bit_is_set() || r.ipo_rdy().bit_is_set())
+                && (!r.iso_en().bit_is_set() || r.iso_rdy().bit_is_set())
+                && (!r.ertco_en().bit_is_set() || r.ertco_rdy().bit_is_set())
+        };
+        while !ready(&reg.gcr.clkctrl().read()) {}
+        // Restore the system clock source and divider
+        reg.gcr.clkctrl().write(|w| unsafe { w.bits(clkctrl) });
+        while reg.gcr.clkctrl().read().sysclk_rdy().bit_is_clear() {}
+    }
+
+    /// Enter BACKUP mode. The core and the RAM banks not selected with
+    /// [`Power::set_ram_retention()`] are powered off, and an enabled
+    /// [`WakeSource`] restarts the device from reset, so this never
+    /// returns.
+    pub fn enter_backup(&mut self, reg: &mut crate::gcr::GcrRegisters) -> ! {
+        self.clear_wake_status();
+        reg.gcr.pm().modify(|_, w| w.mode().backup());
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+
+    /// Release the power sequencer peripheral.
+    pub fn free(self) -> crate::pac::Pwrseq {
+        self.pwrseq
+    }
+}
+
+/// Enter sleep mode until an interrupt or event occurs.
+///
+/// In sleep mode the CPU clock is stopped while the peripherals keep
+/// running, so any enabled interrupt wakes the CPU, including the RTC
+/// alarms of [`crate::rtc::Rtc`]. Execution continues after this call once
+/// the interrupt has been handled.
+#[inline]
+pub fn enter_sleep(scb: &mut SCB) {
+    scb.clear_sleepdeep();
+    cortex_m::asm::dsb();
+    cortex_m::asm::wfi();
+}
+
+/// The addresses used by the stack, from the current stack pointer up to
+/// the initial stack pointer the reset handler starts with.
+#[doc(hidden)]
+fn _stack_range() -> (u32, u32) {
+    let current = cortex_m::register::msp::read();
+    // Safety: The first word of the vector table is the initial stack pointer
+    let initial = unsafe {
+        let vtor = (*SCB::PTR).vtor.read();
+        core::ptr::read_volatile(vtor as *const u32)
+    };
+    (current, initial)
+}
@@ -0,0 +1,324 @@
+//! # Pulse Train Engine (PT)
+//!
+//! Four pulse train outputs that shift out a square wave or a repeating
+//! bit pattern of 2 to 32 bits without CPU involvement, for example IR
+//! remote codes or the bit timing of addressable LEDs.
+//!
+//! Each bit lasts a whole number of peripheral clock cycles, from 1 to
+//! 2^27 - 1. With a 50 MHz peripheral clock this gives bit rates from
+//! 50 MHz down to about 0.37 Hz, and square waves from 25 MHz down to
+//! about 0.19 Hz. The actual rate is rounded to the nearest achievable
+//! value and returned when configuring.
+use core::ops::Deref;
+
+use crate::gcr::{
+    clocks::{Clock, PeripheralClock},
+    ClockForPeripheral, ResetForPeripheral,
+};
+use crate::gpio::{Af2, Pin};
+use crate::pac::pt0::rate_length::Mode;
+use paste::paste;
+
+/// Largest number of peripheral clock cycles per bit (27-bit field).
+const MAX_RATE_CONTROL: u32 = (1 << 27) - 1;
+/// Shortest and longest bit patterns.
+const MIN_PATTERN_BITS: u8 = 2;
+const MAX_PATTERN_BITS: u8 = 32;
+/// Largest delay between repetitions (12-bit field).
+const MAX_LOOP_DELAY: u16 = (1 << 12) - 1;
+
+/// Errors that can occur while configuring a pulse train.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PulseTrainError {
+    /// The requested rate is outside of the range that can be generated
+    /// from the peripheral clock.
+    RateOutOfRange,
+    /// The pattern length is not between 2 and 32 bits.
+    InvalidPatternLength,
+    /// The delay between repetitions is longer than 4095 cycles.
+    InvalidLoopDelay,
+}
+
+/// How many times the output is repeated once started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Repeat {
+    /// Repeat until stopped.
+    Continuous,
+    /// Output the square wave period or bit pattern the given number of
+    /// times (at least 1), then stop and raise the stopped interrupt.
+    Count(u16),
+}
+
+impl Repeat {
+    /// Output the square wave period or bit pattern once.
+    pub const ONE_SHOT: Self = Self::Count(1);
+}
+
+/// Pins that can be driven by a pulse train output.
+pub trait OutputPin<PT>: crate::Sealed {}
+
+// All pulse trains are derived from the same register block
+type PulseTrainRegisterBlock = crate::pac::pt0::RegisterBlock;
+
+/// # Pulse Train Group
+///
+/// The global controls shared by all pulse trains. It enables the pulse
+/// train clock and must exist for individual [`PulseTrain`]s to be
+/// created. The clock and reset are shared by every pulse train, so they
+/// are only reachable through the PTG peripheral; gate them with
+/// [`crate::gcr::GcrRegisters::disable_clock()`] after [`PulseTrains::free()`].
+///
+/// Example:
+/// ```
+/// let trains = PulseTrains::new(p.ptg, &mut gcr.reg, &clks.pclk);
+/// let mut pt = PulseTrain::pt0(p.pt0, pins.p0_18.into_af2(), &trains);
+/// // 38 kHz IR carrier
+/// pt.square_wave(38_000).unwrap();
+/// pt.start();
+/// ```
+pub struct PulseTrains {
+    ptg: crate::pac::Ptg,
+    clk_freq: u32,
+}
+
+impl PulseTrains {
+    /// Enable the pulse train clock, with all pulse trains stopped.
+    pub fn new(
+        ptg: crate::pac::Ptg,
+        reg: &mut crate::gcr::GcrRegisters,
+        clock: &Clock<PeripheralClock>,
+    ) -> Self {
+        unsafe {
+            ptg.reset(&mut reg.gcr);
+            ptg.enable_clock(&mut reg.gcr);
+        }
+        ptg.intfl().write(|w| unsafe { w.bits(u32::MAX) });
+        Self {
+            ptg,
+            clk_freq: clock.frequency,
+        }
+    }
+
+    /// Start the pulse trains in `mask` (bit N for PT N) on the same clock
+    /// cycle, so their outputs stay aligned.
+    #[inline(always)]
+    pub fn start_synchronized(&mut self, mask: u8) {
+        let mask = mask as u32 & 0xf;
+        self.ptg.resync().write(|w| unsafe { w.bits(mask) });
+        self.ptg.safe_en().write(|w| unsafe { w.bits(mask) });
+    }
+
+    /// Stop all pulse trains.
+    #[inline(always)]
+    pub fn stop_all(&mut self) {
+        self.ptg.safe_dis().write(|w| unsafe { w.bits(0xf) });
+    }
+
+    /// Stop all pulse trains and release the PTG peripheral.
+    pub fn free(mut self) -> crate::pac::Ptg {
+        self.stop_all();
+        self.ptg
+    }
+}
+
+/// # Pulse Train (PT) Output
+///
+/// A single pulse train driving its output pin.
+///
+/// Example:
+/// ```
+/// // Repeat an 8-bit pattern three times at 1 kbit/s, LSB first
+/// pt.bit_pattern(0b1010_0110, 8, 1_000).unwrap();
+/// pt.set_repeat(Repeat::Count(3), 0).unwrap();
+/// pt.start();
+/// while pt.is_running() {}
+/// ```
+pub struct PulseTrain<PT, PIN> {
+    pt: PT,
+    _pin: PIN,
+    index: u8,
+    clk_freq: u32,
+}
+
+macro_rules! pulse_train {
+    ($pt:ident, $index:literal, out: $out_pin:ty) => {
+        paste! {
+            use crate::pac::$pt;
+
+            impl crate::Sealed for $out_pin {}
+            impl OutputPin<$pt> for $out_pin {}
+
+            impl<PIN: OutputPin<$pt>> PulseTrain<$pt, PIN> {
+                #[doc = "Construct the "]
+                #[doc = stringify!([<$pt:upper>])]
+                #[doc = " pulse train output. The output is stopped until"]
+                #[doc = " it is configured and started."]
+                pub fn [<$pt:lower>](pt: $pt, pin: PIN, trains: &PulseTrains) -> Self {
+                    let pt = PulseTrain {
+                        pt,
+                        _pin: pin,
+                        index: $index,
+                        clk_freq: trains.clk_freq,
+                    };
+                    pt.stop();
+                    pt
+                }
+            }
+        }
+    };
+}
+
+pulse_train!(Pt0, 0, out: Pin<0, 18, Af2>);
+pulse_train!(Pt1, 1, out: Pin<0, 19, Af2>);
+pulse_train!(Pt2, 2, out: Pin<0, 16, Af2>);
+pulse_train!(Pt3, 3, out: Pin<0, 17, Af2>);
+
+impl<PT, PIN> PulseTrain<PT, PIN>
+where
+    PT: Deref<Target = PulseTrainRegisterBlock>,
+{
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _ptg(&self) -> &'static crate::pac::ptg::RegisterBlock {
+        // Safety: Each pulse train only touches its own bit of the global
+        // registers, using the atomic set and clear registers
+        unsafe { &*crate::pac::Ptg::ptr() }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _mask(&self) -> u32 {
+        1 << self.index
+    }
+
+    /// Compute the number of clock cycles per bit for a bit rate.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _rate_control(&self, bit_rate: u32) -> Result<u32, PulseTrainError> {
+        if bit_rate == 0 {
+            return Err(PulseTrainError::RateOutOfRange);
+        }
+        let cycles = (self.clk_freq as u64 + bit_rate as u64 / 2) / bit_rate as u64;
+        if cycles == 0 || cycles > MAX_RATE_CONTROL as u64 {
+            return Err(PulseTrainError::RateOutOfRange);
+        }
+        Ok(cycles as u32)
+    }
+
+    /// Configure a square wave of the given frequency in Hz, returning the
+    /// actual frequency. Each half period lasts one bit.
+    pub fn square_wave(&mut self, frequency: u32) -> Result<u32, PulseTrainError> {
+        let bit_rate = frequency
+            .checked_mul(2)
+            .ok_or(PulseTrainError::RateOutOfRange)?;
+        let rate = self._rate_control(bit_rate)?;
+        self.stop();
+        self.pt.rate_length().write(|w| unsafe {
+            w.rate_control().bits(rate);
+            w.mode().variant(Mode::SquareWave)
+        });
+        Ok(self.clk_freq / (2 * rate))
+    }
+
+    /// Configure a repeating bit pattern of `bits` bits (2 to 32), shifted
+    /// out least significant bit first at `bit_rate` bits per second.
+    /// Returns the actual bit rate.
+    pub fn bit_pattern(
+        &mut self,
+        pattern: u32,
+        bits: u8,
+        bit_rate: u32,
+    ) -> Result<u32, PulseTrainError> {
+        if !(MIN_PATTERN_BITS..=MAX_PATTERN_BITS).contains(&bits) {
+            return Err(PulseTrainError::InvalidPatternLength);
+        }
+        let rate = self._rate_control(bit_rate)?;
+        self.stop();
+        // A length of 32 bits is encoded as zero
+        let mode = bits % MAX_PATTERN_BITS;
+        self.pt.train().write(|w| unsafe { w.bits(pattern) });
+        self.pt.rate_length().write(|w| unsafe {
+            w.rate_control().bits(rate);
+            w.mode().bits(mode)
+        });
+        Ok(self.clk_freq / rate)
+    }
+
+    /// Set how many times the output repeats once started, and the number
+    /// of peripheral clock cycles (at most 4095) the output is held between
+    /// repetitions.
+    pub fn set_repeat(&mut self, repeat: Repeat, delay_cycles: u16) -> Result<(), PulseTrainError> {
+        if delay_cycles > MAX_LOOP_DELAY {
+            return Err(PulseTrainError::InvalidLoopDelay);
+        }
+        let count = match repeat {
+            // A count of zero repeats forever
+            Repeat::Continuous => 0,
+            Repeat::Count(count) => count.max(1),
+        };
+        self.pt.loop_().write(|w| unsafe {
+            w.count().bits(count);
+            w.delay().bits(delay_cycles)
+        });
+        Ok(())
+    }
+
+    /// Start the output.
+    #[inline(always)]
+    pub fn start(&mut self) {
+        self._ptg().intfl().write(|w| unsafe { w.bits(self._mask()) });
+        self._ptg().safe_en().write(|w| unsafe { w.bits(self._mask()) });
+    }
+
+    /// Stop the output.
+    #[inline(always)]
+    pub fn stop(&self) {
+        self._ptg().safe_dis().write(|w| unsafe { w.bits(self._mask()) });
+    }
+
+    /// Check if the output is running. A pulse train with a repeat count
+    /// stops by itself after the last repetition.
+    #[inline(always)]
+    pub fn is_running(&self) -> bool {
+        self._ptg().enable().read().bits() & self._mask() != 0
+    }
+
+    /// Enable the interrupt raised when the pulse train stops after its
+    /// last repetition. All pulse trains share [`crate::Interrupt::PT`].
+    #[inline(always)]
+    pub fn enable_interrupt(&mut self) {
+        let mask = self._mask();
+        cortex_m::interrupt::free(|_| {
+            self._ptg().inten().modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        });
+    }
+
+    /// Disable the stopped interrupt.
+    #[inline(always)]
+    pub fn disable_interrupt(&mut self) {
+        let mask = self._mask();
+        cortex_m::interrupt::free(|_| {
+            self._ptg().inten().modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        });
+    }
+
+    /// Check if the pulse train has stopped after its last repetition.
+    #[inline(always)]
+    pub fn is_interrupt_pending(&self) -> bool {
+        self._ptg().intfl().read().bits() & self._mask() != 0
+    }
+
+    /// Clear the stopped interrupt flag.
+    #[inline(always)]
+    pub fn clear_interrupt(&mut self) {
+        self._ptg().intfl().write(|w| unsafe { w.bits(self._mask()) });
+    }
+
+    /// Stop the output and release the pulse train and its pin.
+    pub fn free(self) -> (PT, PIN) {
+        self.stop();
+        (self.pt, self._pin)
+    }
+}
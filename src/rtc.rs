@@ -0,0 +1,286 @@
+//! # Real-Time Clock (RTC)
+//!
+//! The RTC counts seconds and fractions of a second from the 32.768 kHz
+//! external RTC oscillator (ERTCO). It is powered from the always-on domain
+//! and keeps counting across soft resets and low power modes.
+use crate::pac::rtc::ctrl;
+
+/// Frequency of the sub-second counter. One sub-second tick is 1/4096 s.
+pub const SUBSECOND_HZ: u32 = 4096;
+/// Mask of the 12-bit sub-second counter.
+const SUBSECOND_MASK: u32 = SUBSECOND_HZ - 1;
+/// The time-of-day alarm only compares the low 20 bits of the seconds
+/// counter, so it can be at most this many seconds (about 12 days) ahead.
+pub const MAX_ALARM_SECONDS: u32 = (1 << 20) - 1;
+/// Mask of the time-of-day alarm register.
+const ALARM_MASK: u32 = 0x000f_ffff;
+/// Status flags in CTRL that are cleared by writing zero (RDY, TOD_ALARM
+/// and SSEC_ALARM).
+const CTRL_FLAGS: u32 = (1 << 4) | (1 << 6) | (1 << 7);
+
+/// Errors that can occur while using the RTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcError {
+    /// The alarm time is not between 1 and [`MAX_ALARM_SECONDS`] seconds
+    /// after the current time.
+    AlarmOutOfRange,
+}
+
+/// Alarms of the RTC. Both alarms can wake the CPU from sleep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcAlarm {
+    /// Fires once when the seconds counter reaches the time set with
+    /// [`Rtc::set_alarm()`].
+    TimeOfDay,
+    /// Fires periodically at the interval set with
+    /// [`Rtc::set_subsecond_alarm()`].
+    Subsecond,
+}
+
+/// Clock source of the RTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcClockSource {
+    /// 32.768 kHz crystal connected between the 32KIN and 32KOUT pins.
+    Crystal,
+    /// 32.768 kHz square wave driven into the 32KIN pin, bypassing the
+    /// crystal oscillator.
+    External,
+}
+
+/// # Real-Time Clock (RTC) Peripheral
+///
+/// Time is kept as a 32-bit count of seconds and a 12-bit count of
+/// sub-seconds in units of 1/[`SUBSECOND_HZ`] s. The epoch is up to the
+/// application, for example the Unix epoch.
+///
+/// The counters run in the ERTCO clock domain, so writes take effect after
+/// the hardware synchronizes them, and reads can return a torn value while
+/// the counters roll over. [`Rtc::set_time()`] waits for the
+/// synchronization to finish and [`Rtc::time()`] retries until it reads a
+/// stable value.
+///
+/// Example:
+/// ```
+/// let rtc = Rtc::new(p.rtc, &mut p.mcr, RtcClockSource::Crystal);
+/// rtc.set_time(1_700_000_000, 0);
+/// // ...
+/// let (seconds, subseconds) = rtc.time();
+/// let expired = seconds >= token_expiry;
+/// ```
+pub struct Rtc {
+    rtc: crate::pac::Rtc,
+}
+
+impl Rtc {
+    /// Construct a new RTC peripheral and enable its oscillator. If the RTC
+    /// was already running, for example before a soft reset, the current
+    /// time is preserved.
+    pub fn new(rtc: crate::pac::Rtc, mcr: &mut crate::pac::Mcr, source: RtcClockSource) -> Self {
+        mcr.ctrl().modify(|_, w| w.ertco_en().set_bit());
+        rtc.oscctrl().modify(|_, w| match source {
+            RtcClockSource::Crystal => w.bypass().clear_bit(),
+            RtcClockSource::External => w.bypass().set_bit(),
+        });
+        let s = Self { rtc };
+        if s.rtc.ctrl().read().en().is_dis() {
+            s._enable_write();
+            s._modify_ctrl(|w| w.en().en());
+            s._wait_busy();
+            s._disable_write();
+        }
+        s
+    }
+
+    /// Wait until changes to the RTC registers have been synchronized.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _wait_busy(&self) {
+        while self.rtc.ctrl().read().busy().is_busy() {}
+    }
+
+    /// Read-modify-write CTRL without clearing any status flag that was
+    /// raised between the read and the write.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _modify_ctrl(&self, f: impl FnOnce(&mut ctrl::W) -> &mut ctrl::W) {
+        self.rtc
+            .ctrl()
+            .modify(|r, w| f(unsafe { w.bits(r.bits() | CTRL_FLAGS) }));
+    }
+
+    /// Allow writes to the protected RTC registers.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _enable_write(&self) {
+        self._wait_busy();
+        self._modify_ctrl(|w| w.wr_en().pending());
+        self._wait_busy();
+    }
+
+    /// Protect the RTC registers from unintentional writes.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _disable_write(&self) {
+        self._modify_ctrl(|w| w.wr_en().inactive());
+    }
+
+    /// Set the current time. `subseconds` is in units of 1/[`SUBSECOND_HZ`]
+    /// s and is truncated to 12 bits. Blocks until the new time has been
+    /// synchronized to the RTC clock domain.
+    pub fn set_time(&self, seconds: u32, subseconds: u32) {
+        self._enable_write();
+        // The counters can only be written while the RTC is stopped
+        self._modify_ctrl(|w| w.en().dis());
+        self._wait_busy();
+        self.rtc.sec().write(|w| unsafe { w.sec().bits(seconds) });
+        self._wait_busy();
+        self.rtc
+            .ssec()
+            .write(|w| unsafe { w.ssec().bits((subseconds & SUBSECOND_MASK) as u16) });
+        self._wait_busy();
+        self._modify_ctrl(|w| w.en().en());
+        self._wait_busy();
+        self._disable_write();
+    }
+
+    /// Get the current time as `(seconds, subseconds)`, with `subseconds` in
+    /// units of 1/[`SUBSECOND_HZ`] s.
+    ///
+    /// The seconds counter is read before and after the sub-seconds
+    /// counter, and the read is retried if the seconds changed in between,
+    /// so a rollover cannot produce a torn value.
+    pub fn time(&self) -> (u32, u32) {
+        loop {
+            self._wait_busy();
+            let seconds = self.rtc.sec().read().sec().bits();
+            let subseconds = self.rtc.ssec().read().ssec().bits() as u32 & SUBSECOND_MASK;
+            if self.rtc.sec().read().sec().bits() == seconds {
+                return (seconds, subseconds);
+            }
+        }
+    }
+
+    /// Get the number of whole seconds of the current time.
+    #[inline]
+    pub fn seconds(&self) -> u32 {
+        self.time().0
+    }
+
+    /// Set the time-of-day alarm to fire when the seconds counter reaches
+    /// `seconds`. The alarm must be in the future and no more than
+    /// [`MAX_ALARM_SECONDS`] ahead of the current time, since the hardware
+    /// only compares the low 20 bits of the seconds counter.
+    ///
+    /// The range check uses wrapping arithmetic, so an alarm shortly after
+    /// the 32-bit seconds counter rolls over to zero is accepted and fires
+    /// at the right time.
+    ///
+    /// Example:
+    /// ```
+    /// use hal::pac::interrupt;
+    ///
+    /// // Wake up every 10 seconds to take a sample
+    /// rtc.set_alarm(rtc.seconds().wrapping_add(10)).unwrap();
+    /// rtc.enable_alarm_interrupt(RtcAlarm::TimeOfDay);
+    /// unsafe { cortex_m::peripheral::NVIC::unmask(Interrupt::RTC) };
+    /// loop {
+    ///     hal::power::enter_sleep(&mut core.SCB);
+    /// }
+    ///
+    /// #[interrupt]
+    /// fn RTC() {
+    ///     // ...
+    ///     rtc.clear_alarm(RtcAlarm::TimeOfDay);
+    ///     rtc.set_alarm(rtc.seconds().wrapping_add(10)).unwrap();
+    /// }
+    /// ```
+    pub fn set_alarm(&self, seconds: u32) -> Result<(), RtcError> {
+        let ahead = seconds.wrapping_sub(self.seconds());
+        if ahead == 0 || ahead > MAX_ALARM_SECONDS {
+            return Err(RtcError::AlarmOutOfRange);
+        }
+        // The alarm must be disabled while it is changed
+        let enabled = self.rtc.ctrl().read().tod_alarm_ie().is_en();
+        self.disable_alarm_interrupt(RtcAlarm::TimeOfDay);
+        self.rtc
+            .toda()
+            .write(|w| unsafe { w.tod_alarm().bits(seconds & ALARM_MASK) });
+        self._wait_busy();
+        if enabled {
+            self.enable_alarm_interrupt(RtcAlarm::TimeOfDay);
+        }
+        Ok(())
+    }
+
+    /// Set the sub-second alarm to fire every `ticks` sub-seconds, in units
+    /// of 1/[`SUBSECOND_HZ`] s. The alarm repeats until it is disabled.
+    ///
+    /// A `ticks` of 0 gives the longest interval, 2^32 ticks (about 12
+    /// days).
+    pub fn set_subsecond_alarm(&self, ticks: u32) {
+        // The alarm counts up from the reload value and fires when it
+        // rolls over, so it must be disabled while it is changed
+        let enabled = self.rtc.ctrl().read().ssec_alarm_ie().is_en();
+        self.disable_alarm_interrupt(RtcAlarm::Subsecond);
+        self.rtc
+            .sseca()
+            .write(|w| unsafe { w.ssec_alarm().bits(0u32.wrapping_sub(ticks)) });
+        self._wait_busy();
+        if enabled {
+            self.enable_alarm_interrupt(RtcAlarm::Subsecond);
+        }
+    }
+
+    /// Enable an alarm and its interrupt. The alarm raises the `RTC`
+    /// interrupt and wakes the CPU from sleep.
+    pub fn enable_alarm_interrupt(&self, alarm: RtcAlarm) {
+        self._wait_busy();
+        match alarm {
+            RtcAlarm::TimeOfDay => self._modify_ctrl(|w| w.tod_alarm_ie().en()),
+            RtcAlarm::Subsecond => self._modify_ctrl(|w| w.ssec_alarm_ie().en()),
+        }
+        self._wait_busy();
+    }
+
+    /// Disable an alarm and its interrupt.
+    pub fn disable_alarm_interrupt(&self, alarm: RtcAlarm) {
+        self._wait_busy();
+        match alarm {
+            RtcAlarm::TimeOfDay => self._modify_ctrl(|w| w.tod_alarm_ie().dis()),
+            RtcAlarm::Subsecond => self._modify_ctrl(|w| w.ssec_alarm_ie().dis()),
+        }
+        self._wait_busy();
+    }
+
+    /// Check if an alarm has fired.
+    #[inline]
+    pub fn is_alarm_pending(&self, alarm: RtcAlarm) -> bool {
+        let ctrl = self.rtc.ctrl().read();
+        match alarm {
+            RtcAlarm::TimeOfDay => ctrl.tod_alarm().bit_is_set(),
+            RtcAlarm::Subsecond => ctrl.ssec_alarm().bit_is_set(),
+        }
+    }
+
+    /// Clear the pending flag of an alarm.
+    #[inline]
+    pub fn clear_alarm(&self, alarm: RtcAlarm) {
+        let flag = match alarm {
+            RtcAlarm::TimeOfDay => 1 << 6,
+            RtcAlarm::Subsecond => 1 << 7,
+        };
+        self._wait_busy();
+        self.rtc
+            .ctrl()
+            .modify(|r, w| unsafe { w.bits((r.bits() | CTRL_FLAGS) & !flag) });
+        self._wait_busy();
+    }
+
+    /// Release the RTC peripheral. The RTC keeps counting.
+    pub fn free(self) -> crate::pac::Rtc {
+        self.rtc
+    }
+}
@@ -0,0 +1,527 @@
+//! # SD Card over SPI
+//!
+//! A blocking driver for SD cards in SPI mode, built on the [`Spi`]
+//! master and a GPIO chip select. Enabled with the `sdcard` feature.
+//!
+//! [`SdCard::new()`] runs the initialization handshake (CMD0, CMD8, and
+//! ACMD41), detects the card type, and reads the capacity. Both standard
+//! capacity cards (SDSC, addressed in bytes) and high capacity cards
+//! (SDHC and SDXC, addressed in blocks) are supported, and the driver
+//! always works in 512-byte blocks.
+//!
+//! Cards must be initialized with SCK between 100 and 400 kHz. Once
+//! initialized, the bus can be sped up to 25 MHz with
+//! [`Spi::set_frequency()`] through [`SdCard::bus_mut()`].
+//!
+//! Besides whole blocks, [`SdCard::read()`] and [`SdCard::write()`] access
+//! the card at any byte offset, reading back the blocks that are only
+//! partially written. With the `embedded-storage` feature, they also
+//! implement the `embedded-storage` `ReadStorage` and `Storage` traits.
+use core::convert::Infallible;
+use core::ops::Deref;
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::SpiBus;
+
+use crate::spi::{Spi, SpiError};
+
+/// Size of a block, in bytes.
+pub const BLOCK_LEN: usize = 512;
+/// Byte clocked out while only reading, and sent by an idle card.
+const IDLE_BYTE: u8 = 0xff;
+/// Token that starts a single block of data.
+const DATA_START_TOKEN: u8 = 0xfe;
+/// Status of a data response token for accepted data.
+const DATA_ACCEPTED: u8 = 0x05;
+/// Card is in the idle state (R1).
+const R1_IDLE: u8 = 1 << 0;
+/// Card rejected the command as illegal (R1).
+const R1_ILLEGAL_COMMAND: u8 = 1 << 2;
+/// Argument of CMD8: 2.7-3.6 V supply and a check pattern.
+const CMD8_ARG: u32 = 0x1aa;
+/// High capacity support, in the argument of ACMD41 and in the OCR.
+const OCR_CCS: u32 = 1 << 30;
+/// Number of bytes polled for a command response.
+const RESPONSE_POLLS: usize = 8;
+/// Number of attempts of CMD0 before giving up on a card.
+const GO_IDLE_ATTEMPTS: usize = 32;
+/// Number of attempts of ACMD41, about 3 s at 400 kHz.
+const INIT_ATTEMPTS: usize = 10_000;
+/// Number of bytes polled while waiting for a data token or for the card
+/// to become ready, about 1 s at 400 kHz.
+const BUSY_POLLS: usize = 50_000;
+
+/// Errors that can occur while using an SD card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SdCardError {
+    /// The SPI bus reported an error.
+    Spi(SpiError),
+    /// No card answered the reset command.
+    NoCard,
+    /// The card does not support the 2.7-3.6 V supply range, or did not
+    /// finish its initialization.
+    UnsupportedCard,
+    /// The card returned an error for a command, with its R1 response.
+    Command(u8),
+    /// The card returned an error token instead of a block of data.
+    Read(u8),
+    /// The card rejected a written block, with its data response.
+    Write(u8),
+    /// The card did not respond or stayed busy for too long.
+    Timeout,
+    /// The access extends past the end of the card.
+    OutOfBounds,
+}
+
+impl From<SpiError> for SdCardError {
+    #[inline(always)]
+    fn from(error: SpiError) -> Self {
+        SdCardError::Spi(error)
+    }
+}
+
+/// Addressing scheme of a card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CardType {
+    /// Version 1 standard capacity card, addressed in bytes.
+    Sd1,
+    /// Version 2 standard capacity card, addressed in bytes.
+    Sd2,
+    /// High or extended capacity card (SDHC or SDXC), addressed in blocks.
+    Sdhc,
+}
+
+/// # SD Card
+///
+/// An initialized SD card on an [`Spi`] master, selected by a GPIO pin.
+///
+/// Example:
+/// ```
+/// let spi = hal::spi::Spi::spi0(
+///     p.spi0,
+///     &mut gcr.reg,
+///     &clks.pclk,
+///     pins.p0_7.into_af1(),
+///     pins.p0_5.into_af1(),
+///     pins.p0_6.into_af1(),
+///     SpiConfig { frequency: 400_000, ..Default::default() },
+/// )
+/// .unwrap();
+/// let cs = pins.p0_11.into_input_output();
+/// let mut card = SdCard::new(spi, cs).unwrap();
+/// card.bus_mut().set_frequency(20_000_000).unwrap();
+///
+/// let mut block = [0u8; BLOCK_LEN];
+/// card.read_block(0, &mut block).unwrap();
+/// // Append a log record at any byte offset
+/// card.write(log_offset, b"t=1234 temp=21.5\n").unwrap();
+/// ```
+pub struct SdCard<SPI, SCK, MOSI, MISO, CS> {
+    bus: Spi<SPI, SCK, MOSI, MISO>,
+    cs: CS,
+    card_type: CardType,
+    blocks: u32,
+}
+
+impl<SPI, SCK, MOSI, MISO, CS> SdCard<SPI, SCK, MOSI, MISO, CS>
+where
+    SPI: Deref<Target = crate::pac::spi0::RegisterBlock>,
+    CS: OutputPin<Error = Infallible>,
+{
+    /// Initialize the card and read its capacity. The bus must run at 100
+    /// to 400 kHz.
+    ///
+    /// On failure the bus and chip select are dropped; use
+    /// [`SdCard::new_or_release()`] to get them back.
+    pub fn new(bus: Spi<SPI, SCK, MOSI, MISO>, cs: CS) -> Result<Self, SdCardError> {
+        Self::new_or_release(bus, cs).map_err(|(error, _, _)| error)
+    }
+
+    /// Initialize the card and read its capacity, returning the bus and
+    /// chip select along with the error on failure.
+    #[allow(clippy::type_complexity)]
+    pub fn new_or_release(
+        bus: Spi<SPI, SCK, MOSI, MISO>,
+        cs: CS,
+    ) -> Result<Self, (SdCardError, Spi<SPI, SCK, MOSI, MISO>, CS)> {
+        let mut card = Self {
+            bus,
+            cs,
+            card_type: CardType::Sd1,
+            blocks: 0,
+        };
+        let result = card._init();
+        card._deselect();
+        match result {
+            Ok(()) => Ok(card),
+            Err(error) => Err((error, card.bus, card.cs)),
+        }
+    }
+
+    #[doc(hidden)]
+    fn _init(&mut self) -> Result<(), SdCardError> {
+        // At least 74 clock cycles with the card deselected enter SPI mode
+        let _ = self.cs.set_high();
+        self.bus.write(&[IDLE_BYTE; 10])?;
+
+        let mut idle = false;
+        for _ in 0..GO_IDLE_ATTEMPTS {
+            // An empty slot never answers, so a timeout is retried too
+            match self._command(0, 0) {
+                Ok(R1_IDLE) => {
+                    idle = true;
+                    break;
+                }
+                Ok(_) | Err(SdCardError::Timeout) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        if !idle {
+            return Err(SdCardError::NoCard);
+        }
+
+        // Version 1 cards do not know CMD8
+        let r1 = self._command(8, CMD8_ARG)?;
+        let version2 = r1 & R1_ILLEGAL_COMMAND == 0;
+        if version2 {
+            let r7 = self._read_u32()?;
+            if r7 & 0xfff != CMD8_ARG {
+                return Err(SdCardError::UnsupportedCard);
+            }
+        }
+
+        let arg = if version2 { OCR_CCS } else { 0 };
+        let mut ready = false;
+        for _ in 0..INIT_ATTEMPTS {
+            self._command(55, 0)?;
+            let r1 = self._command(41, arg)?;
+            if r1 & !R1_IDLE != 0 {
+                return Err(SdCardError::UnsupportedCard);
+            }
+            if r1 == 0 {
+                ready = true;
+                break;
+            }
+        }
+        if !ready {
+            return Err(SdCardError::UnsupportedCard);
+        }
+
+        self.card_type = if version2 {
+            Self::_check(self._command(58, 0)?)?;
+            if self._read_u32()? & OCR_CCS != 0 {
+                CardType::Sdhc
+            } else {
+                CardType::Sd2
+            }
+        } else {
+            CardType::Sd1
+        };
+        // High capacity cards always use 512-byte blocks
+        if self.card_type != CardType::Sdhc {
+            Self::_check(self._command(16, BLOCK_LEN as u32)?)?;
+        }
+
+        let mut csd = [0u8; 16];
+        Self::_check(self._command(9, 0)?)?;
+        self._read_data(&mut csd)?;
+        self.blocks = csd_blocks(&csd);
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _deselect(&mut self) {
+        let _ = self.cs.set_high();
+        // The card only releases MISO on the next clock edge
+        let _ = self.bus.write(&[IDLE_BYTE]);
+    }
+
+    /// Wait for the card to stop holding MISO low.
+    #[doc(hidden)]
+    fn _wait_ready(&mut self) -> Result<(), SdCardError> {
+        let mut byte = [0u8];
+        for _ in 0..BUSY_POLLS {
+            self.bus.read(&mut byte)?;
+            if byte[0] == IDLE_BYTE {
+                return Ok(());
+            }
+        }
+        Err(SdCardError::Timeout)
+    }
+
+    /// Select the card, send a command, and return its R1 response. The
+    /// card stays selected for the rest of the response or data.
+    #[doc(hidden)]
+    fn _command(&mut self, index: u8, arg: u32) -> Result<u8, SdCardError> {
+        let _ = self.cs.set_high();
+        self.bus.write(&[IDLE_BYTE])?;
+        let _ = self.cs.set_low();
+        // The card is not busy before it has been reset
+        if index != 0 {
+            self._wait_ready()?;
+        }
+        let mut frame = [0u8; 6];
+        frame[0] = 0x40 | index;
+        frame[1..5].copy_from_slice(&arg.to_be_bytes());
+        frame[5] = (crc7(&frame[..5]) << 1) | 1;
+        self.bus.write(&frame)?;
+        let mut r1 = [IDLE_BYTE];
+        for _ in 0..RESPONSE_POLLS {
+            self.bus.read(&mut r1)?;
+            if r1[0] & 0x80 == 0 {
+                return Ok(r1[0]);
+            }
+        }
+        Err(SdCardError::Timeout)
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check(r1: u8) -> Result<(), SdCardError> {
+        if r1 != 0 {
+            return Err(SdCardError::Command(r1));
+        }
+        Ok(())
+    }
+
+    /// Read the 32-bit trailer of an R3 or R7 response.
+    #[doc(hidden)]
+    fn _read_u32(&mut self) -> Result<u32, SdCardError> {
+        let mut buf = [IDLE_BYTE; 4];
+        self.bus.read(&mut buf)?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    /// Wait for the start token, then read a block of data and its CRC.
+    #[doc(hidden)]
+    fn _read_data(&mut self, data: &mut [u8]) -> Result<(), SdCardError> {
+        let mut token = [IDLE_BYTE];
+        for _ in 0..BUSY_POLLS {
+            self.bus.read(&mut token)?;
+            if token[0] != IDLE_BYTE {
+                break;
+            }
+        }
+        match token[0] {
+            DATA_START_TOKEN => (),
+            IDLE_BYTE => return Err(SdCardError::Timeout),
+            error => return Err(SdCardError::Read(error)),
+        }
+        self.bus.read(data)?;
+        let mut crc = [0u8; 2];
+        self.bus.read(&mut crc)?;
+        Ok(())
+    }
+
+    /// Convert a block number to a command argument.
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _address(&self, block: u32) -> Result<u32, SdCardError> {
+        if block >= self.blocks {
+            return Err(SdCardError::OutOfBounds);
+        }
+        Ok(match self.card_type {
+            CardType::Sdhc => block,
+            CardType::Sd1 | CardType::Sd2 => block * BLOCK_LEN as u32,
+        })
+    }
+
+    /// Returns the type of the card.
+    #[inline(always)]
+    pub fn card_type(&self) -> CardType {
+        self.card_type
+    }
+
+    /// Returns the number of 512-byte blocks of the card.
+    #[inline(always)]
+    pub fn num_blocks(&self) -> u32 {
+        self.blocks
+    }
+
+    /// Returns the capacity of the card in bytes.
+    #[inline(always)]
+    pub fn capacity(&self) -> u64 {
+        self.blocks as u64 * BLOCK_LEN as u64
+    }
+
+    /// Read the block with number `block`.
+    pub fn read_block(&mut self, block: u32, data: &mut [u8; BLOCK_LEN]) -> Result<(), SdCardError> {
+        let address = self._address(block)?;
+        let result = self
+            ._command(17, address)
+            .and_then(Self::_check)
+            .and_then(|_| self._read_data(data));
+        self._deselect();
+        result
+    }
+
+    /// Write the block with number `block`, waiting for the card to finish
+    /// programming it.
+    pub fn write_block(&mut self, block: u32, data: &[u8; BLOCK_LEN]) -> Result<(), SdCardError> {
+        let address = self._address(block)?;
+        let result = self._write_block(address, data);
+        self._deselect();
+        result
+    }
+
+    #[doc(hidden)]
+    fn _write_block(&mut self, address: u32, data: &[u8; BLOCK_LEN]) -> Result<(), SdCardError> {
+        Self::_check(self._command(24, address)?)?;
+        self.bus.write(&[IDLE_BYTE, DATA_START_TOKEN])?;
+        self.bus.write(data)?;
+        // The CRC is not checked in SPI mode
+        self.bus.write(&[IDLE_BYTE; 2])?;
+        let mut response = [IDLE_BYTE];
+        self.bus.read(&mut response)?;
+        if response[0] & 0x1f != DATA_ACCEPTED {
+            return Err(SdCardError::Write(response[0]));
+        }
+        self._wait_ready()
+    }
+
+    /// Read `bytes.len()` bytes starting at byte `offset` of the card.
+    pub fn read(&mut self, offset: u64, bytes: &mut [u8]) -> Result<(), SdCardError> {
+        self._check_bounds(offset, bytes.len())?;
+        let mut block = [0u8; BLOCK_LEN];
+        let mut offset = offset;
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let start = (offset % BLOCK_LEN as u64) as usize;
+            let len = rest.len().min(BLOCK_LEN - start);
+            self.read_block((offset / BLOCK_LEN as u64) as u32, &mut block)?;
+            let (head, tail) = rest.split_at_mut(len);
+            head.copy_from_slice(&block[start..start + len]);
+            offset += len as u64;
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` starting at byte `offset` of the card. Blocks that
+    /// are only partially written are read first, so the bytes around
+    /// `bytes` are preserved.
+    pub fn write(&mut self, offset: u64, bytes: &[u8]) -> Result<(), SdCardError> {
+        self._check_bounds(offset, bytes.len())?;
+        let mut block = [0u8; BLOCK_LEN];
+        let mut offset = offset;
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let number = (offset / BLOCK_LEN as u64) as u32;
+            let start = (offset % BLOCK_LEN as u64) as usize;
+            let len = rest.len().min(BLOCK_LEN - start);
+            if len < BLOCK_LEN {
+                self.read_block(number, &mut block)?;
+            }
+            let (head, tail) = rest.split_at(len);
+            block[start..start + len].copy_from_slice(head);
+            self.write_block(number, &block)?;
+            offset += len as u64;
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_bounds(&self, offset: u64, len: usize) -> Result<(), SdCardError> {
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= self.capacity() => Ok(()),
+            _ => Err(SdCardError::OutOfBounds),
+        }
+    }
+
+    /// Get mutable access to the SPI bus, for example to raise its
+    /// frequency after initialization.
+    #[inline(always)]
+    pub fn bus_mut(&mut self) -> &mut Spi<SPI, SCK, MOSI, MISO> {
+        &mut self.bus
+    }
+
+    /// Release the SPI bus and the chip select pin.
+    pub fn free(self) -> (Spi<SPI, SCK, MOSI, MISO>, CS) {
+        (self.bus, self.cs)
+    }
+}
+
+#[cfg(feature = "embedded-storage")]
+impl<SPI, SCK, MOSI, MISO, CS> embedded_storage::ReadStorage for SdCard<SPI, SCK, MOSI, MISO, CS>
+where
+    SPI: Deref<Target = crate::pac::spi0::RegisterBlock>,
+    CS: OutputPin<Error = Infallible>,
+{
+    type Error = SdCardError;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        SdCard::read(self, offset as u64, bytes)
+    }
+
+    /// Capacity of the card, saturated to [`u32::MAX`] bytes since the
+    /// trait addresses bytes with 32-bit offsets.
+    fn capacity(&self) -> usize {
+        self.capacity().min(u32::MAX as u64) as usize
+    }
+}
+
+#[cfg(feature = "embedded-storage")]
+impl<SPI, SCK, MOSI, MISO, CS> embedded_storage::Storage for SdCard<SPI, SCK, MOSI, MISO, CS>
+where
+    SPI: Deref<Target = crate::pac::spi0::RegisterBlock>,
+    CS: OutputPin<Error = Infallible>,
+{
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        SdCard::write(self, offset as u64, bytes)
+    }
+}
+
+/// Compute the CRC7 of a command frame, as required for CMD0 and CMD8.
+const fn crc7(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    let mut i = 0;
+    while i < data.len() {
+        let mut bit = 0;
+        while bit < 8 {
+            let input = (data[i] >> (7 - bit)) & 1;
+            let feedback = ((crc >> 6) & 1) ^ input;
+            crc = (crc << 1) & 0x7f;
+            if feedback != 0 {
+                crc ^= 0x09;
+            }
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
+
+/// Extract the field in bits `msb` to `lsb` of a card specific data
+/// register, where bit 127 is the first bit received.
+const fn csd_field(csd: &[u8; 16], msb: usize, lsb: usize) -> u32 {
+    let mut value = 0u32;
+    let mut bit = msb + 1;
+    while bit > lsb {
+        bit -= 1;
+        let byte = csd[15 - bit / 8];
+        value = (value << 1) | ((byte >> (bit % 8)) & 1) as u32;
+    }
+    value
+}
+
+/// Compute the number of 512-byte blocks from a card specific data
+/// register.
+const fn csd_blocks(csd: &[u8; 16]) -> u32 {
+    if csd_field(csd, 127, 126) == 0 {
+        // CSD version 1: (C_SIZE + 1) * 2^(C_SIZE_MULT + 2) blocks of
+        // 2^READ_BL_LEN bytes
+        let read_bl_len = csd_field(csd, 83, 80);
+        let c_size = csd_field(csd, 73, 62);
+        let c_size_mult = csd_field(csd, 49, 47);
+        (c_size + 1) << (c_size_mult + 2 + read_bl_len.saturating_sub(9))
+    } else {
+        // CSD version 2: (C_SIZE + 1) * 512 KiB
+        let c_size = csd_field(csd, 69, 48);
+        (c_size + 1) * 1024
+    }
+}
@@ -1,12 +1,304 @@
-//! SIMO
+//! # Single Inductor Multiple Output Power Supply (SIMO)
 
-pub struct Simo {}
+/// Lowest output voltage of the low range, in millivolts.
+const VSET_LOW_RANGE_BASE_MV: u16 = 500;
+/// Lowest output voltage of the high range, in millivolts.
+const VSET_HIGH_RANGE_BASE_MV: u16 = 600;
+/// Output voltage step of a single `vset` code, in millivolts.
+const VSET_STEP_MV: u16 = 10;
+/// Largest `vset` code (7-bit field).
+const VSET_MAX: u8 = 0x7f;
+/// Largest peak current setting (4-bit field).
+const IPKSET_MAX: u8 = 0x0f;
+
+/// Lowest output voltage that can be requested, in millivolts.
+pub const VREGO_MIN_MV: u16 = VSET_LOW_RANGE_BASE_MV;
+/// Highest output voltage that can be requested, in millivolts.
+pub const VREGO_MAX_MV: u16 = VSET_HIGH_RANGE_BASE_MV + VSET_STEP_MV * VSET_MAX as u16;
+
+/// Number of polls of `BUCK_OUT_READY` before [`SimoError::Timeout`] is
+/// returned, and the most polls spent waiting for a stale ready bit to
+/// clear after a new voltage is set.
+pub const SIMO_READY_TIMEOUT: u32 = 100_000;
+
+/// Errors that can occur while configuring the SIMO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SimoError {
+    /// The requested voltage cannot be represented by the regulator.
+    VoltageOutOfRange,
+    /// The requested peak current setting is above the 4-bit maximum.
+    PeakCurrentOutOfRange,
+    /// The output did not report ready within [`SIMO_READY_TIMEOUT`] polls.
+    Timeout,
+}
+
+/// The buck converter outputs of the SIMO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SimoOutput {
+    VregoA,
+    VregoB,
+    VregoC,
+    VregoD,
+}
+
+/// # Single Inductor Multiple Output Power Supply (SIMO)
+///
+/// Example:
+/// ```
+/// let simo = hal::simo::Simo::new(p.simo, &mut gcr.reg);
+/// // Raise the core rail to 1.1 V
+/// simo.set_vrego_c(1100).unwrap();
+/// while !simo.is_ready() {}
+/// assert_eq!(simo.vrego_c(), 1100);
+/// ```
+pub struct Simo {
+    simo: crate::pac::Simo,
+}
 
 impl Simo {
     /// Create a new SIMO peripheral instance.
     pub fn new(simo: crate::pac::Simo, _reg: &mut crate::gcr::GcrRegisters) -> Self {
         simo.vrego_c().modify(|_, w| unsafe { w.vsetc().bits(59) } );
 
-        Self {}
+        Self { simo }
+    }
+
+    /// Release the underlying SIMO peripheral. The output voltages are left
+    /// as configured.
+    pub fn free(self) -> crate::pac::Simo {
+        self.simo
+    }
+
+    /// Set the output voltage of VREGO_A in millivolts.
+    ///
+    /// The regulator steps in 10 mV, so a voltage between two steps is
+    /// rounded down to the step below it; [`Simo::vrego_a()`] returns the
+    /// voltage actually set. Returns [`SimoError::VoltageOutOfRange`]
+    /// outside [`VREGO_MIN_MV`] to [`VREGO_MAX_MV`].
+    ///
+    /// Example:
+    /// ```
+    /// simo.set_vrego_a(1815).unwrap();
+    /// assert_eq!(simo.vrego_a(), 1810);
+    /// ```
+    pub fn set_vrego_a(&self, millivolts: u16) -> Result<(), SimoError> {
+        let (vset, high_range) = _mv_to_vset(millivolts)?;
+        self.simo.vrego_a().modify(|_, w| unsafe {
+            w.rangea().bit(high_range);
+            w.vseta().bits(vset)
+        });
+        Ok(())
+    }
+
+    /// Set the output voltage of VREGO_B in millivolts, rounded
+    /// down to a 10 mV step. See [`Simo::set_vrego_a()`].
+    pub fn set_vrego_b(&self, millivolts: u16) -> Result<(), SimoError> {
+        let (vset, high_range) = _mv_to_vset(millivolts)?;
+        self.simo.vrego_b().modify(|_, w| unsafe {
+            w.rangeb().bit(high_range);
+            w.vsetb().bits(vset)
+        });
+        Ok(())
+    }
+
+    /// Set the output voltage of VREGO_C (the core rail) in millivolts, rounded
+    /// down to a 10 mV step. See [`Simo::set_vrego_a()`].
+    pub fn set_vrego_c(&self, millivolts: u16) -> Result<(), SimoError> {
+        let (vset, high_range) = _mv_to_vset(millivolts)?;
+        self.simo.vrego_c().modify(|_, w| unsafe {
+            w.rangec().bit(high_range);
+            w.vsetc().bits(vset)
+        });
+        Ok(())
+    }
+
+    /// Set the output voltage of VREGO_D in millivolts, rounded
+    /// down to a 10 mV step. See [`Simo::set_vrego_a()`].
+    pub fn set_vrego_d(&self, millivolts: u16) -> Result<(), SimoError> {
+        let (vset, high_range) = _mv_to_vset(millivolts)?;
+        self.simo.vrego_d().modify(|_, w| unsafe {
+            w.ranged().bit(high_range);
+            w.vsetd().bits(vset)
+        });
+        Ok(())
+    }
+
+    /// Set the core voltage (VREGO_C) in millivolts, rounded down to a
+    /// 10 mV step like [`Simo::set_vrego_c()`], and wait for the regulator
+    /// to settle before returning.
+    ///
+    /// The core voltage must always be high enough for the current system
+    /// clock frequency, so the order of operations matters when scaling:
+    /// - Speeding up: raise the voltage first, then increase the clock.
+    /// - Slowing down: decrease the clock first, then lower the voltage.
+    ///
+    /// The ready bit cannot be cleared and still reflects the old voltage
+    /// right after a change, so when the setting changes this first waits
+    /// for the bit to drop, for at most [`SIMO_READY_TIMEOUT`] polls since
+    /// a small step may never drop it. Returns [`SimoError::Timeout`] if
+    /// the output is then not ready within [`SIMO_READY_TIMEOUT`] polls.
+    ///
+    /// Example:
+    /// ```
+    /// // Slow down, then drop the core rail
+    /// let clks = gcr.sys_clk.set_divider::<Div4>(&mut gcr.reg).freeze();
+    /// simo.set_core_voltage(900).unwrap();
+    /// ```
+    pub fn set_core_voltage(&mut self, millivolts: u16) -> Result<(), SimoError> {
+        let previous = self.simo.vrego_c().read().bits();
+        self.set_vrego_c(millivolts)?;
+        if self.simo.vrego_c().read().bits() != previous {
+            for _ in 0..SIMO_READY_TIMEOUT {
+                if !self.is_output_ready(SimoOutput::VregoC) {
+                    break;
+                }
+            }
+        }
+        for _ in 0..SIMO_READY_TIMEOUT {
+            if self.is_output_ready(SimoOutput::VregoC) {
+                return Ok(());
+            }
+        }
+        Err(SimoError::Timeout)
+    }
+
+    /// Get the output voltage VREGO_A is set to, in millivolts.
+    #[inline(always)]
+    pub fn vrego_a(&self) -> u16 {
+        let r = self.simo.vrego_a().read();
+        _vset_to_mv(r.vseta().bits(), r.rangea().bit())
+    }
+
+    /// Get the output voltage VREGO_B is set to, in millivolts.
+    #[inline(always)]
+    pub fn vrego_b(&self) -> u16 {
+        let r = self.simo.vrego_b().read();
+        _vset_to_mv(r.vsetb().bits(), r.rangeb().bit())
+    }
+
+    /// Get the output voltage VREGO_C is set to, in millivolts.
+    #[inline(always)]
+    pub fn vrego_c(&self) -> u16 {
+        let r = self.simo.vrego_c().read();
+        _vset_to_mv(r.vsetc().bits(), r.rangec().bit())
+    }
+
+    /// Get the output voltage VREGO_D is set to, in millivolts.
+    #[inline(always)]
+    pub fn vrego_d(&self) -> u16 {
+        let r = self.simo.vrego_d().read();
+        _vset_to_mv(r.vsetd().bits(), r.ranged().bit())
+    }
+
+    /// Returns [`true`] if an output has reached its regulated voltage.
+    #[inline(always)]
+    pub fn is_output_ready(&self, output: SimoOutput) -> bool {
+        let r = self.simo.buck_out_ready().read();
+        match output {
+            SimoOutput::VregoA => r.buckoutrdya().is_rdy(),
+            SimoOutput::VregoB => r.buckoutrdyb().is_rdy(),
+            SimoOutput::VregoC => r.buckoutrdyc().is_rdy(),
+            SimoOutput::VregoD => r.buckoutrdyd().is_rdy(),
+        }
+    }
+
+    /// Returns [`true`] if all outputs have reached their regulated voltages.
+    #[inline(always)]
+    pub fn is_ready(&self) -> bool {
+        let r = self.simo.buck_out_ready().read();
+        r.buckoutrdya().is_rdy()
+            && r.buckoutrdyb().is_rdy()
+            && r.buckoutrdyc().is_rdy()
+            && r.buckoutrdyd().is_rdy()
+    }
+
+    /// Set the high side FET peak current setting (0 to 15) of an output.
+    pub fn set_peak_current(&self, output: SimoOutput, setting: u8) -> Result<(), SimoError> {
+        if setting > IPKSET_MAX {
+            return Err(SimoError::PeakCurrentOutOfRange);
+        }
+        match output {
+            SimoOutput::VregoA => self.simo.ipka().modify(|_, w| unsafe { w.ipkseta().bits(setting) }),
+            SimoOutput::VregoB => self.simo.ipka().modify(|_, w| unsafe { w.ipksetb().bits(setting) }),
+            SimoOutput::VregoC => self.simo.ipkb().modify(|_, w| unsafe { w.ipksetc().bits(setting) }),
+            SimoOutput::VregoD => self.simo.ipkb().modify(|_, w| unsafe { w.ipksetd().bits(setting) }),
+        };
+        Ok(())
+    }
+}
+
+/// Converts a voltage in millivolts into a `vset` code and range bit.
+///
+/// The low range covers 500 mV to 1770 mV and the high range covers 600 mV
+/// to 1870 mV, both in 10 mV steps. The low range is preferred when both
+/// can represent the voltage. Voltages between steps are rounded down.
+/// This is the inverse of [`_vset_to_mv`].
+#[doc(hidden)]
+#[inline(always)]
+fn _mv_to_vset(millivolts: u16) -> Result<(u8, bool), SimoError> {
+    let low_range_max = VSET_LOW_RANGE_BASE_MV + VSET_STEP_MV * VSET_MAX as u16;
+    if !(VREGO_MIN_MV..=VREGO_MAX_MV).contains(&millivolts) {
+        Err(SimoError::VoltageOutOfRange)
+    } else if millivolts <= low_range_max {
+        Ok((((millivolts - VSET_LOW_RANGE_BASE_MV) / VSET_STEP_MV) as u8, false))
+    } else {
+        Ok((((millivolts - VSET_HIGH_RANGE_BASE_MV) / VSET_STEP_MV) as u8, true))
+    }
+}
+
+/// Converts a `vset` code and range bit into a voltage in millivolts.
+#[doc(hidden)]
+#[inline(always)]
+fn _vset_to_mv(vset: u8, high_range: bool) -> u16 {
+    let base = if high_range { VSET_HIGH_RANGE_BASE_MV } else { VSET_LOW_RANGE_BASE_MV };
+    base + VSET_STEP_MV * vset as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mv_to_vset_bounds() {
+        assert_eq!(_mv_to_vset(VREGO_MIN_MV), Ok((0, false)));
+        assert_eq!(_mv_to_vset(VREGO_MAX_MV), Ok((VSET_MAX, true)));
+        assert_eq!(_mv_to_vset(VREGO_MIN_MV - 1), Err(SimoError::VoltageOutOfRange));
+        assert_eq!(_mv_to_vset(VREGO_MAX_MV + 1), Err(SimoError::VoltageOutOfRange));
+        assert_eq!(_mv_to_vset(0), Err(SimoError::VoltageOutOfRange));
+    }
+
+    #[test]
+    fn mv_to_vset_prefers_low_range() {
+        // Top of the low range, and the first voltage only the high range reaches
+        assert_eq!(_mv_to_vset(1770), Ok((VSET_MAX, false)));
+        assert_eq!(_mv_to_vset(1780), Ok((118, true)));
+        assert_eq!(_mv_to_vset(1100), Ok((60, false)));
+    }
+
+    #[test]
+    fn mv_to_vset_rounds_down() {
+        assert_eq!(_mv_to_vset(509), Ok((0, false)));
+        assert_eq!(_mv_to_vset(1105), Ok((60, false)));
+        assert_eq!(_mv_to_vset(1815), Ok((121, true)));
+        // Above the low range, but rounds down to a voltage it also covers
+        assert_eq!(_mv_to_vset(1779), Ok((117, true)));
+        assert_eq!(_vset_to_mv(117, true), 1770);
+        assert_eq!(_mv_to_vset(1869), Ok((126, true)));
+    }
+
+    #[test]
+    fn vset_round_trip() {
+        for vset in 0..=VSET_MAX {
+            for high_range in [false, true] {
+                let mv = _vset_to_mv(vset, high_range);
+                let (code, range) = _mv_to_vset(mv).unwrap();
+                // The low range is preferred, but the voltage must be the same
+                assert_eq!(_vset_to_mv(code, range), mv);
+            }
+        }
+        assert_eq!(_vset_to_mv(0, false), VREGO_MIN_MV);
+        assert_eq!(_vset_to_mv(VSET_MAX, true), VREGO_MAX_MV);
     }
 }
@@ -0,0 +1,1326 @@
+//! # Serial Peripheral Interface (SPI)
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+use crate::dma::{Channel, Request, TransferConfig, TransferWidth};
+
+use crate::gcr::{
+    clocks::{Clock, PeripheralClock},
+    ClockForPeripheral, ResetForPeripheral,
+};
+use crate::gpio::{Af1, Af2, Pin};
+use crate::wait::{Spin, WaitStrategy};
+pub use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+use embedded_hal_nb::nb;
+use paste::paste;
+
+/// Depth of the TX and RX FIFOs, in bytes.
+const FIFO_DEPTH: usize = 32;
+/// Largest value of the combined high and low SCK periods (8-bit fields).
+const MAX_CLK_PERIODS: u32 = 510;
+/// Largest usable exponent of the SCK prescaler.
+const MAX_CLK_SCALE: u32 = 8;
+/// Byte clocked out while only reading.
+const READ_FILL_BYTE: u8 = 0xff;
+/// Largest number of frames in a single DMA transaction (16-bit counters).
+const MAX_DMA_FRAMES: usize = u16::MAX as usize;
+/// TX FIFO level below which the TX DMA channel is requested.
+const DMA_TX_THRESHOLD: u8 = 16;
+/// Smallest and largest supported frame widths, in bits.
+const MIN_FRAME_BITS: u8 = 2;
+const MAX_FRAME_BITS: u8 = 16;
+
+/// Errors that can occur while configuring or using an SPI peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpiError {
+    /// The requested SCK frequency is outside of the range that can be
+    /// generated from the peripheral clock.
+    UnachievableFrequency,
+    /// The requested frame width is outside of 2 to 16 bits.
+    InvalidFrameWidth,
+    /// The word type does not match the configured frame width: `u8` words
+    /// need frames of at most 8 bits, and `u16` words need wider frames.
+    FrameWidthMismatch,
+    /// The buffers of a DMA transfer are longer than 65535 frames.
+    TransferTooLong,
+    /// The read and write buffers of a DMA transfer differ in length.
+    LengthMismatch,
+    /// DMA transfers cannot reverse the bit order in software, so they
+    /// require [`BitOrder::MsbFirst`].
+    UnsupportedBitOrder,
+    /// A DMA channel was stopped by a bus error.
+    DmaBusError,
+}
+
+impl embedded_hal::spi::Error for SpiError {
+    fn kind(&self) -> embedded_hal::spi::ErrorKind {
+        embedded_hal::spi::ErrorKind::Other
+    }
+}
+
+/// Order in which the bits of each word are shifted out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BitOrder {
+    /// Most significant bit first.
+    MsbFirst,
+    /// Least significant bit first. The SPI peripheral only shifts MSB
+    /// first, so words are bit-reversed in software.
+    LsbFirst,
+}
+
+/// Configuration of an SPI peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpiConfig {
+    /// Clock polarity and phase.
+    pub mode: Mode,
+    /// Bit order of each word.
+    pub bit_order: BitOrder,
+    /// Requested SCK frequency in Hz. The closest achievable frequency is
+    /// used, see [`Spi::frequency()`].
+    pub frequency: u32,
+}
+
+impl Default for SpiConfig {
+    /// Mode 0, MSB first, 1 MHz.
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            bit_order: BitOrder::MsbFirst,
+            frequency: 1_000_000,
+        }
+    }
+}
+
+/// Active level of a hardware slave select line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SsPolarity {
+    /// The slave select line is driven low while selected.
+    ActiveLow,
+    /// The slave select line is driven high while selected.
+    ActiveHigh,
+}
+
+/// Configuration of a hardware slave select line.
+///
+/// Delays are counted in system clock cycles, from 1 to 256 (`0` is
+/// treated as 256 by the hardware).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SsConfig {
+    /// Active level of the slave select line.
+    pub polarity: SsPolarity,
+    /// Delay between asserting SS and the first SCK edge.
+    pub setup: u8,
+    /// Delay between the last SCK edge and deasserting SS.
+    pub hold: u8,
+    /// Minimum time SS stays deasserted between transactions.
+    pub inactive: u8,
+}
+
+impl Default for SsConfig {
+    /// Active low, with the shortest delays.
+    fn default() -> Self {
+        Self {
+            polarity: SsPolarity::ActiveLow,
+            setup: 1,
+            hold: 1,
+            inactive: 1,
+        }
+    }
+}
+
+/// # Serial Peripheral Interface (SPI) Master
+///
+/// Implements [`embedded_hal::spi::SpiBus`], so stock drivers for SD cards,
+/// flash chips and displays can be used with a separate chip select pin.
+///
+/// Example:
+/// ```
+/// let pins = hal::gpio::Gpio0::new(p.gpio0, &mut gcr.reg).split();
+/// let mut spi = hal::spi::Spi::spi0(
+///     p.spi0,                 // SPI peripheral from the PAC
+///     &mut gcr.reg,           // GCR instance
+///     &clks.pclk,             // Peripheral clock
+///     pins.p0_7.into_af1(),   // SCK pin
+///     pins.p0_5.into_af1(),   // MOSI pin
+///     pins.p0_6.into_af1(),   // MISO pin
+///     hal::spi::SpiConfig {
+///         mode: hal::spi::MODE_0,
+///         bit_order: hal::spi::BitOrder::MsbFirst,
+///         frequency: 10_000_000,
+///     },
+/// )
+/// .unwrap();
+/// // The divider is rounded, so the actual frequency may differ
+/// let sck_hz = spi.frequency();
+///
+/// // Read a JEDEC ID, toggling chip select around the transfer
+/// let mut buf = [0x9f, 0, 0, 0];
+/// cs.set_low();
+/// spi.transfer_in_place(&mut buf).unwrap();
+/// cs.set_high();
+/// ```
+pub struct Spi<SPI, SCK, MOSI, MISO> {
+    spi: SPI,
+    _sck_pin: SCK,
+    _mosi_pin: MOSI,
+    _miso_pin: MISO,
+    clk_freq: u32,
+    frequency: u32,
+    bit_order: BitOrder,
+    frame_bits: u8,
+}
+
+/// # SPI Device with Hardware Slave Select
+///
+/// Wraps an [`Spi`] master and one of its hardware slave select lines,
+/// created with [`Spi::into_device()`]. Implements
+/// [`embedded_hal::spi::SpiDevice`]: SS is asserted for the whole of each
+/// [`transaction()`](embedded_hal::spi::SpiDevice::transaction) and
+/// deasserted afterwards.
+///
+/// Example:
+/// ```
+/// use embedded_hal::spi::{Operation, SpiDevice};
+///
+/// let mut flash = spi.into_device(pins.p0_4.into_af1(), hal::spi::SsConfig::default());
+/// // SS stays asserted across both writes: a single page program command
+/// flash.transaction(&mut [
+///     Operation::Write(&[0x02, 0x00, 0x10, 0x00]),
+///     Operation::Write(&page),
+/// ]).unwrap();
+/// // SS is toggled between separate calls
+/// flash.write(&[0x06]).unwrap();
+/// flash.write(&[0x04]).unwrap();
+/// ```
+pub struct SpiSsDevice<SPI, SCK, MOSI, MISO, SS> {
+    bus: Spi<SPI, SCK, MOSI, MISO>,
+    _ss_pin: SS,
+}
+
+/// Configuration of an SPI slave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpiSlaveConfig {
+    /// Clock polarity and phase, which must match the master.
+    pub mode: Mode,
+    /// Bit order of each word.
+    pub bit_order: BitOrder,
+    /// Active level of the slave select input.
+    pub ss_polarity: SsPolarity,
+}
+
+impl Default for SpiSlaveConfig {
+    /// Mode 0, MSB first, active low slave select.
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            bit_order: BitOrder::MsbFirst,
+            ss_polarity: SsPolarity::ActiveLow,
+        }
+    }
+}
+
+/// Event reported by [`SpiSlave::poll()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpiSlaveEvent {
+    /// The master asserted slave select. The response to the transaction
+    /// should already be queued with [`SpiSlave::write()`].
+    Selected,
+    /// The master deasserted slave select and the transaction ended. Bytes
+    /// received before stay in the RX FIFO until they are read.
+    Deselected,
+}
+
+/// # Serial Peripheral Interface (SPI) Slave
+///
+/// Responds to an external SPI master, using a polled interface. The
+/// master drives SCK, so the application has to keep up with it: bytes
+/// must be queued in the TX FIFO before the master clocks them out, and
+/// received bytes must be read before the RX FIFO fills up. Both FIFOs are
+/// 32 bytes deep.
+///
+/// If the master clocks a byte while the TX FIFO is empty, the byte sent is
+/// undefined and the underrun flag is set, see [`SpiSlave::is_underrun()`].
+/// Bytes received while the RX FIFO is full are lost and set the overrun
+/// flag, see [`SpiSlave::is_overrun()`].
+///
+/// Only the first slave select line (SS0) can be used as the slave select
+/// input.
+///
+/// Example:
+/// ```
+/// use hal::spi::{SpiSlave, SpiSlaveConfig, SpiSlaveEvent};
+///
+/// let mut slave = SpiSlave::spi1(
+///     p.spi1,
+///     &mut gcr.reg,
+///     pins.p0_23.into_af1(),  // SCK pin
+///     pins.p0_21.into_af1(),  // MOSI pin
+///     pins.p0_22.into_af1(),  // MISO pin
+///     pins.p0_20.into_af1(),  // SS pin
+///     SpiSlaveConfig::default(),
+/// );
+///
+/// let mut command = [0u8; 32];
+/// let mut len = 0;
+/// slave.write(&status);
+/// loop {
+///     len += slave.read(&mut command[len..]);
+///     if let Ok(SpiSlaveEvent::Deselected) = slave.poll() {
+///         len += slave.read(&mut command[len..]);
+///         if slave.is_underrun() {
+///             // The master read more than was queued
+///             slave.clear_underrun();
+///         }
+///         handle(&command[..len]);
+///         len = 0;
+///         // Queue the response to the next transaction
+///         slave.flush_fifos();
+///         slave.write(&status);
+///     }
+/// }
+/// ```
+pub struct SpiSlave<SPI, SCK, MOSI, MISO, SS> {
+    spi: SPI,
+    _sck_pin: SCK,
+    _mosi_pin: MOSI,
+    _miso_pin: MISO,
+    _ss_pin: SS,
+    bit_order: BitOrder,
+}
+
+/// Pins that can be used as the serial clock output of an SPI peripheral.
+pub trait SckPin<SPI>: crate::Sealed {}
+/// Pins that can be used as the data output of an SPI master.
+pub trait MosiPin<SPI>: crate::Sealed {}
+/// Pins that can be used as the data input of an SPI master.
+pub trait MisoPin<SPI>: crate::Sealed {}
+/// Pins that can be used as a hardware slave select output of an SPI master.
+pub trait SsPin<SPI>: crate::Sealed {
+    #[doc(hidden)]
+    /// Index of the slave select line driven by the pin.
+    const INDEX: u8;
+}
+
+#[doc(hidden)]
+pub mod marker {
+    /// DMA request lines of an SPI peripheral.
+    pub trait SpiDma: crate::Sealed {
+        const TX_REQUEST: super::Request;
+        const RX_REQUEST: super::Request;
+    }
+}
+
+// All SPI peripherals are derived from the same register block
+type SpiRegisterBlock = crate::pac::spi0::RegisterBlock;
+
+macro_rules! spi {
+    (
+        $spi:ident,
+        sck: $sck_pin:ty,
+        mosi: $mosi_pin:ty,
+        miso: $miso_pin:ty,
+        ss: [0: $ss0_pin:ty $(, $ss_index:literal: $ss_pin:ty)* $(,)?],
+        dma: ($tx_request:ident, $rx_request:ident),
+    ) => {
+        paste! {
+            use crate::pac::$spi;
+
+            impl crate::Sealed for $spi {}
+            impl marker::SpiDma for $spi {
+                const TX_REQUEST: Request = Request::$tx_request;
+                const RX_REQUEST: Request = Request::$rx_request;
+            }
+
+            impl crate::Sealed for $sck_pin {}
+            impl SckPin<$spi> for $sck_pin {}
+
+            impl crate::Sealed for $mosi_pin {}
+            impl MosiPin<$spi> for $mosi_pin {}
+
+            impl crate::Sealed for $miso_pin {}
+            impl MisoPin<$spi> for $miso_pin {}
+            impl crate::Sealed for $ss0_pin {}
+            impl SsPin<$spi> for $ss0_pin {
+                const INDEX: u8 = 0;
+            }
+            $(
+                impl crate::Sealed for $ss_pin {}
+                impl SsPin<$spi> for $ss_pin {
+                    const INDEX: u8 = $ss_index;
+                }
+            )*
+
+            impl Spi<$spi, $sck_pin, $mosi_pin, $miso_pin> {
+                #[doc = "Construct and configure a new "]
+                #[doc = stringify!([<$spi:upper>])]
+                #[doc = " master."]
+                ///
+                /// Returns [`SpiError::UnachievableFrequency`] if the
+                /// requested frequency cannot be generated from the clock.
+                pub fn [<$spi:lower>](
+                    spi: $spi,
+                    reg: &mut crate::gcr::GcrRegisters,
+                    clock: &Clock<PeripheralClock>,
+                    sck_pin: $sck_pin,
+                    mosi_pin: $mosi_pin,
+                    miso_pin: $miso_pin,
+                    config: SpiConfig,
+                ) -> Result<Self, SpiError> {
+                    let (scale, hi, lo) = _clock_divider(clock.frequency, config.frequency)?;
+                    unsafe {
+                        spi.reset(&mut reg.gcr);
+                        spi.enable_clock(&mut reg.gcr);
+                    }
+                    let spi = Spi {
+                        spi,
+                        _sck_pin: sck_pin,
+                        _mosi_pin: mosi_pin,
+                        _miso_pin: miso_pin,
+                        clk_freq: clock.frequency,
+                        frequency: clock.frequency / ((hi + lo) << scale),
+                        bit_order: config.bit_order,
+                        frame_bits: 8,
+                    };
+                    spi._configure(config.mode, scale, hi, lo);
+                    Ok(spi)
+                }
+            }
+
+            impl SpiSlave<$spi, $sck_pin, $mosi_pin, $miso_pin, $ss0_pin> {
+                #[doc = "Construct and configure a new "]
+                #[doc = stringify!([<$spi:upper>])]
+                #[doc = " slave, selected by SS0."]
+                pub fn [<$spi:lower>](
+                    spi: $spi,
+                    reg: &mut crate::gcr::GcrRegisters,
+                    sck_pin: $sck_pin,
+                    mosi_pin: $mosi_pin,
+                    miso_pin: $miso_pin,
+                    ss_pin: $ss0_pin,
+                    config: SpiSlaveConfig,
+                ) -> Self {
+                    unsafe {
+                        spi.reset(&mut reg.gcr);
+                        spi.enable_clock(&mut reg.gcr);
+                    }
+                    let slave = SpiSlave {
+                        spi,
+                        _sck_pin: sck_pin,
+                        _mosi_pin: mosi_pin,
+                        _miso_pin: miso_pin,
+                        _ss_pin: ss_pin,
+                        bit_order: config.bit_order,
+                    };
+                    slave._configure(config.mode, config.ss_polarity);
+                    slave
+                }
+
+                /// Returns the interrupt line of the peripheral, to unmask
+                /// in the NVIC after enabling an interrupt with
+                /// [`Self::enable_interrupt()`].
+                #[inline(always)]
+                pub const fn interrupt(&self) -> crate::Interrupt {
+                    crate::Interrupt::[<$spi:upper>]
+                }
+            }
+        }
+    };
+}
+
+spi! {Spi0,
+    sck: Pin<0, 7, Af1>,
+    mosi: Pin<0, 5, Af1>,
+    miso: Pin<0, 6, Af1>,
+    ss: [0: Pin<0, 4, Af1>, 1: Pin<0, 11, Af2>, 2: Pin<0, 10, Af2>],
+    dma: (Spi0tx, Spi0rx),
+}
+
+spi! {Spi1,
+    sck: Pin<0, 23, Af1>,
+    mosi: Pin<0, 21, Af1>,
+    miso: Pin<0, 22, Af1>,
+    ss: [0: Pin<0, 20, Af1>],
+    dma: (Spi1tx, Spi1rx),
+}
+
+impl<SPI, SCK, MOSI, MISO> Spi<SPI, SCK, MOSI, MISO>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _configure(&self, mode: Mode, scale: u32, hi: u32, lo: u32) {
+        self.spi.ctrl0().write(|w| {
+            w.mst_mode().en();
+            w.ss_io().output();
+            w.en().en()
+        });
+        self.spi.ctrl2().write(|w| {
+            match mode.polarity {
+                Polarity::IdleLow => w.clkpol().normal(),
+                Polarity::IdleHigh => w.clkpol().inverted(),
+            };
+            match mode.phase {
+                Phase::CaptureOnFirstTransition => w.clkpha().rising_edge(),
+                Phase::CaptureOnSecondTransition => w.clkpha().falling_edge(),
+            };
+            w.numbits()._8();
+            w.data_width().mono();
+            w.three_wire().dis()
+        });
+        self.spi.clkctrl().write(|w| unsafe {
+            w.clkdiv().bits(scale as u8);
+            w.hi().bits(hi as u8);
+            w.lo().bits(lo as u8)
+        });
+        self.spi.dma().write(|w| {
+            w.tx_fifo_en().en();
+            w.tx_flush().clear();
+            w.rx_fifo_en().en();
+            w.rx_flush().clear()
+        });
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _is_busy(&self) -> bool {
+        self.spi.stat().read().busy().bit_is_set()
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _order(&self, byte: u8) -> u8 {
+        _reorder_u8(byte, self.bit_order, self.frame_bits)
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _order_u16(&self, word: u16) -> u16 {
+        _reorder_u16(word, self.bit_order, self.frame_bits)
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_u8_frames(&self) -> Result<(), SpiError> {
+        if self.frame_bits > 8 {
+            return Err(SpiError::FrameWidthMismatch);
+        }
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _check_u16_frames(&self) -> Result<(), SpiError> {
+        if self.frame_bits <= 8 {
+            return Err(SpiError::FrameWidthMismatch);
+        }
+        Ok(())
+    }
+
+    /// Runs a single transaction of at most [`FIFO_DEPTH`] bytes. Bytes
+    /// missing from `write` are sent as [`READ_FILL_BYTE`] and bytes beyond
+    /// `read` are discarded. If `keep_ss` is set, the slave select line
+    /// stays asserted after the transaction.
+    #[doc(hidden)]
+    fn _transfer_chunk(&mut self, read: &mut [u8], write: &[u8], keep_ss: bool) {
+        let len = read.len().max(write.len());
+        debug_assert!(len <= FIFO_DEPTH);
+        self.spi.dma().modify(|_, w| {
+            w.tx_flush().clear();
+            w.rx_flush().clear()
+        });
+        self.spi.intfl().write(|w| w.mst_done().clear());
+        self.spi.ctrl1().write(|w| unsafe {
+            w.tx_num_char().bits(len as u16);
+            w.rx_num_char().bits(len as u16)
+        });
+        for i in 0..len {
+            let byte = write.get(i).copied().unwrap_or(READ_FILL_BYTE);
+            self.spi
+                .fifo8(0)
+                .write(|w| unsafe { w.data().bits(self._order(byte)) });
+        }
+        self.spi.ctrl0().modify(|_, w| {
+            w.ss_ctrl().bit(keep_ss);
+            w.start().start()
+        });
+        while self.spi.intfl().read().mst_done().bit_is_clear() {}
+        for i in 0..len {
+            let byte = self._order(self.spi.fifo8(0).read().data().bits());
+            if let Some(slot) = read.get_mut(i) {
+                *slot = byte;
+            }
+        }
+    }
+
+    /// Runs a single transaction of at most half of [`FIFO_DEPTH`] frames
+    /// wider than 8 bits. Each frame occupies two bytes of the FIFO.
+    #[doc(hidden)]
+    fn _transfer_chunk_u16(&mut self, words: &mut [u16], keep_ss: bool) {
+        let len = words.len();
+        let mask = u16::MAX >> (16 - self.frame_bits);
+        debug_assert!(len <= FIFO_DEPTH / 2);
+        self.spi.dma().modify(|_, w| {
+            w.tx_flush().clear();
+            w.rx_flush().clear()
+        });
+        self.spi.intfl().write(|w| w.mst_done().clear());
+        self.spi.ctrl1().write(|w| unsafe {
+            w.tx_num_char().bits(len as u16);
+            w.rx_num_char().bits(len as u16)
+        });
+        for word in words.iter() {
+            let word = self._order_u16(*word & mask);
+            self.spi.fifo16(0).write(|w| unsafe { w.data().bits(word) });
+        }
+        self.spi.ctrl0().modify(|_, w| {
+            w.ss_ctrl().bit(keep_ss);
+            w.start().start()
+        });
+        while self.spi.intfl().read().mst_done().bit_is_clear() {}
+        for word in words.iter_mut() {
+            *word = self._order_u16(self.spi.fifo16(0).read().data().bits()) & mask;
+        }
+    }
+
+    #[doc(hidden)]
+    fn _read(&mut self, words: &mut [u8], keep_ss: bool) {
+        let chunks = words.len().div_ceil(FIFO_DEPTH);
+        for (i, chunk) in words.chunks_mut(FIFO_DEPTH).enumerate() {
+            let len = chunk.len();
+            let keep = keep_ss || i + 1 < chunks;
+            self._transfer_chunk(chunk, &[READ_FILL_BYTE; FIFO_DEPTH][..len], keep);
+        }
+    }
+
+    #[doc(hidden)]
+    fn _write(&mut self, words: &[u8], keep_ss: bool) {
+        let chunks = words.len().div_ceil(FIFO_DEPTH);
+        for (i, chunk) in words.chunks(FIFO_DEPTH).enumerate() {
+            self._transfer_chunk(&mut [], chunk, keep_ss || i + 1 < chunks);
+        }
+    }
+
+    #[doc(hidden)]
+    fn _transfer(&mut self, read: &mut [u8], write: &[u8], keep_ss: bool) {
+        let (read_len, write_len) = (read.len(), write.len());
+        let len = read_len.max(write_len);
+        for start in (0..len).step_by(FIFO_DEPTH) {
+            let end = (start + FIFO_DEPTH).min(len);
+            let read_chunk = &mut read[start.min(read_len)..end.min(read_len)];
+            let write_chunk = &write[start.min(write_len)..end.min(write_len)];
+            // Keep both chunks aligned to the same transaction
+            let mut buf = [0u8; FIFO_DEPTH];
+            buf[..write_chunk.len()].copy_from_slice(write_chunk);
+            buf[write_chunk.len()..end - start].fill(READ_FILL_BYTE);
+            self._transfer_chunk(read_chunk, &buf[..end - start], keep_ss || end < len);
+        }
+    }
+
+    #[doc(hidden)]
+    fn _transfer_in_place(&mut self, words: &mut [u8], keep_ss: bool) {
+        let chunks = words.len().div_ceil(FIFO_DEPTH);
+        for (i, chunk) in words.chunks_mut(FIFO_DEPTH).enumerate() {
+            let mut buf = [0u8; FIFO_DEPTH];
+            let len = chunk.len();
+            buf[..len].copy_from_slice(chunk);
+            self._transfer_chunk(chunk, &buf[..len], keep_ss || i + 1 < chunks);
+        }
+    }
+
+    /// Returns the actual SCK frequency in Hz.
+    #[inline(always)]
+    pub fn frequency(&self) -> u32 {
+        self.frequency
+    }
+
+    /// Change the SCK frequency, returning the actual frequency in Hz. Waits
+    /// for any ongoing transaction to finish first.
+    ///
+    /// Returns [`SpiError::UnachievableFrequency`] if the requested
+    /// frequency cannot be generated from the clock, leaving the current
+    /// frequency unchanged.
+    pub fn set_frequency(&mut self, frequency: u32) -> Result<u32, SpiError> {
+        let (scale, hi, lo) = _clock_divider(self.clk_freq, frequency)?;
+        while self._is_busy() {}
+        self.spi.clkctrl().write(|w| unsafe {
+            w.clkdiv().bits(scale as u8);
+            w.hi().bits(hi as u8);
+            w.lo().bits(lo as u8)
+        });
+        self.frequency = self.clk_freq / ((hi + lo) << scale);
+        Ok(self.frequency)
+    }
+
+    /// Returns the number of bits in each frame.
+    #[inline(always)]
+    pub fn frame_bits(&self) -> u8 {
+        self.frame_bits
+    }
+
+    /// Set the number of bits in each frame (2 to 16).
+    ///
+    /// Frames of up to 8 bits are transferred with the `u8` methods and
+    /// [`embedded_hal::spi::SpiBus`], wider frames with
+    /// [`Spi::transfer_u16()`] and [`Spi::write_u16()`].
+    ///
+    /// Default: `8`
+    ///
+    /// Example (12-bit round trip with MOSI wired to MISO):
+    /// ```
+    /// spi.set_frame_bits(12).unwrap();
+    /// let mut words = [0x0abc, 0x0123, 0x0fff];
+    /// spi.transfer_u16(&mut words).unwrap();
+    /// assert_eq!(words, [0x0abc, 0x0123, 0x0fff]);
+    /// // Frames wider than 16 bits are rejected
+    /// assert_eq!(spi.set_frame_bits(17), Err(hal::spi::SpiError::InvalidFrameWidth));
+    /// ```
+    pub fn set_frame_bits(&mut self, bits: u8) -> Result<(), SpiError> {
+        if !(MIN_FRAME_BITS..=MAX_FRAME_BITS).contains(&bits) {
+            return Err(SpiError::InvalidFrameWidth);
+        }
+        // A value of 0 selects 16 bits
+        self.spi
+            .ctrl2()
+            .modify(|_, w| w.numbits().set(bits & 0x0f));
+        self.frame_bits = bits;
+        Ok(())
+    }
+
+    /// Sends the frames in `words` and replaces them with the frames
+    /// received at the same time. Only bits below the frame width are used.
+    ///
+    /// Returns [`SpiError::FrameWidthMismatch`] unless the frame width is
+    /// above 8 bits.
+    pub fn transfer_u16(&mut self, words: &mut [u16]) -> Result<(), SpiError> {
+        self._check_u16_frames()?;
+        let chunks = words.len().div_ceil(FIFO_DEPTH / 2);
+        for (i, chunk) in words.chunks_mut(FIFO_DEPTH / 2).enumerate() {
+            self._transfer_chunk_u16(chunk, i + 1 < chunks);
+        }
+        Ok(())
+    }
+
+    /// Sends the frames in `words`, discarding the received frames.
+    ///
+    /// Returns [`SpiError::FrameWidthMismatch`] unless the frame width is
+    /// above 8 bits.
+    pub fn write_u16(&mut self, words: &[u16]) -> Result<(), SpiError> {
+        self._check_u16_frames()?;
+        let chunks = words.len().div_ceil(FIFO_DEPTH / 2);
+        for (i, chunk) in words.chunks(FIFO_DEPTH / 2).enumerate() {
+            let mut buf = [0u16; FIFO_DEPTH / 2];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            self._transfer_chunk_u16(&mut buf[..chunk.len()], i + 1 < chunks);
+        }
+        Ok(())
+    }
+
+    /// Selects a hardware slave select line and wraps the master into an
+    /// [`SpiSsDevice`] that drives it around each transaction.
+    pub fn into_device<SS: SsPin<SPI>>(
+        self,
+        ss_pin: SS,
+        config: SsConfig,
+    ) -> SpiSsDevice<SPI, SCK, MOSI, MISO, SS> {
+        let mask = 1 << SS::INDEX;
+        self.spi.ctrl2().modify(|r, w| unsafe {
+            let pol = match config.polarity {
+                SsPolarity::ActiveLow => r.ss_pol().bits() & !mask,
+                SsPolarity::ActiveHigh => r.ss_pol().bits() | mask,
+            };
+            w.ss_pol().bits(pol)
+        });
+        self.spi.sstime().write(|w| unsafe {
+            w.pre().bits(config.setup);
+            w.post().bits(config.hold);
+            w.inact().bits(config.inactive)
+        });
+        self.spi.ctrl0().modify(|_, w| unsafe { w.ss_active().bits(mask) });
+        SpiSsDevice {
+            bus: self,
+            _ss_pin: ss_pin,
+        }
+    }
+}
+
+/// # DMA Methods
+/// These methods move whole buffers through the FIFOs with DMA channels
+/// instead of the CPU. Only 8-bit frames in [`BitOrder::MsbFirst`] order
+/// are supported.
+impl<SPI, SCK, MOSI, MISO> Spi<SPI, SCK, MOSI, MISO>
+where
+    SPI: Deref<Target = SpiRegisterBlock> + marker::SpiDma,
+{
+    #[doc(hidden)]
+    fn _check_dma(&self, len: usize) -> Result<(), SpiError> {
+        self._check_u8_frames()?;
+        if self.bit_order != BitOrder::MsbFirst {
+            return Err(SpiError::UnsupportedBitOrder);
+        }
+        if len > MAX_DMA_FRAMES {
+            return Err(SpiError::TransferTooLong);
+        }
+        Ok(())
+    }
+
+    /// Prepares a transaction of `len` frames to be fed by DMA. The RX FIFO
+    /// is disabled for transmit-only transactions so it cannot overflow.
+    #[doc(hidden)]
+    fn _setup_dma(&mut self, len: usize, receive: bool) {
+        self.spi.intfl().write(|w| w.mst_done().clear());
+        self.spi.ctrl1().write(|w| unsafe {
+            w.tx_num_char().bits(len as u16);
+            w.rx_num_char().bits(if receive { len as u16 } else { 0 })
+        });
+        self.spi.dma().write(|w| unsafe {
+            w.tx_thd_val().bits(DMA_TX_THRESHOLD);
+            w.tx_fifo_en().en();
+            w.tx_flush().clear();
+            // Request the RX channel for every received frame
+            w.rx_thd_val().bits(0);
+            w.rx_fifo_en().bit(receive);
+            w.rx_flush().clear()
+        });
+    }
+
+    #[doc(hidden)]
+    fn _start_dma_tx<const TX: usize>(&mut self, tx: &mut Channel<TX>, words: &[u8]) {
+        let fifo = self.spi.fifo8(0).as_ptr() as u32;
+        // Safety: The callers require the transfer, which borrows the
+        // buffer, to be dropped before the buffer is released
+        unsafe {
+            tx.start(&TransferConfig {
+                request: SPI::TX_REQUEST,
+                src: words.as_ptr() as u32,
+                src_width: TransferWidth::Byte,
+                src_increment: true,
+                dst: fifo,
+                dst_width: TransferWidth::Byte,
+                dst_increment: false,
+                len: words.len() as u32,
+                burst_size: DMA_TX_THRESHOLD,
+            });
+        }
+        self.spi.dma().modify(|_, w| w.dma_tx_en().en());
+        self.spi.ctrl0().modify(|_, w| {
+            w.ss_ctrl().deassert();
+            w.start().start()
+        });
+    }
+
+    /// Writes a buffer using a single DMA channel, discarding everything
+    /// received. No RX buffer or channel is needed.
+    ///
+    /// The returned [`SpiDmaTransfer`] can be polled for completion.
+    ///
+    /// # Safety
+    /// The DMA channel keeps reading `words` until the transfer is dropped,
+    /// so the transfer must not be leaked, for example with
+    /// [`core::mem::forget()`]. The borrow of `words` would end while the
+    /// channel still reads from it.
+    ///
+    /// Example:
+    /// ```
+    /// let mut channels = hal::dma::Dma::new(p.dma, &mut gcr.reg).split();
+    /// // Push a whole framebuffer to a display
+    /// let transfer = unsafe { spi.write_dma(&mut channels.ch0, &framebuffer) }.unwrap();
+    /// // Render the next frame while this one is sent
+    /// transfer.wait().unwrap();
+    /// ```
+    pub unsafe fn write_dma<'a, const TX: usize>(
+        &'a mut self,
+        tx: &'a mut Channel<TX>,
+        words: &'a [u8],
+    ) -> Result<SpiDmaTransfer<'a, SPI, SCK, MOSI, MISO, TX, TX>, SpiError> {
+        self._check_dma(words.len())?;
+        if !words.is_empty() {
+            self._setup_dma(words.len(), false);
+            self._start_dma_tx(tx, words);
+        }
+        Ok(SpiDmaTransfer {
+            spi: self,
+            tx,
+            rx: None,
+            active: !words.is_empty(),
+            _data: PhantomData,
+        })
+    }
+
+    /// Writes `write` while reading the same number of frames into `read`,
+    /// using one DMA channel for each direction.
+    ///
+    /// The RX channel is started before the TX channel and is requested for
+    /// every received frame, so it drains the RX FIFO as fast as it is
+    /// filled. Returns [`SpiError::LengthMismatch`] if the buffers differ in
+    /// length.
+    ///
+    /// # Safety
+    /// The transfer must not be leaked, see [`Spi::write_dma`]. Leaking it
+    /// would also let the RX channel write into `read` after its borrow has
+    /// ended.
+    pub unsafe fn transfer_dma<'a, const TX: usize, const RX: usize>(
+        &'a mut self,
+        tx: &'a mut Channel<TX>,
+        rx: &'a mut Channel<RX>,
+        read: &'a mut [u8],
+        write: &'a [u8],
+    ) -> Result<SpiDmaTransfer<'a, SPI, SCK, MOSI, MISO, TX, RX>, SpiError> {
+        if read.len() != write.len() {
+            return Err(SpiError::LengthMismatch);
+        }
+        self._check_dma(write.len())?;
+        if !write.is_empty() {
+            self._setup_dma(write.len(), true);
+            let fifo = self.spi.fifo8(0).as_ptr() as u32;
+            // Safety: The caller keeps `read` borrowed until the transfer is
+            // dropped, which stops the channel
+            unsafe {
+                rx.start(&TransferConfig {
+                    request: SPI::RX_REQUEST,
+                    src: fifo,
+                    src_width: TransferWidth::Byte,
+                    src_increment: false,
+                    dst: read.as_mut_ptr() as u32,
+                    dst_width: TransferWidth::Byte,
+                    dst_increment: true,
+                    len: read.len() as u32,
+                    burst_size: 1,
+                });
+            }
+            self.spi.dma().modify(|_, w| w.dma_rx_en().en());
+            self._start_dma_tx(tx, write);
+        }
+        Ok(SpiDmaTransfer {
+            spi: self,
+            tx,
+            rx: Some(rx),
+            active: !write.is_empty(),
+            _data: PhantomData,
+        })
+    }
+}
+
+/// # SPI DMA Transfer
+///
+/// An in-progress DMA transfer started by [`Spi::write_dma`] or
+/// [`Spi::transfer_dma`]. The SPI master, buffers and DMA channels stay
+/// borrowed until the transfer is dropped. Dropping an incomplete transfer
+/// stops the DMA channels and aborts the SPI transaction. Only dropping it
+/// stops the channels, so it must not be leaked.
+pub struct SpiDmaTransfer<'a, SPI, SCK, MOSI, MISO, const TX: usize, const RX: usize>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    spi: &'a mut Spi<SPI, SCK, MOSI, MISO>,
+    tx: &'a mut Channel<TX>,
+    rx: Option<&'a mut Channel<RX>>,
+    active: bool,
+    _data: PhantomData<&'a mut [u8]>,
+}
+
+impl<SPI, SCK, MOSI, MISO, const TX: usize, const RX: usize> SpiDmaTransfer<'_, SPI, SCK, MOSI, MISO, TX, RX>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    /// Returns [`true`] once every frame has been sent and received.
+    #[inline(always)]
+    pub fn is_complete(&self) -> bool {
+        !self.active
+            || (!self.tx.is_busy()
+                && !self.rx.as_ref().is_some_and(|rx| rx.is_busy())
+                && self.spi.spi.intfl().read().mst_done().bit_is_set())
+    }
+
+    /// Blocks until the transfer has completed.
+    pub fn wait(self) -> Result<(), SpiError> {
+        self.wait_with(&mut Spin)
+    }
+
+    /// Blocks until the transfer has completed, running `strategy` between
+    /// polls.
+    pub fn wait_with(self, strategy: &mut impl WaitStrategy) -> Result<(), SpiError> {
+        while !self.is_complete() {
+            strategy.wait();
+        }
+        if self.tx.bus_error() || self.rx.as_ref().is_some_and(|rx| rx.bus_error()) {
+            return Err(SpiError::DmaBusError);
+        }
+        Ok(())
+    }
+}
+
+impl<SPI, SCK, MOSI, MISO, const TX: usize, const RX: usize> Drop for SpiDmaTransfer<'_, SPI, SCK, MOSI, MISO, TX, RX>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        let aborted = !self.is_complete();
+        self.tx.stop();
+        if let Some(rx) = self.rx.as_mut() {
+            rx.stop();
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        let spi = &self.spi.spi;
+        if aborted {
+            spi.ctrl0().modify(|_, w| w.en().dis());
+            spi.ctrl0().modify(|_, w| w.en().en());
+        }
+        // Return the FIFOs to the polled mode of operation
+        spi.dma().write(|w| {
+            w.tx_fifo_en().en();
+            w.tx_flush().clear();
+            w.rx_fifo_en().en();
+            w.rx_flush().clear()
+        });
+    }
+}
+
+impl<SPI, SCK, MOSI, MISO, SS> SpiSsDevice<SPI, SCK, MOSI, MISO, SS>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    /// Deselects the slave select line and returns the SPI master and the
+    /// slave select pin.
+    pub fn free(self) -> (Spi<SPI, SCK, MOSI, MISO>, SS) {
+        self.bus
+            .spi
+            .ctrl0()
+            .modify(|_, w| unsafe { w.ss_active().bits(0) });
+        (self.bus, self._ss_pin)
+    }
+}
+
+impl<SPI, SCK, MOSI, MISO, SS> SpiSlave<SPI, SCK, MOSI, MISO, SS>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _configure(&self, mode: Mode, ss_polarity: SsPolarity) {
+        self.spi.ctrl0().write(|w| {
+            w.mst_mode().dis();
+            w.ss_io().input();
+            w.en().en()
+        });
+        self.spi.ctrl2().write(|w| {
+            match mode.polarity {
+                Polarity::IdleLow => w.clkpol().normal(),
+                Polarity::IdleHigh => w.clkpol().inverted(),
+            };
+            match mode.phase {
+                Phase::CaptureOnFirstTransition => w.clkpha().rising_edge(),
+                Phase::CaptureOnSecondTransition => w.clkpha().falling_edge(),
+            };
+            if ss_polarity == SsPolarity::ActiveHigh {
+                unsafe { w.ss_pol().bits(1) };
+            }
+            w.numbits()._8();
+            w.data_width().mono();
+            w.three_wire().dis()
+        });
+        self.spi.dma().write(|w| {
+            w.tx_fifo_en().en();
+            w.tx_flush().clear();
+            w.rx_fifo_en().en();
+            w.rx_flush().clear()
+        });
+        // Safety: The interrupt flags are write-1-to-clear
+        self.spi.intfl().write(|w| unsafe { w.bits(u32::MAX) });
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _order(&self, byte: u8) -> u8 {
+        // The slave always uses 8-bit frames
+        _reorder_u8(byte, self.bit_order, 8)
+    }
+
+    /// Returns the next slave select event. An assertion is reported before
+    /// a deassertion that is pending at the same time, since both then
+    /// belong to the same short transaction.
+    pub fn poll(&mut self) -> nb::Result<SpiSlaveEvent, core::convert::Infallible> {
+        let flags = self.spi.intfl().read();
+        if flags.ssa().bit_is_set() {
+            self.spi.intfl().write(|w| w.ssa().clear());
+            return Ok(SpiSlaveEvent::Selected);
+        }
+        if flags.ssd().bit_is_set() {
+            self.spi.intfl().write(|w| w.ssd().clear());
+            return Ok(SpiSlaveEvent::Deselected);
+        }
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Returns [`true`] while the master asserts slave select.
+    #[inline(always)]
+    pub fn is_selected(&self) -> bool {
+        self.spi.stat().read().busy().bit_is_set()
+    }
+
+    /// Moves received bytes from the RX FIFO into `buf`, returning how
+    /// many were read. Never blocks.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let available = self.spi.dma().read().rx_lvl().bits() as usize;
+        let count = available.min(buf.len());
+        for slot in buf[..count].iter_mut() {
+            *slot = self._order(self.spi.fifo8(0).read().data().bits());
+        }
+        count
+    }
+
+    /// Queues bytes for the master to clock out, returning how many fit in
+    /// the TX FIFO. Never blocks.
+    pub fn write(&mut self, bytes: &[u8]) -> usize {
+        let free = FIFO_DEPTH - self.spi.dma().read().tx_lvl().bits() as usize;
+        let count = free.min(bytes.len());
+        for &byte in bytes[..count].iter() {
+            self.spi
+                .fifo8(0)
+                .write(|w| unsafe { w.data().bits(self._order(byte)) });
+        }
+        count
+    }
+
+    /// Discards the bytes left in both FIFOs, such as the rest of a
+    /// response the master did not read.
+    pub fn flush_fifos(&mut self) {
+        self.spi.dma().modify(|_, w| {
+            w.tx_flush().clear();
+            w.rx_flush().clear()
+        });
+    }
+
+    /// Returns [`true`] if the master clocked out a byte while the TX FIFO
+    /// was empty since the flag was last cleared.
+    #[inline(always)]
+    pub fn is_underrun(&self) -> bool {
+        self.spi.intfl().read().tx_un().bit_is_set()
+    }
+
+    /// Clears the underrun flag.
+    #[inline(always)]
+    pub fn clear_underrun(&mut self) {
+        self.spi.intfl().write(|w| w.tx_un().clear());
+    }
+
+    /// Returns [`true`] if a byte was received while the RX FIFO was full
+    /// since the flag was last cleared. The byte is lost.
+    #[inline(always)]
+    pub fn is_overrun(&self) -> bool {
+        self.spi.intfl().read().rx_ov().bit_is_set()
+    }
+
+    /// Clears the overrun flag.
+    #[inline(always)]
+    pub fn clear_overrun(&mut self) {
+        self.spi.intfl().write(|w| w.rx_ov().clear());
+    }
+
+    /// Enables the interrupt for `event`. The flag stays set until it is
+    /// reported by [`SpiSlave::poll()`], so the handler must call it to
+    /// clear the interrupt.
+    ///
+    /// Example:
+    /// ```
+    /// slave.enable_interrupt(SpiSlaveEvent::Selected);
+    /// unsafe { cortex_m::peripheral::NVIC::unmask(slave.interrupt()) };
+    /// ```
+    pub fn enable_interrupt(&mut self, event: SpiSlaveEvent) {
+        self.spi.inten().modify(|_, w| match event {
+            SpiSlaveEvent::Selected => w.ssa().en(),
+            SpiSlaveEvent::Deselected => w.ssd().en(),
+        });
+    }
+
+    /// Disables the interrupt for `event`.
+    pub fn disable_interrupt(&mut self, event: SpiSlaveEvent) {
+        self.spi.inten().modify(|_, w| match event {
+            SpiSlaveEvent::Selected => w.ssa().dis(),
+            SpiSlaveEvent::Deselected => w.ssd().dis(),
+        });
+    }
+}
+
+impl<SPI, SCK, MOSI, MISO> embedded_hal::spi::ErrorType for Spi<SPI, SCK, MOSI, MISO> {
+    type Error = SpiError;
+}
+
+impl<SPI, SCK, MOSI, MISO> embedded_hal::spi::SpiBus<u8> for Spi<SPI, SCK, MOSI, MISO>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self._check_u8_frames()?;
+        self._read(words, false);
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self._check_u8_frames()?;
+        self._write(words, false);
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self._check_u8_frames()?;
+        self._transfer(read, write, false);
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self._check_u8_frames()?;
+        self._transfer_in_place(words, false);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while self._is_busy() {}
+        Ok(())
+    }
+}
+
+impl<SPI, SCK, MOSI, MISO, SS> embedded_hal::spi::ErrorType for SpiSsDevice<SPI, SCK, MOSI, MISO, SS> {
+    type Error = SpiError;
+}
+
+impl<SPI, SCK, MOSI, MISO, SS> embedded_hal::spi::SpiDevice<u8> for SpiSsDevice<SPI, SCK, MOSI, MISO, SS>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    /// SS is released at the end of the last operation that moves data, so
+    /// trailing [`Operation::DelayNs`](embedded_hal::spi::Operation::DelayNs)
+    /// entries run with SS deasserted.
+    fn transaction(
+        &mut self,
+        operations: &mut [embedded_hal::spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        use embedded_hal::spi::Operation;
+
+        self.bus._check_u8_frames()?;
+        let is_data = |op: &Operation<'_, u8>| match op {
+            Operation::Read(words) => !words.is_empty(),
+            Operation::Write(words) => !words.is_empty(),
+            Operation::Transfer(read, write) => !read.is_empty() || !write.is_empty(),
+            Operation::TransferInPlace(words) => !words.is_empty(),
+            Operation::DelayNs(_) => false,
+        };
+        let last = operations.iter().rposition(is_data);
+        for (i, op) in operations.iter_mut().enumerate() {
+            let keep_ss = Some(i) != last;
+            match op {
+                Operation::Read(words) => self.bus._read(words, keep_ss),
+                Operation::Write(words) => self.bus._write(words, keep_ss),
+                Operation::Transfer(read, write) => self.bus._transfer(read, write, keep_ss),
+                Operation::TransferInPlace(words) => self.bus._transfer_in_place(words, keep_ss),
+                Operation::DelayNs(ns) => {
+                    // The system clock runs at twice the peripheral clock
+                    let cycles = (*ns as u64 * self.bus.clk_freq as u64 * 2).div_ceil(1_000_000_000);
+                    cortex_m::asm::delay(cycles as u32);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reverses the low `frame_bits` bits of a frame of up to 8 bits for LSB
+/// first transfers, since the peripheral always shifts MSB first.
+#[doc(hidden)]
+#[inline(always)]
+fn _reorder_u8(byte: u8, order: BitOrder, frame_bits: u8) -> u8 {
+    match order {
+        BitOrder::MsbFirst => byte,
+        BitOrder::LsbFirst => byte.reverse_bits() >> (8 - frame_bits.min(8)),
+    }
+}
+
+/// Reverses the low `frame_bits` bits of a frame of up to 16 bits, see
+/// [`_reorder_u8()`].
+#[doc(hidden)]
+#[inline(always)]
+fn _reorder_u16(word: u16, order: BitOrder, frame_bits: u8) -> u16 {
+    match order {
+        BitOrder::MsbFirst => word,
+        BitOrder::LsbFirst => word.reverse_bits() >> (16 - frame_bits.min(16)),
+    }
+}
+
+/// Calculates the SCK prescaler exponent and the high and low periods that
+/// produce the frequency closest to `frequency` from `clk_src_freq`.
+///
+/// SCK is `clk_src_freq / (2^scale * (hi + lo))`. The smallest prescaler
+/// that fits is used, since it gives the finest frequency resolution.
+#[doc(hidden)]
+#[inline(always)]
+fn _clock_divider(clk_src_freq: u32, frequency: u32) -> Result<(u32, u32, u32), SpiError> {
+    if frequency == 0 || frequency > clk_src_freq / 2 {
+        return Err(SpiError::UnachievableFrequency);
+    }
+    let divisor = (clk_src_freq + frequency / 2) / frequency;
+    for scale in 0..=MAX_CLK_SCALE {
+        let periods = ((divisor + (1 << scale >> 1)) >> scale).max(2);
+        if periods <= MAX_CLK_PERIODS {
+            let lo = periods / 2;
+            return Ok((scale, periods - lo, lo));
+        }
+    }
+    Err(SpiError::UnachievableFrequency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lsb_first_reverses_only_the_frame_bits() {
+        assert_eq!(_reorder_u8(0b1000_0001, BitOrder::LsbFirst, 8), 0b1000_0001);
+        assert_eq!(_reorder_u8(0b0000_0001, BitOrder::LsbFirst, 8), 0b1000_0000);
+        // A 5-bit frame stays in the low 5 bits
+        assert_eq!(_reorder_u8(0b0_0001, BitOrder::LsbFirst, 5), 0b1_0000);
+        assert_eq!(_reorder_u8(0b0_0110, BitOrder::LsbFirst, 5), 0b0_1100);
+        assert_eq!(_reorder_u16(0x0001, BitOrder::LsbFirst, 16), 0x8000);
+        // A 12-bit frame stays in the low 12 bits
+        assert_eq!(_reorder_u16(0x001, BitOrder::LsbFirst, 12), 0x800);
+        assert_eq!(_reorder_u16(0xabc, BitOrder::LsbFirst, 12), 0x3d5);
+    }
+
+    #[test]
+    fn msb_first_is_unchanged() {
+        assert_eq!(_reorder_u8(0b0_0110, BitOrder::MsbFirst, 5), 0b0_0110);
+        assert_eq!(_reorder_u16(0xabc, BitOrder::MsbFirst, 12), 0xabc);
+    }
+
+    #[test]
+    fn clock_divider_without_prescaler() {
+        // 50 MHz / (25 + 25) = 1 MHz
+        assert_eq!(_clock_divider(50_000_000, 1_000_000), Ok((0, 25, 25)));
+        // The fastest SCK is half the source clock
+        assert_eq!(_clock_divider(50_000_000, 25_000_000), Ok((0, 1, 1)));
+    }
+
+    #[test]
+    fn clock_divider_with_prescaler() {
+        // 50 MHz / 1 kHz = 50000 = 2^7 * 391 (rounded), the odd period goes high
+        assert_eq!(_clock_divider(50_000_000, 1_000), Ok((7, 196, 195)));
+    }
+
+    #[test]
+    fn clock_divider_out_of_range() {
+        let err = Err(SpiError::UnachievableFrequency);
+        assert_eq!(_clock_divider(50_000_000, 0), err);
+        assert_eq!(_clock_divider(50_000_000, 25_000_001), err);
+        // Below 50 MHz / (2^8 * 510)
+        assert_eq!(_clock_divider(50_000_000, 100), err);
+    }
+}
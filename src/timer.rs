@@ -0,0 +1,691 @@
+//! # Timers (TMR)
+//!
+//! General-purpose 32-bit timers clocked from the peripheral clock. Only
+//! TMR0 to TMR3 are supported, since the low-power timers (TMR4 and TMR5)
+//! cannot be clocked from the peripheral clock.
+use core::ops::Deref;
+use core::time::Duration;
+
+use cortex_m::interrupt;
+use crate::gcr::{
+    clocks::{Clock, PeripheralClock},
+    ClockForPeripheral, ResetForPeripheral,
+};
+use crate::gpio::{Af2, Pin};
+use embedded_hal_nb::nb;
+use paste::paste;
+
+/// Largest prescaler setting, which divides the clock by 2^12.
+const MAX_PRESCALER: u8 = 12;
+/// Value the counter restarts from at the end of each period.
+const COUNT_START: u32 = 1;
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+/// Capture register value that marks a capture as consumed. The counter
+/// never holds zero, so a real capture cannot be mistaken for it.
+const NO_CAPTURE: u32 = 0;
+/// Shortest PWM period, so that the output has both a high and a low phase.
+const MIN_PWM_TICKS: u32 = 2;
+
+/// Errors that can occur while configuring a timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimerError {
+    /// The period is too short or too long for any prescaler setting.
+    PeriodOutOfRange,
+    /// The prescaler setting is above the maximum of 12.
+    InvalidPrescaler,
+}
+
+/// Counting modes of a timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimerMode {
+    /// Count a single period, then stop.
+    OneShot,
+    /// Restart after each period until cancelled.
+    Continuous,
+}
+
+/// Edges of the input pin that latch the count in capture mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CaptureEdge {
+    Rising,
+    Falling,
+}
+
+/// # Timer (TMR) Peripheral
+///
+/// The timer runs as a 32-bit counter (both 16-bit halves cascaded) from
+/// the peripheral clock. When configured, the prescaler and compare value
+/// that get closest to the requested period are chosen, and the period
+/// that is actually achieved is returned.
+///
+/// Example:
+/// ```
+/// use hal::timer::{Timer, TimerMode};
+///
+/// let mut timer = Timer::tmr0(p.tmr0, &mut gcr.reg, &clks.pclk);
+/// let actual = timer.configure(TimerMode::Continuous, 1_000).unwrap();
+/// assert_eq!(actual, core::time::Duration::from_millis(1));
+/// timer.start();
+/// loop {
+///     timer.wait(); // Returns once every millisecond
+///     control_loop();
+/// }
+/// ```
+pub struct Timer<TMR> {
+    tmr: TMR,
+    clk_freq: u32,
+    prescaler: u8,
+    ticks: u32,
+}
+
+/// Pins that can be driven by the PWM output of a timer.
+pub trait PwmPin<TMR>: crate::Sealed {}
+/// Pins that can be used as the capture input of a timer.
+pub trait CapturePin<TMR>: crate::Sealed {}
+
+// All timers are derived from the same register block
+type TimerRegisterBlock = crate::pac::tmr0::RegisterBlock;
+
+macro_rules! timer {
+    ($tmr:ident, io: $io_pin:ty) => {
+        paste! {
+            use crate::pac::$tmr;
+
+            impl crate::Sealed for $io_pin {}
+            impl PwmPin<$tmr> for $io_pin {}
+            impl CapturePin<$tmr> for $io_pin {}
+
+            impl Timer<$tmr> {
+                #[doc = "Construct a new "]
+                #[doc = stringify!([<$tmr:upper>])]
+                #[doc = " timer. The timer is stopped until it is configured and"]
+                #[doc = " started."]
+                pub fn [<$tmr:lower>](
+                    tmr: $tmr,
+                    reg: &mut crate::gcr::GcrRegisters,
+                    clock: &Clock<PeripheralClock>,
+                ) -> Self {
+                    unsafe {
+                        tmr.reset(&mut reg.gcr);
+                        tmr.enable_clock(&mut reg.gcr);
+                    }
+                    let timer = Timer {
+                        tmr,
+                        clk_freq: clock.frequency,
+                        prescaler: 0,
+                        ticks: u32::MAX,
+                    };
+                    timer._init();
+                    timer
+                }
+            }
+        }
+    };
+}
+
+timer!(Tmr0, io: Pin<0, 2, Af2>);
+timer!(Tmr1, io: Pin<0, 14, Af2>);
+timer!(Tmr2, io: Pin<0, 26, Af2>);
+timer!(Tmr3, io: Pin<1, 6, Af2>);
+
+impl<TMR> Timer<TMR>
+where
+    TMR: Deref<Target = TimerRegisterBlock>,
+{
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _init(&self) {
+        self.tmr.ctrl0().modify(|_, w| w.rst_a().set_bit());
+        while self.tmr.ctrl0().read().rst_a().bit_is_set() {}
+        // Peripheral clock source, both halves cascaded into a 32-bit timer
+        self.tmr.ctrl1().write(|w| {
+            unsafe { w.clksel_a().bits(0) };
+            w.cascade().set_bit()
+        });
+        self.tmr.ctrl0().write(|w| w.mode_a().one_shot());
+        self.tmr.cmp().write(|w| unsafe { w.compare().bits(self.ticks) });
+        self.tmr.intfl().write(|w| w.irq_a().set_bit());
+        self.tmr.ctrl0().modify(|_, w| w.clken_a().set_bit());
+        while self.tmr.ctrl1().read().clkrdy_a().bit_is_clear() {}
+    }
+
+    /// Configure the mode and period of the timer, in microseconds. The
+    /// timer is stopped and must be started again with [`Timer::start()`].
+    ///
+    /// Returns the period that is actually achieved, or
+    /// [`TimerError::PeriodOutOfRange`] if the period is zero or longer
+    /// than the timer can count.
+    pub fn configure(&mut self, mode: TimerMode, period_us: u32) -> Result<Duration, TimerError> {
+        let (prescaler, ticks) =
+            _prescaler_ticks(self.clk_freq, period_us as u128, 1_000_000, 1)?;
+        self._set_period(mode, prescaler, ticks);
+        Ok(self.period())
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _set_period(&mut self, mode: TimerMode, prescaler: u8, ticks: u32) {
+        self.cancel();
+        self.prescaler = prescaler;
+        self.ticks = ticks;
+        self.tmr.ctrl0().modify(|_, w| {
+            match mode {
+                TimerMode::OneShot => w.mode_a().one_shot(),
+                TimerMode::Continuous => w.mode_a().continuous(),
+            };
+            unsafe { w.clkdiv_a().bits(prescaler) }
+        });
+        self.tmr.cmp().write(|w| unsafe { w.compare().bits(ticks) });
+    }
+
+    /// The period the timer is configured for.
+    pub fn period(&self) -> Duration {
+        _ticks_to_duration(self.clk_freq, self.prescaler, self.ticks as u64)
+    }
+
+    /// Start the timer from the beginning of a period. Any pending
+    /// interrupt is cleared.
+    pub fn start(&mut self) {
+        self.cancel();
+        self.tmr.cnt().write(|w| unsafe { w.count().bits(COUNT_START) });
+        self.clear_interrupt();
+        self.tmr.ctrl0().modify(|_, w| w.en_a().set_bit());
+        while self.tmr.ctrl1().read().clken_a().bit_is_clear() {}
+    }
+
+    /// Block until the current period ends, then clear the interrupt flag.
+    /// In one-shot mode this returns immediately if the period already
+    /// ended and the flag has not been cleared since, and never returns if
+    /// the timer was not started.
+    pub fn wait(&mut self) {
+        while !self.is_interrupt_pending() {}
+        self.clear_interrupt();
+    }
+
+    /// Stop the timer. The interrupt flag is left as is.
+    pub fn cancel(&mut self) {
+        self.tmr.ctrl0().modify(|_, w| w.en_a().clear_bit());
+        while self.tmr.ctrl1().read().clken_a().bit_is_set() {}
+    }
+
+    /// Returns [`true`] if the timer is counting. A one-shot timer stops
+    /// itself at the end of its period.
+    #[inline(always)]
+    pub fn is_running(&self) -> bool {
+        self.tmr.ctrl0().read().en_a().bit_is_set()
+    }
+
+    /// Enable the timer interrupt, raised at the end of each period.
+    #[inline(always)]
+    pub fn enable_interrupt(&mut self) {
+        self.tmr.ctrl1().modify(|_, w| w.ie_a().set_bit());
+    }
+
+    /// Disable the timer interrupt. The interrupt flag is still set at the
+    /// end of each period.
+    #[inline(always)]
+    pub fn disable_interrupt(&mut self) {
+        self.tmr.ctrl1().modify(|_, w| w.ie_a().clear_bit());
+    }
+
+    /// Returns [`true`] if a period has ended since the interrupt flag was
+    /// last cleared.
+    #[inline(always)]
+    pub fn is_interrupt_pending(&self) -> bool {
+        self.tmr.intfl().read().irq_a().bit_is_set()
+    }
+
+    /// Clear the interrupt flag.
+    ///
+    /// Example:
+    /// ```
+    /// #[interrupt]
+    /// fn TMR0() {
+    ///     timer.clear_interrupt();
+    ///     control_loop();
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn clear_interrupt(&mut self) {
+        self.tmr.intfl().write(|w| w.irq_a().set_bit());
+    }
+
+    /// Stop the timer and release the underlying peripheral.
+    pub fn free(mut self) -> TMR {
+        self.cancel();
+        self.tmr
+    }
+
+    /// Turn the timer into a PWM output on a pin, running at the given
+    /// frequency in hertz. The output starts at 0% duty cycle.
+    ///
+    /// Returns [`TimerError::PeriodOutOfRange`] if the frequency is zero,
+    /// above half the peripheral clock, or too low for the timer.
+    pub fn into_pwm<PIN: PwmPin<TMR>>(
+        mut self,
+        pin: PIN,
+        frequency: u32,
+    ) -> Result<Pwm<TMR, PIN>, TimerError> {
+        if frequency == 0 {
+            return Err(TimerError::PeriodOutOfRange);
+        }
+        let (prescaler, ticks) =
+            _prescaler_ticks(self.clk_freq, 1, frequency as u128, MIN_PWM_TICKS)?;
+        self.cancel();
+        self.prescaler = prescaler;
+        self.ticks = ticks;
+        self.tmr.ctrl0().modify(|_, w| {
+            w.mode_a().pwm();
+            unsafe { w.clkdiv_a().bits(prescaler) };
+            // Only drive the main output, not the complementary one
+            w.pwmckbd_a().set_bit();
+            w.pol_a().clear_bit()
+        });
+        self.tmr.cmp().write(|w| unsafe { w.compare().bits(ticks) });
+        self.tmr.pwm().write(|w| unsafe { w.pwm().bits(ticks) });
+        self.start();
+        Ok(Pwm {
+            timer: self,
+            _pin: pin,
+            high_ticks: 0,
+        })
+    }
+
+    /// Turn the timer into an input capture on a pin. The timer counts at
+    /// the peripheral clock divided by `2^prescaler`, and the count is
+    /// latched on every `edge` of the input.
+    ///
+    /// The counter is 32 bits wide and wraps are counted in software, so
+    /// intervals of any length can be measured as long as the capture is
+    /// serviced (by [`Capture::read_capture()`] or
+    /// [`Capture::on_interrupt()`]) at least once per wrap, every
+    /// `2^(32 + prescaler) / f_pclk`. At a 50 MHz peripheral clock this is
+    /// 85.9 s with a prescaler of 0 (20 ns resolution) and 97.7 hours with
+    /// the maximum prescaler of 12 (81.92 us resolution).
+    ///
+    /// Returns [`TimerError::InvalidPrescaler`] if the prescaler is above
+    /// 12.
+    pub fn into_capture<PIN: CapturePin<TMR>>(
+        mut self,
+        pin: PIN,
+        edge: CaptureEdge,
+        prescaler: u8,
+    ) -> Result<Capture<TMR, PIN>, TimerError> {
+        if prescaler > MAX_PRESCALER {
+            return Err(TimerError::InvalidPrescaler);
+        }
+        self.cancel();
+        self.prescaler = prescaler;
+        self.ticks = u32::MAX;
+        self.tmr.ctrl0().modify(|_, w| {
+            w.mode_a().capture();
+            unsafe { w.clkdiv_a().bits(prescaler) };
+            w.pol_a().bit(edge == CaptureEdge::Falling)
+        });
+        self.tmr.cmp().write(|w| unsafe { w.compare().bits(u32::MAX) });
+        self.tmr.pwm().write(|w| unsafe { w.pwm().bits(NO_CAPTURE) });
+        self.start();
+        Ok(Capture {
+            timer: self,
+            _pin: pin,
+            wraps: 0,
+            last_count: COUNT_START,
+            last_capture: None,
+        })
+    }
+}
+
+/// # Pulse Width Modulation (PWM) Output
+///
+/// Drives a pin with a square wave from a timer in PWM mode, implementing
+/// [`embedded_hal::pwm::SetDutyCycle`]. At each duty cycle the output is
+/// high for the start of the period and low for the rest of it.
+///
+/// The maximum duty cycle is the number of timer ticks in a period, so it
+/// reflects the resolution of the output at the chosen frequency. Periods
+/// longer than [`u16::MAX`] ticks are scaled down to fit, losing some of
+/// the resolution.
+///
+/// 0% and 100% duty cycles keep the output at one level for the whole
+/// period. Changing the duty cycle while running is glitch-free: the new
+/// value is applied at a point of the period where the old and new
+/// waveforms agree, so no period has a pulse of an intermediate width.
+/// The exception is entering or leaving 0%, which inverts the output
+/// polarity and can cause a spike a few clock cycles wide between the two
+/// register writes.
+///
+/// Example:
+/// ```
+/// use embedded_hal::pwm::SetDutyCycle;
+/// use hal::timer::Timer;
+///
+/// let timer = Timer::tmr0(p.tmr0, &mut gcr.reg, &clks.pclk);
+/// // 50 Hz servo signal
+/// let mut servo = timer.into_pwm(pins.p0_2.into_af2(), 50).unwrap();
+/// // 1.5 ms pulse, centered
+/// servo.set_duty_cycle_fraction(3, 40).unwrap();
+/// ```
+pub struct Pwm<TMR, PIN> {
+    timer: Timer<TMR>,
+    _pin: PIN,
+    high_ticks: u32,
+}
+
+impl<TMR, PIN> Pwm<TMR, PIN>
+where
+    TMR: Deref<Target = TimerRegisterBlock>,
+{
+    /// The period of the PWM output.
+    pub fn period(&self) -> Duration {
+        self.timer.period()
+    }
+
+    /// Number of timer ticks in a period. This is the finest resolution of
+    /// the duty cycle.
+    #[inline(always)]
+    pub fn period_ticks(&self) -> u32 {
+        self.timer.ticks
+    }
+
+    /// Set how many timer ticks the output is high for in each period,
+    /// from `0` up to [`Pwm::period_ticks()`]. Larger values are clamped.
+    pub fn set_high_ticks(&mut self, high_ticks: u32) {
+        let tmr = &self.timer.tmr;
+        let ticks = self.timer.ticks;
+        let high_ticks = high_ticks.min(ticks);
+        // The output is high while the count is at most `high_ticks`, so
+        // both waveforms agree except for counts between the two values
+        let low = self.high_ticks.min(high_ticks);
+        let high = self.high_ticks.max(high_ticks);
+        while self.timer.is_running() {
+            let count = tmr.cnt().read().count().bits();
+            if count <= low || count > high {
+                break;
+            }
+        }
+        // 0% is the inverted waveform of 100%, so the output never changes
+        // level in either case
+        interrupt::free(|_| {
+            if high_ticks == 0 {
+                tmr.ctrl0().modify(|_, w| w.pol_a().clear_bit());
+                tmr.pwm().write(|w| unsafe { w.pwm().bits(ticks) });
+            } else {
+                tmr.pwm().write(|w| unsafe { w.pwm().bits(high_ticks) });
+                tmr.ctrl0().modify(|_, w| w.pol_a().set_bit());
+            }
+        });
+        self.high_ticks = high_ticks;
+    }
+
+    /// Stop the PWM output and release the timer and pin. The timer is
+    /// returned to one-shot mode and must be configured again.
+    pub fn free(mut self) -> (Timer<TMR>, PIN) {
+        self.timer.cancel();
+        self.timer.prescaler = 0;
+        self.timer.ticks = u32::MAX;
+        self.timer._init();
+        (self.timer, self._pin)
+    }
+}
+
+impl<TMR, PIN> embedded_hal::pwm::ErrorType for Pwm<TMR, PIN> {
+    type Error = core::convert::Infallible;
+}
+
+impl<TMR, PIN> embedded_hal::pwm::SetDutyCycle for Pwm<TMR, PIN>
+where
+    TMR: Deref<Target = TimerRegisterBlock>,
+{
+    fn max_duty_cycle(&self) -> u16 {
+        self.timer.ticks.min(u16::MAX as u32) as u16
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        let max = self.max_duty_cycle() as u64;
+        let duty = (duty as u64).min(max);
+        let high_ticks = duty * self.timer.ticks as u64 / max;
+        self.set_high_ticks(high_ticks as u32);
+        Ok(())
+    }
+}
+
+/// # Timer-Based Delay
+///
+/// Implements [`embedded_hal::delay::DelayNs`] by running a timer in
+/// one-shot mode. Unlike [`cortex_m::asm::delay`], the delay is derived
+/// from the peripheral clock frequency, so it stays accurate whatever the
+/// instruction timing. Delays longer than the 32-bit counter allows are
+/// split into several timer periods. Each delay lasts at least as long as
+/// requested, rounded up to a whole peripheral clock cycle.
+///
+/// Example:
+/// ```
+/// use embedded_hal::delay::DelayNs;
+/// use hal::timer::{Delay, Timer};
+///
+/// let timer = Timer::tmr1(p.tmr1, &mut gcr.reg, &clks.pclk);
+/// let mut delay = Delay::new(timer);
+/// delay.delay_ms(100);
+/// ```
+pub struct Delay<TMR> {
+    timer: Timer<TMR>,
+}
+
+impl<TMR> Delay<TMR>
+where
+    TMR: Deref<Target = TimerRegisterBlock>,
+{
+    /// Create a delay provider from a timer. Any configuration of the timer
+    /// is replaced.
+    pub fn new(mut timer: Timer<TMR>) -> Self {
+        timer.cancel();
+        timer.disable_interrupt();
+        Self { timer }
+    }
+
+    /// Release the underlying timer. It is left stopped, with whatever
+    /// period the last delay used.
+    pub fn free(self) -> Timer<TMR> {
+        self.timer
+    }
+
+    #[doc(hidden)]
+    fn _delay_ticks(&mut self, mut ticks: u64) {
+        while ticks > 0 {
+            let chunk = ticks.min(u32::MAX as u64) as u32;
+            self.timer._set_period(TimerMode::OneShot, 0, chunk);
+            self.timer.start();
+            self.timer.wait();
+            ticks -= chunk as u64;
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _delay(&mut self, amount: u32, per_sec: u64) {
+        let clk_freq = self.timer.clk_freq as u64;
+        self._delay_ticks((amount as u64 * clk_freq).div_ceil(per_sec));
+    }
+}
+
+impl<TMR> embedded_hal::delay::DelayNs for Delay<TMR>
+where
+    TMR: Deref<Target = TimerRegisterBlock>,
+{
+    fn delay_ns(&mut self, ns: u32) {
+        self._delay(ns, 1_000_000_000);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self._delay(us, 1_000_000);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self._delay(ms, 1_000);
+    }
+}
+
+/// # Input Capture
+///
+/// Latches the timer count on an edge of the input pin and reports the
+/// interval between successive edges, for example to decode sensors that
+/// report through a pulse width. See [`Timer::into_capture()`] for the
+/// resolution and servicing interval at each prescaler.
+///
+/// Example:
+/// ```
+/// use hal::timer::{CaptureEdge, Timer};
+///
+/// let timer = Timer::tmr1(p.tmr1, &mut gcr.reg, &clks.pclk);
+/// let mut capture = timer
+///     .into_capture(pins.p0_14.into_af2(), CaptureEdge::Rising, 0)
+///     .unwrap();
+/// // Period of the input signal
+/// let period = nb::block!(capture.read_capture()).unwrap();
+/// ```
+pub struct Capture<TMR, PIN> {
+    timer: Timer<TMR>,
+    _pin: PIN,
+    wraps: u64,
+    last_count: u32,
+    last_capture: Option<u64>,
+}
+
+impl<TMR, PIN> Capture<TMR, PIN>
+where
+    TMR: Deref<Target = TimerRegisterBlock>,
+{
+    /// Catch up on counter wraps and consume a new capture, returning the
+    /// number of ticks since the previous one.
+    #[doc(hidden)]
+    fn _update(&mut self) -> Option<u64> {
+        let tmr = &self.timer.tmr;
+        // Clear first, so that events after the reads below set it again
+        self.timer.tmr.intfl().write(|w| w.irq_a().set_bit());
+        let count = tmr.cnt().read().count().bits();
+        let capture = tmr.pwm().read().pwm().bits();
+        let wrapped = count < self.last_count;
+        self.last_count = count;
+
+        let mut delta = None;
+        if capture != NO_CAPTURE {
+            tmr.pwm().write(|w| unsafe { w.pwm().bits(NO_CAPTURE) });
+            // After a wrap the count restarts from 1, so a capture above the
+            // current count was latched before the wrap
+            let lap = if wrapped && capture <= count {
+                self.wraps + 1
+            } else {
+                self.wraps
+            };
+            let timestamp = lap * u32::MAX as u64 + capture as u64;
+            if let Some(last) = self.last_capture {
+                delta = Some(timestamp - last);
+            }
+            self.last_capture = Some(timestamp);
+        }
+        if wrapped {
+            self.wraps += 1;
+        }
+        delta
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _to_duration(&self, ticks: u64) -> Duration {
+        _ticks_to_duration(self.timer.clk_freq, self.timer.prescaler, ticks)
+    }
+
+    /// Returns the interval between the two most recent edges, or
+    /// [`nb::Error::WouldBlock`] if no edge was latched since the last
+    /// call. The first edge only starts the measurement.
+    ///
+    /// Only the latest edge is kept, so edges closer together than the
+    /// polling interval are missed.
+    pub fn read_capture(&mut self) -> nb::Result<Duration, core::convert::Infallible> {
+        match self._update() {
+            Some(ticks) => Ok(self._to_duration(ticks)),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Enable the timer interrupt, raised on every captured edge and every
+    /// counter wrap.
+    ///
+    /// Example:
+    /// ```
+    /// capture.enable_interrupt();
+    ///
+    /// #[interrupt]
+    /// fn TMR1() {
+    ///     if let Some(width) = capture.on_interrupt() {
+    ///         handle(width);
+    ///     }
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn enable_interrupt(&mut self) {
+        self.timer.enable_interrupt();
+    }
+
+    /// Disable the timer interrupt.
+    #[inline(always)]
+    pub fn disable_interrupt(&mut self) {
+        self.timer.disable_interrupt();
+    }
+
+    /// Service the capture from the timer interrupt handler. Clears the
+    /// interrupt and returns the interval between the two most recent
+    /// edges if a new edge was latched.
+    pub fn on_interrupt(&mut self) -> Option<Duration> {
+        self._update().map(|ticks| self._to_duration(ticks))
+    }
+
+    /// Stop capturing and release the timer and pin. The timer is
+    /// returned to one-shot mode and must be configured again.
+    pub fn free(mut self) -> (Timer<TMR>, PIN) {
+        self.timer.cancel();
+        self.timer.prescaler = 0;
+        self.timer.ticks = u32::MAX;
+        self.timer._init();
+        (self.timer, self._pin)
+    }
+}
+
+/// Picks the prescaler and compare value that get closest to a period of
+/// `num / den` seconds, with at least `min_ticks` ticks. Smaller
+/// prescalers win ties since they give a finer resolution.
+#[doc(hidden)]
+fn _prescaler_ticks(
+    clk_freq: u32,
+    num: u128,
+    den: u128,
+    min_ticks: u32,
+) -> Result<(u8, u32), TimerError> {
+    // Compare in units of 1 / (clk_freq * den) seconds to avoid rounding
+    let target = num * clk_freq as u128;
+    let mut best: Option<(u8, u32, u128)> = None;
+    for prescaler in 0..=MAX_PRESCALER {
+        let unit = den << prescaler;
+        let ticks = (target + unit / 2) / unit;
+        if ticks < min_ticks.max(1) as u128 || ticks > u32::MAX as u128 {
+            continue;
+        }
+        let error = (ticks * unit).abs_diff(target);
+        if best.is_none_or(|(_, _, best_error)| error < best_error) {
+            best = Some((prescaler, ticks as u32, error));
+        }
+    }
+    best.map(|(prescaler, ticks, _)| (prescaler, ticks))
+        .ok_or(TimerError::PeriodOutOfRange)
+}
+
+/// Converts a prescaler and compare value into a period.
+#[doc(hidden)]
+#[inline(always)]
+fn _ticks_to_duration(clk_freq: u32, prescaler: u8, ticks: u64) -> Duration {
+    let nanos = ((ticks as u128) << prescaler) * NANOS_PER_SEC / clk_freq as u128;
+    Duration::from_nanos(nanos as u64)
+}
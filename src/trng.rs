@@ -2,34 +2,88 @@
 //!
 //! The TRNG is a hardware module that generates random numbers using
 //! physical entropy sources.
-#[cfg(feature = "rand")]
+#[cfg(feature = "rand_core")]
 use rand_core::CryptoRng;
-#[cfg(feature = "rand")]
+#[cfg(feature = "rand_core")]
 use rand_core::RngCore;
-#[cfg(feature = "rand")]
-use rand_core::impls::{fill_bytes_via_next, next_u64_via_u32};
+#[cfg(feature = "rand_core")]
+use rand_core::impls::next_u64_via_u32;
+
+/// Number of words discarded after the TRNG is enabled.
+const STARTUP_DISCARD_WORDS: usize = 4;
+/// Number of words sampled by the health check.
+const HEALTH_CHECK_WORDS: usize = 64;
+
+/// Errors that can occur while using the TRNG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrngError {
+    /// The output of the TRNG looks stuck.
+    HealthCheckFailed,
+}
 
 /// # True Random Number Generator (TRNG) Peripheral
 ///
 /// Example:
 /// ```
 /// // Create a new TRNG peripheral instance
-/// let trng = Trng::new(p.trng, &mut gcr.reg);
+/// let trng = Trng::new(p.trng, &mut gcr.reg).unwrap();
 /// // Generate a random 32-bit number
-/// let random_u32 = trng.gen_u32();
+/// let random_u32 = trng.random_u32();
+/// // Generate a 12-byte nonce
+/// let mut nonce = [0u8; 12];
+/// trng.fill_bytes(&mut nonce);
 /// ```
 pub struct Trng {
     trng: crate::pac::Trng,
 }
 
 impl Trng {
-    /// Create a new TRNG peripheral instance.
-    pub fn new(trng: crate::pac::Trng, reg: &mut crate::gcr::GcrRegisters) -> Self {
+    /// Create a new TRNG peripheral instance. The first few words after
+    /// startup are discarded and the health check from [`Trng::self_test()`]
+    /// is run before the TRNG is handed out.
+    pub fn new(trng: crate::pac::Trng, reg: &mut crate::gcr::GcrRegisters) -> Result<Self, TrngError> {
         use crate::gcr::ClockForPeripheral;
+        use crate::gcr::ResetForPeripheral;
         unsafe {
+            trng.reset(&mut reg.gcr);
             trng.enable_clock(&mut reg.gcr);
         }
-        Self { trng }
+        let trng = Self { trng };
+        for _ in 0..STARTUP_DISCARD_WORDS {
+            trng.random_u32();
+        }
+        trng.self_test()?;
+        Ok(trng)
+    }
+
+    /// Run a basic health check on the TRNG output.
+    ///
+    /// 64 words are sampled and the check fails if two consecutive words are
+    /// identical (a repetition count test with a cutoff of two) or if any bit
+    /// holds the same value in every sample. A healthy TRNG fails either
+    /// test with a probability of less than 2^-25.
+    ///
+    /// This only detects an output that is stuck or has stuck bits. It is not
+    /// a statistical test of entropy quality and cannot detect a biased
+    /// source or one that has been replaced with a deterministic generator.
+    pub fn self_test(&self) -> Result<(), TrngError> {
+        let mut prev = self.random_u32();
+        let mut ones = prev;
+        let mut zeros = !prev;
+        for _ in 1..HEALTH_CHECK_WORDS {
+            let word = self.random_u32();
+            if word == prev {
+                return Err(TrngError::HealthCheckFailed);
+            }
+            ones |= word;
+            zeros |= !word;
+            prev = word;
+        }
+        if ones != u32::MAX || zeros != u32::MAX {
+            return Err(TrngError::HealthCheckFailed);
+        }
+        Ok(())
     }
 
     /// Check if the TRNG peripheral is ready to generate random numbers.
@@ -39,35 +93,62 @@ impl Trng {
         self.trng.status().read().rdy().is_ready()
     }
 
-    /// Generate a random 32-bit number.
+    /// Generate a random 32-bit number. Blocks until the TRNG has a fresh
+    /// word ready.
     #[inline(always)]
-    pub fn gen_u32(&self) -> u32 {
+    pub fn random_u32(&self) -> u32 {
         while !self._is_ready() {}
-        self.trng.data().read().bits() as u32
+        self.trng.data().read().bits()
+    }
+
+    /// Generate a random 32-bit number. Equivalent to
+    /// [`Trng::random_u32()`].
+    #[inline(always)]
+    pub fn gen_u32(&self) -> u32 {
+        self.random_u32()
+    }
+
+    /// Fill a buffer of any length with random bytes. If the length is not a
+    /// multiple of four, the unused bytes of the final word are discarded.
+    pub fn fill_bytes(&self, dest: &mut [u8]) {
+        let mut chunks = dest.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            chunk.copy_from_slice(&self.random_u32().to_le_bytes());
+        }
+        let rem = chunks.into_remainder();
+        if !rem.is_empty() {
+            let len = rem.len();
+            rem.copy_from_slice(&self.random_u32().to_le_bytes()[..len]);
+        }
     }
 }
 
-/// Enhanced functionality for the TRNG peripheral using the [`rand`] crate.
-/// This trait implementation can be disabled by removing the `rand` feature
-/// flag since you may want to implement your own [`RngCore`].
+/// Enhanced functionality for the TRNG peripheral using the [`rand_core`]
+/// crate, so the TRNG can be used anywhere an [`RngCore`] + [`CryptoRng`] is
+/// expected. This trait implementation can be disabled by removing the
+/// `rand_core` feature flag (and its `rand` alias) since you may want to
+/// implement your own [`RngCore`].
 ///
 /// Example:
 /// ```
 /// // Create a new TRNG peripheral instance
-/// let trng = Trng::new(p.trng, &mut gcr.reg);
+/// let mut trng = Trng::new(p.trng, &mut gcr.reg).unwrap();
 /// // Generate a random 32-bit number
-/// let random_u32 = trng.next_u32(); // Equivalent to trng.gen_u32()
+/// let random_u32 = trng.next_u32(); // Equivalent to trng.random_u32()
 /// // Generate a random 64-bit number
 /// let random_u64 = trng.next_u64();
 /// // Fill a buffer with random bytes
 /// let mut buffer = [0u8; 16];
 /// trng.fill_bytes(&mut buffer);
+/// // Sanity check: consecutive words should (almost) never repeat
+/// let (a, b) = (trng.next_u32(), trng.next_u32());
+/// assert_ne!(a, b);
 /// ```
-#[cfg(feature = "rand")]
+#[cfg(feature = "rand_core")]
 impl RngCore for Trng {
     #[inline(always)]
     fn next_u32(&mut self) -> u32 {
-        self.gen_u32()
+        self.random_u32()
     }
 
     #[inline(always)]
@@ -77,9 +158,9 @@ impl RngCore for Trng {
 
     #[inline(always)]
     fn fill_bytes(&mut self, dest: &mut [u8]) {
-        fill_bytes_via_next(self, dest);
+        Trng::fill_bytes(self, dest);
     }
 }
 
-#[cfg(feature = "rand")]
+#[cfg(feature = "rand_core")]
 impl CryptoRng for Trng {}
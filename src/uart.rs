@@ -10,12 +10,99 @@ use crate::gpio::{Af1, Pin};
 use embedded_hal_nb::{nb, serial};
 use paste::paste;
 
+/// Maximum relative error between the requested and achieved baud rate, in
+/// tenths of a percent.
+const MAX_BAUD_ERROR_PERMILLE: u32 = 20;
+
+/// Errors that can occur while configuring or using a UART peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UartError {
+    /// The requested baud rate cannot be generated from the clock source
+    /// within 2%.
+    UnachievableBaud,
+    /// The combination of data bits and stop bits is not supported.
+    InvalidFrame,
+    /// A byte was received with the wrong parity bit.
+    Parity,
+    /// A byte was received without a valid stop bit.
+    Framing,
+    /// The RX FIFO overflowed and received bytes were lost. Reads do not
+    /// fail with this, overflows are reported by `take_overrun()`.
+    Overrun,
+}
+
+impl From<UartError> for serial::ErrorKind {
+    fn from(error: UartError) -> Self {
+        match error {
+            UartError::Parity => serial::ErrorKind::Parity,
+            UartError::Framing => serial::ErrorKind::FrameFormat,
+            UartError::Overrun => serial::ErrorKind::Overrun,
+            _ => serial::ErrorKind::Other,
+        }
+    }
+}
+
+impl embedded_io::Error for UartError {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            UartError::Parity | UartError::Framing => embedded_io::ErrorKind::InvalidData,
+            _ => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
+/// Frame configuration of a UART peripheral.
+///
+/// Example (7E1 for a legacy device):
+/// ```
+/// let config = UartConfig {
+///     baud: 9600,
+///     data_bits: DataBits::Seven,
+///     stop_bits: StopBits::One,
+///     parity: ParityBit::Even,
+/// };
+/// let uart = UartPeripheral::uart0(p.uart0, &mut gcr.reg, rx, tx)
+///     .clock_pclk(&clks.pclk)
+///     .config(config)
+///     .build()
+///     .unwrap();
+/// // A byte received with the wrong parity bit is reported as an error
+/// assert_eq!(uart.read_byte(), Err(UartError::Parity));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UartConfig {
+    /// Baud rate in bits per second.
+    pub baud: u32,
+    /// Number of data bits.
+    pub data_bits: DataBits,
+    /// Number of stop bits.
+    pub stop_bits: StopBits,
+    /// Parity bit configuration.
+    pub parity: ParityBit,
+}
+
+impl Default for UartConfig {
+    /// 115200 baud, 8N1.
+    fn default() -> Self {
+        Self {
+            baud: 115200,
+            data_bits: DataBits::Eight,
+            stop_bits: StopBits::One,
+            parity: ParityBit::None,
+        }
+    }
+}
+
 enum UartClockSource {
     Pclk,
     Ibro,
 }
 
 /// Number of data bits in a UART frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DataBits {
     /// 5 data bits.
     Five,
@@ -28,15 +115,23 @@ pub enum DataBits {
 }
 
 /// Number of stop bits in a UART frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum StopBits {
     /// 1 stop bit.
     One,
     /// 1.5 stop bits when using 5 data bits.
     /// 2 stop bits when using 6-8 data bits.
     More,
+    /// 1.5 stop bits, only valid with 5 data bits.
+    OneAndHalf,
+    /// 2 stop bits, only valid with 6-8 data bits.
+    Two,
 }
 
 /// Parity bit configuration for a UART frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ParityBit {
     /// Parity bit is not used.
     None,
@@ -45,18 +140,18 @@ pub enum ParityBit {
     ///
     /// Examples:
     /// - `01101` would have a parity bit of `1` since there is an odd number
-    /// of 1s and an extra 1 is needed to make it even.
+    ///   of 1s and an extra 1 is needed to make it even.
     /// - `01100` would have a parity bit of `0` since there is already an even
-    /// number of 1s.
+    ///   number of 1s.
     Even,
     /// The total count of 1 bits in the data frame, including the parity bit,
     /// is odd.
     ///
     /// Examples:
     /// - `01101` would have a parity bit of `0` since there is already an odd
-    /// number of 1s.
+    ///   number of 1s.
     /// - `01100` would have a parity bit of `1` since there is an even number
-    /// of 1s and an extra 1 is needed to make it odd.
+    ///   of 1s and an extra 1 is needed to make it odd.
     Odd,
     /// The parity bit is always `0`.
     SpaceZero,
@@ -108,15 +203,15 @@ pub mod marker {
 ///     pins.p0_0.into_af1(),   // RX pin
 ///     pins.p0_1.into_af1()    // TX pin
 /// )
-///     .clock_pclk(&clks.pclk) // or clocks_ibro(&ibro)
+///     .clock_pclk(&clks.pclk) // or clock_ibro(&ibro)
 ///     .baud(115200)
 ///     .data_bits(hal::uart::DataBits::Eight)
 ///     .stop_bits(hal::uart::StopBits::One)
-///     .parity(hal::uart::Parity::None)
-///     .build();
+///     .parity(hal::uart::ParityBit::None)
+///     .build()
+///     .unwrap();
 ///
 /// uart.write_bytes(b"Hello, world!\r\n");
-
 /// ```
 pub struct UartPeripheral<STATE: marker::UartState, CLOCK, UART, RX, TX, CTS, RTS> {
     _state: PhantomData<STATE>,
@@ -132,6 +227,7 @@ pub struct UartPeripheral<STATE: marker::UartState, CLOCK, UART, RX, TX, CTS, RT
     data_bits: DataBits,
     stop_bits: StopBits,
     parity: ParityBit,
+    flow_control: bool,
 }
 
 pub struct BuiltUartPeripheral<UART, RX, TX, CTS, RTS> {
@@ -142,28 +238,41 @@ pub struct BuiltUartPeripheral<UART, RX, TX, CTS, RTS> {
     _rts_pin: RTS,
 }
 
-// TODO
-// pub struct UartReceiver<UART, RX, CTS> {
-//     _uart: UART,
-//     _rx_pin: RX,
-//     _cts_pin: CTS,
-// }
+/// Receiving half of a UART peripheral, created by
+/// [`BuiltUartPeripheral::split()`].
+///
+/// The receiver only accesses the RX FIFO, the RX status bits, and the RX
+/// bits (`RX_*`) of the interrupt registers. It never writes `CTRL`.
+pub struct UartReceiver<UART, RX, RTS> {
+    uart: *const UartRegisterBlock,
+    _uart: PhantomData<UART>,
+    _rx_pin: RX,
+    _rts_pin: RTS,
+}
 
-// TODO
-// pub struct UartTransmitter<UART, TX, RTS> {
-//     _uart: UART,
-//     _tx_pin: TX,
-//     _rts_pin: RTS,
-// }
+// Safety: The receiver only accesses registers that the transmitter does not
+unsafe impl<UART: Send, RX: Send, RTS: Send> Send for UartReceiver<UART, RX, RTS> {}
+
+/// Transmitting half of a UART peripheral, created by
+/// [`BuiltUartPeripheral::split()`].
+///
+/// The transmitter only accesses the TX FIFO, the TX status bits, and the
+/// TX bits (`TX_*`) of the interrupt registers. It never writes `CTRL`.
+pub struct UartTransmitter<UART, TX, CTS> {
+    uart: UART,
+    _tx_pin: TX,
+    _cts_pin: CTS,
+}
 
 /// Pins that can be used for receiving data on a UART peripheral.
 pub trait RxPin<UART>: crate::Sealed {}
 /// Pins that can be used for transmitting data on a UART peripheral.
 pub trait TxPin<UART>: crate::Sealed {}
 
-// TODO: Implement CTS and RTS pins for hardware flow control
-// pub trait CtsPin<UART>: crate::Sealed {}
-// pub trait RtsPin<UART>: crate::Sealed {}
+/// Pins that can be used as the clear-to-send input of a UART peripheral.
+pub trait CtsPin<UART>: crate::Sealed {}
+/// Pins that can be used as the request-to-send output of a UART peripheral.
+pub trait RtsPin<UART>: crate::Sealed {}
 
 // All UART peripherals are derived from the same register block
 type UartRegisterBlock = crate::pac::uart0::RegisterBlock;
@@ -185,14 +294,18 @@ macro_rules! uart {
             impl crate::Sealed for $tx_pin {}
             impl TxPin<$uart> for $tx_pin {}
 
+            impl crate::Sealed for $cts_pin {}
+            impl CtsPin<$uart> for $cts_pin {}
+
+            impl crate::Sealed for $rts_pin {}
+            impl RtsPin<$uart> for $rts_pin {}
+
             impl UartPeripheral<
                 marker::NotBuilt,
                 marker::NotClockSet,
                 $uart,
                 $rx_pin,
                 $tx_pin,
-                // $cts_pin,
-                // $rts_pin
                 (),
                 (),
             >
@@ -222,6 +335,7 @@ macro_rules! uart {
                         data_bits: DataBits::Eight,
                         stop_bits: StopBits::One,
                         parity: ParityBit::None,
+                        flow_control: false,
                     }
                 }
             }
@@ -232,22 +346,22 @@ macro_rules! uart {
 uart! {Uart0,
     rx: Pin<0, 0, Af1>,
     tx: Pin<0, 1, Af1>,
-    cts: (),
-    rts: (),
+    cts: Pin<0, 2, Af1>,
+    rts: Pin<0, 3, Af1>,
 }
 
 uart! {Uart1,
     rx: Pin<0, 12, Af1>,
     tx: Pin<0, 13, Af1>,
-    cts: (),
-    rts: (),
+    cts: Pin<0, 14, Af1>,
+    rts: Pin<0, 15, Af1>,
 }
 
 uart! {Uart2,
     rx: Pin<1, 0, Af1>,
     tx: Pin<1, 1, Af1>,
-    cts: (),
-    rts: (),
+    cts: Pin<1, 2, Af1>,
+    rts: Pin<1, 3, Af1>,
 }
 
 /// # Clock Methods
@@ -275,6 +389,7 @@ impl<UART, RX, TX, CTS, RTS>
             data_bits: self.data_bits,
             stop_bits: self.stop_bits,
             parity: self.parity,
+            flow_control: self.flow_control,
         }
     }
 
@@ -297,6 +412,7 @@ impl<UART, RX, TX, CTS, RTS>
             data_bits: self.data_bits,
             stop_bits: self.stop_bits,
             parity: self.parity,
+            flow_control: self.flow_control,
         }
     }
 }
@@ -341,31 +457,47 @@ where
         self
     }
 
-    // TODO: Implement hardware flow control
-    // pub fn enable_hfc(
-    //     self,
-    //     cts_pin: $cts_pin,
-    //     rts_pin: $rts_pin
-    // ) -> UartPeripheral<NotBuilt, CLOCK, $uart, RX, TX, $cts_pin, $rts_pin> {
-    //     // Enable CTS and RTS pins
-    //     // cts_pin.enable();
-    //     // rts_pin.enable();
-    //     UartPeripheral {
-    //         _state: PhantomData,
-    //         _clock: PhantomData,
-    //         uart: self.uart,
-    //         _rx_pin: self._rx_pin,
-    //         _tx_pin: self._tx_pin,
-    //         _cts_pin: cts_pin,
-    //         _rts_pin: rts_pin,
-    //         clk_src: self.clk_src,
-    //         clk_src_freq: self.clk_src_freq,
-    //         baud: self.baud,
-    //         data_bits: self.data_bits,
-    //         stop_bits: self.stop_bits,
-    //         parity: self.parity,
-    //     }
-    // }
+    /// Set the baud rate and frame format from a [`UartConfig`].
+    pub fn config(mut self, config: UartConfig) -> Self {
+        self.baud = config.baud;
+        self.data_bits = config.data_bits;
+        self.stop_bits = config.stop_bits;
+        self.parity = config.parity;
+        self
+    }
+}
+
+impl<CLOCK, UART, RX, TX> UartPeripheral<marker::NotBuilt, CLOCK, UART, RX, TX, (), ()>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    /// Enable hardware flow control with the given CTS and RTS pins.
+    /// Transmission pauses while CTS is deasserted, and RTS is deasserted
+    /// while the RX FIFO is full.
+    ///
+    /// Default: disabled, for two-wire setups.
+    pub fn with_flow_control<CTS: CtsPin<UART>, RTS: RtsPin<UART>>(
+        self,
+        cts_pin: CTS,
+        rts_pin: RTS,
+    ) -> UartPeripheral<marker::NotBuilt, CLOCK, UART, RX, TX, CTS, RTS> {
+        UartPeripheral {
+            _state: PhantomData,
+            _clock: PhantomData,
+            uart: self.uart,
+            _rx_pin: self._rx_pin,
+            _tx_pin: self._tx_pin,
+            _cts_pin: cts_pin,
+            _rts_pin: rts_pin,
+            clk_src: self.clk_src,
+            clk_src_freq: self.clk_src_freq,
+            baud: self.baud,
+            data_bits: self.data_bits,
+            stop_bits: self.stop_bits,
+            parity: self.parity,
+            flow_control: true,
+        }
+    }
 }
 
 impl<UART, RX, TX, CTS, RTS>
@@ -375,10 +507,24 @@ where
 {
     /// Apply all settings and configure the UART peripheral.
     /// This must be called before the UART peripheral can be used.
-    pub fn build(self) -> BuiltUartPeripheral<UART, RX, TX, CTS, RTS> {
-        // Configure the UART peripheral
+    ///
+    /// Returns [`UartError::UnachievableBaud`] if the baud rate cannot be
+    /// generated from the clock source within 2%, or
+    /// [`UartError::InvalidFrame`] if the stop bits do not match the data
+    /// bits.
+    pub fn build(self) -> Result<BuiltUartPeripheral<UART, RX, TX, CTS, RTS>, UartError> {
+        match (&self.stop_bits, &self.data_bits) {
+            (StopBits::OneAndHalf, DataBits::Five) => {}
+            (StopBits::OneAndHalf, _) => return Err(UartError::InvalidFrame),
+            (StopBits::Two, DataBits::Five) => return Err(UartError::InvalidFrame),
+            _ => {}
+        }
         let clk_src_freq = self.clk_src_freq.unwrap();
+        let clkdiv = _clock_divisor(clk_src_freq, self.baud)?;
+        // Configure the UART peripheral
         self.uart.ctrl().write(|w| {
+            // Raise the RX threshold interrupt (if enabled) for every byte
+            unsafe { w.rx_thd_val().bits(1) };
             w.ucagm().set_bit();
             match self.clk_src {
                 Some(UartClockSource::Pclk) => w.bclksrc().peripheral_clock(),
@@ -386,6 +532,7 @@ where
                 None => unreachable!("UART clock source not set"),
             };
             w.bclken().set_bit();
+            w.hfc_en().bit(self.flow_control);
             match self.data_bits {
                 DataBits::Five => w.char_size()._5bits(),
                 DataBits::Six => w.char_size()._6bits(),
@@ -394,7 +541,7 @@ where
             };
             match self.stop_bits {
                 StopBits::One => w.stopbits().clear_bit(),
-                StopBits::More => w.stopbits().set_bit(),
+                StopBits::More | StopBits::OneAndHalf | StopBits::Two => w.stopbits().set_bit(),
             };
             match self.parity {
                 ParityBit::None => w.par_en().clear_bit(),
@@ -403,22 +550,21 @@ where
                 ParityBit::SpaceZero => w.par_en().set_bit().par_md().clear_bit(),
                 ParityBit::MarkOne => w.par_en().set_bit().par_md().set_bit(),
             };
-            return w;
+            w
         });
         // Set the baud rate
-        let clkdiv = clk_src_freq / self.baud;
         self.uart
             .clkdiv()
             .write(|w| unsafe { w.clkdiv().bits(clkdiv) });
         // Wait until baud clock is ready
         while self.uart.ctrl().read().bclkrdy().bit_is_clear() {}
-        BuiltUartPeripheral {
+        Ok(BuiltUartPeripheral {
             uart: self.uart,
             _rx_pin: self._rx_pin,
             _tx_pin: self._tx_pin,
             _cts_pin: self._cts_pin,
             _rts_pin: self._rts_pin,
-        }
+        })
     }
 }
 
@@ -449,11 +595,8 @@ where
 
     #[doc(hidden)]
     #[inline(always)]
-    fn _read_byte(&self) -> nb::Result<u8, serial::ErrorKind> {
-        if self._is_rx_empty() {
-            return Err(nb::Error::WouldBlock);
-        }
-        Ok(self.uart.fifo().read().data().bits())
+    fn _read_byte(&self) -> nb::Result<u8, UartError> {
+        _read_fifo(&self.uart)
     }
 
     #[doc(hidden)]
@@ -474,8 +617,21 @@ where
     }
 
     /// Reads a single byte. This is a blocking operation.
-    pub fn read_byte(&self) -> u8 {
-        nb::block!(self._read_byte()).unwrap()
+    ///
+    /// A byte received with a parity or framing error is consumed and
+    /// reported as [`UartError::Parity`] or [`UartError::Framing`]. Bytes
+    /// lost to an RX FIFO overflow are reported by
+    /// [`BuiltUartPeripheral::take_overrun()`] instead, so the bytes that
+    /// did arrive are still returned.
+    pub fn read_byte(&self) -> Result<u8, UartError> {
+        nb::block!(self._read_byte())
+    }
+
+    /// Returns [`true`] if the RX FIFO overflowed, losing received bytes,
+    /// since the last call.
+    #[inline(always)]
+    pub fn take_overrun(&self) -> bool {
+        _take_overrun(&self.uart)
     }
 
     /// Writes a single byte. This is a blocking operation.
@@ -485,11 +641,12 @@ where
 
     /// Reads bytes to a buffer. The entire length of the buffer will be
     /// filled with bytes from the UART peripheral. This is a blocking
-    /// operation.
-    pub fn read_bytes(&self, buffer: &mut [u8]) {
+    /// operation that stops at the first receive error.
+    pub fn read_bytes(&self, buffer: &mut [u8]) -> Result<(), UartError> {
         for byte in buffer {
-            *byte = self.read_byte();
+            *byte = self.read_byte()?;
         }
+        Ok(())
     }
 
     /// Write bytes from a buffer (blocking). The entire buffer will be written
@@ -501,6 +658,358 @@ where
     }
 }
 
+impl<UART, RX, TX, CTS, RTS> BuiltUartPeripheral<UART, RX, TX, CTS, RTS>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    /// Split the UART peripheral into independent receiving and
+    /// transmitting halves, e.g. to move them into different tasks.
+    ///
+    /// Example:
+    /// ```
+    /// let (tx, rx) = uart.split();
+    /// tx.write_bytes(b"ready\r\n");
+    /// let byte = rx.read_byte().unwrap();
+    /// let uart = BuiltUartPeripheral::join(tx, rx);
+    /// ```
+    pub fn split(self) -> (UartTransmitter<UART, TX, CTS>, UartReceiver<UART, RX, RTS>) {
+        let rx = UartReceiver {
+            uart: &*self.uart as *const UartRegisterBlock,
+            _uart: PhantomData,
+            _rx_pin: self._rx_pin,
+            _rts_pin: self._rts_pin,
+        };
+        let tx = UartTransmitter {
+            uart: self.uart,
+            _tx_pin: self._tx_pin,
+            _cts_pin: self._cts_pin,
+        };
+        (tx, rx)
+    }
+
+    /// Join the halves of a split UART peripheral back together.
+    pub fn join(tx: UartTransmitter<UART, TX, CTS>, rx: UartReceiver<UART, RX, RTS>) -> Self {
+        BuiltUartPeripheral {
+            uart: tx.uart,
+            _rx_pin: rx._rx_pin,
+            _tx_pin: tx._tx_pin,
+            _cts_pin: tx._cts_pin,
+            _rts_pin: rx._rts_pin,
+        }
+    }
+}
+
+impl<UART, RX, RTS> UartReceiver<UART, RX, RTS> {
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _regs(&self) -> &UartRegisterBlock {
+        // Safety: The pointer comes from the UART peripheral, which is kept
+        // alive by the transmitting half until the halves are joined
+        unsafe { &*self.uart }
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _is_rx_empty(&self) -> bool {
+        self._regs().status().read().rx_em().bit_is_set()
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _read_byte(&self) -> nb::Result<u8, UartError> {
+        _read_fifo(self._regs())
+    }
+
+    /// Reads a single byte. This is a blocking operation.
+    ///
+    /// A byte received with a parity or framing error is consumed and
+    /// reported as [`UartError::Parity`] or [`UartError::Framing`]. See
+    /// [`UartReceiver::take_overrun()`] for RX FIFO overflows.
+    pub fn read_byte(&self) -> Result<u8, UartError> {
+        nb::block!(self._read_byte())
+    }
+
+    /// Returns [`true`] if the RX FIFO overflowed, losing received bytes,
+    /// since the last call.
+    #[inline(always)]
+    pub fn take_overrun(&self) -> bool {
+        _take_overrun(self._regs())
+    }
+
+    /// Reads bytes to a buffer. The entire length of the buffer will be
+    /// filled with bytes from the UART peripheral. This is a blocking
+    /// operation that stops at the first receive error.
+    pub fn read_bytes(&self, buffer: &mut [u8]) -> Result<(), UartError> {
+        for byte in buffer {
+            *byte = self.read_byte()?;
+        }
+        Ok(())
+    }
+}
+
+impl<UART, RX, RTS> UartReceiver<UART, RX, RTS> {
+    /// Switch to interrupt-driven reception into a ring buffer.
+    ///
+    /// Example:
+    /// ```
+    /// static RX: Mutex<RefCell<Option<InterruptReceiver<'static, Uart0, RxPin, ()>>>> =
+    ///     Mutex::new(RefCell::new(None));
+    ///
+    /// static mut RX_BUF: [u8; 256] = [0; 256];
+    /// let rx = rx.into_interrupt(unsafe { &mut RX_BUF });
+    /// interrupt::free(|cs| RX.borrow(cs).replace(Some(rx)));
+    /// unsafe { NVIC::unmask(Interrupt::UART0) };
+    ///
+    /// #[interrupt]
+    /// fn UART0() {
+    ///     interrupt::free(|cs| {
+    ///         if let Some(rx) = RX.borrow(cs).borrow_mut().as_mut() {
+    ///             rx.on_interrupt();
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn into_interrupt(self, buffer: &mut [u8]) -> InterruptReceiver<'_, UART, RX, RTS> {
+        let regs = self._regs();
+        regs.int_fl().write(|w| {
+            w.rx_thd().set_bit();
+            w.rx_ov().set_bit()
+        });
+        regs.int_en().modify(|_, w| {
+            w.rx_thd().set_bit();
+            w.rx_ov().set_bit()
+        });
+        InterruptReceiver {
+            rx: self,
+            buffer,
+            head: 0,
+            len: 0,
+            overrun: false,
+        }
+    }
+}
+
+/// Interrupt-driven UART receiver that buffers incoming bytes in a
+/// user-provided ring buffer.
+///
+/// [`InterruptReceiver::on_interrupt()`] must be called from the UART
+/// interrupt handler to move bytes from the RX FIFO into the buffer.
+pub struct InterruptReceiver<'a, UART, RX, RTS> {
+    rx: UartReceiver<UART, RX, RTS>,
+    buffer: &'a mut [u8],
+    head: usize,
+    len: usize,
+    overrun: bool,
+}
+
+impl<'a, UART, RX, RTS> InterruptReceiver<'a, UART, RX, RTS> {
+    /// Drain the RX FIFO into the ring buffer. Call this from the UART
+    /// interrupt handler.
+    ///
+    /// If the ring buffer is full, incoming bytes are dropped and the
+    /// overrun flag is set; buffered bytes are never overwritten.
+    pub fn on_interrupt(&mut self) {
+        let regs = self.rx._regs();
+        if _take_overrun(regs) {
+            // The hardware FIFO overflowed before the interrupt was serviced,
+            // the bytes still in it are valid and buffered below
+            self.overrun = true;
+        }
+        loop {
+            let byte = match self.rx._read_byte() {
+                Ok(byte) => byte,
+                Err(nb::Error::WouldBlock) => break,
+                // Bytes with parity or framing errors are dropped
+                Err(nb::Error::Other(_)) => continue,
+            };
+            if self.len == self.buffer.len() {
+                self.overrun = true;
+                continue;
+            }
+            let tail = (self.head + self.len) % self.buffer.len();
+            self.buffer[tail] = byte;
+            self.len += 1;
+        }
+        regs.int_fl().write(|w| w.rx_thd().set_bit());
+    }
+
+    /// Number of bytes waiting in the ring buffer.
+    #[inline(always)]
+    pub fn bytes_available(&self) -> usize {
+        self.len
+    }
+
+    /// Move buffered bytes into `buf`, returning the number of bytes read.
+    /// This never blocks, and returns `0` if no bytes are buffered.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let count = buf.len().min(self.len);
+        for byte in buf.iter_mut().take(count) {
+            *byte = self.buffer[self.head];
+            self.head = (self.head + 1) % self.buffer.len();
+        }
+        self.len -= count;
+        count
+    }
+
+    /// Returns [`true`] if bytes were dropped because the ring buffer or the
+    /// RX FIFO was full.
+    #[inline(always)]
+    pub fn overrun(&self) -> bool {
+        self.overrun
+    }
+
+    /// Clear the overrun flag.
+    #[inline(always)]
+    pub fn clear_overrun(&mut self) {
+        self.overrun = false;
+    }
+
+    /// Disable the RX interrupts and return the receiver and the buffer.
+    pub fn free(self) -> (UartReceiver<UART, RX, RTS>, &'a mut [u8]) {
+        self.rx._regs().int_en().modify(|_, w| {
+            w.rx_thd().clear_bit();
+            w.rx_ov().clear_bit()
+        });
+        (self.rx, self.buffer)
+    }
+}
+
+impl<UART, TX, CTS> UartTransmitter<UART, TX, CTS>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _is_tx_full(&self) -> bool {
+        self.uart.status().read().tx_full().bit_is_set()
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _is_tx_empty(&self) -> bool {
+        self.uart.status().read().tx_em().bit_is_set()
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    fn _write_byte(&self, byte: u8) -> nb::Result<(), serial::ErrorKind> {
+        if self._is_tx_full() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.uart.fifo().write(|w| unsafe { w.data().bits(byte) });
+        Ok(())
+    }
+
+    /// Flush the transmit buffer, ensuring that all bytes have been sent.
+    /// This is a blocking operation.
+    #[inline(always)]
+    pub fn flush_tx(&self) {
+        while !self._is_tx_empty() {}
+    }
+
+    /// Writes a single byte. This is a blocking operation.
+    pub fn write_byte(&self, byte: u8) {
+        nb::block!(self._write_byte(byte)).unwrap()
+    }
+
+    /// Write bytes from a buffer (blocking). The entire buffer will be written
+    /// to the UART peripheral. This is a blocking operation.
+    pub fn write_bytes(&self, buffer: &[u8]) {
+        for byte in buffer {
+            self.write_byte(*byte);
+        }
+    }
+}
+
+impl<UART, RX, RTS> serial::ErrorType for UartReceiver<UART, RX, RTS> {
+    type Error = serial::ErrorKind;
+}
+
+impl<UART, RX, RTS> serial::Read<u8> for UartReceiver<UART, RX, RTS> {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self._read_byte().map_err(|e| e.map(Into::into))
+    }
+}
+
+impl<UART, RX, RTS> embedded_io::ErrorType for UartReceiver<UART, RX, RTS> {
+    type Error = UartError;
+}
+
+impl<UART, RX, RTS> embedded_io::Read for UartReceiver<UART, RX, RTS> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // Block until at least one byte is available, then read as many
+        // bytes as are available
+        buf[0] = self.read_byte()?;
+        let mut count = 1;
+        while count < buf.len() && !self._is_rx_empty() {
+            buf[count] = self.read_byte()?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl<UART, RX, RTS> embedded_io::ReadReady for UartReceiver<UART, RX, RTS> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self._is_rx_empty())
+    }
+}
+
+impl<UART, TX, CTS> serial::ErrorType for UartTransmitter<UART, TX, CTS>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    type Error = serial::ErrorKind;
+}
+
+impl<UART, TX, CTS> serial::Write<u8> for UartTransmitter<UART, TX, CTS>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self._write_byte(byte)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.flush_tx();
+        Ok(())
+    }
+}
+
+impl<UART, TX, CTS> embedded_io::ErrorType for UartTransmitter<UART, TX, CTS>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    type Error = core::convert::Infallible;
+}
+
+impl<UART, TX, CTS> embedded_io::Write for UartTransmitter<UART, TX, CTS>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_tx();
+        Ok(())
+    }
+}
+
+impl<UART, TX, CTS> embedded_io::WriteReady for UartTransmitter<UART, TX, CTS>
+where
+    UART: Deref<Target = UartRegisterBlock>,
+{
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self._is_tx_full())
+    }
+}
+
 // Embedded HAL non-blocking serial traits
 impl<UART, RX, TX, CTS, RTS> serial::ErrorType for BuiltUartPeripheral<UART, RX, TX, CTS, RTS>
 where
@@ -514,7 +1023,7 @@ where
     UART: Deref<Target = UartRegisterBlock>,
 {
     fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        self._read_byte()
+        self._read_byte().map_err(|e| e.map(Into::into))
     }
 }
 
@@ -537,7 +1046,7 @@ impl<UART, RX, TX, CTS, RTS> embedded_io::ErrorType for BuiltUartPeripheral<UART
 where
     UART: Deref<Target = UartRegisterBlock>,
 {
-    type Error = core::convert::Infallible;
+    type Error = UartError;
 }
 
 impl<UART, RX, TX, CTS, RTS> embedded_io::Read for BuiltUartPeripheral<UART, RX, TX, CTS, RTS>
@@ -546,19 +1055,19 @@ where
 {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         let mut count = 0;
-        if buf.len() == 0 {
+        if buf.is_empty() {
             return Ok(0);
         }
         // If no bytes are currently available to read, this function blocks
         // until at least one byte is available.
         if self._is_rx_empty() {
-            let byte = self.read_byte();
+            let byte = self.read_byte()?;
             buf[count] = byte;
             count += 1;
         // If bytes are available, a non-zero amount of bytes is read.
         } else {
             while count < buf.len() && !self._is_rx_empty() {
-                let byte = self.read_byte();
+                let byte = self.read_byte()?;
                 buf[count] = byte;
                 count += 1;
             }
@@ -601,3 +1110,61 @@ where
         Ok(!self._is_tx_full())
     }
 }
+
+/// Reads a byte from the RX FIFO, reporting a parity or framing error
+/// flagged by the peripheral. A byte with an error is still removed from
+/// the FIFO, and the error flags are cleared along with it. FIFO overflows
+/// do not fail the read, see [`_take_overrun()`].
+#[doc(hidden)]
+#[inline(always)]
+fn _read_fifo(uart: &UartRegisterBlock) -> nb::Result<u8, UartError> {
+    if uart.status().read().rx_em().bit_is_set() {
+        return Err(nb::Error::WouldBlock);
+    }
+    let flags = uart.int_fl().read();
+    let byte = uart.fifo().read().data().bits();
+    let (parity, framing) = (flags.rx_par().bit_is_set(), flags.rx_ferr().bit_is_set());
+    if parity || framing {
+        uart.int_fl().write(|w| {
+            w.rx_par().bit(parity);
+            w.rx_ferr().bit(framing)
+        });
+    }
+    if parity {
+        return Err(nb::Error::Other(UartError::Parity));
+    }
+    if framing {
+        return Err(nb::Error::Other(UartError::Framing));
+    }
+    Ok(byte)
+}
+
+/// Checks if the RX FIFO overflowed and clears the flag.
+#[doc(hidden)]
+#[inline(always)]
+fn _take_overrun(uart: &UartRegisterBlock) -> bool {
+    let overrun = uart.int_fl().read().rx_ov().bit_is_set();
+    if overrun {
+        uart.int_fl().write(|w| w.rx_ov().set_bit());
+    }
+    overrun
+}
+
+/// Calculates the baud clock divisor, rounded to the nearest integer.
+#[doc(hidden)]
+#[inline(always)]
+fn _clock_divisor(clk_src_freq: u32, baud: u32) -> Result<u32, UartError> {
+    if baud == 0 {
+        return Err(UartError::UnachievableBaud);
+    }
+    let clkdiv = (clk_src_freq + baud / 2) / baud;
+    // The divisor field is 20 bits wide
+    if clkdiv == 0 || clkdiv >= 1 << 20 {
+        return Err(UartError::UnachievableBaud);
+    }
+    let actual = clk_src_freq / clkdiv;
+    if actual.abs_diff(baud) as u64 * 1000 > baud as u64 * MAX_BAUD_ERROR_PERMILLE as u64 {
+        return Err(UartError::UnachievableBaud);
+    }
+    Ok(clkdiv)
+}
@@ -0,0 +1,74 @@
+//! # Wait Strategies
+//!
+//! Blocking methods that poll a peripheral until it is done normally spin
+//! in a tight loop. Their `_with` variants take a [`WaitStrategy`] instead,
+//! which runs on each poll and decides how the CPU spends that time:
+//! - [`Spin`]: keep polling, the default behavior.
+//! - [`Wfi`]: sleep until the next interrupt, to save power.
+//! - Any `FnMut()` closure: for example to feed the watchdog, or to yield
+//!   to a cooperative scheduler.
+//!
+//! A closure that feeds the watchdog must respect its early window. The
+//! default watchdog configuration is windowed, and a feed before the early
+//! threshold resets the chip, so feeding on every poll resets the board
+//! almost immediately. Only feed once
+//! [`WdtMargin::until_early_ok`](crate::wdt::WdtMargin::until_early_ok) is
+//! zero, which is free to check on each poll.
+//!
+//! Example:
+//! ```
+//! // Feed the watchdog while a long batch is encrypted, outside its early window
+//! let mut feed = || {
+//!     if wdt.margin().until_early_ok == 0 {
+//!         wdt.feed();
+//!     }
+//! };
+//! aes.encrypt_blocks_with(&mut blocks, &mut feed).unwrap();
+//! // Sleep while a DMA transfer runs, woken by its interrupt
+//! transfer.wait_with(&mut Wfi).unwrap();
+//! ```
+//!
+//! The flash controller does not accept a strategy, since code that runs
+//! while the flash is busy must not be fetched from flash.
+
+/// What to do while waiting for a peripheral, called between polls of its
+/// status.
+pub trait WaitStrategy {
+    /// Called once per poll while the peripheral is still busy.
+    fn wait(&mut self);
+}
+
+/// Poll continuously, with a spin loop hint to the CPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Spin;
+
+impl WaitStrategy for Spin {
+    #[inline(always)]
+    fn wait(&mut self) {
+        core::hint::spin_loop();
+    }
+}
+
+/// Sleep until the next interrupt between polls.
+///
+/// The interrupt of the peripheral being waited on, or another periodic
+/// interrupt such as SysTick, must be enabled in the NVIC. Otherwise the
+/// CPU may never wake up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Wfi;
+
+impl WaitStrategy for Wfi {
+    #[inline(always)]
+    fn wait(&mut self) {
+        cortex_m::asm::wfi();
+    }
+}
+
+impl<F: FnMut()> WaitStrategy for F {
+    #[inline(always)]
+    fn wait(&mut self) {
+        self()
+    }
+}
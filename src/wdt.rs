@@ -11,66 +11,838 @@
 //! ^ by Joan Stark
 
 use cortex_m::interrupt;
-use crate::pac::wdt0::ctrl::{
+pub use crate::pac::wdt0::ctrl::{
     RstEarlyVal,  RstLateVal, 
     IntLateVal, IntEarlyVal,
 };
 
-pub struct Wdt0 {
-    wdt: crate::pac::Wdt0,
+/// Timeout thresholds of the watchdog timer, in WDT clock cycles.
+///
+/// The late thresholds trigger when Cupcake is fed too late, the early
+/// thresholds trigger when he is fed too soon. The real-world timeout is
+/// `2^n / f`, where `f` is the frequency of the [`WdtClockSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WdtConfig {
+    /// Interrupt if not fed within this many cycles.
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub int_late: IntLateVal,
+    /// Reset if not fed within this many cycles.
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub rst_late: RstLateVal,
+    /// Interrupt if fed before this many cycles.
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub int_early: IntEarlyVal,
+    /// Reset if fed before this many cycles.
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub rst_early: RstEarlyVal,
+    /// Enable windowed mode. When disabled, feeding early is harmless and
+    /// the early thresholds are ignored.
+    pub window: bool,
+    /// Clock the watchdog counts with. [`None`] keeps the default clock of
+    /// the instance, see [`WdtClockSource`].
+    pub source: Option<WdtClockSource>,
 }
 
-impl Wdt0 {
-    /// Create a new AES peripheral instance.
-    pub fn new(wdt: crate::pac::Wdt0, reg: &mut crate::gcr::GcrRegisters) -> Self {
-        use crate::gcr::ResetForPeripheral;
-        use crate::gcr::ClockForPeripheral;
-        
-        unsafe {
-            wdt.reset(&mut reg.gcr);
-            wdt.enable_clock(&mut reg.gcr);
+impl Default for WdtConfig {
+    fn default() -> Self {
+        Self {
+            int_late: IntLateVal::Wdt2pow27, // INT after 2.68 sec at 50 MHz PCLK
+            rst_late: RstLateVal::Wdt2pow28, // RST after 5.37 sec at 50 MHz PCLK
+            int_early: IntEarlyVal::Wdt2pow16, // No early interrupt
+            rst_early: RstEarlyVal::Wdt2pow16, // RST if fed < 1.31 ms at 50 MHz PCLK
+            window: true,
+            source: None,
         }
-
-        interrupt::free(|cs| feed_sequence(&wdt, cs));
-        wdt.ctrl().write(|w| w.en().clear_bit());
-        while wdt.ctrl().read().clkrdy().bit_is_clear() {}
-
-        // Configure Peripheral
-        wdt.clksel().write(|w| unsafe { w.source().bits(0x0) });
-        wdt.ctrl().write(|w| {
-            w.int_late_val().variant(IntLateVal::Wdt2pow27); // INT after 1.34 sec
-            w.rst_late_val().variant(RstLateVal::Wdt2pow28); // RST after 2.68 sec
-            
-            w.win_en().set_bit();
-            w.int_early_val().variant(IntEarlyVal::Wdt2pow16); // No early interrupt
-            w.rst_early_val().variant(RstEarlyVal::Wdt2pow16); // RST if fed < 655.36 us
-            
-            w.wdt_int_en().set_bit();
-            w.wdt_rst_en().set_bit();
-            return w;
-        });
-
-        interrupt::free(|cs| feed_sequence(&wdt, cs));
-        wdt.ctrl().write(|w| w.en().set_bit());
-        while wdt.ctrl().read().clkrdy().bit_is_clear() {}
-
-        Self { wdt }
     }
+}
+
+/// Clock sources of the watchdog timers.
+///
+/// For a threshold of 2^28 cycles, the resulting reset timeout is:
+/// - [`WdtClockSource::Pclk`] (50 MHz, half the 100 MHz IPO): 5.37 s
+/// - [`WdtClockSource::Ibro`] (7.3728 MHz): 36.4 s
+/// - [`WdtClockSource::Inro`] (8 kHz): 9.3 hours
+/// - [`WdtClockSource::Ertco`] (32.768 kHz): 2.3 hours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WdtClockSource {
+    /// Peripheral clock, only available on [`Wdt0`]. This is the default
+    /// for [`Wdt0`].
+    Pclk,
+    /// Internal baud rate oscillator. This is the default for [`Wdt1`].
+    Ibro,
+    /// Internal nano-ring oscillator, only available on [`Wdt1`]. Keeps
+    /// running in low power modes.
+    Inro,
+    /// External 32 kHz RTC oscillator, only available on [`Wdt1`]. The RTC
+    /// oscillator must already be running.
+    Ertco,
+}
+
+/// Number of polls of `CTRL.CLKRDY` before [`WdtError::ClockNotReady`] is
+/// returned. Enough for several cycles of the slowest WDT clock.
+pub const WDT_CLKRDY_TIMEOUT: u32 = 1_000_000;
+
+/// Errors that can occur while configuring a watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WdtError {
+    /// The clock source is not available on this watchdog instance.
+    UnsupportedClockSource,
+    /// The watchdog did not report its clock as ready after being stopped
+    /// or started, for example because its clock source is not running.
+    ClockNotReady,
+}
+
+/// Time remaining relative to the watchdog thresholds, in WDT clock cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WdtMargin {
+    /// Cycles until feeding no longer causes an early reset. Zero if
+    /// feeding is already safe or windowed mode is disabled.
+    pub until_early_ok: u32,
+    /// Cycles until the late reset.
+    pub until_reset: u32,
+}
+
+/// Generates a watchdog driver and its disabled typestate for one instance.
+/// All instances share the same register layout and feed sequence.
+macro_rules! wdt {
+    (
+        $(#[$wdt_meta:meta])* $Wdt:ident,
+        $(#[$disabled_meta:meta])* $DisabledWdt:ident,
+        $PAC:ident, $GCR:ident, $clksel:ident, $IRQ:ident
+    ) => {
+        $(#[$wdt_meta])*
+        pub struct $Wdt {
+            wdt: $crate::pac::$PAC,
+        }
+
+        impl $Wdt {
+            /// Create a new WDT peripheral instance with the default configuration.
+            /// See [`Self::new_with()`] for the errors.
+            pub fn new(
+                wdt: $crate::pac::$PAC,
+                reg: &mut $crate::gcr::GcrRegisters,
+            ) -> Result<Self, WdtError> {
+                Self::new_with(wdt, reg, WdtConfig::default())
+            }
+
+            /// Create a new WDT peripheral instance with the given thresholds
+            /// and clock source.
+            ///
+            /// Returns [`WdtError::UnsupportedClockSource`] before touching the
+            /// peripheral if `config.source` is not available on this instance,
+            /// and [`WdtError::ClockNotReady`] if `CTRL.CLKRDY` does not assert
+            /// within [`WDT_CLKRDY_TIMEOUT`] polls, for example because the
+            /// selected clock is not running. The watchdog is left stopped on
+            /// error.
+            pub fn new_with(
+                wdt: $crate::pac::$PAC,
+                reg: &mut $crate::gcr::GcrRegisters,
+                config: WdtConfig,
+            ) -> Result<Self, WdtError> {
+                use $crate::gcr::ResetForPeripheral;
+                use $crate::gcr::ClockForPeripheral;
+
+                // Source 0 is the reset default of each instance
+                let source = match config.source {
+                    Some(source) => $clksel(source).ok_or(WdtError::UnsupportedClockSource)?,
+                    None => 0,
+                };
+
+                unsafe {
+                    wdt.reset(&mut reg.$GCR);
+                    wdt.enable_clock(&mut reg.$GCR);
+                }
+
+                interrupt::free(|cs| feed_sequence(&wdt, cs));
+                wdt.ctrl().modify(|_, w| w.en().clear_bit());
+                _wait_clock_ready(&wdt)?;
+
+                let (int_early, rst_early) = _early_thresholds(&config);
+
+                // Configure Peripheral
+                wdt.clksel().write(|w| unsafe { w.source().bits(source) });
+                _wait_clock_ready(&wdt)?;
+                wdt.ctrl().write(|w| {
+                    w.int_late_val().variant(config.int_late);
+                    w.rst_late_val().variant(config.rst_late);
+
+                    w.win_en().bit(config.window);
+                    w.int_early_val().variant(int_early);
+                    w.rst_early_val().variant(rst_early);
+
+                    w.wdt_int_en().set_bit();
+                    w.wdt_rst_en().set_bit()
+                });
+
+                interrupt::free(|cs| feed_sequence(&wdt, cs));
+                // Modify so the configuration written above is kept
+                wdt.ctrl().modify(|_, w| w.en().set_bit());
+                if let Err(error) = _wait_clock_ready(&wdt) {
+                    wdt.ctrl().modify(|_, w| w.en().clear_bit());
+                    return Err(error);
+                }
+
+                Ok(Self { wdt })
+            }
+
+            /// Give Cupcake his treat :D
+            #[inline(always)]
+            pub fn feed(&self) {
+                // Ask cupcake to not bite of your hand while you give him his treat
+                interrupt::free(|cs| feed_sequence(&self.wdt, cs));
+            }
+
+            /// Feed Cupcake as soon as it is safe to do so. In windowed mode this
+            /// waits until feeding no longer causes an early reset.
+            #[inline]
+            pub fn feed_when_ready(&self) {
+                while self.margin().until_early_ok > 0 {}
+                self.feed();
+            }
+
+            /// Run a recovery routine, such as resetting and re-initializing
+            /// peripherals or clocks, between two feeds.
+            ///
+            /// Cupcake is fed right before the routine starts, so the routine
+            /// gets the full late reset threshold (5.37 s with the default
+            /// configuration at the 50 MHz PCLK) no matter how close to a
+            /// reset it was called. He is fed again once it returns, waiting
+            /// out the early window first so a routine that finishes quickly
+            /// does not cause an early reset. A routine that may take longer
+            /// than the late threshold must feed Cupcake itself or run with
+            /// him disabled.
+            ///
+            /// Example:
+            /// ```
+            /// // The host bus is wedged: reset the I2C peripheral and start over
+            /// let i2c = wdt.feed_around(|| {
+            ///     // The constructor resets the peripheral through the GCR
+            ///     let (pac_i2c, scl, sda) = i2c.free();
+            ///     I2c::i2c1(pac_i2c, &mut gcr.reg, &clks.pclk, scl, sda, BusSpeed::Standard)
+            /// })
+            /// .unwrap();
+            /// ```
+            pub fn feed_around<R>(&self, routine: impl FnOnce() -> R) -> R {
+                self.feed_when_ready();
+                let result = routine();
+                self.feed_when_ready();
+                result
+            }
 
-    /// Give Cupcake his treat :D
-    #[inline(always)]
-    pub fn feed(&self) {
-        // Ask cupcake to not bite of your hand while you give him his treat
-        interrupt::free(|cs| feed_sequence(&self.wdt, cs));
+            /// Returns [`true`] if the too-late interrupt is pending (`CTRL.INT_LATE`,
+            /// bit 9). This fires when Cupcake has not been fed within the
+            /// `int_late` threshold and is a warning that a reset is coming.
+            #[inline(always)]
+            pub fn is_late_interrupt_pending(&self) -> bool {
+                self.wdt.ctrl().read().int_late().is_pending()
+            }
+
+            /// Returns [`true`] if the too-early interrupt is pending
+            /// (`CTRL.INT_EARLY`, bit 12). This fires when Cupcake was fed before
+            /// the `int_early` threshold in windowed mode.
+            #[inline(always)]
+            pub fn is_early_interrupt_pending(&self) -> bool {
+                self.wdt.ctrl().read().int_early().is_pending()
+            }
+
+            /// Returns [`true`] if either watchdog interrupt is pending.
+            #[inline(always)]
+            pub fn is_interrupt_pending(&self) -> bool {
+                let ctrl = self.wdt.ctrl().read();
+                ctrl.int_late().is_pending() || ctrl.int_early().is_pending()
+            }
+
+            /// The NVIC interrupt of this watchdog, shared by the late and
+            /// early interrupts.
+            #[inline(always)]
+            pub const fn interrupt(&self) -> $crate::Interrupt {
+                $crate::Interrupt::$IRQ
+            }
+
+            /// Enable the watchdog interrupts. They are enabled by the
+            /// constructors, but only reach the CPU once the line returned
+            /// by [`Self::interrupt()`] is unmasked in the NVIC.
+            ///
+            /// The late interrupt is the warning that Cupcake is about to
+            /// bite: it fires when he has not been fed within the `int_late`
+            /// threshold, and the reset follows [`Self::warning_lead_cycles()`]
+            /// later. The handler only needs a few cycles to be entered, so
+            /// nearly all of that lead is left to finish or abandon the
+            /// current task and feed him. Feeding does not clear the flag, so
+            /// the handler must call [`Self::clear_interrupt()`].
+            ///
+            /// Example:
+            /// ```
+            /// wdt.enable_interrupt();
+            /// unsafe { cortex_m::peripheral::NVIC::unmask(wdt.interrupt()) };
+            ///
+            /// #[interrupt]
+            /// fn WDT0() {
+            ///     // Access the Wdt0 instance, e.g. through a critical section Mutex
+            ///     if wdt.is_late_interrupt_pending() {
+            ///         // Ask the scheduler to defer long tasks until fed
+            ///         DEFER_LONG_TASKS.store(true, Ordering::Relaxed);
+            ///     }
+            ///     wdt.clear_interrupt();
+            /// }
+            /// ```
+            #[inline(always)]
+            pub fn enable_interrupt(&self) {
+                self.wdt.ctrl().modify(|_, w| w.wdt_int_en().set_bit());
+            }
+
+            /// Disable the watchdog interrupts. The resets are not affected.
+            #[inline(always)]
+            pub fn disable_interrupt(&self) {
+                self.wdt.ctrl().modify(|_, w| w.wdt_int_en().clear_bit());
+            }
+
+            /// Number of WDT clock cycles between the late interrupt and the
+            /// late reset, which is how long firmware has to react to the
+            /// warning. Zero if the interrupt threshold is not below the
+            /// reset threshold, in which case no warning is given.
+            ///
+            /// With the default configuration this is 2^27 cycles, or about
+            /// 2.68 s with the 50 MHz peripheral clock.
+            #[inline(always)]
+            pub fn warning_lead_cycles(&self) -> u32 {
+                let ctrl = self.wdt.ctrl().read();
+                _threshold_cycles(ctrl.rst_late_val().bits())
+                    .saturating_sub(_threshold_cycles(ctrl.int_late_val().bits()))
+            }
+
+            /// Time between the late interrupt and the late reset, given the
+            /// frequency of the WDT clock. See [`Self::warning_lead_cycles()`].
+            /// [`None`] if `wdt_clk_hz` is zero.
+            #[inline(always)]
+            pub fn warning_lead(&self, wdt_clk_hz: u32) -> Option<core::time::Duration> {
+                _cycles_to_duration(self.warning_lead_cycles(), wdt_clk_hz)
+            }
+
+            /// Number of WDT clock cycles left before the late interrupt
+            /// fires, zero if it already has.
+            #[inline(always)]
+            pub fn cycles_until_warning(&self) -> u32 {
+                let threshold = _threshold_cycles(self.wdt.ctrl().read().int_late_val().bits());
+                threshold.saturating_sub(self.wdt.cnt().read().count().bits())
+            }
+
+            /// Acknowledge both watchdog interrupts by clearing `CTRL.INT_LATE` and
+            /// `CTRL.INT_EARLY`. Feeding does not clear them.
+            ///
+            /// Example:
+            /// ```
+            /// #[interrupt]
+            /// fn WDT0() {
+            ///     // Almost out of time, clean up and feed
+            ///     wdt.clear_interrupt();
+            ///     wdt.feed();
+            /// }
+            /// ```
+            #[inline(always)]
+            pub fn clear_interrupt(&self) {
+                self.wdt.ctrl().modify(|_, w| {
+                    w.int_late().clear_bit();
+                    w.int_early().clear_bit()
+                });
+            }
+
+            /// Number of WDT clock cycles left before Cupcake resets the device
+            /// for not being fed.
+            #[inline(always)]
+            pub fn cycles_until_reset(&self) -> u32 {
+                self.margin().until_reset
+            }
+
+            /// Time left before Cupcake resets the device for not being fed,
+            /// given the frequency of the WDT clock. [`None`] if `wdt_clk_hz`
+            /// is zero.
+            #[inline(always)]
+            pub fn time_until_reset(&self, wdt_clk_hz: u32) -> Option<core::time::Duration> {
+                _cycles_to_duration(self.cycles_until_reset(), wdt_clk_hz)
+            }
+
+            /// How long until it is safe to feed Cupcake, and how long until he
+            /// resets the device, in WDT clock cycles.
+            ///
+            /// Example:
+            /// ```
+            /// let margin = wdt.margin();
+            /// if margin.until_early_ok == 0 && margin.until_reset < LONG_TASK_CYCLES {
+            ///     wdt.feed();
+            /// }
+            /// long_task();
+            /// ```
+            pub fn margin(&self) -> WdtMargin {
+                let ctrl = self.wdt.ctrl().read();
+                let count = self.wdt.cnt().read().count().bits();
+                let until_early_ok = if ctrl.win_en().bit_is_set() {
+                    _threshold_cycles(ctrl.rst_early_val().bits()).saturating_sub(count)
+                } else {
+                    0
+                };
+                WdtMargin {
+                    until_early_ok,
+                    until_reset: _threshold_cycles(ctrl.rst_late_val().bits()).saturating_sub(count),
+                }
+            }
+
+            /// The thresholds and clock source Cupcake is currently running with.
+            pub fn config(&self) -> WdtConfig {
+                let ctrl = self.wdt.ctrl().read();
+                let bits = self.wdt.clksel().read().source().bits();
+                let source = [
+                    WdtClockSource::Pclk,
+                    WdtClockSource::Ibro,
+                    WdtClockSource::Inro,
+                    WdtClockSource::Ertco,
+                ]
+                .into_iter()
+                .find(|&source| $clksel(source) == Some(bits));
+                WdtConfig {
+                    int_late: ctrl.int_late_val().variant(),
+                    rst_late: ctrl.rst_late_val().variant(),
+                    int_early: ctrl.int_early_val().variant(),
+                    rst_early: ctrl.rst_early_val().variant(),
+                    window: ctrl.win_en().bit_is_set(),
+                    source,
+                }
+            }
+
+            /// Change the thresholds while Cupcake is running, for example to
+            /// give a firmware update more time, and restore them afterwards.
+            /// The clock source and interrupt enable are kept, `config.source`
+            /// is ignored; use [`Self::set_clock_source()`] to change it.
+            ///
+            /// The thresholds can only be written while the watchdog is
+            /// stopped, so the sequence is:
+            /// 1. Wait until feeding no longer causes an early reset.
+            /// 2. Feed, so no late threshold is reached during the sequence.
+            /// 3. Stop the watchdog and wait for `CTRL.CLKRDY`.
+            /// 4. Write the new thresholds and clear any pending interrupt.
+            /// 5. Feed and start the watchdog, and wait for `CTRL.CLKRDY`.
+            ///
+            /// The counter restarts from zero with the new thresholds, so in
+            /// windowed mode the next feed must wait for the new early window.
+            /// Interrupts are not disabled during the sequence, but a handler
+            /// that runs in the gap cannot trigger a reset, since the watchdog
+            /// was just fed or is stopped.
+            ///
+            /// Returns [`WdtError::ClockNotReady`] if `CTRL.CLKRDY` does not
+            /// assert within [`WDT_CLKRDY_TIMEOUT`] polls. If this happens
+            /// while stopping, the old configuration is kept and the watchdog
+            /// is started again; if it happens while starting, the new
+            /// configuration is in place but the watchdog may not be running.
+            ///
+            /// Example:
+            /// ```
+            /// // Widen the late thresholds from 5.37 s to 21.5 s at the 50 MHz PCLK
+            /// let normal = wdt.config();
+            /// wdt.reconfigure(WdtConfig {
+            ///     int_late: IntLateVal::Wdt2pow29,
+            ///     rst_late: RstLateVal::Wdt2pow30,
+            ///     ..normal
+            /// })
+            /// .unwrap();
+            /// assert_eq!(wdt.config().rst_late, RstLateVal::Wdt2pow30);
+            ///
+            /// apply_firmware_update();
+            ///
+            /// wdt.reconfigure(normal).unwrap();
+            /// assert_eq!(wdt.config(), normal);
+            /// ```
+            pub fn reconfigure(&self, config: WdtConfig) -> Result<(), WdtError> {
+                let (int_early, rst_early) = _early_thresholds(&config);
+
+                while self.margin().until_early_ok > 0 {}
+                interrupt::free(|cs| feed_sequence(&self.wdt, cs));
+                self.wdt.ctrl().modify(|_, w| w.en().clear_bit());
+                if let Err(error) = _wait_clock_ready(&self.wdt) {
+                    interrupt::free(|cs| feed_sequence(&self.wdt, cs));
+                    self.wdt.ctrl().modify(|_, w| w.en().set_bit());
+                    return Err(error);
+                }
+
+                self.wdt.ctrl().modify(|_, w| {
+                    w.int_late_val().variant(config.int_late);
+                    w.rst_late_val().variant(config.rst_late);
+
+                    w.win_en().bit(config.window);
+                    w.int_early_val().variant(int_early);
+                    w.rst_early_val().variant(rst_early);
+
+                    w.int_late().clear_bit();
+                    w.int_early().clear_bit()
+                });
+
+                interrupt::free(|cs| feed_sequence(&self.wdt, cs));
+                self.wdt.ctrl().modify(|_, w| w.en().set_bit());
+                _wait_clock_ready(&self.wdt)
+            }
+
+            /// Switch the clock that Cupcake counts with. This changes the
+            /// real-world duration of all thresholds.
+            ///
+            /// A running watchdog is fed before it is stopped, so in windowed
+            /// mode this first waits until feeding no longer causes an early
+            /// reset. The counter then restarts from zero on the new clock.
+            ///
+            /// Returns [`WdtError::ClockNotReady`] if `CTRL.CLKRDY` does not
+            /// assert within [`WDT_CLKRDY_TIMEOUT`] polls. If the new clock
+            /// is not running the old clock source is selected again; either
+            /// way a running watchdog is started again.
+            ///
+            /// Example:
+            /// ```
+            /// // Keep running in deep sleep
+            /// wdt1.set_clock_source(WdtClockSource::Inro).unwrap();
+            /// ```
+            pub fn set_clock_source(&mut self, source: WdtClockSource) -> Result<(), WdtError> {
+                let bits = $clksel(source).ok_or(WdtError::UnsupportedClockSource)?;
+                let previous = self.wdt.clksel().read().source().bits();
+                let enabled = self.wdt.ctrl().read().en().bit_is_set();
+                if enabled {
+                    // Feed before stopping, so the counter is restarted from
+                    // zero without tripping the early reset
+                    while self.margin().until_early_ok > 0 {}
+                    interrupt::free(|cs| feed_sequence(&self.wdt, cs));
+                }
+                // The clock may only be switched while the watchdog is stopped
+                self.wdt.ctrl().modify(|_, w| w.en().clear_bit());
+                let mut result = _wait_clock_ready(&self.wdt);
+                if result.is_ok() {
+                    self.wdt.clksel().write(|w| unsafe { w.source().bits(bits) });
+                    result = _wait_clock_ready(&self.wdt);
+                    if result.is_err() {
+                        // The new clock is not running, keep counting on the old one
+                        self.wdt.clksel().write(|w| unsafe { w.source().bits(previous) });
+                    }
+                }
+                if enabled {
+                    self.wdt.ctrl().modify(|_, w| w.en().set_bit());
+                    result = result.and(_wait_clock_ready(&self.wdt));
+                }
+                result
+            }
+
+            /// Stop Cupcake, e.g. for a long blocking operation such as a flash
+            /// erase. The configuration is kept and restored when the disabled
+            /// watchdog is enabled again.
+            ///
+            /// While disabled the watchdog provides no protection against the
+            /// firmware hanging or being tampered with, so keep this window short.
+            ///
+            /// Cupcake is fed right before he is stopped, so in windowed mode
+            /// this first waits until feeding no longer causes an early reset.
+            ///
+            /// Returns [`WdtError::ClockNotReady`] along with the watchdog,
+            /// started again, if `CTRL.CLKRDY` does not assert within
+            /// [`WDT_CLKRDY_TIMEOUT`] polls.
+            pub fn disable(self) -> Result<$DisabledWdt, (Self, WdtError)> {
+                while self.margin().until_early_ok > 0 {}
+                interrupt::free(|cs| feed_sequence(&self.wdt, cs));
+                self.wdt.ctrl().modify(|_, w| w.en().clear_bit());
+                if let Err(error) = _wait_clock_ready(&self.wdt) {
+                    self.wdt.ctrl().modify(|_, w| w.en().set_bit());
+                    return Err((self, error));
+                }
+                Ok($DisabledWdt { wdt: self.wdt })
+            }
+        }
+
+        $(#[$disabled_meta])*
+        pub struct $DisabledWdt {
+            wdt: $crate::pac::$PAC,
+        }
+
+        impl $DisabledWdt {
+            /// Restart Cupcake with the configuration he had before being disabled.
+            ///
+            /// He was fed when he was disabled, so the counter resumes from
+            /// zero with the full late threshold and is not fed again here.
+            ///
+            /// Returns [`WdtError::ClockNotReady`] along with the watchdog,
+            /// stopped again, if `CTRL.CLKRDY` does not assert within
+            /// [`WDT_CLKRDY_TIMEOUT`] polls.
+            pub fn enable(self) -> Result<$Wdt, (Self, WdtError)> {
+                self.wdt.ctrl().modify(|_, w| w.en().set_bit());
+                if let Err(error) = _wait_clock_ready(&self.wdt) {
+                    self.wdt.ctrl().modify(|_, w| w.en().clear_bit());
+                    return Err((self, error));
+                }
+                Ok($Wdt { wdt: self.wdt })
+            }
+        }
+    };
+}
+
+wdt!(
+    /// # Watchdog Timer 0 (WDT0)
+    ///
+    /// Example:
+    /// ```
+    /// // Short timeouts for a fast control loop
+    /// let config = WdtConfig {
+    ///     int_late: IntLateVal::Wdt2pow24,
+    ///     rst_late: RstLateVal::Wdt2pow25,
+    ///     ..WdtConfig::default()
+    /// };
+    /// let wdt = hal::wdt::Wdt0::new_with(p.wdt0, &mut gcr.reg, config).unwrap();
+    /// loop {
+    ///     wdt.feed();
+    /// }
+    ///
+    /// // Non-windowed, feeding at any time before the late reset is fine
+    /// let config = WdtConfig {
+    ///     window: false,
+    ///     ..WdtConfig::default()
+    /// };
+    /// let wdt = hal::wdt::Wdt0::new_with(p.wdt0, &mut gcr.reg, config).unwrap();
+    /// wdt.feed();
+    /// wdt.feed();
+    /// ```
+    Wdt0,
+    /// A stopped [`Wdt0`]. It cannot be fed until it is enabled again.
+    ///
+    /// Example:
+    /// ```
+    /// let disabled = wdt.disable().map_err(|(_, err)| err).unwrap();
+    /// flc.write_u32_slice(address, &data).unwrap();
+    /// let wdt = disabled.enable().map_err(|(_, err)| err).unwrap();
+    /// ```
+    DisabledWdt0,
+    Wdt0, gcr, _wdt0_clksel, WDT0
+);
+
+wdt!(
+    /// # Watchdog Timer 1 (WDT1)
+    ///
+    /// Identical to [`Wdt0`], but clocked and reset through the low power
+    /// GCR.
+    ///
+    /// Example:
+    /// ```
+    /// let wdt1 = hal::wdt::Wdt1::new(p.wdt1, &mut gcr.reg).unwrap();
+    /// wdt1.feed();
+    ///
+    /// // Keep running in deep sleep
+    /// let config = WdtConfig {
+    ///     source: Some(WdtClockSource::Inro),
+    ///     ..WdtConfig::default()
+    /// };
+    /// let wdt1 = hal::wdt::Wdt1::new_with(p.wdt1, &mut gcr.reg, config).unwrap();
+    /// ```
+    Wdt1,
+    /// A stopped [`Wdt1`]. It cannot be fed until it is enabled again.
+    DisabledWdt1,
+    Wdt1, lpgcr, _wdt1_clksel, WDT1
+);
+
+/// The cause of the last reset, as far as the watchdog timers can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetCause {
+    /// A watchdog was not fed before its late reset threshold.
+    WatchdogTimeout,
+    /// A watchdog was fed before its early reset threshold in windowed mode.
+    WatchdogWindow,
+    /// Any other reset: power-on, the external reset pin, or a system
+    /// reset. The MAX7800x does not record which one of these occurred.
+    Other,
+}
+
+/// Read the cause of the last reset from the reset flags of a watchdog
+/// (`CTRL.RST_LATE`, bit 31 and `CTRL.RST_EARLY`, bit 30).
+///
+/// The flags are only cleared by a power-on reset or by
+/// [`clear_reset_cause`], and initializing the watchdog with
+/// [`Wdt0::new`] resets the peripheral, so read them before that.
+///
+/// Example:
+/// ```
+/// if hal::wdt::reset_cause(&p.wdt0) == ResetCause::WatchdogWindow {
+///     window_violations += 1;
+/// }
+/// hal::wdt::clear_reset_cause(&p.wdt0);
+/// let wdt = hal::wdt::Wdt0::new(p.wdt0, &mut gcr.reg).unwrap();
+/// ```
+pub fn reset_cause(wdt: &crate::pac::wdt0::RegisterBlock) -> ResetCause {
+    let ctrl = wdt.ctrl().read();
+    if ctrl.rst_late().is_occurred() {
+        ResetCause::WatchdogTimeout
+    } else if ctrl.rst_early().is_occurred() {
+        ResetCause::WatchdogWindow
+    } else {
+        ResetCause::Other
     }
 }
 
+/// Clear the reset flags of a watchdog so the next call to
+/// [`reset_cause`] only reports resets that happen from now on.
+pub fn clear_reset_cause(wdt: &crate::pac::wdt0::RegisterBlock) {
+    wdt.ctrl().modify(|_, w| {
+        w.rst_late().no_event();
+        w.rst_early().no_event()
+    });
+}
+
 /// Internal feed sequence that only runs when Cupkake promises he wont bite off your hand
 #[inline(always)]
-fn feed_sequence(wdt: &crate::pac::Wdt0, _cs: &interrupt::CriticalSection) {
-    wdt.rst().write(|w| unsafe {
-        w.bits(0xA5);
-        w.bits(0x5A);
-        return w;
-    });
+fn feed_sequence(wdt: &crate::pac::wdt0::RegisterBlock, _cs: &interrupt::CriticalSection) {
+    // The two halves of the sequence must be separate register writes
+    wdt.rst().write(|w| w.reset().seq0());
+    wdt.rst().write(|w| w.reset().seq1());
+}
+
+/// The early thresholds to write for a configuration. Without windowing
+/// the smallest early thresholds are used, so that even if windowing were
+/// enabled later an early feed would be harmless.
+#[doc(hidden)]
+#[inline(always)]
+fn _early_thresholds(config: &WdtConfig) -> (IntEarlyVal, RstEarlyVal) {
+    if config.window {
+        (config.int_early, config.rst_early)
+    } else {
+        (IntEarlyVal::Wdt2pow16, RstEarlyVal::Wdt2pow16)
+    }
+}
+
+/// Wait for `CTRL.CLKRDY` after stopping or starting a watchdog, giving up
+/// after [`WDT_CLKRDY_TIMEOUT`] polls.
+#[doc(hidden)]
+fn _wait_clock_ready(wdt: &crate::pac::wdt0::RegisterBlock) -> Result<(), WdtError> {
+    for _ in 0..WDT_CLKRDY_TIMEOUT {
+        if wdt.ctrl().read().clkrdy().bit_is_set() {
+            return Ok(());
+        }
+    }
+    Err(WdtError::ClockNotReady)
+}
+
+/// Converts a threshold field value (0 = 2^31 cycles, 15 = 2^16 cycles)
+/// into a number of WDT clock cycles.
+#[doc(hidden)]
+#[inline(always)]
+fn _threshold_cycles(field: u8) -> u32 {
+    1 << (31 - field as u32)
+}
+
+/// Converts a number of WDT clock cycles into time, [`None`] if `clk_hz`
+/// is zero.
+#[doc(hidden)]
+#[inline(always)]
+fn _cycles_to_duration(cycles: u32, clk_hz: u32) -> Option<core::time::Duration> {
+    (cycles as u64 * 1_000_000)
+        .checked_div(clk_hz as u64)
+        .map(core::time::Duration::from_micros)
+}
+
+/// `CLKSEL.SOURCE` value of a clock source for [`Wdt0`].
+#[doc(hidden)]
+#[inline(always)]
+fn _wdt0_clksel(source: WdtClockSource) -> Option<u8> {
+    match source {
+        WdtClockSource::Pclk => Some(0),
+        WdtClockSource::Ibro => Some(1),
+        _ => None,
+    }
+}
+
+/// `CLKSEL.SOURCE` value of a clock source for [`Wdt1`].
+#[doc(hidden)]
+#[inline(always)]
+fn _wdt1_clksel(source: WdtClockSource) -> Option<u8> {
+    match source {
+        WdtClockSource::Ibro => Some(0),
+        WdtClockSource::Inro => Some(4),
+        WdtClockSource::Ertco => Some(5),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threshold_field_to_cycles() {
+        assert_eq!(_threshold_cycles(0), 1 << 31);
+        assert_eq!(_threshold_cycles(RstLateVal::Wdt2pow28 as u8), 1 << 28);
+        assert_eq!(_threshold_cycles(15), 1 << 16);
+    }
+
+    #[test]
+    fn windowed_keeps_early_thresholds() {
+        let config = WdtConfig {
+            int_early: IntEarlyVal::Wdt2pow20,
+            rst_early: RstEarlyVal::Wdt2pow19,
+            window: true,
+            ..WdtConfig::default()
+        };
+        let (int_early, rst_early) = _early_thresholds(&config);
+        assert_eq!(int_early, IntEarlyVal::Wdt2pow20);
+        assert_eq!(rst_early, RstEarlyVal::Wdt2pow19);
+        assert_eq!(_threshold_cycles(rst_early as u8), 1 << 19);
+    }
+
+    #[test]
+    fn non_windowed_uses_most_permissive_early_thresholds() {
+        let config = WdtConfig {
+            int_early: IntEarlyVal::Wdt2pow20,
+            rst_early: RstEarlyVal::Wdt2pow19,
+            window: false,
+            ..WdtConfig::default()
+        };
+        let (int_early, rst_early) = _early_thresholds(&config);
+        assert_eq!(int_early, IntEarlyVal::Wdt2pow16);
+        assert_eq!(rst_early, RstEarlyVal::Wdt2pow16);
+        // The smallest threshold the hardware supports
+        assert_eq!(_threshold_cycles(int_early as u8), 1 << 16);
+        assert_eq!(_threshold_cycles(rst_early as u8), 1 << 16);
+    }
+
+    #[test]
+    fn reconfigured_thresholds_to_duration() {
+        use core::time::Duration;
+
+        const PCLK_HZ: u32 = 50_000_000;
+        let normal = WdtConfig {
+            int_late: IntLateVal::Wdt2pow26,
+            rst_late: RstLateVal::Wdt2pow27,
+            ..WdtConfig::default()
+        };
+        let widened = WdtConfig {
+            int_late: IntLateVal::Wdt2pow28,
+            rst_late: RstLateVal::Wdt2pow29,
+            ..normal
+        };
+        let rst_late = |config: &WdtConfig| {
+            _cycles_to_duration(_threshold_cycles(config.rst_late as u8), PCLK_HZ)
+        };
+
+        // 2^27 cycles is 2.68 s and 2^29 cycles is 10.7 s at 50 MHz
+        assert_eq!(rst_late(&normal), Some(Duration::from_micros(2_684_354)));
+        assert_eq!(rst_late(&widened), Some(Duration::from_micros(10_737_418)));
+        // The warning comes half the reset threshold ahead in both settings
+        let lead = |config: &WdtConfig| {
+            _threshold_cycles(config.rst_late as u8) - _threshold_cycles(config.int_late as u8)
+        };
+        assert_eq!(
+            _cycles_to_duration(lead(&normal), PCLK_HZ),
+            Some(Duration::from_micros(1_342_177))
+        );
+        assert_eq!(
+            _cycles_to_duration(lead(&widened), PCLK_HZ),
+            Some(Duration::from_micros(5_368_709))
+        );
+    }
+
+    #[test]
+    fn duration_of_zero_hz_clock() {
+        assert_eq!(_cycles_to_duration(1 << 28, 0), None);
+    }
 }